@@ -0,0 +1,154 @@
+//! Throughput of the stream wrappers and the upload staging helpers
+//!
+//! Run with `cargo bench --bench readers`. The part uploads of [B2UploadWriter][raze::utils::B2UploadWriter]
+//! need B2 and aren't covered, but spooling parts to memory or disk is.
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures_util::TryStreamExt;
+use raze::utils::{
+    reader_to_stream, reader_to_stream_with_chunk_size, BytesStreamExt, BytesStreamHashAtEnd,
+    RateLimiter, SpooledBody,
+};
+use std::io::Error as IoError;
+use std::time::{Duration, Instant};
+
+const SIZE: usize = 16 * 1024 * 1024;
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+// 'SIZE' bytes as a stream of 'chunk_size' chunks
+fn chunks(
+    data: &Bytes,
+    chunk_size: usize,
+) -> impl futures_util::Stream<Item = Result<Bytes, IoError>> {
+    let chunks: Vec<_> = (0..data.len())
+        .step_by(chunk_size)
+        .map(|i| Ok(data.slice(i..(i + chunk_size).min(data.len()))))
+        .collect();
+    futures_util::stream::iter(chunks)
+}
+
+fn hashing(c: &mut Criterion) {
+    let rt = runtime();
+    let data = Bytes::from(vec![7u8; SIZE]);
+    let mut group = c.benchmark_group("hash_at_end");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    for chunk_size in [8 * 1024, 64 * 1024, 1024 * 1024] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(chunk_size),
+            &chunk_size,
+            |b, &chunk_size| {
+                b.to_async(&rt).iter(|| async {
+                    let stream = BytesStreamHashAtEnd::wrap(chunks(&data, chunk_size));
+                    stream
+                        .try_fold(0, |n, c| async move { Ok(n + c.len()) })
+                        .await
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn framing(c: &mut Criterion) {
+    let rt = runtime();
+    let data = vec![7u8; SIZE];
+    let mut group = c.benchmark_group("reader_to_stream");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    group.bench_function("in_memory", |b| {
+        b.to_async(&rt).iter(|| async {
+            reader_to_stream(std::io::Cursor::new(data.clone()))
+                .try_fold(0, |n, c| async move { Ok(n + c.len()) })
+                .await
+                .unwrap()
+        })
+    });
+    for chunk_size in [64 * 1024, 256 * 1024, 512 * 1024] {
+        group.bench_with_input(
+            BenchmarkId::new("chunk_size", chunk_size),
+            &chunk_size,
+            |b, &chunk_size| {
+                b.to_async(&rt).iter(|| async {
+                    reader_to_stream_with_chunk_size(std::io::Cursor::new(data.clone()), chunk_size)
+                        .try_fold(0, |n, c| async move { Ok(n + c.len()) })
+                        .await
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+// Reports how long a throttled transfer actually takes, 1MiB at 4MiB/s should take 250ms
+fn throttling(c: &mut Criterion) {
+    let rt = runtime();
+    let data = Bytes::from(vec![7u8; 1024 * 1024]);
+    let mut group = c.benchmark_group("throttle_accuracy");
+    group.sample_size(10);
+    group.bench_function("throttled_4MiBps", |b| {
+        b.to_async(&rt).iter_custom(|iters| {
+            let data = data.clone();
+            async move {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    let start = Instant::now();
+                    chunks(&data, 64 * 1024)
+                        .throttled(4 * 1024 * 1024)
+                        .try_for_each(|_| async { Ok(()) })
+                        .await
+                        .unwrap();
+                    total += start.elapsed();
+                }
+                total
+            }
+        })
+    });
+    group.bench_function("limited_4MiBps", |b| {
+        b.to_async(&rt).iter_custom(|iters| {
+            let data = data.clone();
+            async move {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    let limiter = RateLimiter::new(4 * 1024 * 1024);
+                    let start = Instant::now();
+                    chunks(&data, 64 * 1024)
+                        .limited(limiter)
+                        .try_for_each(|_| async { Ok(()) })
+                        .await
+                        .unwrap();
+                    total += start.elapsed();
+                }
+                total
+            }
+        })
+    });
+    group.finish();
+}
+
+fn spooling(c: &mut Criterion) {
+    let rt = runtime();
+    let data = Bytes::from(vec![7u8; SIZE]);
+    let mut group = c.benchmark_group("spool_part");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    group.sample_size(10);
+    for (name, threshold) in [("memory", SIZE), ("file", 0)] {
+        group.bench_function(name, |b| {
+            b.to_async(&rt).iter(|| async {
+                SpooledBody::from_stream(chunks(&data, 1024 * 1024), threshold)
+                    .await
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, hashing, framing, throttling, spooling);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+//! Shows what an application key may do and keeps its authorization between runs
+//!
+//! Usage: `cargo run --example key_management -- [auth cache path]`
+//!
+//! Reads B2_APPLICATION_KEY_ID and B2_APPLICATION_KEY, see [raze::client::B2Config::from_env]. \
+//! Creating and deleting keys (b2_create_key, b2_delete_key) isn't implemented yet,
+//! so this only inspects the key the process runs with.
+use raze::api::{B2Auth, Capability};
+use raze::client::B2Config;
+use raze::utils::resume_or_reauthorize;
+use raze::Error;
+
+fn describe(auth: &B2Auth) {
+    println!("Account {}", auth.account_id);
+    match &auth.allowed {
+        Some(allowed) => {
+            let capabilities: Vec<&str> = allowed.capabilities.iter().map(|c| c.as_str()).collect();
+            println!("Capabilities: {}", capabilities.join(", "));
+            if allowed.buckets.is_empty() {
+                println!("Buckets: all");
+            }
+            for bucket in &allowed.buckets {
+                let name = bucket.name.as_deref().unwrap_or("<deleted>");
+                println!("Bucket: {} ({})", name, bucket.id);
+            }
+            if let Some(prefix) = &allowed.name_prefix {
+                println!("Only files starting with '{}'", prefix);
+            }
+        }
+        None => println!("Restrictions unknown"),
+    }
+    for capability in [Capability::WriteFiles, Capability::DeleteFiles] {
+        if !auth.can(capability.clone()) {
+            println!("Note: this key can't {}", capability.as_str());
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let cache = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| ".raze-auth.json".to_string());
+    let config = B2Config::from_env()?;
+    println!("Key id {}", config.key_id);
+
+    // Later runs reuse the saved token for about a day instead of authorizing again
+    let auth = resume_or_reauthorize(&config.http_client(), &cache, &config.credentials()).await?;
+    describe(&auth);
+    println!("Authorization cached in {}", cache);
+    Ok(())
+}
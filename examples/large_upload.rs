@@ -0,0 +1,57 @@
+//! Uploads a local file of any size, streaming it through a B2UploadWriter
+//!
+//! Usage: `cargo run --example large_upload -- <local path> [remote name]`
+//!
+//! Reads B2_APPLICATION_KEY_ID, B2_APPLICATION_KEY and B2_BUCKET_ID, see [raze::client::B2Config::from_env]
+use raze::client::B2Config;
+use raze::utils::{B2UploadWriter, ContentTypeDetector};
+use raze::Error;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().ok_or_else(|| {
+        Error::ConfigError("usage: large_upload <local path> [remote name]".into())
+    })?;
+    let remote = args.next().unwrap_or_else(|| path.replace('\\', "/"));
+
+    let config = B2Config::from_env()?;
+    let bucket_id = config
+        .bucket_id
+        .clone()
+        .ok_or_else(|| Error::ConfigError("B2_BUCKET_ID is not set".into()))?;
+    let client = config.client(config.http_client()).await?;
+
+    let mut file = tokio::fs::File::open(&path).await.map_err(Error::IOError)?;
+    let size = file.metadata().await.map_err(Error::IOError)?.len();
+    let content_type = ContentTypeDetector::new().content_type_for(&path);
+
+    // Parts are uploaded in the background while the next one is read
+    let mut writer = B2UploadWriter::new(
+        client.http().clone(),
+        client.auth(),
+        bucket_id,
+        remote.as_str(),
+    )
+    .with_expected_size(size)
+    .with_content_type(content_type);
+
+    let copied = tokio::io::copy(&mut file, &mut writer).await;
+    if let Err(e) = copied {
+        writer.abort().await?;
+        return Err(Error::IOError(e));
+    }
+    tokio::io::AsyncWriteExt::shutdown(&mut writer)
+        .await
+        .map_err(Error::IOError)?;
+
+    let info = writer.file_info().expect("finished upload");
+    println!(
+        "Uploaded {} ({} bytes) as {}, sha1 {}",
+        path,
+        size,
+        info.file_name,
+        writer.sha1().unwrap_or("unknown")
+    );
+    Ok(())
+}
@@ -0,0 +1,63 @@
+//! Downloads several files at once into the current directory
+//!
+//! Usage: `cargo run --example parallel_download -- <remote name>...`
+//!
+//! Reads B2_APPLICATION_KEY_ID, B2_APPLICATION_KEY and B2_BUCKET_NAME, see [raze::client::B2Config::from_env]. \
+//! Set B2_DOWNLOAD_BANDWIDTH to limit all downloads together to that many bytes per second.
+use futures_util::{StreamExt, TryStreamExt};
+use raze::client::{B2Config, Bucket};
+use raze::Error;
+use tokio::io::AsyncWriteExt;
+
+// How many files are downloaded at the same time
+const CONCURRENCY: usize = 4;
+// How often a broken connection is resumed before giving up on a file
+const MAX_RETRIES: u32 = 5;
+
+async fn download(bucket: &Bucket, name: String) -> Result<u64, Error> {
+    let local = name.rsplit('/').next().unwrap_or(&name).to_string();
+    let mut file = tokio::fs::File::create(&local)
+        .await
+        .map_err(Error::IOError)?;
+    // Resumes after connection failures and checks the Sha1 at the end
+    let mut chunks = Box::pin(bucket.download_stream(&name, MAX_RETRIES));
+    let mut written = 0;
+    while let Some(chunk) = chunks.try_next().await? {
+        file.write_all(&chunk).await.map_err(Error::IOError)?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await.map_err(Error::IOError)?;
+    println!("{} -> {} ({} bytes)", name, local, written);
+    Ok(written)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let names: Vec<String> = std::env::args().skip(1).collect();
+    if names.is_empty() {
+        return Err(Error::ConfigError(
+            "usage: parallel_download <remote name>...".into(),
+        ));
+    }
+
+    let config = B2Config::from_env()?;
+    let bucket_name = config
+        .bucket_name
+        .clone()
+        .ok_or_else(|| Error::ConfigError("B2_BUCKET_NAME is not set".into()))?;
+    let client = config.client(config.http_client()).await?;
+    let bucket = client.bucket_by_name(&bucket_name).await?;
+
+    let results: Vec<Result<u64, Error>> = futures_util::stream::iter(names)
+        .map(|name| download(&bucket, name))
+        .buffer_unordered(CONCURRENCY)
+        .collect()
+        .await;
+    let total: u64 = results.iter().filter_map(|r| r.as_ref().ok()).sum();
+    let failed: Vec<_> = results.into_iter().filter_map(Result::err).collect();
+    println!("Downloaded {} bytes, {} failed", total, failed.len());
+    match failed.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
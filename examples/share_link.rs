@@ -0,0 +1,61 @@
+//! Prints a link to a file in a private bucket that works without credentials for a while
+//!
+//! Usage: `cargo run --example share_link -- <remote name> [hours]`
+//!
+//! Reads B2_APPLICATION_KEY_ID, B2_APPLICATION_KEY, B2_BUCKET_ID and B2_BUCKET_NAME, see [raze::client::B2Config::from_env]. \
+//! The key needs the 'shareFiles' capability.
+use raze::api::{B2GetDownloadAuthParams, BucketId, Capability};
+use raze::client::B2Config;
+use raze::utils::ExpiringDownloadAuth;
+use raze::Error;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let mut args = std::env::args().skip(1);
+    let file_name = args
+        .next()
+        .ok_or_else(|| Error::ConfigError("usage: share_link <remote name> [hours]".into()))?;
+    let hours: u32 = match args.next() {
+        Some(h) => h
+            .parse()
+            .map_err(|_| Error::ConfigError(format!("'{}' is not a number of hours", h)))?,
+        None => 24,
+    };
+
+    let config = B2Config::from_env()?;
+    let (bucket_id, bucket_name) = match (config.bucket_id.clone(), config.bucket_name.clone()) {
+        (Some(id), Some(name)) => (BucketId::new(id), name),
+        _ => {
+            return Err(Error::ConfigError(
+                "B2_BUCKET_ID and B2_BUCKET_NAME must be set".into(),
+            ))
+        }
+    };
+    let client = config.client(config.http_client()).await?;
+    let auth = client.auth();
+    if !auth.can(Capability::ShareFiles) {
+        return Err(Error::ConfigError(
+            "the key lacks the 'shareFiles' capability".into(),
+        ));
+    }
+
+    // B2 tokens last at most a week
+    let valid_for = Duration::from_secs(hours.min(7 * 24) as u64 * 3600);
+    let mut download_auth = ExpiringDownloadAuth::fetch(
+        client.http(),
+        &auth,
+        B2GetDownloadAuthParams {
+            bucket_id,
+            file_name_prefix: file_name.clone(),
+            valid_duration_in_seconds: valid_for.as_secs() as u32,
+        },
+    )
+    .await?
+    .with_margin(Duration::ZERO);
+    let url = download_auth
+        .url_for(client.http(), &auth, &bucket_name, &file_name, valid_for)
+        .await?;
+    println!("{}", url);
+    Ok(())
+}
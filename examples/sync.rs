@@ -0,0 +1,86 @@
+//! Uploads a local directory to a bucket, skipping files that are already there
+//!
+//! Usage: `cargo run --example sync -- <local dir> [prefix]`
+//!
+//! Reads B2_APPLICATION_KEY_ID, B2_APPLICATION_KEY and B2_BUCKET_ID, see [raze::client::B2Config::from_env]. \
+//! Files are compared by name, size and Sha1, nothing is ever deleted remotely. \
+//! If B2_BUCKET_NAME is set too, a manifest is kept next to the files so unchanged files aren't hashed again.
+use raze::client::B2Config;
+use raze::utils::{
+    prefixed_file_name, sync_dir, SyncManifest, SyncOptions, SyncOutcome, UploadUrlPool,
+};
+use raze::Error;
+use std::path::PathBuf;
+
+const CONCURRENCY: usize = 4;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let mut args = std::env::args().skip(1);
+    let dir = PathBuf::from(
+        args.next()
+            .ok_or_else(|| Error::ConfigError("usage: sync <local dir> [prefix]".into()))?,
+    );
+    let prefix = args.next().unwrap_or_default();
+
+    let config = B2Config::from_env()?;
+    let bucket_id = config
+        .bucket_id
+        .clone()
+        .ok_or_else(|| Error::ConfigError("B2_BUCKET_ID is not set".into()))?;
+    let client = config.client(config.http_client()).await?;
+    let pool = UploadUrlPool::new(client.http().clone(), client.auth(), bucket_id, CONCURRENCY);
+    let mut options = SyncOptions::new().with_concurrency(CONCURRENCY);
+    if let Some(bucket_name) = &config.bucket_name {
+        let manifest_name = prefixed_file_name(&prefix, ".raze-manifest.json");
+        let previous =
+            SyncManifest::fetch(client.http(), &client.auth(), bucket_name, &manifest_name).await?;
+        if let Some(previous) = previous {
+            options = options.with_previous_manifest(previous);
+        }
+        options = options.with_manifest(manifest_name);
+    }
+
+    let report = sync_dir(
+        client.http(),
+        &client.auth(),
+        &pool,
+        &dir,
+        &prefix,
+        &options,
+    )
+    .await;
+    let (mut uploaded, mut unchanged, mut skipped, mut failed) = (0, 0, 0, 0);
+    for entry in &report.entries {
+        match &entry.outcome {
+            SyncOutcome::Uploaded(info) => {
+                uploaded += 1;
+                println!("uploaded {}", info.file_name);
+            }
+            SyncOutcome::Unchanged(info) => {
+                unchanged += 1;
+                println!("unchanged {}", info.file_name);
+            }
+            SyncOutcome::Unmodified(entry) => {
+                unchanged += 1;
+                println!("unchanged {}", entry.name);
+            }
+            SyncOutcome::Skipped(reason) => {
+                skipped += 1;
+                println!("skipped {} ({})", entry.path.display(), reason);
+            }
+            SyncOutcome::Failed(e) => {
+                failed += 1;
+                eprintln!("failed {}: {}", entry.path.display(), e);
+            }
+        }
+    }
+    if let Some(Err(e)) = &report.manifest {
+        eprintln!("failed to upload the manifest: {}", e);
+    }
+    println!(
+        "{} uploaded, {} unchanged, {} skipped, {} failed",
+        uploaded, unchanged, skipped, failed
+    );
+    Ok(())
+}
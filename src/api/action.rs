@@ -0,0 +1,81 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// What a file version represents, the 'action' of a [B2FileInfo][crate::api::B2FileInfo]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum Action {
+    /// A file that was uploaded
+    Upload,
+    /// A marker hiding the file, created by b2_hide_file
+    Hide,
+    /// A large file that was started, but not finished or cancelled yet
+    Start,
+    /// A virtual folder, only returned when listing with a delimiter
+    Folder,
+    /// An action this version of raze doesn't know about, as B2 named it
+    Unknown(String),
+}
+
+impl Action {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Action::Upload => "upload",
+            Action::Hide => "hide",
+            Action::Start => "start",
+            Action::Folder => "folder",
+            Action::Unknown(action) => action,
+        }
+    }
+}
+
+impl From<&str> for Action {
+    fn from(s: &str) -> Action {
+        match s {
+            "upload" => Action::Upload,
+            "hide" => Action::Hide,
+            "start" => Action::Start,
+            "folder" => Action::Folder,
+            other => Action::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl Serialize for Action {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Action, D::Error> {
+        let action = String::deserialize(deserializer)?;
+        Ok(Action::from(action.as_str()))
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_serde() {
+        let action: Action = serde_json::from_str(r#""hide""#).unwrap();
+        assert_eq!(action, Action::Hide);
+        let action: Action = serde_json::from_str(r#""archive""#).unwrap();
+        assert_eq!(action, Action::Unknown("archive".to_string()));
+        // Unknown actions are kept, so they can be sent back as they were
+        assert_eq!(serde_json::to_string(&action).unwrap(), r#""archive""#);
+        assert_eq!(
+            serde_json::to_string(&Action::Upload).unwrap(),
+            r#""upload""#
+        );
+        assert_eq!(Action::from("folder"), Action::Folder);
+    }
+}
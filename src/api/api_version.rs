@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Version of the B2 native API that calls are made against
+///
+/// v3 moves the URLs in the [b2_authorize_account][crate::api::b2_authorize_account] response into 'apiInfo',
+/// which is handled when authorizing, so a [B2Auth][crate::api::B2Auth] looks the same for both versions. \
+/// The other calls used by this crate have the same shape in both versions.
+#[derive(
+    Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+    #[default]
+    V2,
+    V3,
+}
+
+impl ApiVersion {
+    /// The version as it appears in URLs, e.g. "v2"
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V2 => "v2",
+            ApiVersion::V3 => "v3",
+        }
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
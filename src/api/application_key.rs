@@ -0,0 +1,104 @@
+use crate::api::redact::Redacted;
+use crate::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// An application key id and its secret, as used by [b2_authorize_account][crate::api::b2_authorize_account]
+///
+/// Debug and Display only show the key id, so the secret doesn't end up in logs \
+/// Parses from the "applicationKeyId:applicationKey" format
+#[derive(Clone, Eq, PartialEq)]
+pub struct ApplicationKey {
+    pub key_id: String,
+    pub key: String,
+}
+
+impl ApplicationKey {
+    pub fn new<T: Into<String>, Q: Into<String>>(key_id: T, key: Q) -> ApplicationKey {
+        ApplicationKey {
+            key_id: key_id.into(),
+            key: key.into(),
+        }
+    }
+
+    /// The value of the 'Authorization' header for b2_authorize_account
+    pub(crate) fn basic_auth(&self) -> String {
+        format!(
+            "Basic {}",
+            base64::encode(format!("{}:{}", self.key_id, self.key))
+        )
+    }
+}
+
+impl FromStr for ApplicationKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((key_id, key)) => Ok(ApplicationKey::new(key_id, key)),
+            None => Err(Error::ConfigError(
+                "application key must have the format \"applicationKeyId:applicationKey\""
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+/// Splits "applicationKeyId:applicationKey"
+///
+/// Without a colon, the whole string is used as the key id and the key is empty, which B2 rejects \
+/// Use [FromStr] to catch this up front instead
+impl From<&str> for ApplicationKey {
+    fn from(s: &str) -> Self {
+        s.parse()
+            .unwrap_or_else(|_| ApplicationKey::new(s, String::new()))
+    }
+}
+
+impl From<String> for ApplicationKey {
+    fn from(s: String) -> Self {
+        ApplicationKey::from(s.as_str())
+    }
+}
+
+impl From<&String> for ApplicationKey {
+    fn from(s: &String) -> Self {
+        ApplicationKey::from(s.as_str())
+    }
+}
+
+impl From<&ApplicationKey> for ApplicationKey {
+    fn from(key: &ApplicationKey) -> Self {
+        key.clone()
+    }
+}
+
+impl fmt::Debug for ApplicationKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ApplicationKey")
+            .field("key_id", &self.key_id)
+            .field("key", &Redacted)
+            .finish()
+    }
+}
+
+impl fmt::Display for ApplicationKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:<redacted>", self.key_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_redact() {
+        let key: ApplicationKey = "0012345:K001secret".parse().unwrap();
+        assert_eq!(key, ApplicationKey::new("0012345", "K001secret"));
+        assert!(!format!("{:?}", key).contains("K001secret"));
+        assert_eq!(key.to_string(), "0012345:<redacted>");
+        assert_eq!(key.basic_auth(), "Basic MDAxMjM0NTpLMDAxc2VjcmV0");
+        assert!("no-colon".parse::<ApplicationKey>().is_err());
+    }
+}
@@ -1,28 +1,192 @@
-use crate::handle_b2error_kinds;
+use crate::api::encoding::{encode_path, encode_segment};
+use crate::api::redact::Redacted;
+use crate::api::{AccountId, ApiVersion, ApplicationKey, BucketId, Capability, FileId};
+use crate::metrics::send;
+use crate::parse_response;
 use crate::Error;
-use base64::encode;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Ord, PartialOrd)]
 #[serde(rename_all = "camelCase")]
 /// An authorization from [b2_authorize_account] - Required for most other calls
 ///
-/// Note: 'allowed' object is currently *unsupported*
+/// The Debug output leaves out the authorization token
 pub struct B2Auth {
-    pub account_id: String,
+    pub account_id: AccountId,
     pub authorization_token: String,
     pub api_url: String,
     pub download_url: String,
     pub absolute_minimum_part_size: usize,
     pub recommended_part_size: usize,
+    /// Base URL for the S3-compatible API, see [S3Auth][crate::s3::S3Auth]
+    #[serde(default)]
+    pub s3_api_url: String,
+    /// When this authorization was obtained, in seconds since the unix epoch
+    ///
+    /// Set by [b2_authorize_account], not part of B2's response, but kept when (de)serializing
+    #[serde(default)]
+    pub issued_at: Option<u64>,
+    /// Which API version [api_url_for][B2Auth::api_url_for] targets
+    #[serde(default)]
+    pub api_version: ApiVersion,
+    /// What the key is allowed to do, see [can][B2Auth::can]
+    ///
+    /// None for authorizations saved before this was kept
+    #[serde(default)]
+    pub allowed: Option<B2Allowed>,
+}
+
+/// The 'allowed' object of an authorization, describing the key's restrictions
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "camelCase", from = "RawAllowed")]
+pub struct B2Allowed {
+    pub capabilities: Vec<Capability>,
+    /// The buckets the key is restricted to, empty if it can access all buckets
+    pub buckets: Vec<AllowedBucket>,
+    /// If set, only files whose names start with this can be accessed
+    pub name_prefix: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "camelCase")]
+pub struct AllowedBucket {
+    pub id: BucketId,
+    /// None if the bucket has been deleted
+    pub name: Option<String>,
+}
+
+// v2 restricts a key to at most one bucket with 'bucketId' and 'bucketName', v3 has a 'buckets' list
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAllowed {
+    capabilities: Vec<Capability>,
+    #[serde(default)]
+    buckets: Option<Vec<AllowedBucket>>,
+    #[serde(default)]
+    bucket_id: Option<BucketId>,
+    #[serde(default)]
+    bucket_name: Option<String>,
+    #[serde(default)]
+    name_prefix: Option<String>,
+}
+
+impl From<RawAllowed> for B2Allowed {
+    fn from(raw: RawAllowed) -> Self {
+        let buckets = match (raw.buckets, raw.bucket_id) {
+            (Some(buckets), _) => buckets,
+            (None, Some(id)) => vec![AllowedBucket {
+                id,
+                name: raw.bucket_name,
+            }],
+            (None, None) => Vec::new(),
+        };
+        B2Allowed {
+            capabilities: raw.capabilities,
+            buckets,
+            name_prefix: raw.name_prefix,
+        }
+    }
+}
+
+impl fmt::Debug for B2Auth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("B2Auth")
+            .field("account_id", &self.account_id)
+            .field("authorization_token", &Redacted)
+            .field("api_url", &self.api_url)
+            .field("download_url", &self.download_url)
+            .field(
+                "absolute_minimum_part_size",
+                &self.absolute_minimum_part_size,
+            )
+            .field("recommended_part_size", &self.recommended_part_size)
+            .field("s3_api_url", &self.s3_api_url)
+            .field("issued_at", &self.issued_at)
+            .field("api_version", &self.api_version)
+            .field("allowed", &self.allowed)
+            .finish()
+    }
+}
+
+/// How long an authorization token from [b2_authorize_account] is valid, 24 hours
+pub const AUTH_TOKEN_LIFETIME_SECS: u64 = 24 * 60 * 60;
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
 
 impl B2Auth {
+    /// Whether the token is likely to be expired, or to expire within the next hour
+    ///
+    /// Tokens are valid for 24 hours, but B2 may invalidate them earlier, so an auth that
+    /// isn't "probably expired" can still fail with 'expired_auth_token' \
+    /// An auth without an 'issued_at' time is always considered expired
+    pub fn is_probably_expired(&self) -> bool {
+        match self.issued_at {
+            Some(issued_at) => unix_now() + 3600 >= issued_at + AUTH_TOKEN_LIFETIME_SECS,
+            None => true,
+        }
+    }
+
+    /// Writes the authorization as JSON to 'path', so it can be reused by later processes with [load][B2Auth::load]
+    ///
+    /// The file contains the authorization token, so on unix it is made readable only by the current user, even if it already existed
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let json = serde_json::to_string(self).map_err(Error::SerdeError)?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path).map_err(Error::IOError)?;
+        // The mode only applies to new files, an existing one is narrowed before the token is written to it
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))
+            .map_err(Error::IOError)?;
+        file.write_all(json.as_bytes()).map_err(Error::IOError)
+    }
+
+    /// Reads an authorization written by [save][B2Auth::save]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<B2Auth, Error> {
+        let json = std::fs::read_to_string(path).map_err(Error::IOError)?;
+        serde_json::from_str(&json).map_err(Error::SerdeError)
+    }
+
     // Given the name of an api call, return the full url for it
     // See https://www.backblaze.com/b2/docs/calling.html "Constructing the URL"
     pub fn api_url_for(&self, call_name: &str) -> String {
-        format!("{}/b2api/v2/{}", self.api_url, call_name)
+        self.api_url_at(self.api_version, call_name)
+    }
+
+    /// Same as [api_url_for][B2Auth::api_url_for], but for a specific API version, for calls that only exist in some versions
+    pub fn api_url_at(&self, api_version: ApiVersion, call_name: &str) -> String {
+        format!("{}/b2api/{}/{}", self.api_url, api_version, call_name)
+    }
+
+    /// Whether the key has the given capability
+    ///
+    /// Use this to check up front whether an operation will be allowed \
+    /// Returns true if the capabilities are unknown, leaving the decision to B2
+    pub fn can(&self, capability: Capability) -> bool {
+        match &self.allowed {
+            Some(allowed) => allowed.capabilities.contains(&capability),
+            None => true,
+        }
+    }
+
+    /// Makes calls using this authorization target another API version
+    ///
+    /// Tokens are valid for every version, so there is no need to authorize again
+    pub fn with_api_version(mut self, api_version: ApiVersion) -> Self {
+        self.api_version = api_version;
+        self
     }
 
     // Given a bucket name and a file name, returns a url for downloading the file
@@ -40,52 +204,238 @@ impl B2Auth {
     // Given a file id, returns a url for download the file
     // See https://www.backblaze.com/b2/docs/calling.html "Download Files by ID"
     // **BEWARE** This is only for use with 'b2_download_file_by_id'
-    pub fn download_url_by_id<T: AsRef<str>>(&self, file_id: T) -> String {
+    pub fn download_url_by_id(&self, file_id: &FileId) -> String {
         format!(
             "{}/b2api/v2/b2_download_file_by_id?fileId={}",
-            self.download_url,
-            file_id.as_ref()
+            self.download_url, file_id
         )
     }
+
+    /// Returns the public URL of a file in an 'allPublic' bucket
+    ///
+    /// Unlike [download_url_by_name][B2Auth::download_url_by_name], each segment of the names is percent-encoded,
+    /// so the URL can be handed to browsers as-is \
+    /// The URL contains no authorization and only works for public buckets
+    pub fn public_url_for<T: AsRef<str>, Q: AsRef<str>>(
+        &self,
+        bucket_name: T,
+        file_name: Q,
+    ) -> String {
+        self.public_url_via(&self.download_url, bucket_name, file_name)
+    }
+
+    /// Same as [public_url_for][B2Auth::public_url_for], but routed via another host, e.g. a CDN
+    ///
+    /// 'download_host' replaces the download_url, e.g. "https://cdn.example.com", keeping B2's
+    /// "/file/<bucket_name>/<file_name>" path layout, which is what proxying CDNs like Cloudflare expect
+    pub fn public_url_via<H: AsRef<str>, T: AsRef<str>, Q: AsRef<str>>(
+        &self,
+        download_host: H,
+        bucket_name: T,
+        file_name: Q,
+    ) -> String {
+        format!(
+            "{}/file/{}/{}",
+            download_host.as_ref().trim_end_matches('/'),
+            encode_segment(bucket_name.as_ref()),
+            encode_path(file_name.as_ref())
+        )
+    }
+}
+
+/// Returns the public URL of a file served from a custom domain or CDN
+///
+/// 'base' replaces the "<download_url>/file/<bucket_name>" part of the URL, e.g. "https://cdn.example.com/assets" \
+/// This is meant for setups where the custom domain points at the root of a public bucket \
+/// Each segment of 'file_name' is percent-encoded
+pub fn public_url_with_base<T: AsRef<str>, Q: AsRef<str>>(base: T, file_name: Q) -> String {
+    format!(
+        "{}/{}",
+        base.as_ref().trim_end_matches('/'),
+        encode_path(file_name.as_ref())
+    )
 }
 
 /// Authenticate with the API - B2Auth is required by other commands
 ///
-/// 'keystring' is a string with the format "applicationKeyId:applicationKey" (Remember the colon)
+/// 'key' is an [ApplicationKey], or a string with the format "applicationKeyId:applicationKey" (Remember the colon)
 ///
 /// <https://www.backblaze.com/b2/docs/b2_authorize_account.html>
-pub async fn b2_authorize_account<T: AsRef<str>>(
+pub async fn b2_authorize_account<K: Into<ApplicationKey>>(
     client: &Client,
-    keystring: T,
+    key: K,
+) -> Result<B2Auth, Error> {
+    b2_authorize_account_at(client, DEFAULT_API_ENDPOINT, key).await
+}
+
+/// Where [b2_authorize_account] authorizes, "https://api.backblazeb2.com"
+pub const DEFAULT_API_ENDPOINT: &str = "https://api.backblazeb2.com";
+
+/// Same as [b2_authorize_account], but against another endpoint, e.g. a proxy or a mock server
+///
+/// 'endpoint' replaces [DEFAULT_API_ENDPOINT], the "/b2api/v2/b2_authorize_account" path is added to it
+pub async fn b2_authorize_account_at<E: AsRef<str>, K: Into<ApplicationKey>>(
+    client: &Client,
+    endpoint: E,
+    key: K,
+) -> Result<B2Auth, Error> {
+    b2_authorize_account_version(client, endpoint, ApiVersion::V2, key).await
+}
+
+/// Same as [b2_authorize_account_at], but using the given API version
+///
+/// The returned [B2Auth] makes all calls with 'api_version'
+pub async fn b2_authorize_account_version<E: AsRef<str>, K: Into<ApplicationKey>>(
+    client: &Client,
+    endpoint: E,
+    api_version: ApiVersion,
+    key: K,
 ) -> Result<B2Auth, Error> {
     // Encode the key
-    let encoded = format!("{}{}", "Basic ", encode(keystring.as_ref()));
+    let encoded = key.into().basic_auth();
 
     // Submit the request
-    let resp = match client
-        .get("https://api.backblazeb2.com/b2api/v2/b2_authorize_account")
-        .header(reqwest::header::AUTHORIZATION, encoded)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    // If it didn't succeed, return ReqwestError
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
-
-    // Read the response to a string containing the JSON response
-    let response_string = resp.text().await.unwrap();
-    // Attempt to deserialize the JSON
-    // There are 3 cases here
-    // 1. API call succeeded and it deserializes to a B2Auth struct
-    // 2. API call succeeded but response is an API Error - returns B2Error
-    // 3. API call went through, but response matches neither B2Auth nor B2Error - returns SerdeError
-    let deserialized: B2Auth = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => return Err(handle_b2error_kinds(&response_string)),
-    };
+    let resp = send(
+        "b2_authorize_account",
+        client
+            .get(format!(
+                "{}/b2api/{}/b2_authorize_account",
+                endpoint.as_ref().trim_end_matches('/'),
+                api_version
+            ))
+            .header(reqwest::header::AUTHORIZATION, encoded),
+    )
+    .await?;
+
+    let response: AuthResponse = parse_response("b2_authorize_account", resp).await?;
+    let mut deserialized = B2Auth::from(response);
+    deserialized.issued_at = Some(unix_now());
+    deserialized.api_version = api_version;
     Ok(deserialized)
 }
+
+// The v3 response, which nests the URLs and part sizes in 'apiInfo.storageApi'
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AuthResponseV3 {
+    account_id: AccountId,
+    authorization_token: String,
+    api_info: ApiInfoV3,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiInfoV3 {
+    storage_api: StorageApiV3,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StorageApiV3 {
+    api_url: String,
+    download_url: String,
+    absolute_minimum_part_size: usize,
+    recommended_part_size: usize,
+    #[serde(default)]
+    s3_api_url: String,
+    #[serde(default)]
+    allowed: Option<B2Allowed>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AuthResponse {
+    V2(B2Auth),
+    V3(AuthResponseV3),
+}
+
+// Either version of the response as a B2Auth
+impl From<AuthResponse> for B2Auth {
+    fn from(response: AuthResponse) -> B2Auth {
+        match response {
+            AuthResponse::V2(auth) => auth,
+            AuthResponse::V3(v3) => {
+                let storage = v3.api_info.storage_api;
+                B2Auth {
+                    account_id: v3.account_id,
+                    authorization_token: v3.authorization_token,
+                    api_url: storage.api_url,
+                    download_url: storage.download_url,
+                    absolute_minimum_part_size: storage.absolute_minimum_part_size,
+                    recommended_part_size: storage.recommended_part_size,
+                    s3_api_url: storage.s3_api_url,
+                    issued_at: None,
+                    api_version: ApiVersion::V3,
+                    allowed: storage.allowed,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_v3_auth() {
+        let json = r#"{
+            "accountId": "abc123",
+            "authorizationToken": "4_token",
+            "apiInfo": {
+                "storageApi": {
+                    "absoluteMinimumPartSize": 5000000,
+                    "apiUrl": "https://api001.backblazeb2.com",
+                    "allowed": {
+                        "buckets": [{"id": "bucket1", "name": "photos"}],
+                        "capabilities": ["listBuckets", "readFiles"],
+                        "namePrefix": null
+                    },
+                    "downloadUrl": "https://f001.backblazeb2.com",
+                    "infoType": "storageApi",
+                    "recommendedPartSize": 100000000,
+                    "s3ApiUrl": "https://s3.us-west-001.backblazeb2.com"
+                }
+            },
+            "applicationKeyExpirationTimestamp": null
+        }"#;
+        let auth = B2Auth::from(serde_json::from_str::<AuthResponse>(json).unwrap());
+        assert_eq!(auth.api_url, "https://api001.backblazeb2.com");
+        assert_eq!(auth.recommended_part_size, 100000000);
+        assert_eq!(
+            auth.api_url_for("b2_list_buckets"),
+            "https://api001.backblazeb2.com/b2api/v3/b2_list_buckets"
+        );
+        assert!(auth.can(Capability::ReadFiles));
+        assert!(!auth.can(Capability::WriteFiles));
+        assert_eq!(auth.allowed.as_ref().unwrap().buckets[0].id, "bucket1");
+        let v2 = auth.with_api_version(ApiVersion::V2);
+        assert!(v2.api_url_for("b2_list_buckets").contains("/b2api/v2/"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_narrows_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("raze-auth-{}.json", std::process::id()));
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let auth = B2Auth {
+            account_id: "a".into(),
+            authorization_token: "4_token".to_string(),
+            api_url: String::new(),
+            download_url: String::new(),
+            absolute_minimum_part_size: 0,
+            recommended_part_size: 0,
+            s3_api_url: String::new(),
+            issued_at: Some(1),
+            api_version: ApiVersion::V2,
+            allowed: None,
+        };
+        auth.save(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(B2Auth::load(&path).unwrap().issued_at, Some(1));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,40 @@
+use crate::api::post_json;
+use crate::api::{AccountId, B2Auth, BucketId, FileId};
+use crate::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CancelLargeFileBody<'a> {
+    file_id: &'a str,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "camelCase")]
+/// Result object from [b2_cancel_large_file]
+pub struct CancelLargeFileResult {
+    pub file_id: FileId,
+    pub account_id: AccountId,
+    pub bucket_id: BucketId,
+    pub file_name: String,
+}
+
+/// <https://www.backblaze.com/b2/docs/b2_cancel_large_file.html>
+///
+/// Deletes the parts uploaded so far for an unfinished large file
+pub async fn b2_cancel_large_file(
+    client: &Client,
+    auth: &B2Auth,
+    file_id: &FileId,
+) -> Result<CancelLargeFileResult, Error> {
+    post_json(
+        client,
+        auth,
+        "b2_cancel_large_file",
+        &CancelLargeFileBody {
+            file_id: file_id.as_ref(),
+        },
+    )
+    .await
+}
@@ -0,0 +1,48 @@
+use crate::api::post_json;
+use crate::api::{B2Auth, B2FileInfo, BucketId, FileId};
+use crate::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Whether [b2_copy_file] keeps the source's metadata or replaces it
+///
+/// With REPLACE, 'content_type' **must** be set in [B2CopyFileParams]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum MetadataDirective {
+    Copy,
+    Replace,
+}
+
+/// Parameters for [b2_copy_file]
+///
+/// 'destination_bucket_id' defaults to the source's bucket \
+/// 'range' copies only part of the source, e.g. "bytes=0-99" \
+/// 'content_type' and 'file_info' must be None when using [MetadataDirective::Copy]
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct B2CopyFileParams {
+    pub source_file_id: FileId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination_bucket_id: Option<BucketId>,
+    pub file_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<String>,
+    pub metadata_directive: MetadataDirective,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_info: Option<HashMap<String, String>>,
+}
+
+/// <https://www.backblaze.com/b2/docs/b2_copy_file.html>
+///
+/// Creates a new file from an existing one without downloading and re-uploading it
+pub async fn b2_copy_file(
+    client: &Client,
+    auth: &B2Auth,
+    params: B2CopyFileParams,
+) -> Result<B2FileInfo, Error> {
+    post_json(client, auth, "b2_copy_file", &params).await
+}
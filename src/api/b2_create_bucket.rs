@@ -1,52 +1,132 @@
-use crate::api::{B2Auth, B2BucketType, BucketResult};
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+use crate::api::{
+    B2Auth, B2BucketType, BucketResult, DefaultRetention, ReplicationConfiguration,
+    ServerSideEncryption,
+};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct CreateBucketBody<'a> {
     account_id: &'a str,
-    bucket_name: &'a str,
-    bucket_type: B2BucketType,
+    #[serde(flatten)]
+    params: &'a CreateBucketRequest,
+}
+
+/// Parameters for [b2_create_bucket_with]
+///
+/// 'bucket_name' and 'bucket_type' are required, the other settings are set with the builder methods
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateBucketRequest {
+    pub bucket_name: String,
+    pub bucket_type: B2BucketType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_server_side_encryption: Option<ServerSideEncryption>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub file_lock_enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_retention: Option<DefaultRetention>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replication_configuration: Option<ReplicationConfiguration>,
+}
+
+impl CreateBucketRequest {
+    pub fn new<T: Into<String>>(bucket_name: T, bucket_type: B2BucketType) -> CreateBucketRequest {
+        CreateBucketRequest {
+            bucket_name: bucket_name.into(),
+            bucket_type,
+            default_server_side_encryption: None,
+            file_lock_enabled: false,
+            default_retention: None,
+            replication_configuration: None,
+        }
+    }
+
+    /// The encryption of files uploaded without their own encryption settings
+    pub fn default_server_side_encryption(mut self, encryption: ServerSideEncryption) -> Self {
+        self.default_server_side_encryption = Some(encryption);
+        self
+    }
+
+    /// Enables Object Lock, so files can be protected from deletion for a retention period
+    pub fn enable_file_lock(mut self) -> Self {
+        self.file_lock_enabled = true;
+        self
+    }
+
+    /// The retention of new files, requires [enable_file_lock][CreateBucketRequest::enable_file_lock]
+    pub fn default_retention(mut self, retention: DefaultRetention) -> Self {
+        self.default_retention = Some(retention);
+        self
+    }
+
+    /// Replication to or from other buckets, see [ReplicationConfiguration]
+    pub fn replication_configuration(mut self, replication: ReplicationConfiguration) -> Self {
+        self.replication_configuration = Some(replication);
+        self
+    }
 }
 
 /// <https://www.backblaze.com/b2/docs/b2_create_bucket.html>
+///
+/// See [b2_create_bucket_with] for creating buckets with other settings
 pub async fn b2_create_bucket<T: AsRef<str>>(
     client: &Client,
     auth: &B2Auth,
     bucket_name: T,
     bucket_type: B2BucketType,
 ) -> Result<BucketResult, Error> {
-    let req_body = serde_json::to_string(&CreateBucketBody {
-        account_id: &auth.account_id,
-        bucket_name: bucket_name.as_ref(),
-        bucket_type,
-    })
-    .unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_create_bucket"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
+    b2_create_bucket_with(
+        client,
+        auth,
+        CreateBucketRequest::new(bucket_name.as_ref(), bucket_type),
+    )
+    .await
+}
 
-    let response_string = resp.text().await.unwrap();
-    let deserialized: BucketResult = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
-    Ok(deserialized)
+/// <https://www.backblaze.com/b2/docs/b2_create_bucket.html>
+pub async fn b2_create_bucket_with(
+    client: &Client,
+    auth: &B2Auth,
+    params: CreateBucketRequest,
+) -> Result<BucketResult, Error> {
+    post_json(
+        client,
+        auth,
+        "b2_create_bucket",
+        &CreateBucketBody {
+            account_id: &auth.account_id,
+            params: &params,
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{RetentionMode, RetentionPeriodUnit};
+
+    #[test]
+    fn test_create_bucket_body() {
+        let params = CreateBucketRequest::new("archive", B2BucketType::AllPrivate)
+            .enable_file_lock()
+            .default_retention(DefaultRetention::new(
+                RetentionMode::Governance,
+                30,
+                RetentionPeriodUnit::Days,
+            ));
+        let body: serde_json::Value = serde_json::to_value(&CreateBucketBody {
+            account_id: "a",
+            params: &params,
+        })
+        .unwrap();
+        assert_eq!(body["fileLockEnabled"], true);
+        assert_eq!(body["defaultRetention"]["mode"], "governance");
+        assert_eq!(body["defaultRetention"]["period"]["duration"], 30);
+        assert!(body.get("defaultServerSideEncryption").is_none());
+    }
 }
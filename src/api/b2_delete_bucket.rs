@@ -1,5 +1,5 @@
-use crate::api::{B2Auth, BucketResult};
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+use crate::api::{B2Auth, BucketId, BucketResult};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -12,38 +12,19 @@ struct DeleteBucketBody<'a> {
 }
 
 /// <https://www.backblaze.com/b2/docs/b2_delete_bucket.html>
-pub async fn b2_delete_bucket<T: AsRef<str>>(
+pub async fn b2_delete_bucket(
     client: &Client,
     auth: &B2Auth,
-    bucket_id: T,
+    bucket_id: &BucketId,
 ) -> Result<BucketResult, Error> {
-    let req_body = serde_json::to_string(&DeleteBucketBody {
-        account_id: &auth.account_id,
-        bucket_id: bucket_id.as_ref(),
-    })
-    .unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_delete_bucket"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
-
-    let response_string = resp.text().await.unwrap();
-    let deserialized: BucketResult = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
-    Ok(deserialized)
+    post_json(
+        client,
+        auth,
+        "b2_delete_bucket",
+        &DeleteBucketBody {
+            account_id: &auth.account_id,
+            bucket_id: bucket_id.as_ref(),
+        },
+    )
+    .await
 }
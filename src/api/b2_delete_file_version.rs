@@ -1,5 +1,5 @@
-use crate::api::B2Auth;
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+use crate::api::{B2Auth, FileId};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -16,43 +16,24 @@ struct DeleteFileVersionBody<'a> {
 /// Result object from [b2_delete_file_version]
 pub struct DeleteFileVersionResult {
     pub file_name: String,
-    pub file_id: String,
+    pub file_id: FileId,
 }
 
 /// <https://www.backblaze.com/b2/docs/b2_delete_file_version.html>
-pub async fn b2_delete_file_version<T: AsRef<str>, Q: AsRef<str>>(
+pub async fn b2_delete_file_version<T: AsRef<str>>(
     client: &Client,
     auth: &B2Auth,
     file_name: T,
-    file_id: Q,
+    file_id: &FileId,
 ) -> Result<DeleteFileVersionResult, Error> {
-    let req_body = serde_json::to_string(&DeleteFileVersionBody {
-        file_name: file_name.as_ref(),
-        file_id: file_id.as_ref(),
-    })
-    .unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_delete_file_version"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
-
-    let response_string = resp.text().await.unwrap();
-    let deserialized: DeleteFileVersionResult = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
-    Ok(deserialized)
+    post_json(
+        client,
+        auth,
+        "b2_delete_file_version",
+        &DeleteFileVersionBody {
+            file_name: file_name.as_ref(),
+            file_id: file_id.as_ref(),
+        },
+    )
+    .await
 }
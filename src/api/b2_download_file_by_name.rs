@@ -1,4 +1,5 @@
 use crate::api::{B2Auth, B2DownloadAuth};
+use crate::metrics::send;
 use crate::Error;
 use reqwest::{Client, Response};
 use serde::{Deserialize, Serialize};
@@ -9,10 +10,20 @@ use serde::{Deserialize, Serialize};
 ///
 /// Note that authorization is only required if you want to make use of the prefix and/or expiration offered by b2_get_download_authorization
 /// If authorization is None, the B2Auth is used instead
+///
+/// For public buckets behind a CDN (e.g. Cloudflare via the Bandwidth Alliance), set 'download_host' to the CDN's
+/// base URL, e.g. "https://cdn.example.com", and 'omit_authorization' to true, so responses can be cached
 pub struct B2DownloadFileByNameParams {
     pub bucket_name: String,
     pub file_name: String,
     pub authorization: Option<B2DownloadAuth>,
+    /// Replaces the B2Auth's download_url, keeping the "/file/<bucket_name>/<file_name>" path layout \
+    /// The URL is built with [public_url_via][B2Auth::public_url_via], so the file name is percent-encoded
+    pub download_host: Option<String>,
+    /// Don't send an Authorization header at all - Only works for public buckets
+    pub omit_authorization: bool,
+    /// Only download part of the file, as an HTTP 'Range' header value, e.g. "bytes=0-99"
+    pub range: Option<String>,
 }
 
 /// <https://www.backblaze.com/b2/docs/b2_download_file_by_name.html>
@@ -26,18 +37,17 @@ pub async fn b2_download_file_by_name(
         None => &auth.authorization_token,
     };
 
-    let resp = match client
-        .get(&auth.download_url_by_name(&params.bucket_name, &params.file_name))
-        .header(reqwest::header::AUTHORIZATION, auth_token)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
+    let url = match params.download_host {
+        Some(ref host) => auth.public_url_via(host, &params.bucket_name, &params.file_name),
+        None => auth.download_url_by_name(&params.bucket_name, &params.file_name),
     };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
 
-    Ok(resp)
+    let mut req = client.get(url);
+    if !params.omit_authorization {
+        req = req.header(reqwest::header::AUTHORIZATION, auth_token);
+    }
+    if let Some(ref range) = params.range {
+        req = req.header(reqwest::header::RANGE, range);
+    }
+    send("b2_download_file_by_name", req).await
 }
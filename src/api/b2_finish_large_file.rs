@@ -0,0 +1,33 @@
+use crate::api::post_json;
+use crate::api::{B2Auth, B2FileInfo, FileId};
+use crate::Error;
+use reqwest::Client;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct FinishLargeFileBody<'a> {
+    file_id: &'a str,
+    part_sha1_array: &'a [String],
+}
+
+/// <https://www.backblaze.com/b2/docs/b2_finish_large_file.html>
+///
+/// 'part_sha1_array' contains the Sha1 of every uploaded part, ordered by part number
+pub async fn b2_finish_large_file(
+    client: &Client,
+    auth: &B2Auth,
+    file_id: &FileId,
+    part_sha1_array: &[String],
+) -> Result<B2FileInfo, Error> {
+    post_json(
+        client,
+        auth,
+        "b2_finish_large_file",
+        &FinishLargeFileBody {
+            file_id: file_id.as_ref(),
+            part_sha1_array,
+        },
+    )
+    .await
+}
@@ -0,0 +1,31 @@
+use crate::api::post_json_at;
+use crate::api::{ApiVersion, B2Auth, BucketId, BucketNotificationRules};
+use crate::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct GetBucketNotificationRulesBody<'a> {
+    bucket_id: &'a str,
+}
+
+/// <https://www.backblaze.com/apidocs/b2-get-bucket-notification-rules>
+///
+/// Only available in version 3 of the API, which is used regardless of the B2Auth's 'api_version'
+pub async fn b2_get_bucket_notification_rules(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+) -> Result<BucketNotificationRules, Error> {
+    post_json_at(
+        client,
+        auth,
+        ApiVersion::V3,
+        "b2_get_bucket_notification_rules",
+        &GetBucketNotificationRulesBody {
+            bucket_id: bucket_id.as_ref(),
+        },
+    )
+    .await
+}
@@ -1,20 +1,32 @@
-use crate::api::B2Auth;
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+use crate::api::redact::Redacted;
+use crate::api::{B2Auth, BucketId};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// Authorization used to download files from a bucket
-/// Required by b2_download_file_by_name and b2_download_file_by_iduse serde::{Deserialize, Serialize};
-
-#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+/// Required by b2_download_file_by_name and b2_download_file_by_id \
+/// The Debug output leaves out the authorization token
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct B2DownloadAuth {
-    pub bucket_id: String,
+    pub bucket_id: BucketId,
     pub file_name_prefix: String,
     pub authorization_token: String,
 }
 
+impl fmt::Debug for B2DownloadAuth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("B2DownloadAuth")
+            .field("bucket_id", &self.bucket_id)
+            .field("file_name_prefix", &self.file_name_prefix)
+            .field("authorization_token", &Redacted)
+            .finish()
+    }
+}
+
 /// Parameters for the request
 ///
 /// The B2Auth token must have the 'shareFiles' capability
@@ -22,7 +34,7 @@ pub struct B2DownloadAuth {
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct B2GetDownloadAuthParams {
-    pub bucket_id: String,
+    pub bucket_id: BucketId,
     pub file_name_prefix: String,
     pub valid_duration_in_seconds: u32,
 }
@@ -33,29 +45,5 @@ pub async fn b2_get_download_authorization(
     auth: &B2Auth,
     params: B2GetDownloadAuthParams,
 ) -> Result<B2DownloadAuth, Error> {
-    let req_body = serde_json::to_string(&params).unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_get_download_authorization"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
-
-    let response_string = resp.text().await.unwrap();
-    let deserialized: B2DownloadAuth = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
-    Ok(deserialized)
+    post_json(client, auth, "b2_get_download_authorization", &params).await
 }
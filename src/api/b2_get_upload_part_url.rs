@@ -0,0 +1,52 @@
+use crate::api::post_json;
+use crate::api::redact::Redacted;
+use crate::api::{B2Auth, FileId};
+use crate::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct GetUploadPartUrlBody<'a> {
+    file_id: &'a str,
+}
+
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "camelCase")]
+/// Authorization and URL for uploading parts with [b2_upload_part][crate::api::b2_upload_part]
+///
+/// Like [UploadAuth][crate::api::UploadAuth], this should **NOT** be shared - each concurrent part upload needs its own \
+/// The Debug output leaves out the authorization token
+pub struct UploadPartAuth {
+    pub file_id: FileId,
+    pub upload_url: String,
+    pub authorization_token: String,
+}
+
+impl fmt::Debug for UploadPartAuth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UploadPartAuth")
+            .field("file_id", &self.file_id)
+            .field("upload_url", &self.upload_url)
+            .field("authorization_token", &Redacted)
+            .finish()
+    }
+}
+
+/// <https://www.backblaze.com/b2/docs/b2_get_upload_part_url.html>
+pub async fn b2_get_upload_part_url(
+    client: &Client,
+    auth: &B2Auth,
+    file_id: &FileId,
+) -> Result<UploadPartAuth, Error> {
+    post_json(
+        client,
+        auth,
+        "b2_get_upload_part_url",
+        &GetUploadPartUrlBody {
+            file_id: file_id.as_ref(),
+        },
+    )
+    .await
+}
@@ -1,8 +1,10 @@
-use crate::api::B2Auth;
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+use crate::api::redact::Redacted;
+use crate::api::{B2Auth, BucketId};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -10,50 +12,42 @@ struct GetUploadUrlBody<'a> {
     bucket_id: &'a str,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Ord, PartialOrd)]
 #[serde(rename_all = "camelCase")]
 /// Authorization and URL for uploading with [b2_upload_file][crate::api::b2_upload_file] - Distinct from B2Auth
 ///
 /// Note that this should **NOT** be shared - each concurrent upload needs its own UploadAuth
-/// Needed for [b2_upload_file][crate::api::b2_upload_file]
+/// Needed for [b2_upload_file][crate::api::b2_upload_file] \
+/// The Debug output leaves out the authorization token
 pub struct UploadAuth {
-    pub bucket_id: String,
+    pub bucket_id: BucketId,
     pub upload_url: String,
     pub authorization_token: String,
 }
 
+impl fmt::Debug for UploadAuth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UploadAuth")
+            .field("bucket_id", &self.bucket_id)
+            .field("upload_url", &self.upload_url)
+            .field("authorization_token", &Redacted)
+            .finish()
+    }
+}
+
 /// <https://www.backblaze.com/b2/docs/b2_get_upload_url.html>
-pub async fn b2_get_upload_url<T: AsRef<str>>(
+pub async fn b2_get_upload_url(
     client: &Client,
     auth: &B2Auth,
-    bucket_id: T,
+    bucket_id: &BucketId,
 ) -> Result<UploadAuth, Error> {
-    let req_body = serde_json::to_string(&GetUploadUrlBody {
-        bucket_id: bucket_id.as_ref(),
-    })
-    .unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_get_upload_url"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
-
-    let response_string = resp.text().await.unwrap();
-    let deserialized: UploadAuth = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
-    Ok(deserialized)
+    post_json(
+        client,
+        auth,
+        "b2_get_upload_url",
+        &GetUploadUrlBody {
+            bucket_id: bucket_id.as_ref(),
+        },
+    )
+    .await
 }
@@ -1,5 +1,5 @@
-use crate::api::{B2Auth, B2FileInfo};
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+use crate::api::{B2Auth, B2FileInfo, BucketId};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -12,39 +12,20 @@ struct HideFileBody<'a> {
 }
 
 /// <https://www.backblaze.com/b2/docs/b2_delete_file_version.html>
-pub async fn b2_hide_file<T: AsRef<str>, Q: AsRef<str>>(
+pub async fn b2_hide_file<Q: AsRef<str>>(
     client: &Client,
     auth: &B2Auth,
-    bucket_id: T,
+    bucket_id: &BucketId,
     file_name: Q,
 ) -> Result<B2FileInfo, Error> {
-    let req_body = serde_json::to_string(&HideFileBody {
-        bucket_id: bucket_id.as_ref(),
-        file_name: file_name.as_ref(),
-    })
-    .unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_hide_file"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
-
-    let response_string = resp.text().await.unwrap();
-    let deserialized: B2FileInfo = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
-    Ok(deserialized)
+    post_json(
+        client,
+        auth,
+        "b2_hide_file",
+        &HideFileBody {
+            bucket_id: bucket_id.as_ref(),
+            file_name: file_name.as_ref(),
+        },
+    )
+    .await
 }
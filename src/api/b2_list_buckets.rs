@@ -1,5 +1,5 @@
-use crate::api::{B2Auth, BucketResult};
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+use crate::api::{B2Auth, BucketId, BucketResult};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "camelCase")]
 struct ListBucketsBody<'a> {
     account_id: &'a str,
-    bucket_id: Option<String>,
+    bucket_id: Option<BucketId>,
     bucket_name: Option<String>,
     bucket_types: Option<String>,
 }
@@ -21,7 +21,7 @@ struct ListBucketsResult {
 
 /// Represents the optional parameters
 pub struct ListBucketParams {
-    pub bucket_id: Option<String>,
+    pub bucket_id: Option<BucketId>,
     pub bucket_name: Option<String>,
     pub bucket_types: Option<String>,
 }
@@ -32,35 +32,17 @@ pub async fn b2_list_buckets(
     auth: &B2Auth,
     params: ListBucketParams,
 ) -> Result<Vec<BucketResult>, Error> {
-    let req_body = serde_json::to_string(&ListBucketsBody {
-        account_id: &auth.account_id,
-        bucket_id: params.bucket_id,
-        bucket_name: params.bucket_name,
-        bucket_types: params.bucket_types,
-    })
-    .unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_list_buckets"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
-
-    let response_string = resp.text().await.unwrap();
-    let deserialized: ListBucketsResult = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
+    let deserialized: ListBucketsResult = post_json(
+        client,
+        auth,
+        "b2_list_buckets",
+        &ListBucketsBody {
+            account_id: &auth.account_id,
+            bucket_id: params.bucket_id,
+            bucket_name: params.bucket_name,
+            bucket_types: params.bucket_types,
+        },
+    )
+    .await?;
     Ok(deserialized.buckets)
 }
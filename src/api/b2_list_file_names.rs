@@ -1,65 +1,111 @@
-use crate::api::{B2Auth, B2FileInfo};
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+#[cfg(feature = "utils")]
+use crate::api::{post, ListingBody};
+use crate::api::{B2Auth, B2FileInfo, BucketId, ListCursor, Page};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
+/// Parameters for [b2_list_file_names]
+///
+/// Only 'bucket_id' is required, the other parameters are set with the builder methods, e.g.
+/// ```rust
+/// # use raze::api::*;
+/// let request = ListFileNamesRequest::new(BucketId::new("bucket_id"))
+///     .prefix("photos/")
+///     .delimiter("/")
+///     .max_file_count(1000);
+/// ```
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
-struct ListFileNamesBody<'a> {
-    bucket_id: &'a str,
-    start_file_name: &'a str,
-    max_file_count: u32,
+pub struct ListFileNamesRequest {
+    pub bucket_id: BucketId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_file_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
-#[serde(rename_all = "camelCase")]
-/// Contains up to `max_file_count` files and potentially where to continue from with [b2_list_file_names]
-pub struct ListFilesResult {
-    pub files: Vec<B2FileInfo>,
-    pub next_file_name: Option<String>,
+impl ListFileNamesRequest {
+    pub fn new(bucket_id: BucketId) -> ListFileNamesRequest {
+        ListFileNamesRequest {
+            bucket_id,
+            start_file_name: None,
+            max_file_count: None,
+            prefix: None,
+            delimiter: None,
+        }
+    }
+
+    /// The first file name to return, e.g. the 'next_file_name' of a previous call
+    pub fn start_file_name<T: Into<String>>(mut self, start_file_name: T) -> Self {
+        self.start_file_name = Some(start_file_name.into());
+        self
+    }
+
+    /// Continues a listing where a [ListCursor] from an earlier page left off
+    pub fn resume_from(mut self, cursor: &ListCursor) -> Self {
+        self.start_file_name = cursor.file_name.clone();
+        self
+    }
+
+    /// At most 10000, defaults to 100 \
+    /// Note that every 1000 files are billed as a separate transaction
+    pub fn max_file_count(mut self, max_file_count: u32) -> Self {
+        self.max_file_count = Some(max_file_count);
+        self
+    }
+
+    /// Only return files whose names start with 'prefix'
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Collapse names containing 'delimiter' after the prefix into a single "folder" entry
+    pub fn delimiter<T: Into<String>>(mut self, delimiter: T) -> Self {
+        self.delimiter = Some(delimiter.into());
+        self
+    }
 }
 
+/// Contains up to `max_file_count` files and potentially where to continue from with [b2_list_file_names]
+pub type ListFilesResult = Page<B2FileInfo>;
+
 /// <https://www.backblaze.com/b2/docs/b2_list_file_names.html>
 ///
 /// Note billing behavior regarding 'max_file_count' \
-/// Leaving 'start_file_name' empty will go from the first file \
-/// May return a 'next_file_name' which can be used to continue from where the previous call ended
-pub async fn b2_list_file_names<T: AsRef<str>, Q: AsRef<str>>(
+/// Without a 'start_file_name', listing starts from the first file \
+/// May return a 'next_file_name' which can be used to continue from where the previous call ended,
+/// see [into_stream][Page::into_stream] to go through every page
+pub async fn b2_list_file_names(
     client: &Client,
     auth: &B2Auth,
-    bucket_id: T,
-    start_file_name: Q,
-    max_file_count: u32,
+    params: ListFileNamesRequest,
 ) -> Result<ListFilesResult, Error> {
-    let req_body = serde_json::to_string(&ListFileNamesBody {
-        bucket_id: bucket_id.as_ref(),
-        start_file_name: start_file_name.as_ref(),
-        max_file_count,
-    })
-    .unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_list_file_names"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
+    post_json(client, auth, "b2_list_file_names", &params).await
+}
 
-    let response_string = resp.text().await.unwrap();
-    let deserialized: ListFilesResult = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
-    Ok(deserialized)
+/// Like [b2_list_file_names], but returns the response unparsed, to read [B2FileInfoRef][crate::api::B2FileInfoRef]s from it
+///
+/// See [ListingBody]
+#[cfg(feature = "utils")]
+pub async fn b2_list_file_names_body(
+    client: &Client,
+    auth: &B2Auth,
+    params: ListFileNamesRequest,
+) -> Result<ListingBody, Error> {
+    let resp = post(
+        client,
+        auth,
+        auth.api_version,
+        "b2_list_file_names",
+        &params,
+    )
+    .await?;
+    ListingBody::read(resp).await
 }
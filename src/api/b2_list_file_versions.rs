@@ -0,0 +1,110 @@
+use crate::api::post_json;
+#[cfg(feature = "utils")]
+use crate::api::{post, ListingBody};
+use crate::api::{B2Auth, B2FileInfo, BucketId, FileId, ListCursor, Page};
+use crate::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+/// Parameters for [b2_list_file_versions]
+///
+/// Only 'bucket_id' is required, the other parameters are set with the builder methods, see [ListFileNamesRequest][crate::api::ListFileNamesRequest]
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListFileVersionsRequest {
+    pub bucket_id: BucketId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_file_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_file_id: Option<FileId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
+}
+
+impl ListFileVersionsRequest {
+    pub fn new(bucket_id: BucketId) -> ListFileVersionsRequest {
+        ListFileVersionsRequest {
+            bucket_id,
+            start_file_name: None,
+            start_file_id: None,
+            max_file_count: None,
+            prefix: None,
+            delimiter: None,
+        }
+    }
+
+    /// The first file name to return
+    pub fn start_file_name<T: Into<String>>(mut self, start_file_name: T) -> Self {
+        self.start_file_name = Some(start_file_name.into());
+        self
+    }
+
+    /// The first version of 'start_file_name' to return, requires 'start_file_name' to be set
+    pub fn start_file_id(mut self, start_file_id: FileId) -> Self {
+        self.start_file_id = Some(start_file_id);
+        self
+    }
+
+    /// Continues a listing where a [ListCursor] from an earlier page left off
+    pub fn resume_from(mut self, cursor: &ListCursor) -> Self {
+        self.start_file_name = cursor.file_name.clone();
+        self.start_file_id = cursor.file_id.clone();
+        self
+    }
+
+    /// At most 10000, defaults to 100 \
+    /// Note that every 1000 versions are billed as a separate transaction
+    pub fn max_file_count(mut self, max_file_count: u32) -> Self {
+        self.max_file_count = Some(max_file_count);
+        self
+    }
+
+    /// Only return versions of files whose names start with 'prefix'
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Collapse names containing 'delimiter' after the prefix into a single "folder" entry
+    pub fn delimiter<T: Into<String>>(mut self, delimiter: T) -> Self {
+        self.delimiter = Some(delimiter.into());
+        self
+    }
+}
+
+/// <https://www.backblaze.com/b2/docs/b2_list_file_versions.html>
+///
+/// Versions are sorted by file name, and the versions of one name from newest to oldest \
+/// May return a 'next_file_name' and 'next_file_id' to continue from,
+/// see [into_stream][Page::into_stream] to go through every page
+pub async fn b2_list_file_versions(
+    client: &Client,
+    auth: &B2Auth,
+    params: ListFileVersionsRequest,
+) -> Result<Page<B2FileInfo>, Error> {
+    post_json(client, auth, "b2_list_file_versions", &params).await
+}
+
+/// Like [b2_list_file_versions], but returns the response unparsed, to read [B2FileInfoRef][crate::api::B2FileInfoRef]s from it
+///
+/// See [ListingBody]
+#[cfg(feature = "utils")]
+pub async fn b2_list_file_versions_body(
+    client: &Client,
+    auth: &B2Auth,
+    params: ListFileVersionsRequest,
+) -> Result<ListingBody, Error> {
+    let resp = post(
+        client,
+        auth,
+        auth.api_version,
+        "b2_list_file_versions",
+        &params,
+    )
+    .await?;
+    ListingBody::read(resp).await
+}
@@ -0,0 +1,36 @@
+use crate::api::post_json_at;
+use crate::api::{ApiVersion, B2Auth, BucketId, BucketNotificationRules, NotificationRule};
+use crate::Error;
+use reqwest::Client;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct SetBucketNotificationRulesBody<'a> {
+    bucket_id: &'a str,
+    event_notification_rules: &'a [NotificationRule],
+}
+
+/// <https://www.backblaze.com/apidocs/b2-set-bucket-notification-rules>
+///
+/// Replaces all rules of the bucket, pass an empty slice to remove them. \
+/// B2 sends a test event to the webhooks of enabled rules before saving, and fails the call if they don't accept it. \
+/// Only available in version 3 of the API, which is used regardless of the B2Auth's 'api_version'
+pub async fn b2_set_bucket_notification_rules(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+    rules: &[NotificationRule],
+) -> Result<BucketNotificationRules, Error> {
+    post_json_at(
+        client,
+        auth,
+        ApiVersion::V3,
+        "b2_set_bucket_notification_rules",
+        &SetBucketNotificationRulesBody {
+            bucket_id: bucket_id.as_ref(),
+            event_notification_rules: rules,
+        },
+    )
+    .await
+}
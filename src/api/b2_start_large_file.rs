@@ -0,0 +1,50 @@
+use crate::api::post_json;
+use crate::api::{B2Auth, B2FileInfo, BucketId};
+use crate::Error;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct StartLargeFileBody<'a> {
+    bucket_id: &'a str,
+    file_name: &'a str,
+    content_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_info: Option<&'a HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Information about a large file being started with [b2_start_large_file]
+///
+/// If 'content_type' is None, "b2/x-auto" is used as default \
+/// 'file_info' holds custom "X-Bz-Info-*" style metadata, e.g. "src_last_modified_millis"
+pub struct StartLargeFileParameters<'a> {
+    pub bucket_id: &'a BucketId,
+    pub file_name: &'a str,
+    pub content_type: Option<&'a str>,
+    pub file_info: Option<HashMap<String, String>>,
+}
+
+/// <https://www.backblaze.com/b2/docs/b2_start_large_file.html>
+///
+/// Returns a [B2FileInfo] with the action "start" - Its 'file_id' is needed to upload parts and finish the file
+pub async fn b2_start_large_file(
+    client: &Client,
+    auth: &B2Auth,
+    params: StartLargeFileParameters<'_>,
+) -> Result<B2FileInfo, Error> {
+    post_json(
+        client,
+        auth,
+        "b2_start_large_file",
+        &StartLargeFileBody {
+            bucket_id: params.bucket_id,
+            file_name: params.file_name,
+            content_type: params.content_type.unwrap_or("b2/x-auto"),
+            file_info: params.file_info.as_ref(),
+        },
+    )
+    .await
+}
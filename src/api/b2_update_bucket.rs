@@ -1,52 +1,112 @@
-use crate::api::{B2Auth, B2BucketType, BucketResult};
-use crate::handle_b2error_kinds;
+use crate::api::post_json;
+use crate::api::{
+    B2Auth, B2BucketType, BucketId, BucketResult, DefaultRetention, ReplicationConfiguration,
+    ServerSideEncryption,
+};
 use crate::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 struct UpdateBucketBody<'a> {
     account_id: &'a str,
-    bucket_id: &'a str,
-    bucket_type: B2BucketType,
+    #[serde(flatten)]
+    params: &'a UpdateBucketRequest,
+}
+
+/// Parameters for [b2_update_bucket_with]
+///
+/// Only 'bucket_id' is required, settings that aren't set are left unchanged
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateBucketRequest {
+    pub bucket_id: BucketId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket_type: Option<B2BucketType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_server_side_encryption: Option<ServerSideEncryption>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_lock_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_retention: Option<DefaultRetention>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replication_configuration: Option<ReplicationConfiguration>,
+}
+
+impl UpdateBucketRequest {
+    pub fn new(bucket_id: BucketId) -> UpdateBucketRequest {
+        UpdateBucketRequest {
+            bucket_id,
+            bucket_type: None,
+            default_server_side_encryption: None,
+            file_lock_enabled: None,
+            default_retention: None,
+            replication_configuration: None,
+        }
+    }
+
+    pub fn bucket_type(mut self, bucket_type: B2BucketType) -> Self {
+        self.bucket_type = Some(bucket_type);
+        self
+    }
+
+    /// The encryption of files uploaded without their own encryption settings, use [ServerSideEncryption::none] to disable it
+    pub fn default_server_side_encryption(mut self, encryption: ServerSideEncryption) -> Self {
+        self.default_server_side_encryption = Some(encryption);
+        self
+    }
+
+    /// Enables Object Lock on an existing bucket, which can't be disabled again
+    pub fn enable_file_lock(mut self) -> Self {
+        self.file_lock_enabled = Some(true);
+        self
+    }
+
+    /// The retention of new files, requires Object Lock to be enabled
+    pub fn default_retention(mut self, retention: DefaultRetention) -> Self {
+        self.default_retention = Some(retention);
+        self
+    }
+
+    /// Replication to or from other buckets, see [ReplicationConfiguration]
+    pub fn replication_configuration(mut self, replication: ReplicationConfiguration) -> Self {
+        self.replication_configuration = Some(replication);
+        self
+    }
 }
 
 /// <https://www.backblaze.com/b2/docs/b2_update_bucket.html>
-pub async fn b2_update_bucket<T: AsRef<str>>(
+///
+/// Only changes the bucket type, see [b2_update_bucket_with] for other settings
+pub async fn b2_update_bucket(
     client: &Client,
     auth: &B2Auth,
-    bucket_id: T,
+    bucket_id: &BucketId,
     bucket_type: B2BucketType,
 ) -> Result<BucketResult, Error> {
-    let req_body = serde_json::to_string(&UpdateBucketBody {
-        account_id: &auth.account_id,
-        bucket_id: bucket_id.as_ref(),
-        bucket_type,
-    })
-    .unwrap();
-
-    let resp = match client
-        .post(&auth.api_url_for("b2_update_bucket"))
-        .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
-        .body(req_body)
-        .send()
-        .await
-    {
-        Ok(v) => v,
-        Err(e) => return Err(Error::ReqwestError(e)),
-    };
-    if !resp.status().is_success() {
-        return Err(Error::from_response(resp).await);
-    }
+    b2_update_bucket_with(
+        client,
+        auth,
+        UpdateBucketRequest::new(bucket_id.clone()).bucket_type(bucket_type),
+    )
+    .await
+}
 
-    let response_string = resp.text().await.unwrap();
-    let deserialized: BucketResult = match serde_json::from_str(&response_string) {
-        Ok(v) => v,
-        Err(_e) => {
-            eprintln!("{:?}", response_string);
-            return Err(handle_b2error_kinds(&response_string));
-        }
-    };
-    Ok(deserialized)
+/// <https://www.backblaze.com/b2/docs/b2_update_bucket.html>
+pub async fn b2_update_bucket_with(
+    client: &Client,
+    auth: &B2Auth,
+    params: UpdateBucketRequest,
+) -> Result<BucketResult, Error> {
+    post_json(
+        client,
+        auth,
+        "b2_update_bucket",
+        &UpdateBucketBody {
+            account_id: &auth.account_id,
+            params: &params,
+        },
+    )
+    .await
 }
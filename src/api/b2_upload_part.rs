@@ -0,0 +1,57 @@
+use crate::api::{FileId, Sha1Variant, UploadHeaders, UploadPartAuth};
+use crate::metrics::send;
+use crate::parse_response;
+use crate::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+/// Information about a part being uploaded with [b2_upload_part]
+///
+/// 'part_number' starts at 1 and must be at most 10000 \
+/// 'part_size' **has to match the size of the upload**, the extra size from using hex-digits-at-end is added automatically \
+/// Every part but the last must be at least 'absolute_minimum_part_size' bytes, see [B2Auth][crate::api::B2Auth]
+pub struct PartParameters<'a> {
+    pub part_number: u32,
+    pub part_size: u64,
+    #[serde(borrow)]
+    pub content_sha1: Sha1Variant<'a>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "camelCase")]
+/// Result object from [b2_upload_part]
+///
+/// The 'content_sha1' of every part is needed, in order, by [b2_finish_large_file][crate::api::b2_finish_large_file]
+pub struct UploadPartResult {
+    pub file_id: FileId,
+    pub part_number: u32,
+    pub content_length: u64,
+    pub content_sha1: String,
+}
+
+/// <https://www.backblaze.com/b2/docs/b2_upload_part.html>
+///
+/// Works like [b2_upload_file][crate::api::b2_upload_file], but for a single part of a large file \
+/// Requires an [UploadPartAuth] instead of a B2Auth.
+pub async fn b2_upload_part<B: Into<reqwest::Body>>(
+    client: &Client,
+    auth: &UploadPartAuth,
+    body: B,
+    params: PartParameters<'_>,
+) -> Result<UploadPartResult, Error> {
+    let headers = UploadHeaders::new(&auth.authorization_token)
+        .content_length(params.part_size, &params.content_sha1)
+        .part_number(params.part_number)
+        .content_sha1(&params.content_sha1)
+        .build();
+
+    let resp = send(
+        "b2_upload_part",
+        client.post(&auth.upload_url).headers(headers).body(body),
+    )
+    .await?;
+
+    let deserialized: UploadPartResult = parse_response("b2_upload_part", resp).await?;
+    Ok(deserialized)
+}
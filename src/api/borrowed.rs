@@ -0,0 +1,195 @@
+use crate::api::{Action, B2FileInfo, ServerSideEncryption, LARGE_FILE_SHA1};
+#[cfg(feature = "utils")]
+use crate::{api::Page, Error};
+use serde::de::{Deserializer, Visitor};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A [B2FileInfo] borrowing its strings from the response it was parsed from
+///
+/// Parsing a listing into these skips a String allocation for almost every field of every file,
+/// which adds up when enumerating millions of files. Only strings B2 had to escape, e.g. names containing quotes, are copied. \
+/// Read with [ListingBody::files], and turned into a [B2FileInfo] with [into_owned][B2FileInfoRef::into_owned] where needed.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct B2FileInfoRef<'a> {
+    #[serde(borrow)]
+    pub account_id: Cow<'a, str>,
+    pub action: Action,
+    #[serde(borrow)]
+    pub bucket_id: Cow<'a, str>,
+    pub content_length: u64,
+    #[serde(borrow, default, deserialize_with = "optional_cow")]
+    pub content_sha1: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "optional_cow")]
+    pub content_type: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "optional_cow")]
+    pub file_id: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "optional_cow_map")]
+    pub file_info: Option<HashMap<Cow<'a, str>, Cow<'a, str>>>,
+    #[serde(borrow)]
+    pub file_name: Cow<'a, str>,
+    pub upload_timestamp: u64,
+    #[serde(default)]
+    pub server_side_encryption: Option<ServerSideEncryption>,
+}
+
+impl B2FileInfoRef<'_> {
+    /// Same as [B2FileInfo::whole_file_sha1]
+    pub fn whole_file_sha1(&self) -> Option<&str> {
+        match self.content_sha1.as_deref() {
+            None | Some("none") => self
+                .file_info
+                .as_ref()
+                .and_then(|fi| fi.get(LARGE_FILE_SHA1))
+                .map(|s| s.as_ref()),
+            Some(sha1) => Some(sha1.trim_start_matches("unverified:")),
+        }
+    }
+
+    /// Same as [B2FileInfo::modified]
+    pub fn modified(&self) -> u64 {
+        self.file_info
+            .as_ref()
+            .and_then(|fi| fi.get("src_last_modified_millis"))
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Copies the strings, so the file info outlives the response
+    pub fn into_owned(self) -> B2FileInfo {
+        B2FileInfo {
+            account_id: self.account_id.into_owned().into(),
+            action: self.action,
+            bucket_id: self.bucket_id.into_owned().into(),
+            content_length: self.content_length,
+            content_sha1: self.content_sha1.map(Cow::into_owned),
+            content_type: self.content_type.map(Cow::into_owned),
+            file_id: self.file_id.map(|id| id.into_owned().into()),
+            file_info: self.file_info.map(|fi| {
+                fi.into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect()
+            }),
+            file_name: self.file_name.into_owned(),
+            upload_timestamp: self.upload_timestamp,
+            server_side_encryption: self.server_side_encryption,
+        }
+    }
+}
+
+/// The unparsed response of a listing call, e.g. from [b2_list_file_names_body][crate::api::b2_list_file_names_body]
+///
+/// Keep it around while reading the [B2FileInfoRef]s parsed from it.
+///
+/// ```rust,no_run
+/// # use raze::api::*;
+/// # async fn f(client: reqwest::Client, auth: B2Auth) -> Result<(), raze::Error> {
+/// let body = b2_list_file_names_body(&client, &auth, ListFileNamesRequest::new("bucket_id".into())).await?;
+/// let page = body.files()?;
+/// let total: u64 = page.items.iter().map(|f| f.content_length).sum();
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "utils")]
+#[derive(Debug, Clone)]
+pub struct ListingBody {
+    body: bytes::Bytes,
+}
+
+#[cfg(feature = "utils")]
+impl ListingBody {
+    pub(crate) async fn read(resp: reqwest::Response) -> Result<ListingBody, Error> {
+        let body = resp.bytes().await.map_err(Error::ReqwestError)?;
+        Ok(ListingBody { body })
+    }
+
+    /// Parses the page of files, borrowing from the body
+    pub fn files(&self) -> Result<Page<B2FileInfoRef<'_>>, Error> {
+        self.page()
+    }
+
+    /// Parses the page into any type, e.g. `Page<B2FileInfo>` to get owned file infos after all
+    pub fn page<'a, T: Deserialize<'a>>(&'a self) -> Result<Page<T>, Error> {
+        serde_json::from_slice(&self.body).map_err(Error::SerdeError)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+// A string that is borrowed unless it had to be unescaped, which Cow<str> only does for direct fields
+#[derive(PartialEq, Eq, Hash)]
+struct CowStr<'a>(Cow<'a, str>);
+
+impl<'de> Deserialize<'de> for CowStr<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CowStrVisitor;
+
+        impl<'de> Visitor<'de> for CowStrVisitor {
+            type Value = CowStr<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(CowStr(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(CowStr(Cow::Owned(v.to_string())))
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+                Ok(CowStr(Cow::Owned(v)))
+            }
+        }
+
+        deserializer.deserialize_str(CowStrVisitor)
+    }
+}
+
+fn optional_cow<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Cow<'de, str>>, D::Error> {
+    let value: Option<CowStr<'de>> = Option::deserialize(deserializer)?;
+    Ok(value.map(|s| s.0))
+}
+
+#[allow(clippy::type_complexity)]
+fn optional_cow_map<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<HashMap<Cow<'de, str>, Cow<'de, str>>>, D::Error> {
+    let value: Option<HashMap<CowStr<'de>, CowStr<'de>>> = Option::deserialize(deserializer)?;
+    Ok(value.map(|map| map.into_iter().map(|(k, v)| (k.0, v.0)).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_file_json;
+
+    #[test]
+    fn test_borrowed_file_info() {
+        let mut value = test_file_json("say \"hi\".txt");
+        value["fileId"] = "4_z1".into();
+        value["fileInfo"] =
+            serde_json::json!({"large_file_sha1": "abc", "src_last_modified_millis": "1500"});
+        let json = &value.to_string();
+        let info: B2FileInfoRef = serde_json::from_str(json).unwrap();
+        assert!(matches!(info.file_id, Some(Cow::Borrowed("4_z1"))));
+        assert!(matches!(info.file_name, Cow::Owned(_)));
+        assert_eq!(info.file_name, "say \"hi\".txt");
+        assert_eq!(info.whole_file_sha1(), Some("abc"));
+        assert_eq!(info.modified(), 1500);
+
+        let owned: B2FileInfo = serde_json::from_str(json).unwrap();
+        let converted = info.into_owned();
+        assert_eq!(converted, owned);
+        assert_eq!(converted.file_info, owned.file_info);
+    }
+}
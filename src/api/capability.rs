@@ -0,0 +1,108 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+macro_rules! capabilities {
+    ($($variant:ident => $name:literal,)*) => {
+        /// A capability of an application key, e.g. 'readFiles'
+        ///
+        /// Capabilities this crate doesn't know yet are kept as [Other][Capability::Other]
+        #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        pub enum Capability {
+            $($variant,)*
+            Other(String),
+        }
+
+        impl Capability {
+            /// The name B2 uses, e.g. "readFiles"
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Capability::$variant => $name,)*
+                    Capability::Other(name) => name,
+                }
+            }
+        }
+
+        impl FromStr for Capability {
+            type Err = std::convert::Infallible;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok(match s {
+                    $($name => Capability::$variant,)*
+                    other => Capability::Other(other.to_string()),
+                })
+            }
+        }
+    };
+}
+
+capabilities! {
+    ListKeys => "listKeys",
+    WriteKeys => "writeKeys",
+    DeleteKeys => "deleteKeys",
+    ListAllBucketNames => "listAllBucketNames",
+    ListBuckets => "listBuckets",
+    ReadBuckets => "readBuckets",
+    WriteBuckets => "writeBuckets",
+    DeleteBuckets => "deleteBuckets",
+    ReadBucketRetentions => "readBucketRetentions",
+    WriteBucketRetentions => "writeBucketRetentions",
+    ReadBucketEncryption => "readBucketEncryption",
+    WriteBucketEncryption => "writeBucketEncryption",
+    ReadBucketReplications => "readBucketReplications",
+    WriteBucketReplications => "writeBucketReplications",
+    ReadBucketNotifications => "readBucketNotifications",
+    WriteBucketNotifications => "writeBucketNotifications",
+    ListFiles => "listFiles",
+    ReadFiles => "readFiles",
+    ShareFiles => "shareFiles",
+    WriteFiles => "writeFiles",
+    DeleteFiles => "deleteFiles",
+    ReadFileLegalHolds => "readFileLegalHolds",
+    WriteFileLegalHolds => "writeFileLegalHolds",
+    ReadFileRetentions => "readFileRetentions",
+    WriteFileRetentions => "writeFileRetentions",
+    BypassGovernance => "bypassGovernance",
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Capability {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Capability {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(name.parse().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_names() {
+        let caps: Vec<Capability> =
+            serde_json::from_str(r#"["readFiles", "bypassGovernance", "launchRockets"]"#).unwrap();
+        assert_eq!(
+            caps,
+            vec![
+                Capability::ReadFiles,
+                Capability::BypassGovernance,
+                Capability::Other("launchRockets".to_string())
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&caps).unwrap(),
+            r#"["readFiles","bypassGovernance","launchRockets"]"#
+        );
+    }
+}
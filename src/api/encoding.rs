@@ -0,0 +1,42 @@
+//! Percent-encoding following B2's string encoding rules
+//!
+//! <https://www.backblaze.com/b2/docs/string_encoding.html>
+
+// Characters B2 allows without encoding, besides ASCII letters and digits
+const SAFE: &[u8] = b"-._~!$'()*;=:@";
+
+/// Percent-encodes a single segment, '/' included
+pub(crate) fn encode_segment(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || SAFE.contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Percent-encodes each '/'-separated segment of a path, keeping the separators
+pub(crate) fn encode_path(input: &str) -> String {
+    input
+        .split('/')
+        .map(encode_segment)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_path() {
+        assert_eq!(encode_path("photos/dog.jpg"), "photos/dog.jpg");
+        assert_eq!(encode_path("my file+1.txt"), "my%20file%2B1.txt");
+        assert_eq!(encode_path("~user/a=b@c"), "~user/a=b@c");
+        assert_eq!(encode_path("kitten/ümlaut"), "kitten/%C3%BCmlaut");
+        assert_eq!(encode_segment("a/b"), "a%2Fb");
+    }
+}
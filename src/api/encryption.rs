@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+/// How B2 encrypts data at rest
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum EncryptionMode {
+    /// Keys managed by B2
+    #[serde(rename = "SSE-B2")]
+    SseB2,
+    /// Keys provided by the customer with every request
+    #[serde(rename = "SSE-C")]
+    SseC,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum EncryptionAlgorithm {
+    AES256,
+}
+
+/// Server-side encryption settings, of a single file or the default of a bucket
+///
+/// A 'mode' of None means no encryption
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ServerSideEncryption {
+    pub mode: Option<EncryptionMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<EncryptionAlgorithm>,
+}
+
+impl ServerSideEncryption {
+    /// No encryption
+    pub fn none() -> ServerSideEncryption {
+        ServerSideEncryption::default()
+    }
+
+    /// SSE-B2 with AES256, the only algorithm B2 supports
+    pub fn sse_b2() -> ServerSideEncryption {
+        ServerSideEncryption {
+            mode: Some(EncryptionMode::SseB2),
+            algorithm: Some(EncryptionAlgorithm::AES256),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode.is_some()
+    }
+}
+
+/// The encryption applied to new files of a bucket, as returned by the bucket calls
+///
+/// 'value' is None if the key isn't allowed to read the bucket's encryption settings
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct DefaultServerSideEncryption {
+    pub is_client_authorized_to_read: bool,
+    #[serde(default)]
+    pub value: Option<ServerSideEncryption>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encryption_serde() {
+        assert_eq!(
+            serde_json::to_string(&ServerSideEncryption::sse_b2()).unwrap(),
+            r#"{"mode":"SSE-B2","algorithm":"AES256"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&ServerSideEncryption::none()).unwrap(),
+            r#"{"mode":null}"#
+        );
+        let default: DefaultServerSideEncryption = serde_json::from_str(
+            r#"{"isClientAuthorizedToRead": true, "value": {"algorithm": null, "mode": null}}"#,
+        )
+        .unwrap();
+        assert_eq!(default.value, Some(ServerSideEncryption::none()));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::api::{ApiVersion, B2Auth};
+use crate::metrics::send;
+use crate::{parse_response, Error};
+use reqwest::{Client, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Makes the API call 'call' at the account's API version, posting 'body' as JSON and parsing the JSON response
+//
+// The bindings that talk JSON to the API URL all go through here, so they share the authorization header,
+// the status check and error mapping, the metrics and the retries of send
+pub(crate) async fn post_json<Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    client: &Client,
+    auth: &B2Auth,
+    call: &str,
+    body: &Req,
+) -> Result<Resp, Error> {
+    post_json_at(client, auth, auth.api_version, call, body).await
+}
+
+// Same as post_json, for calls that only exist in some API versions
+pub(crate) async fn post_json_at<Req: Serialize + ?Sized, Resp: DeserializeOwned>(
+    client: &Client,
+    auth: &B2Auth,
+    api_version: ApiVersion,
+    call: &str,
+    body: &Req,
+) -> Result<Resp, Error> {
+    let resp = post(client, auth, api_version, call, body).await?;
+    parse_response(call, resp).await
+}
+
+// Same as post_json_at, but leaves the successful response unparsed
+pub(crate) async fn post<Req: Serialize + ?Sized>(
+    client: &Client,
+    auth: &B2Auth,
+    api_version: ApiVersion,
+    call: &str,
+    body: &Req,
+) -> Result<Response, Error> {
+    let body = serde_json::to_vec(body).map_err(Error::SerdeError)?;
+    send(
+        call,
+        client
+            .post(auth.api_url_at(api_version, call))
+            .header(reqwest::header::AUTHORIZATION, &auth.authorization_token)
+            .body(body),
+    )
+    .await
+}
@@ -0,0 +1,141 @@
+use crate::api::{AccountId, BucketId, FileId};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+macro_rules! event_types {
+    ($($variant:ident => $name:literal,)*) => {
+        /// What happened in a [B2Event], e.g. "b2:ObjectCreated:Upload"
+        ///
+        /// Event types this crate doesn't know yet are kept as [Other][B2EventType::Other]
+        #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        pub enum B2EventType {
+            $($variant,)*
+            Other(String),
+        }
+
+        impl B2EventType {
+            /// The name B2 uses, e.g. "b2:ObjectCreated:Upload"
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(B2EventType::$variant => $name,)*
+                    B2EventType::Other(name) => name,
+                }
+            }
+        }
+
+        impl FromStr for B2EventType {
+            type Err = std::convert::Infallible;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok(match s {
+                    $($name => B2EventType::$variant,)*
+                    other => B2EventType::Other(other.to_string()),
+                })
+            }
+        }
+    };
+}
+
+event_types! {
+    ObjectCreatedUpload => "b2:ObjectCreated:Upload",
+    ObjectCreatedMultipartUpload => "b2:ObjectCreated:MultipartUpload",
+    ObjectCreatedCopy => "b2:ObjectCreated:Copy",
+    ObjectCreatedReplica => "b2:ObjectCreated:Replica",
+    ObjectCreatedMultipartReplica => "b2:ObjectCreated:MultipartReplica",
+    ObjectDeletedDelete => "b2:ObjectDeleted:Delete",
+    ObjectDeletedLifecycleRule => "b2:ObjectDeleted:LifecycleRule",
+    HideMarkerCreatedHide => "b2:HideMarkerCreated:Hide",
+    HideMarkerCreatedLifecycleRule => "b2:HideMarkerCreated:LifecycleRule",
+    TestEvent => "b2:TestEvent",
+}
+
+impl B2EventType {
+    /// The category of the event, e.g. "ObjectCreated" for "b2:ObjectCreated:Upload"
+    pub fn category(&self) -> &str {
+        self.as_str().split(':').nth(1).unwrap_or("")
+    }
+}
+
+impl fmt::Display for B2EventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for B2EventType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for B2EventType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(name.parse().unwrap())
+    }
+}
+
+/// One event sent by B2 to the webhook of an event notification rule
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct B2Event {
+    pub account_id: AccountId,
+    pub bucket_id: BucketId,
+    pub bucket_name: String,
+    pub event_id: String,
+    /// Milliseconds since the epoch
+    pub event_timestamp: u64,
+    pub event_type: B2EventType,
+    pub event_version: u32,
+    pub matched_rule_name: String,
+    pub object_name: String,
+    /// None for events that don't create data, e.g. hide markers
+    #[serde(default)]
+    pub object_size: Option<u64>,
+    #[serde(default)]
+    pub object_version_id: Option<FileId>,
+}
+
+/// The body of a webhook request, which can hold several events
+///
+/// This only parses the payload, the request signature has to be checked separately
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct B2EventNotification {
+    pub events: Vec<B2Event>,
+}
+
+impl FromStr for B2EventNotification {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(crate::Error::SerdeError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_event_notification() {
+        let body = r#"{"events": [{"accountId": "a", "bucketId": "b", "bucketName": "photos",
+            "eventId": "e1", "eventTimestamp": 1684793309123, "eventType": "b2:ObjectCreated:Upload",
+            "eventVersion": 1, "matchedRuleName": "new-photos", "objectName": "cat.jpg",
+            "objectSize": 1024, "objectVersionId": "4_z1"},
+            {"accountId": "a", "bucketId": "b", "bucketName": "photos",
+            "eventId": "e2", "eventTimestamp": 1684793309124, "eventType": "b2:ObjectRestored:Copy",
+            "eventVersion": 1, "matchedRuleName": "new-photos", "objectName": "dog.jpg",
+            "objectSize": null, "objectVersionId": null}]}"#;
+        let notification: B2EventNotification = body.parse().unwrap();
+        let events = notification.events;
+        assert_eq!(events[0].event_type, B2EventType::ObjectCreatedUpload);
+        assert_eq!(events[0].event_type.category(), "ObjectCreated");
+        assert_eq!(events[0].object_size, Some(1024));
+        assert_eq!(
+            events[1].event_type,
+            B2EventType::Other("b2:ObjectRestored:Copy".to_string())
+        );
+        assert_eq!(events[1].object_version_id, None);
+    }
+}
@@ -0,0 +1,179 @@
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Index;
+
+/// The 'fileInfo' of a file, the custom key/value pairs stored with it
+///
+/// Files rarely have more than 2 entries (B2 allows at most 10), so they are kept in a vector sorted by key
+/// instead of a HashMap, which takes a fraction of the memory in large listings and is just as fast to search at these sizes. \
+/// (De)serializes as a JSON object, same as a map. Converts from and to a [HashMap] with [From].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FileInfoMap {
+    entries: Vec<(String, String)>,
+}
+
+impl FileInfoMap {
+    pub fn new() -> FileInfoMap {
+        FileInfoMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The value of 'key', if it is set
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.position(key).ok().map(|i| self.entries[i].1.as_str())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_ok()
+    }
+
+    /// Sets 'key' to 'value', returning the previous value
+    pub fn insert<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) -> Option<String> {
+        let key = key.into();
+        let value = value.into();
+        match self.position(&key) {
+            Ok(i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            Err(i) => {
+                self.entries.insert(i, (key, value));
+                None
+            }
+        }
+    }
+
+    /// Removes 'key', returning its value
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let i = self.position(key).ok()?;
+        Some(self.entries.remove(i).1)
+    }
+
+    /// The entries, sorted by key
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    fn position(&self, key: &str) -> Result<usize, usize> {
+        self.entries.binary_search_by(|(k, _)| k.as_str().cmp(key))
+    }
+}
+
+/// Panics if 'key' isn't set, like [HashMap]
+impl Index<&str> for FileInfoMap {
+    type Output = str;
+
+    fn index(&self, key: &str) -> &str {
+        self.get(key)
+            .unwrap_or_else(|| panic!("no file info {:?}", key))
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for FileInfoMap {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = FileInfoMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for FileInfoMap {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<String>, const N: usize> From<[(K, V); N]> for FileInfoMap {
+    fn from(entries: [(K, V); N]) -> Self {
+        IntoIterator::into_iter(entries).collect()
+    }
+}
+
+impl From<HashMap<String, String>> for FileInfoMap {
+    fn from(map: HashMap<String, String>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl From<FileInfoMap> for HashMap<String, String> {
+    fn from(map: FileInfoMap) -> Self {
+        map.entries.into_iter().collect()
+    }
+}
+
+impl IntoIterator for FileInfoMap {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl Serialize for FileInfoMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.entries.len()))?;
+        for (key, value) in &self.entries {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for FileInfoMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FileInfoVisitor;
+
+        impl<'de> Visitor<'de> for FileInfoVisitor {
+            type Value = FileInfoMap;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of strings")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<FileInfoMap, A::Error> {
+                let mut map = FileInfoMap {
+                    entries: Vec::with_capacity(access.size_hint().unwrap_or(0)),
+                };
+                while let Some((key, value)) = access.next_entry::<String, String>()? {
+                    map.insert(key, value);
+                }
+                Ok(map)
+            }
+        }
+
+        deserializer.deserialize_map(FileInfoVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_info_map() {
+        let mut map: FileInfoMap =
+            serde_json::from_str(r#"{"b": "2", "a": "1", "b": "3"}"#).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("b"), Some("3"));
+        assert_eq!(&map["a"], "1");
+        assert_eq!(serde_json::to_string(&map).unwrap(), r#"{"a":"1","b":"3"}"#);
+
+        assert_eq!(map.insert("a", "x"), Some("1".to_string()));
+        assert_eq!(map.remove("b"), Some("3".to_string()));
+        assert!(!map.contains_key("b"));
+        let hash: HashMap<String, String> = map.clone().into();
+        assert_eq!(FileInfoMap::from(hash), map);
+        assert_eq!(map.iter().collect::<Vec<_>>(), [("a", "x")]);
+    }
+}
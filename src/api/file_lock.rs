@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+/// How strictly a retention setting protects files from being deleted or overwritten
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionMode {
+    /// Can be shortened or removed by keys with the 'bypassGovernance' capability
+    Governance,
+    /// Can't be shortened or removed by anyone until it expires
+    Compliance,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionPeriodUnit {
+    Days,
+    Years,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RetentionPeriod {
+    pub duration: u32,
+    pub unit: RetentionPeriodUnit,
+}
+
+/// The retention applied to new files in a bucket with Object Lock enabled
+///
+/// A 'mode' of None means new files aren't retained by default
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct DefaultRetention {
+    pub mode: Option<RetentionMode>,
+    #[serde(default)]
+    pub period: Option<RetentionPeriod>,
+}
+
+impl DefaultRetention {
+    /// No default retention
+    pub fn none() -> DefaultRetention {
+        DefaultRetention::default()
+    }
+
+    /// Retain new files for 'duration' 'unit's
+    pub fn new(mode: RetentionMode, duration: u32, unit: RetentionPeriodUnit) -> DefaultRetention {
+        DefaultRetention {
+            mode: Some(mode),
+            period: Some(RetentionPeriod { duration, unit }),
+        }
+    }
+}
+
+/// The Object Lock settings of a bucket
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct FileLockSettings {
+    pub is_file_lock_enabled: bool,
+    #[serde(default)]
+    pub default_retention: DefaultRetention,
+}
+
+/// The Object Lock settings of a bucket, as returned by the bucket calls
+///
+/// 'value' is None if the key isn't allowed to read them, which requires the 'readBucketRetentions' capability
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct FileLockConfiguration {
+    pub is_client_authorized_to_read: bool,
+    #[serde(default)]
+    pub value: Option<FileLockSettings>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_lock_serde() {
+        let config: FileLockConfiguration = serde_json::from_str(
+            r#"{"isClientAuthorizedToRead": true, "value": {"isFileLockEnabled": true,
+                "defaultRetention": {"mode": "compliance", "period": {"duration": 7, "unit": "years"}}}}"#,
+        )
+        .unwrap();
+        let settings = config.value.unwrap();
+        assert!(settings.is_file_lock_enabled);
+        assert_eq!(
+            settings.default_retention,
+            DefaultRetention::new(RetentionMode::Compliance, 7, RetentionPeriodUnit::Years)
+        );
+        assert_eq!(
+            serde_json::to_string(&DefaultRetention::none()).unwrap(),
+            r#"{"mode":null,"period":null}"#
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// A rule B2 applies to the files of a bucket whose names start with 'file_name_prefix'
+///
+/// See <https://www.backblaze.com/b2/docs/lifecycle_rules.html> \
+/// [predict_lifecycle][crate::utils::predict_lifecycle] computes what the rules will do to a file's versions.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct LifecycleRule {
+    pub file_name_prefix: String,
+    /// Days after uploading that the current version is hidden
+    #[serde(default)]
+    pub days_from_uploading_to_hiding: Option<u32>,
+    /// Days after a version stopped being the current one that it is deleted
+    #[serde(default)]
+    pub days_from_hiding_to_deleting: Option<u32>,
+    /// Days after starting that an unfinished large file is cancelled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days_from_starting_to_canceling_unfinished_large_files: Option<u32>,
+}
@@ -17,10 +17,18 @@ pub enum B2BucketType {
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 #[serde(rename_all = "camelCase")]
 pub struct BucketResult {
-    pub account_id: String,
-    pub bucket_id: String,
+    pub account_id: AccountId,
+    pub bucket_id: BucketId,
     pub bucket_name: String,
     pub bucket_type: B2BucketType,
+    #[serde(default)]
+    pub lifecycle_rules: Vec<LifecycleRule>,
+    #[serde(default)]
+    pub default_server_side_encryption: Option<DefaultServerSideEncryption>,
+    #[serde(default)]
+    pub file_lock_configuration: Option<FileLockConfiguration>,
+    #[serde(default)]
+    pub replication_configuration: Option<ReplicationConfigurationResult>,
 }
 
 /// Represents a file on B2
@@ -29,39 +37,93 @@ pub struct BucketResult {
 #[derive(Deserialize, Serialize, Debug, Clone, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct B2FileInfo {
-    pub account_id: String,
-    pub action: String,
-    pub bucket_id: String,
+    pub account_id: AccountId,
+    pub action: Action,
+    pub bucket_id: BucketId,
     pub content_length: u64,
     pub content_sha1: Option<String>,
     pub content_type: Option<String>,
-    pub file_id: Option<String>,
-    pub file_info: Option<HashMap<String, String>>,
+    pub file_id: Option<FileId>,
+    pub file_info: Option<FileInfoMap>,
     pub file_name: String,
     pub upload_timestamp: u64,
+    #[serde(default)]
+    pub server_side_encryption: Option<ServerSideEncryption>,
 }
 
-/// Compares by the file_name value
+/// Compares by file_name, then file_id
+///
+/// Two versions of the same file are different values, use [cmp_by_name][B2FileInfo::cmp_by_name] to only compare names
 impl Ord for B2FileInfo {
     fn cmp(&self, other: &B2FileInfo) -> Ordering {
-        self.file_name.cmp(&other.file_name)
+        self.file_name
+            .cmp(&other.file_name)
+            .then_with(|| self.file_id.cmp(&other.file_id))
     }
 }
 
 impl PartialOrd for B2FileInfo {
     fn partial_cmp(&self, other: &B2FileInfo) -> Option<Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
     }
 }
 
-/// Compares by the file_name value
+/// Compares by file_name and file_id, so each version is only equal to itself
 impl PartialEq for B2FileInfo {
     fn eq(&self, other: &B2FileInfo) -> bool {
-        self.file_name == other.file_name
+        self.file_name == other.file_name && self.file_id == other.file_id
+    }
+}
+
+impl Hash for B2FileInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.file_name.hash(state);
+        self.file_id.hash(state);
     }
 }
 
+/// The 'file_info' key holding the Sha1 of a whole large file, as B2 only stores per-part Sha1s for those
+///
+/// This is the convention used by the b2 command line tool
+pub const LARGE_FILE_SHA1: &str = "large_file_sha1";
+
 impl B2FileInfo {
+    /// Compares only the file names, e.g. for `files.sort_by(B2FileInfo::cmp_by_name)`
+    ///
+    /// Unlike [Ord], this keeps the order of versions of the same file when sorting
+    pub fn cmp_by_name(&self, other: &B2FileInfo) -> Ordering {
+        self.file_name.cmp(&other.file_name)
+    }
+
+    /// Compares the upload timestamps, oldest first
+    pub fn cmp_by_upload_time(&self, other: &B2FileInfo) -> Ordering {
+        self.upload_timestamp.cmp(&other.upload_timestamp)
+    }
+
+    /// Compares the sizes, smallest first
+    pub fn cmp_by_size(&self, other: &B2FileInfo) -> Ordering {
+        self.content_length.cmp(&other.content_length)
+    }
+
+    /// Compares the [modified][B2FileInfo::modified] times, oldest first
+    pub fn cmp_by_modified(&self, other: &B2FileInfo) -> Ordering {
+        self.modified().cmp(&other.modified())
+    }
+
+    /// Returns the Sha1 of the whole file, if it is known
+    ///
+    /// This is 'content_sha1', or the [LARGE_FILE_SHA1] file info for large files,
+    /// without any "unverified:" prefix
+    pub fn whole_file_sha1(&self) -> Option<&str> {
+        match self.content_sha1.as_deref() {
+            None | Some("none") => self
+                .file_info
+                .as_ref()
+                .and_then(|fi| fi.get(LARGE_FILE_SHA1)),
+            Some(sha1) => Some(sha1.trim_start_matches("unverified:")),
+        }
+    }
+
     /// Returns the modified timestamp of the file
     /// If it wasn't supplied during upload, this will return 0
     pub fn modified(&self) -> u64 {
@@ -75,6 +137,57 @@ impl B2FileInfo {
     }
 }
 
+// The JSON B2 lists an uploaded, empty text file named 'name' with, for tests to adjust
+#[cfg(test)]
+pub(crate) fn test_file_json(name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "accountId": "a", "action": "upload", "bucketId": "b", "contentLength": 0,
+        "contentSha1": "none", "contentType": "text/plain", "fileId": format!("4_{}", name),
+        "fileInfo": {}, "fileName": name, "uploadTimestamp": 0
+    })
+}
+
+// An uploaded, empty text file named 'name', for tests to adjust
+#[cfg(test)]
+pub(crate) fn test_file(name: &str) -> B2FileInfo {
+    serde_json::from_value(test_file_json(name)).unwrap()
+}
+
+mod action;
+pub use self::action::*;
+mod api_version;
+pub use self::api_version::*;
+mod application_key;
+pub use self::application_key::*;
+mod borrowed;
+pub use self::borrowed::*;
+mod capability;
+pub use self::capability::*;
+mod encryption;
+pub use self::encryption::*;
+mod event_notification;
+pub use self::event_notification::*;
+mod file_info_map;
+pub use self::file_info_map::*;
+mod file_lock;
+pub use self::file_lock::*;
+mod ids;
+pub use self::ids::*;
+mod lifecycle_rule;
+pub use self::lifecycle_rule::*;
+mod notification_rule;
+pub use self::notification_rule::*;
+mod page;
+pub use self::page::*;
+pub(crate) mod encoding;
+mod endpoint;
+pub(crate) use self::endpoint::*;
+pub(crate) mod redact;
+mod replication;
+pub use self::replication::*;
+mod upload_headers;
+pub use self::upload_headers::*;
+
 // Export API calls
 mod b2_authorize_account;
 pub use self::b2_authorize_account::*;
@@ -87,9 +200,15 @@ mod b2_delete_bucket;
 pub use self::b2_delete_bucket::*;
 mod b2_list_buckets;
 pub use self::b2_list_buckets::*;
+mod b2_get_bucket_notification_rules;
+pub use self::b2_get_bucket_notification_rules::*;
+mod b2_set_bucket_notification_rules;
+pub use self::b2_set_bucket_notification_rules::*;
 
 mod b2_list_file_names;
 pub use self::b2_list_file_names::*;
+mod b2_list_file_versions;
+pub use self::b2_list_file_versions::*;
 mod b2_get_file_info;
 pub use self::b2_get_file_info::*;
 
@@ -101,10 +220,43 @@ mod b2_delete_file_version;
 pub use self::b2_delete_file_version::*;
 mod b2_hide_file;
 pub use self::b2_hide_file::*;
+mod b2_copy_file;
+pub use self::b2_copy_file::*;
+
+mod b2_start_large_file;
+pub use self::b2_start_large_file::*;
+mod b2_get_upload_part_url;
+pub use self::b2_get_upload_part_url::*;
+mod b2_upload_part;
+pub use self::b2_upload_part::*;
+mod b2_finish_large_file;
+pub use self::b2_finish_large_file::*;
+mod b2_cancel_large_file;
+pub use self::b2_cancel_large_file::*;
 use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 
 mod b2_get_download_authorization;
 pub use self::b2_get_download_authorization::*;
 mod b2_download_file_by_name;
 pub use self::b2_download_file_by_name::*;
-use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_file_info_identity() {
+        let parse = |id: &str| -> B2FileInfo {
+            let mut file = test_file("a.txt");
+            file.file_id = Some(id.into());
+            file
+        };
+        let (old, new) = (parse("4_z1"), parse("4_z2"));
+        assert_ne!(old, new);
+        assert_eq!(old.cmp_by_name(&new), Ordering::Equal);
+        let set: HashSet<_> = vec![old.clone(), new, old].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::api::BucketId;
+use serde::{Deserialize, Serialize};
+
+/// A custom header B2 adds to webhook requests
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CustomHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// Where the events of a [NotificationRule] are sent
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationTarget {
+    /// Only "webhook" is supported by B2
+    pub target_type: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_headers: Option<Vec<CustomHeader>>,
+    /// When set, requests are signed with an HMAC-SHA256 in the 'x-bz-event-notification-signature' header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hmac_sha256_signing_secret: Option<String>,
+}
+
+impl NotificationTarget {
+    pub fn webhook<T: Into<String>>(url: T) -> NotificationTarget {
+        NotificationTarget {
+            target_type: "webhook".to_string(),
+            url: url.into(),
+            custom_headers: None,
+            hmac_sha256_signing_secret: None,
+        }
+    }
+}
+
+/// Sends events of the given types, for files starting with 'object_name_prefix', to a webhook
+///
+/// 'event_types' are [B2EventType][crate::api::B2EventType] names, which may end in a wildcard, e.g. "b2:ObjectCreated:*"
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationRule {
+    pub name: String,
+    pub event_types: Vec<String>,
+    pub is_enabled: bool,
+    #[serde(default)]
+    pub object_name_prefix: String,
+    pub target_configuration: NotificationTarget,
+    /// Set by B2 when it stopped sending events after repeated failures
+    #[serde(default, skip_serializing)]
+    pub is_suspended: Option<bool>,
+    #[serde(default, skip_serializing)]
+    pub suspension_reason: Option<String>,
+}
+
+/// The event notification rules of a bucket
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketNotificationRules {
+    pub bucket_id: BucketId,
+    pub event_notification_rules: Vec<NotificationRule>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_rule_serde() {
+        let rules: BucketNotificationRules = serde_json::from_str(
+            r#"{"bucketId": "b", "eventNotificationRules": [{"eventTypes": ["b2:ObjectCreated:*"],
+                "isEnabled": true, "isSuspended": false, "name": "uploads", "objectNamePrefix": "",
+                "suspensionReason": "", "targetConfiguration": {"targetType": "webhook",
+                "url": "https://example.com/hook", "hmacSha256SigningSecret": "secret"}}]}"#,
+        )
+        .unwrap();
+        let rule = &rules.event_notification_rules[0];
+        assert_eq!(rule.is_suspended, Some(false));
+        // Fields set by B2 aren't sent back when saving the rules
+        let json = serde_json::to_value(rule).unwrap();
+        assert!(json.get("isSuspended").is_none());
+        assert_eq!(
+            json["targetConfiguration"]["url"],
+            "https://example.com/hook"
+        );
+    }
+}
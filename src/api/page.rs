@@ -0,0 +1,147 @@
+use crate::api::FileId;
+#[cfg(feature = "utils")]
+use crate::Error;
+#[cfg(feature = "utils")]
+use futures::{Future, Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+/// Where a listing stopped, so it can be continued later
+///
+/// Serialize it to checkpoint a long listing, e.g. to disk, and pass it to a request's 'resume_from' after a restart. \
+/// Which fields are set depends on the call that produced it, see [Page].
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ListCursor {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<FileId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_number: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application_key_id: Option<String>,
+}
+
+/// One page of results from a listing call, such as [b2_list_file_names][crate::api::b2_list_file_names]
+///
+/// The 'next_*' fields are where the following page starts, and are all None on the last page. \
+/// Which of them are used depends on the call, e.g. file names use 'next_file_name',
+/// while file versions use both 'next_file_name' and 'next_file_id'.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    #[serde(alias = "files", alias = "parts", alias = "keys")]
+    pub items: Vec<T>,
+    #[serde(default)]
+    pub next_file_name: Option<String>,
+    #[serde(default)]
+    pub next_file_id: Option<FileId>,
+    #[serde(default)]
+    pub next_part_number: Option<u32>,
+    #[serde(default)]
+    pub next_application_key_id: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Whether B2 reported more results after this page
+    pub fn has_more(&self) -> bool {
+        self.next_file_name.is_some()
+            || self.next_file_id.is_some()
+            || self.next_part_number.is_some()
+            || self.next_application_key_id.is_some()
+    }
+
+    /// Where the following page starts, None on the last page
+    pub fn next_cursor(&self) -> Option<ListCursor> {
+        if !self.has_more() {
+            return None;
+        }
+        Some(ListCursor {
+            file_name: self.next_file_name.clone(),
+            file_id: self.next_file_id.clone(),
+            part_number: self.next_part_number,
+            application_key_id: self.next_application_key_id.clone(),
+        })
+    }
+
+    /// Number of items B2 returned in this page
+    ///
+    /// With a delimiter, every "folder" counts as one item
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Turns this page and the ones after it into a stream of items
+    ///
+    /// 'fetch_next' is called with the [next_cursor][Page::next_cursor] of the previous page whenever more items are needed,
+    /// and should request the page starting there. \
+    /// Pages are only fetched once the stream has yielded every item of the previous one.
+    #[cfg(feature = "utils")]
+    pub fn into_stream<F, Fut>(self, fetch_next: F) -> impl Stream<Item = Result<T, Error>>
+    where
+        F: FnMut(ListCursor) -> Fut,
+        Fut: Future<Output = Result<Page<T>, Error>>,
+    {
+        futures::stream::try_unfold(
+            (Some(self), None, fetch_next),
+            |(first, cursor, mut fetch_next)| async move {
+                let page = match (first, cursor) {
+                    (Some(page), _) => page,
+                    (None, Some(cursor)) => fetch_next(cursor).await?,
+                    (None, None) => return Ok(None),
+                };
+                let next = page.next_cursor();
+                Ok(Some((
+                    futures::stream::iter(page.items.into_iter().map(Ok)),
+                    (None, next, fetch_next),
+                )))
+            },
+        )
+        .try_flatten()
+    }
+}
+
+#[cfg(all(test, feature = "utils"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_into_stream() {
+        let first: Page<u32> =
+            serde_json::from_str(r#"{"files": [1, 2], "nextFileName": "c"}"#).unwrap();
+        assert!(first.has_more());
+        let items: Vec<u32> = first
+            .into_stream(|cursor| {
+                let last = cursor.file_name.as_deref() == Some("d");
+                async move {
+                    Ok(Page {
+                        items: if last { vec![4] } else { vec![3] },
+                        next_file_name: if last { None } else { Some("d".to_string()) },
+                        next_file_id: None,
+                        next_part_number: None,
+                        next_application_key_id: None,
+                    })
+                }
+            })
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(items, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let page: Page<u32> = serde_json::from_str(
+            r#"{"files": [], "nextFileName": "a.txt", "nextFileId": "4_z123"}"#,
+        )
+        .unwrap();
+        let cursor = page.next_cursor().unwrap();
+        let json = serde_json::to_string(&cursor).unwrap();
+        assert_eq!(json, r#"{"fileName":"a.txt","fileId":"4_z123"}"#);
+        assert_eq!(serde_json::from_str::<ListCursor>(&json).unwrap(), cursor);
+    }
+}
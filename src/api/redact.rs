@@ -0,0 +1,28 @@
+use std::fmt;
+
+/// Stands in for a secret in Debug output
+pub(crate) struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::UploadAuth;
+
+    #[test]
+    fn test_debug_hides_token() {
+        let upauth = UploadAuth {
+            bucket_id: "bucket".into(),
+            upload_url: "https://pod-000.backblaze.com/b2api/v2/b2_upload_file".to_string(),
+            authorization_token: "4_secret_token".to_string(),
+        };
+        let debug = format!("{:?}", upauth);
+        assert!(!debug.contains("4_secret_token"));
+        assert!(debug.contains("authorization_token: <redacted>"));
+        assert!(debug.contains("pod-000"));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::api::BucketId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Copies new files with names starting with 'file_name_prefix' to another bucket
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicationRule {
+    pub replication_rule_name: String,
+    pub destination_bucket_id: BucketId,
+    #[serde(default)]
+    pub file_name_prefix: String,
+    /// Also replicate files that existed before the rule was created
+    #[serde(default)]
+    pub include_existing_files: bool,
+    pub is_enabled: bool,
+    /// Between 1 and 2147483647, rules with a higher priority win when several match a file
+    pub priority: u32,
+}
+
+/// The replication rules of a source bucket, and the key used to read from it
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicationSource {
+    pub replication_rules: Vec<ReplicationRule>,
+    pub source_application_key_id: String,
+}
+
+/// Which key of the destination account writes the files replicated with each source key
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicationDestination {
+    pub source_to_destination_key_mapping: BTreeMap<String, String>,
+}
+
+/// Cross-bucket replication settings, a bucket can be a source, a destination or both
+///
+/// Set on the source bucket with 'as_replication_source' and on the destination bucket with 'as_replication_destination'. \
+/// Passing an empty configuration to [b2_update_bucket_with][crate::api::b2_update_bucket_with] removes replication.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicationConfiguration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_replication_source: Option<ReplicationSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_replication_destination: Option<ReplicationDestination>,
+}
+
+/// The replication settings of a bucket, as returned by the bucket calls
+///
+/// 'value' is None if the key isn't allowed to read them
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicationConfigurationResult {
+    pub is_client_authorized_to_read: bool,
+    #[serde(default)]
+    pub value: Option<ReplicationConfiguration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replication_serde() {
+        let result: ReplicationConfigurationResult = serde_json::from_str(
+            r#"{"isClientAuthorizedToRead": true, "value": {"asReplicationSource": {
+                "replicationRules": [{"destinationBucketId": "b2", "fileNamePrefix": "logs/",
+                    "includeExistingFiles": false, "isEnabled": true, "priority": 1,
+                    "replicationRuleName": "logs"}],
+                "sourceApplicationKeyId": "k1"}}}"#,
+        )
+        .unwrap();
+        let source = result.value.unwrap().as_replication_source.unwrap();
+        assert_eq!(source.replication_rules[0].destination_bucket_id, "b2");
+        let destination = ReplicationConfiguration {
+            as_replication_source: None,
+            as_replication_destination: Some(ReplicationDestination {
+                source_to_destination_key_mapping: [("k1".to_string(), "k2".to_string())].into(),
+            }),
+        };
+        assert_eq!(
+            serde_json::to_string(&destination).unwrap(),
+            r#"{"asReplicationDestination":{"sourceToDestinationKeyMapping":{"k1":"k2"}}}"#
+        );
+    }
+}
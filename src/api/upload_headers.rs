@@ -0,0 +1,151 @@
+use crate::api::encoding::{encode_path, encode_segment};
+use crate::api::{EncryptionAlgorithm, EncryptionMode, ServerSideEncryption, Sha1Variant};
+use reqwest::header::{HeaderMap, HeaderValue};
+
+/// Builds the headers of an upload, as used by [b2_upload_file][crate::api::b2_upload_file] and [b2_upload_part][crate::api::b2_upload_part]
+///
+/// File names and file info values are percent-encoded following B2's [string encoding rules](https://www.backblaze.com/b2/docs/string_encoding.html) \
+/// Useful for custom uploaders that build their own requests
+///
+/// ```rust
+/// # use raze::api::{Sha1Variant, UploadHeaders};
+/// let headers = UploadHeaders::new("upload_token")
+///     .file_name("photos/my dog.jpg")
+///     .content_type(None)
+///     .content_length(1024, &Sha1Variant::HexAtEnd)
+///     .content_sha1(&Sha1Variant::HexAtEnd)
+///     .build();
+/// assert_eq!(headers["X-Bz-File-Name"], "photos/my%20dog.jpg");
+/// assert_eq!(headers["Content-Length"], "1064");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UploadHeaders {
+    headers: HeaderMap,
+}
+
+impl UploadHeaders {
+    /// Starts with the 'Authorization' header, using the token of an [UploadAuth][crate::api::UploadAuth] or [UploadPartAuth][crate::api::UploadPartAuth]
+    pub fn new(authorization_token: &str) -> UploadHeaders {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            authorization_token.parse().unwrap(),
+        );
+        UploadHeaders { headers }
+    }
+
+    /// Sets 'X-Bz-File-Name', percent-encoding everything but the '/' separators
+    pub fn file_name(mut self, file_name: &str) -> Self {
+        self.headers
+            .insert("X-Bz-File-Name", encoded_value(encode_path(file_name)));
+        self
+    }
+
+    /// Sets 'Content-Type', using "b2/x-auto" if None
+    pub fn content_type(mut self, content_type: Option<&str>) -> Self {
+        self.headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            content_type.unwrap_or("b2/x-auto").parse().unwrap(),
+        );
+        self
+    }
+
+    /// Sets 'Content-Length', adding the 40 bytes of the hash for [Sha1Variant::HexAtEnd]
+    pub fn content_length(mut self, size: u64, content_sha1: &Sha1Variant) -> Self {
+        let size = match content_sha1 {
+            Sha1Variant::HexAtEnd => size + 40,
+            _ => size,
+        };
+        self.headers
+            .insert(reqwest::header::CONTENT_LENGTH, size.into());
+        self
+    }
+
+    /// Sets 'X-Bz-Content-Sha1'
+    pub fn content_sha1(mut self, content_sha1: &Sha1Variant) -> Self {
+        let hash = match content_sha1 {
+            Sha1Variant::Precomputed(hash) => hash,
+            Sha1Variant::HexAtEnd => "hex_digits_at_end",
+            Sha1Variant::DoNotVerify => "do_not_verify",
+        };
+        self.headers
+            .insert("X-Bz-Content-Sha1", hash.parse().unwrap());
+        self
+    }
+
+    /// Sets 'X-Bz-Part-Number', only used when uploading parts
+    pub fn part_number(mut self, part_number: u32) -> Self {
+        self.headers.insert("X-Bz-Part-Number", part_number.into());
+        self
+    }
+
+    /// Sets 'X-Bz-Info-src_last_modified_millis'
+    pub fn last_modified_millis(mut self, millis: u64) -> Self {
+        self.headers
+            .insert("X-Bz-Info-src_last_modified_millis", millis.into());
+        self
+    }
+
+    /// Sets 'X-Bz-Server-Side-Encryption', which only has an effect for [SSE-B2][crate::api::EncryptionMode::SseB2]
+    ///
+    /// Without it, the bucket's default encryption applies
+    pub fn server_side_encryption(mut self, encryption: &ServerSideEncryption) -> Self {
+        if let (Some(EncryptionMode::SseB2), Some(EncryptionAlgorithm::AES256)) =
+            (encryption.mode, encryption.algorithm)
+        {
+            self.headers.insert(
+                "X-Bz-Server-Side-Encryption",
+                HeaderValue::from_static("AES256"),
+            );
+        }
+        self
+    }
+
+    /// Sets a custom 'X-Bz-Info-<name>' header, percent-encoding the value
+    ///
+    /// Panics if 'name' is not a valid header name
+    pub fn file_info(mut self, name: &str, value: &str) -> Self {
+        let name: reqwest::header::HeaderName = format!("X-Bz-Info-{}", name).parse().unwrap();
+        self.headers
+            .insert(name, encoded_value(encode_segment(value)));
+        self
+    }
+
+    /// The finished headers
+    pub fn build(self) -> HeaderMap {
+        self.headers
+    }
+}
+
+// Percent-encoded strings are always ASCII, so this cannot fail
+fn encoded_value(encoded: String) -> HeaderValue {
+    HeaderValue::from_str(&encoded).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_encoding() {
+        let name = |n: &str| UploadHeaders::new("t").file_name(n).build()["X-Bz-File-Name"].clone();
+        assert_eq!(name("a/b c.txt"), "a/b%20c.txt");
+        assert_eq!(name("1+1=2"), "1%2B1=2");
+        assert_eq!(name("~backup/file~"), "~backup/file~");
+        assert_eq!(name("kitten/ümlaut ☺"), "kitten/%C3%BCmlaut%20%E2%98%BA");
+    }
+
+    #[test]
+    fn test_hex_at_end_headers() {
+        let headers = UploadHeaders::new("t")
+            .content_length(100, &Sha1Variant::HexAtEnd)
+            .content_sha1(&Sha1Variant::HexAtEnd)
+            .part_number(3)
+            .file_info("author", "Kongou Desu")
+            .build();
+        assert_eq!(headers["Content-Length"], "140");
+        assert_eq!(headers["X-Bz-Content-Sha1"], "hex_digits_at_end");
+        assert_eq!(headers["X-Bz-Part-Number"], "3");
+        assert_eq!(headers["X-Bz-Info-author"], "Kongou%20Desu");
+    }
+}
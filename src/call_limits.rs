@@ -0,0 +1,235 @@
+//! Limits on how often API calls are made, separate from the bandwidth they use
+//!
+//! With [CallLimits] set, every [api][crate::api] call waits for a free slot of its transaction class before it is sent,
+//! so bulk work like deleting a million files stays below B2's request rate limits and doesn't burn through transaction caps. \
+//! Calls can also be limited by name, which takes precedence over their class.
+//!
+//! ```rust
+//! use raze::call_limits::{set_call_limits, CallClass, CallLimits};
+//! set_call_limits(
+//!     CallLimits::new()
+//!         .with_class_rate(CallClass::C, 10.0)
+//!         .with_call_rate("b2_delete_file_version", 100.0),
+//! );
+//! ```
+//!
+//! The wait happens before the call's latency is measured for [metrics][crate::metrics].
+//! A [hedged][crate::hedging] call only waits once, for the first of its requests.
+use crate::utils::sleep;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// The transaction classes B2 bills calls by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallClass {
+    /// Uploads, deletes and large file management, which are free
+    A,
+    /// Downloads and file info
+    B,
+    /// Listings, copies, authorization and bucket management
+    C,
+}
+
+impl CallClass {
+    /// The class of the API call 'call', e.g. "b2_list_file_names", None for calls B2 doesn't bill
+    pub fn of(call: &str) -> Option<CallClass> {
+        match call {
+            "b2_cancel_large_file"
+            | "b2_delete_bucket"
+            | "b2_delete_file_version"
+            | "b2_delete_key"
+            | "b2_finish_large_file"
+            | "b2_get_upload_part_url"
+            | "b2_get_upload_url"
+            | "b2_hide_file"
+            | "b2_start_large_file"
+            | "b2_upload_file"
+            | "b2_upload_part" => Some(CallClass::A),
+            "b2_download_file_by_id" | "b2_download_file_by_name" | "b2_get_file_info" => {
+                Some(CallClass::B)
+            }
+            "b2_authorize_account"
+            | "b2_copy_file"
+            | "b2_copy_part"
+            | "b2_create_bucket"
+            | "b2_create_key"
+            | "b2_get_bucket_notification_rules"
+            | "b2_get_download_authorization"
+            | "b2_list_buckets"
+            | "b2_list_file_names"
+            | "b2_list_file_versions"
+            | "b2_list_keys"
+            | "b2_list_parts"
+            | "b2_list_unfinished_large_files"
+            | "b2_set_bucket_notification_rules"
+            | "b2_update_bucket" => Some(CallClass::C),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Scope {
+    Class(CallClass),
+    Call(String),
+}
+
+/// How many calls per second may be made, by class or by call
+///
+/// Everything not given a rate is unlimited
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallLimits {
+    rates: HashMap<Scope, f64>,
+    burst: u32,
+}
+
+impl Default for CallLimits {
+    fn default() -> Self {
+        CallLimits {
+            rates: HashMap::new(),
+            burst: 1,
+        }
+    }
+}
+
+impl CallLimits {
+    /// Limits nothing until rates are added
+    pub fn new() -> CallLimits {
+        CallLimits::default()
+    }
+
+    /// Allows 'calls_per_second' calls of 'class', a rate of 0 or less removes the limit
+    pub fn with_class_rate(mut self, class: CallClass, calls_per_second: f64) -> Self {
+        self.rates.insert(Scope::Class(class), calls_per_second);
+        self
+    }
+
+    /// Allows 'calls_per_second' of the API call 'call', e.g. "b2_delete_file_version", instead of the rate of its class
+    ///
+    /// A rate of 0 or less leaves the call unlimited, even if its class isn't
+    pub fn with_call_rate(mut self, call: &str, calls_per_second: f64) -> Self {
+        self.rates
+            .insert(Scope::Call(call.to_string()), calls_per_second);
+        self
+    }
+
+    /// Lets up to 'burst' calls through at once after a quiet period, 1 by default, which spaces all calls evenly
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+}
+
+// When the calls of one scope may be made
+#[derive(Debug)]
+struct Schedule {
+    // None if unlimited
+    interval: Option<Duration>,
+    burst: u32,
+    // When the calls reserved so far have all been made
+    next_free: Mutex<Option<Instant>>,
+}
+
+impl Schedule {
+    // Reserves a slot, returning when it starts, None if unlimited
+    fn reserve(&self, now: Instant) -> Option<Instant> {
+        let interval = self.interval?;
+        let mut next_free = self.next_free.lock().unwrap();
+        // Slots left unused in the past are kept for bursts, up to 'burst' of them
+        let earliest = now.checked_sub(interval * (self.burst - 1)).unwrap_or(now);
+        let slot = next_free.map_or(now, |n| n.max(earliest));
+        *next_free = Some(slot + interval);
+        Some(slot)
+    }
+}
+
+fn schedules(limits: CallLimits) -> HashMap<Scope, Schedule> {
+    let burst = limits.burst;
+    limits
+        .rates
+        .into_iter()
+        .map(|(scope, rate)| {
+            let schedule = Schedule {
+                interval: (rate > 0.0).then(|| Duration::from_secs_f64(1.0 / rate)),
+                burst,
+                next_free: Mutex::new(None),
+            };
+            (scope, schedule)
+        })
+        .collect()
+}
+
+static CALL_LIMITS: RwLock<Option<Arc<HashMap<Scope, Schedule>>>> = RwLock::new(None);
+
+/// Starts limiting calls following 'limits', replacing the previous limits
+pub fn set_call_limits(limits: CallLimits) {
+    *CALL_LIMITS.write().unwrap() = Some(Arc::new(schedules(limits)));
+}
+
+/// Stops limiting calls, removing the limits set by [set_call_limits]
+pub fn clear_call_limits() {
+    *CALL_LIMITS.write().unwrap() = None;
+}
+
+/// Waits until 'call' may be made under the current limits
+pub(crate) async fn wait_for_slot(call: &str) {
+    let schedules = match CALL_LIMITS.read().unwrap().as_ref() {
+        Some(schedules) => schedules.clone(),
+        None => return,
+    };
+    let now = Instant::now();
+    if let Some(slot) = reserve(&schedules, call, now) {
+        if slot > now {
+            sleep(slot - now).await;
+        }
+    }
+}
+
+// Reserves a slot for 'call', None if it isn't limited
+fn reserve(schedules: &HashMap<Scope, Schedule>, call: &str, now: Instant) -> Option<Instant> {
+    let schedule = schedules
+        .get(&Scope::Call(call.to_string()))
+        .or_else(|| CallClass::of(call).and_then(|class| schedules.get(&Scope::Class(class))))?;
+    schedule.reserve(now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        let limits = CallLimits::new()
+            .with_class_rate(CallClass::C, 10.0)
+            .with_call_rate("b2_list_buckets", 0.0)
+            .with_burst(2);
+        let schedules = schedules(limits);
+        let start = Instant::now() + Duration::from_secs(10);
+        let ms = |n| start + Duration::from_millis(n);
+
+        let slots: Vec<_> = (0..3)
+            .map(|_| reserve(&schedules, "b2_list_file_names", start))
+            .collect();
+        assert_eq!(slots, [Some(ms(0)), Some(ms(100)), Some(ms(200))]);
+        // After a quiet second, two calls may go right away
+        assert_eq!(
+            reserve(&schedules, "b2_copy_file", ms(1300)),
+            Some(ms(1200))
+        );
+        assert_eq!(
+            reserve(&schedules, "b2_copy_file", ms(1300)),
+            Some(ms(1300))
+        );
+        assert_eq!(
+            reserve(&schedules, "b2_copy_file", ms(1300)),
+            Some(ms(1400))
+        );
+
+        // A zero rate removes the limit of the class, other classes aren't limited
+        assert_eq!(reserve(&schedules, "b2_list_buckets", start), None);
+        assert_eq!(reserve(&schedules, "b2_upload_file", start), None);
+        assert_eq!(CallClass::of("b2_get_file_info"), Some(CallClass::B));
+        assert_eq!(CallClass::of("s3_get_object"), None);
+    }
+}
@@ -0,0 +1,230 @@
+//! Stores blobs under the Sha1 of their content, e.g. for artifact caches
+//!
+//! Blobs are uploaded as "sha1/<digest>", so uploading the same content twice only stores it once. \
+//! How often each blob was stored is counted in an index file, and a blob is deleted once all its references are released.
+//!
+//! The index is read, modified and written back on every change,
+//! so only one [BlobStore] may modify a given prefix at a time.
+use crate::api::{
+    b2_delete_file_version, b2_download_file_by_name, b2_get_upload_url, b2_upload_file, B2Auth,
+    B2DownloadFileByNameParams, BucketId, FileParameters, Sha1Variant,
+};
+use crate::utils::{get_file_by_name, upload_if_absent};
+use crate::Error;
+use bytes::Bytes;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use std::collections::BTreeMap;
+
+fn sha1_hex(data: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+    hasher.hexdigest()
+}
+
+/// Reference counts of the blobs in a [BlobStore], stored as JSON next to the blobs
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct BlobIndex {
+    pub refs: BTreeMap<String, u64>,
+}
+
+impl BlobIndex {
+    /// Adds a reference to 'digest', returning the new count
+    pub fn add(&mut self, digest: &str) -> u64 {
+        let count = self.refs.entry(digest.to_string()).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    /// Removes a reference to 'digest', returning the remaining count, or None if it wasn't referenced
+    ///
+    /// Digests without references are removed from the index
+    pub fn release(&mut self, digest: &str) -> Option<u64> {
+        let count = self.refs.get_mut(digest)?;
+        *count -= 1;
+        let count = *count;
+        if count == 0 {
+            self.refs.remove(digest);
+        }
+        Some(count)
+    }
+}
+
+/// A content-addressed store of blobs in a bucket
+#[derive(Clone)]
+pub struct BlobStore {
+    client: Client,
+    auth: B2Auth,
+    bucket_id: BucketId,
+    bucket_name: String,
+    prefix: String,
+}
+
+impl BlobStore {
+    /// Stores blobs under "sha1/" in the given bucket
+    ///
+    /// Blobs are downloaded by name, so 'bucket_name' has to be the name of the bucket with id 'bucket_id'
+    pub fn new<T: Into<BucketId>, Q: Into<String>>(
+        client: Client,
+        auth: B2Auth,
+        bucket_id: T,
+        bucket_name: Q,
+    ) -> BlobStore {
+        BlobStore {
+            client,
+            auth,
+            bucket_id: bucket_id.into(),
+            bucket_name: bucket_name.into(),
+            prefix: "sha1/".to_string(),
+        }
+    }
+
+    /// Stores blobs under another prefix, e.g. to keep several independent stores in one bucket
+    pub fn with_prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// The file name of the blob with the given digest
+    pub fn blob_name(&self, digest: &str) -> String {
+        format!("{}{}", self.prefix, digest)
+    }
+
+    /// The file name of the index
+    pub fn index_name(&self) -> String {
+        format!("{}index.json", self.prefix)
+    }
+
+    /// Stores 'data' and adds a reference to it, returning its hex Sha1 digest
+    ///
+    /// Content that is already stored isn't uploaded again
+    pub async fn put(&self, data: Bytes) -> Result<String, Error> {
+        let digest = sha1_hex(&data);
+        let upauth = b2_get_upload_url(&self.client, &self.auth, &self.bucket_id).await?;
+        let size = data.len() as u64;
+        upload_if_absent(
+            &self.client,
+            &self.auth,
+            &upauth,
+            data,
+            FileParameters {
+                file_path: &self.blob_name(&digest),
+                file_size: size,
+                content_type: Some("application/octet-stream"),
+                content_sha1: Sha1Variant::Precomputed(&digest),
+                last_modified_millis: 0,
+            },
+        )
+        .await?;
+        let mut index = self.index().await?;
+        index.add(&digest);
+        self.write_index(&index).await?;
+        Ok(digest)
+    }
+
+    /// Downloads the blob with the given digest, checking its content against the digest
+    pub async fn get(&self, digest: &str) -> Result<Bytes, Error> {
+        let data = self
+            .download(&self.blob_name(&digest.to_ascii_lowercase()))
+            .await?;
+        let actual = sha1_hex(&data);
+        if !actual.eq_ignore_ascii_case(digest) {
+            return Err(Error::ChecksumMismatch {
+                file_name: self.blob_name(digest),
+                expected: digest.to_string(),
+                actual,
+            });
+        }
+        Ok(data)
+    }
+
+    /// Removes a reference to a blob, deleting it once nothing references it
+    ///
+    /// Returns whether the blob was deleted
+    pub async fn release(&self, digest: &str) -> Result<bool, Error> {
+        let mut index = self.index().await?;
+        if index.release(digest) != Some(0) {
+            self.write_index(&index).await?;
+            return Ok(false);
+        }
+        let name = self.blob_name(digest);
+        if let Some(info) =
+            get_file_by_name(&self.client, &self.auth, &self.bucket_id, &name).await?
+        {
+            if let Some(file_id) = info.file_id.as_ref() {
+                b2_delete_file_version(&self.client, &self.auth, &name, file_id).await?;
+            }
+        }
+        self.write_index(&index).await?;
+        Ok(true)
+    }
+
+    /// Reads the index, which is empty if it doesn't exist yet
+    pub async fn index(&self) -> Result<BlobIndex, Error> {
+        match self.download(&self.index_name()).await {
+            Ok(data) => serde_json::from_slice(&data).map_err(Error::SerdeError),
+            Err(Error::B2Error(e)) if e.status == 404 => Ok(BlobIndex::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn download(&self, file_name: &str) -> Result<Bytes, Error> {
+        let resp = b2_download_file_by_name(
+            &self.client,
+            &self.auth,
+            B2DownloadFileByNameParams {
+                bucket_name: self.bucket_name.clone(),
+                file_name: file_name.to_string(),
+                authorization: None,
+                download_host: None,
+                omit_authorization: false,
+                range: None,
+            },
+        )
+        .await?;
+        resp.bytes().await.map_err(Error::ReqwestError)
+    }
+
+    // Uploads a new version of the index and removes the previous one
+    async fn write_index(&self, index: &BlobIndex) -> Result<(), Error> {
+        let name = self.index_name();
+        let previous = get_file_by_name(&self.client, &self.auth, &self.bucket_id, &name).await?;
+        let data = serde_json::to_vec(index).map_err(Error::SerdeError)?;
+        let sha1 = sha1_hex(&data);
+        let upauth = b2_get_upload_url(&self.client, &self.auth, &self.bucket_id).await?;
+        b2_upload_file(
+            &self.client,
+            &upauth,
+            data.clone(),
+            FileParameters {
+                file_path: &name,
+                file_size: data.len() as u64,
+                content_type: Some("application/json"),
+                content_sha1: Sha1Variant::Precomputed(&sha1),
+                last_modified_millis: 0,
+            },
+        )
+        .await?;
+        if let Some(file_id) = previous.and_then(|p| p.file_id) {
+            let _ = b2_delete_file_version(&self.client, &self.auth, &name, &file_id).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_index() {
+        let mut index = BlobIndex::default();
+        assert_eq!(index.add("aaf4"), 1);
+        assert_eq!(index.add("aaf4"), 2);
+        assert_eq!(index.release("aaf4"), Some(1));
+        assert_eq!(index.release("aaf4"), Some(0));
+        assert!(index.refs.is_empty());
+        assert_eq!(index.release("aaf4"), None);
+    }
+}
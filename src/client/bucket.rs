@@ -0,0 +1,448 @@
+use crate::api::{
+    b2_copy_file, b2_delete_file_version, b2_download_file_by_name, b2_get_file_info,
+    b2_get_upload_url, b2_hide_file, b2_list_buckets, b2_list_file_names, b2_list_file_versions,
+    b2_upload_file, Action, B2CopyFileParams, B2DownloadFileByNameParams, B2FileInfo, BucketId,
+    BucketResult, FileId, FileParameters, ListBucketParams, ListCursor, ListFileNamesRequest,
+    ListFileVersionsRequest, MetadataDirective, Sha1Variant,
+};
+use crate::client::B2Client;
+#[cfg(feature = "util_readers")]
+use crate::utils::{download_stream, BytesStreamLimited};
+use crate::utils::{get_file_by_name, list_all_files_stream};
+use crate::Error;
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
+use reqwest::Response;
+use sha1::Sha1;
+
+/// A single bucket, so calls don't need its id or name
+///
+/// Obtained from [B2Client::bucket_by_name] or [B2Client::bucket]. \
+/// Every call goes through [B2Client::call], so expired authorizations are renewed.
+#[derive(Clone)]
+pub struct Bucket {
+    client: B2Client,
+    id: BucketId,
+    name: String,
+}
+
+impl B2Client {
+    /// Looks up a bucket by name
+    ///
+    /// Returns a [ConfigError][Error::ConfigError] if there is no such bucket on the account
+    pub async fn bucket_by_name(&self, name: &str) -> Result<Bucket, Error> {
+        let buckets = self
+            .call(|http, auth| async move {
+                b2_list_buckets(
+                    &http,
+                    &auth,
+                    ListBucketParams {
+                        bucket_id: None,
+                        bucket_name: Some(name.to_string()),
+                        bucket_types: None,
+                    },
+                )
+                .await
+            })
+            .await?;
+        match buckets.into_iter().find(|b| b.bucket_name == name) {
+            Some(bucket) => Ok(self.bucket(bucket)),
+            None => Err(Error::ConfigError(format!("bucket '{}' not found", name))),
+        }
+    }
+
+    /// Makes a handle for a bucket that is already known, e.g. from [b2_list_buckets]
+    pub fn bucket(&self, bucket: BucketResult) -> Bucket {
+        Bucket {
+            client: self.clone(),
+            id: bucket.bucket_id,
+            name: bucket.bucket_name,
+        }
+    }
+}
+
+impl Bucket {
+    pub fn id(&self) -> &BucketId {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The client this handle makes its calls with
+    pub fn client(&self) -> &B2Client {
+        &self.client
+    }
+
+    /// Uploads 'data' as 'file_name', with its Sha1 computed up front and a content type picked by B2
+    ///
+    /// For large or streamed files, use the [api][crate::api] calls or [utils][crate::utils] directly
+    pub async fn upload<T: Into<Bytes>>(
+        &self,
+        file_name: &str,
+        data: T,
+    ) -> Result<B2FileInfo, Error> {
+        let data = data.into();
+        let mut hasher = Sha1::new();
+        hasher.update(&data);
+        let sha1 = hasher.hexdigest();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        self.client
+            .call(|http, auth| {
+                let data = data.clone();
+                let sha1 = &sha1;
+                async move {
+                    let upauth = b2_get_upload_url(&http, &auth, &self.id).await?;
+                    let size = data.len() as u64;
+                    b2_upload_file(
+                        &http,
+                        &upauth,
+                        self.client.upload_body(data),
+                        FileParameters {
+                            file_path: file_name,
+                            file_size: size,
+                            content_type: None,
+                            content_sha1: Sha1Variant::Precomputed(sha1),
+                            last_modified_millis: now,
+                        },
+                    )
+                    .await
+                }
+            })
+            .await
+    }
+
+    /// Starts downloading 'file_name', the body is read from the returned [Response]
+    ///
+    /// The body is limited by the client's [download_limiter][B2Client::download_limiter]
+    pub async fn download(&self, file_name: &str) -> Result<Response, Error> {
+        let resp = self
+            .client
+            .call(|http, auth| async move {
+                b2_download_file_by_name(
+                    &http,
+                    &auth,
+                    B2DownloadFileByNameParams {
+                        bucket_name: self.name.clone(),
+                        file_name: file_name.to_string(),
+                        authorization: None,
+                        download_host: None,
+                        omit_authorization: false,
+                        range: None,
+                    },
+                )
+                .await
+            })
+            .await?;
+        Ok(self.client.download_body(resp))
+    }
+
+    /// Downloads 'file_name' as a stream, limited by the client's [download_limiter][B2Client::download_limiter]
+    ///
+    /// Interrupted downloads are resumed and the data is checked against its Sha1, see [download_stream]. \
+    /// The download uses the authorization current at the time of calling
+    #[cfg(feature = "util_readers")]
+    pub fn download_stream(
+        &self,
+        file_name: &str,
+        max_retries: u32,
+    ) -> impl Stream<Item = Result<Bytes, Error>> {
+        let params = B2DownloadFileByNameParams {
+            bucket_name: self.name.clone(),
+            file_name: file_name.to_string(),
+            authorization: None,
+            download_host: None,
+            omit_authorization: false,
+            range: None,
+        };
+        BytesStreamLimited::wrap(
+            download_stream(
+                self.client.http().clone(),
+                self.client.auth(),
+                params,
+                max_retries,
+            ),
+            self.client.download_limiter().clone(),
+        )
+    }
+
+    /// Lists the current version of every file, see [list_all_files_stream]
+    ///
+    /// The listing uses the authorization current at the time of calling
+    pub fn list(&self) -> impl Stream<Item = Result<B2FileInfo, Error>> {
+        list_all_files_stream(
+            self.client.http().clone(),
+            self.client.auth(),
+            self.id.clone(),
+            1000,
+        )
+    }
+
+    /// A handle that puts 'prefix' in front of every file name, to work in an emulated folder such as "photos/2024/"
+    pub fn prefix<T: Into<String>>(&self, prefix: T) -> BucketPrefix {
+        BucketPrefix {
+            bucket: self.clone(),
+            prefix: prefix.into(),
+        }
+    }
+
+    // Lists the current version of every file starting with 'prefix'
+    fn list_names(&self, prefix: String) -> impl Stream<Item = Result<B2FileInfo, Error>> {
+        let client = self.client.clone();
+        let id = self.id.clone();
+        let fetch = move |cursor: Option<ListCursor>| {
+            let client = client.clone();
+            let mut request = ListFileNamesRequest::new(id.clone())
+                .prefix(prefix.clone())
+                .max_file_count(1000);
+            if let Some(cursor) = cursor {
+                request = request.resume_from(&cursor);
+            }
+            async move {
+                client
+                    .call(|http, auth| {
+                        let request = request.clone();
+                        async move { b2_list_file_names(&http, &auth, request).await }
+                    })
+                    .await
+            }
+        };
+        let fetch_next = fetch.clone();
+        futures::stream::once(fetch(None))
+            .map_ok(move |page| {
+                let fetch_next = fetch_next.clone();
+                page.into_stream(move |cursor| fetch_next(Some(cursor)))
+            })
+            .try_flatten()
+    }
+
+    /// Lists every version of 'file_name', newest first
+    ///
+    /// Besides uploads, this includes the markers left by hiding the file and unfinished large files,
+    /// see [B2FileInfo::action]. \
+    /// Pass the 'file_id' of an upload to [restore_version][Bucket::restore_version] to make it current again.
+    pub fn versions_of(&self, file_name: &str) -> impl Stream<Item = Result<B2FileInfo, Error>> {
+        let request = ListFileVersionsRequest::new(self.id.clone())
+            .start_file_name(file_name)
+            .prefix(file_name)
+            .max_file_count(1000);
+        let file_name = file_name.to_string();
+        self.list_versions(request)
+            // The prefix also matches longer names, which are sorted after this one
+            .try_take_while(move |info| futures::future::ready(Ok(info.file_name == file_name)))
+    }
+
+    // Lists every version 'request' matches, going through all pages
+    fn list_versions(
+        &self,
+        request: ListFileVersionsRequest,
+    ) -> impl Stream<Item = Result<B2FileInfo, Error>> {
+        let client = self.client.clone();
+        let fetch = move |cursor: Option<ListCursor>| {
+            let client = client.clone();
+            let request = match cursor {
+                Some(cursor) => request.clone().resume_from(&cursor),
+                None => request.clone(),
+            };
+            async move {
+                client
+                    .call(|http, auth| {
+                        let request = request.clone();
+                        async move { b2_list_file_versions(&http, &auth, request).await }
+                    })
+                    .await
+            }
+        };
+        let fetch_next = fetch.clone();
+        futures::stream::once(fetch(None))
+            .map_ok(move |page| {
+                let fetch_next = fetch_next.clone();
+                page.into_stream(move |cursor| fetch_next(Some(cursor)))
+            })
+            .try_flatten()
+    }
+
+    /// Moves 'file_name' to the trash by hiding it
+    ///
+    /// The file disappears from listings and downloads by name, but all its versions are kept,
+    /// so [undelete][Bucket::undelete] can bring it back. \
+    /// Returns the hide marker, which lifecycle rules can use to delete trashed files after a while.
+    pub async fn trash(&self, file_name: &str) -> Result<B2FileInfo, Error> {
+        self.client
+            .call(|http, auth| async move { b2_hide_file(&http, &auth, &self.id, file_name).await })
+            .await
+    }
+
+    /// Takes 'file_name' out of the trash, by deleting the hide marker that is its newest version
+    ///
+    /// Returns the version that is current again, or None if the file wasn't in the trash.
+    pub async fn undelete(&self, file_name: &str) -> Result<Option<B2FileInfo>, Error> {
+        let versions = self.versions_of(file_name);
+        futures::pin_mut!(versions);
+        let marker = match versions.try_next().await? {
+            Some(info) if info.action == Action::Hide => info,
+            _ => return Ok(None),
+        };
+        if let Some(file_id) = marker.file_id.as_ref() {
+            self.client
+                .call(|http, auth| async move {
+                    b2_delete_file_version(&http, &auth, file_name, file_id).await
+                })
+                .await?;
+        }
+        Ok(versions
+            .try_next()
+            .await?
+            .filter(|info| info.action == Action::Upload))
+    }
+
+    /// Lists the files whose names start with 'prefix' that are in the trash, as their hide markers
+    ///
+    /// A file is in the trash when its newest version is a hide marker, see [trash][Bucket::trash]. \
+    /// This lists every version under the prefix, so it costs a transaction per 1000 versions.
+    pub fn list_trashed(&self, prefix: &str) -> impl Stream<Item = Result<B2FileInfo, Error>> {
+        let request = ListFileVersionsRequest::new(self.id.clone())
+            .prefix(prefix)
+            .max_file_count(1000);
+        let mut last_name: Option<String> = None;
+        self.list_versions(request).try_filter(move |info| {
+            // Versions of a name are listed newest first
+            let newest = last_name.as_deref() != Some(info.file_name.as_str());
+            if newest {
+                last_name = Some(info.file_name.clone());
+            }
+            futures::future::ready(newest && info.action == Action::Hide)
+        })
+    }
+
+    /// Makes an older version of a file the current one again, by copying it server-side
+    ///
+    /// The version itself is kept, the copy becomes a new version with the same name and content. \
+    /// Returns a [ConfigError][Error::ConfigError] if 'file_id' isn't an uploaded file in this bucket.
+    pub async fn restore_version(&self, file_id: &FileId) -> Result<B2FileInfo, Error> {
+        self.client
+            .call(|http, auth| async move {
+                let info = b2_get_file_info(&http, &auth, file_id).await?;
+                if info.bucket_id != self.id || info.action != Action::Upload {
+                    return Err(Error::ConfigError(format!(
+                        "{} is not an uploaded file in bucket {}",
+                        file_id, self.name
+                    )));
+                }
+                b2_copy_file(
+                    &http,
+                    &auth,
+                    B2CopyFileParams {
+                        source_file_id: file_id.clone(),
+                        destination_bucket_id: None,
+                        file_name: info.file_name,
+                        range: None,
+                        metadata_directive: MetadataDirective::Copy,
+                        content_type: None,
+                        file_info: None,
+                    },
+                )
+                .await
+            })
+            .await
+    }
+
+    /// Deletes the current version of 'file_name'
+    ///
+    /// Older versions are kept, so the previous version becomes the current one. \
+    /// Returns the deleted version, or None if there was no such file.
+    pub async fn delete(&self, file_name: &str) -> Result<Option<B2FileInfo>, Error> {
+        self.client
+            .call(|http, auth| async move {
+                let info = match get_file_by_name(&http, &auth, &self.id, file_name).await? {
+                    Some(info) => info,
+                    None => return Ok(None),
+                };
+                if let Some(file_id) = info.file_id.as_ref() {
+                    b2_delete_file_version(&http, &auth, file_name, file_id).await?;
+                }
+                Ok(Some(info))
+            })
+            .await
+    }
+}
+
+/// A [Bucket] scoped to the files whose names start with a prefix, obtained from [Bucket::prefix]
+///
+/// File names passed to it are relative to the prefix, e.g. uploading "cat.jpg" with the prefix "photos/"
+/// creates "photos/cat.jpg". \
+/// Listed files keep their full name, use [relative_name][BucketPrefix::relative_name] to strip the prefix.
+#[derive(Clone)]
+pub struct BucketPrefix {
+    bucket: Bucket,
+    prefix: String,
+}
+
+impl BucketPrefix {
+    pub fn bucket(&self) -> &Bucket {
+        &self.bucket
+    }
+
+    /// The prefix put in front of file names
+    pub fn as_str(&self) -> &str {
+        &self.prefix
+    }
+
+    /// A handle for a prefix within this one, e.g. "2024/" within "photos/"
+    pub fn prefix(&self, prefix: &str) -> BucketPrefix {
+        self.bucket.prefix(self.full_name(prefix))
+    }
+
+    /// The name of 'file_name' in the bucket
+    pub fn full_name(&self, file_name: &str) -> String {
+        format!("{}{}", self.prefix, file_name)
+    }
+
+    /// The name of a file relative to the prefix, None if it isn't under the prefix
+    pub fn relative_name<'a>(&self, full_name: &'a str) -> Option<&'a str> {
+        full_name.strip_prefix(self.prefix.as_str())
+    }
+
+    /// Same as [Bucket::upload], under the prefix
+    pub async fn upload<T: Into<Bytes>>(
+        &self,
+        file_name: &str,
+        data: T,
+    ) -> Result<B2FileInfo, Error> {
+        self.bucket.upload(&self.full_name(file_name), data).await
+    }
+
+    /// Same as [Bucket::download], under the prefix
+    pub async fn download(&self, file_name: &str) -> Result<Response, Error> {
+        self.bucket.download(&self.full_name(file_name)).await
+    }
+
+    /// Lists the current version of every file under the prefix, including files in nested "folders"
+    pub fn list(&self) -> impl Stream<Item = Result<B2FileInfo, Error>> {
+        self.bucket.list_names(self.prefix.clone())
+    }
+
+    /// Same as [Bucket::delete], under the prefix
+    pub async fn delete(&self, file_name: &str) -> Result<Option<B2FileInfo>, Error> {
+        self.bucket.delete(&self.full_name(file_name)).await
+    }
+
+    /// Same as [Bucket::trash], under the prefix
+    pub async fn trash(&self, file_name: &str) -> Result<B2FileInfo, Error> {
+        self.bucket.trash(&self.full_name(file_name)).await
+    }
+
+    /// Same as [Bucket::undelete], under the prefix
+    pub async fn undelete(&self, file_name: &str) -> Result<Option<B2FileInfo>, Error> {
+        self.bucket.undelete(&self.full_name(file_name)).await
+    }
+
+    /// Lists the trashed files under the prefix, see [Bucket::list_trashed]
+    pub fn list_trashed(&self) -> impl Stream<Item = Result<B2FileInfo, Error>> {
+        self.bucket.list_trashed(&self.prefix)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::api::DEFAULT_API_ENDPOINT;
+use crate::client::credentials::missing_var;
+use crate::client::{B2Client, StaticCredentials};
+use crate::utils::{recommended_client, recommended_client_for};
+use crate::Error;
+use reqwest::Client;
+
+/// Settings for connecting to B2, usually read from the environment with [from_env][B2Config::from_env]
+#[derive(Clone, Eq, PartialEq)]
+pub struct B2Config {
+    pub key_id: String,
+    pub key: String,
+    pub bucket_id: Option<String>,
+    pub bucket_name: Option<String>,
+    /// Replaces [DEFAULT_API_ENDPOINT] for authorization
+    pub endpoint: Option<String>,
+    /// Upload bandwidth limit in bytes per second, shared by all uploads of the client, see [RateLimiter][crate::utils::RateLimiter]
+    pub upload_bandwidth: Option<usize>,
+    /// Download bandwidth limit in bytes per second
+    pub download_bandwidth: Option<usize>,
+    /// Name and version of the application, added to the User-Agent by [http_client][B2Config::http_client]
+    pub app_name: Option<String>,
+}
+
+impl B2Config {
+    /// Reads the configuration from environment variables
+    ///
+    /// Variable | Required | Field
+    /// -------- | -------- | -----
+    /// B2_APPLICATION_KEY_ID | yes | key_id
+    /// B2_APPLICATION_KEY | yes | key
+    /// B2_BUCKET_ID | no | bucket_id
+    /// B2_BUCKET_NAME | no | bucket_name
+    /// B2_ENDPOINT | no | endpoint
+    /// B2_UPLOAD_BANDWIDTH | no | upload_bandwidth
+    /// B2_DOWNLOAD_BANDWIDTH | no | download_bandwidth
+    /// B2_APP_NAME | no | app_name
+    ///
+    /// Empty variables are treated as unset. Returns a [ConfigError][Error::ConfigError]
+    /// if a required variable is missing or a bandwidth isn't a number
+    pub fn from_env() -> Result<B2Config, Error> {
+        B2Config::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup<F: Fn(&str) -> Option<String>>(lookup: F) -> Result<B2Config, Error> {
+        let optional = |name: &str| lookup(name).filter(|v| !v.is_empty());
+        let required = |name: &str| optional(name).ok_or_else(|| missing_var(name));
+        let bandwidth = |name: &str| match optional(name) {
+            Some(v) => v.trim().parse().map(Some).map_err(|_| {
+                Error::ConfigError(format!(
+                    "{} must be a number of bytes per second, got '{}'",
+                    name, v
+                ))
+            }),
+            None => Ok(None),
+        };
+        Ok(B2Config {
+            key_id: required("B2_APPLICATION_KEY_ID")?,
+            key: required("B2_APPLICATION_KEY")?,
+            bucket_id: optional("B2_BUCKET_ID"),
+            bucket_name: optional("B2_BUCKET_NAME"),
+            endpoint: optional("B2_ENDPOINT"),
+            upload_bandwidth: bandwidth("B2_UPLOAD_BANDWIDTH")?,
+            download_bandwidth: bandwidth("B2_DOWNLOAD_BANDWIDTH")?,
+            app_name: optional("B2_APP_NAME"),
+        })
+    }
+
+    /// The application key as credentials for [B2Client]
+    pub fn credentials(&self) -> StaticCredentials {
+        StaticCredentials::from_parts(&self.key_id, &self.key)
+    }
+
+    /// A [recommended_client][crate::utils::recommended_client], identifying 'app_name' in the User-Agent if it is set
+    pub fn http_client(&self) -> Client {
+        match self.app_name.as_deref() {
+            Some(app) => recommended_client_for(app),
+            None => recommended_client(),
+        }
+    }
+
+    /// Creates an authorized [B2Client] with this configuration
+    ///
+    /// The bandwidth limits are applied to the client's limiters when the 'util_readers' feature is enabled
+    pub async fn client(&self, http: Client) -> Result<B2Client, Error> {
+        let endpoint = self.endpoint.as_deref().unwrap_or(DEFAULT_API_ENDPOINT);
+        let client = B2Client::with_endpoint(http, self.credentials(), endpoint).await?;
+        #[cfg(feature = "util_readers")]
+        {
+            client.upload_limiter().set_rate(self.upload_bandwidth);
+            client.download_limiter().set_rate(self.download_bandwidth);
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Result<B2Config, Error> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        B2Config::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_from_lookup() {
+        let c = config(&[
+            ("B2_APPLICATION_KEY_ID", "id"),
+            ("B2_APPLICATION_KEY", "key"),
+            ("B2_BUCKET_NAME", "bucket"),
+            ("B2_BUCKET_ID", ""),
+            ("B2_UPLOAD_BANDWIDTH", "1000000"),
+        ])
+        .unwrap();
+        assert_eq!(c.bucket_name.as_deref(), Some("bucket"));
+        assert_eq!(c.bucket_id, None);
+        assert_eq!(c.upload_bandwidth, Some(1000000));
+        assert_eq!(c.app_name, None);
+
+        assert!(config(&[("B2_APPLICATION_KEY_ID", "id")]).is_err());
+        assert!(config(&[
+            ("B2_APPLICATION_KEY_ID", "id"),
+            ("B2_APPLICATION_KEY", "key"),
+            ("B2_DOWNLOAD_BANDWIDTH", "fast"),
+        ])
+        .is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::api::ApplicationKey;
+use crate::Error;
+use futures::future::BoxFuture;
+use futures::Future;
+use std::path::PathBuf;
+
+/// A source of application keys, used by [B2Client][crate::client::B2Client] to authorize and re-authorize
+///
+/// Implement this to fetch keys from e.g. a secrets manager. \
+/// Since keys are requested again on every re-authorization, rotated keys are picked up automatically.
+pub trait CredentialsProvider: Send + Sync {
+    /// Returns the key passed to [b2_authorize_account][crate::api::b2_authorize_account]
+    fn application_key(&self) -> BoxFuture<'_, Result<ApplicationKey, Error>>;
+}
+
+/// A fixed application key
+#[derive(Clone, Debug)]
+pub struct StaticCredentials {
+    key: ApplicationKey,
+}
+
+impl StaticCredentials {
+    /// 'key' is an [ApplicationKey] or a string with the format "applicationKeyId:applicationKey"
+    pub fn new<K: Into<ApplicationKey>>(key: K) -> StaticCredentials {
+        StaticCredentials { key: key.into() }
+    }
+
+    /// Uses an application key id and application key
+    pub fn from_parts<T: Into<String>, Q: Into<String>>(key_id: T, key: Q) -> StaticCredentials {
+        StaticCredentials::new(ApplicationKey::new(key_id, key))
+    }
+}
+
+impl CredentialsProvider for StaticCredentials {
+    fn application_key(&self) -> BoxFuture<'_, Result<ApplicationKey, Error>> {
+        Box::pin(futures::future::ready(Ok(self.key.clone())))
+    }
+}
+
+/// Reads the key from environment variables, 'B2_APPLICATION_KEY_ID' and 'B2_APPLICATION_KEY' by default
+#[derive(Clone)]
+pub struct EnvCredentials {
+    key_id_var: String,
+    key_var: String,
+}
+
+impl EnvCredentials {
+    pub fn new() -> EnvCredentials {
+        EnvCredentials::with_vars("B2_APPLICATION_KEY_ID", "B2_APPLICATION_KEY")
+    }
+
+    /// Uses the given variable names instead of the defaults
+    pub fn with_vars<T: Into<String>, Q: Into<String>>(
+        key_id_var: T,
+        key_var: Q,
+    ) -> EnvCredentials {
+        EnvCredentials {
+            key_id_var: key_id_var.into(),
+            key_var: key_var.into(),
+        }
+    }
+}
+
+impl Default for EnvCredentials {
+    fn default() -> Self {
+        EnvCredentials::new()
+    }
+}
+
+pub(crate) fn missing_var(name: &str) -> Error {
+    Error::ConfigError(format!("environment variable {} is not set", name))
+}
+
+fn env_var(name: &str) -> Result<String, Error> {
+    std::env::var(name).map_err(|_| missing_var(name))
+}
+
+impl CredentialsProvider for EnvCredentials {
+    fn application_key(&self) -> BoxFuture<'_, Result<ApplicationKey, Error>> {
+        let key = env_var(&self.key_id_var)
+            .and_then(|id| env_var(&self.key_var).map(|key| ApplicationKey::new(id, key)));
+        Box::pin(futures::future::ready(key))
+    }
+}
+
+/// Reads the key from a file containing "applicationKeyId:applicationKey"
+///
+/// Surrounding whitespace, such as a trailing newline, is ignored
+#[derive(Clone)]
+pub struct FileCredentials {
+    path: PathBuf,
+}
+
+impl FileCredentials {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileCredentials {
+        FileCredentials { path: path.into() }
+    }
+}
+
+impl CredentialsProvider for FileCredentials {
+    fn application_key(&self) -> BoxFuture<'_, Result<ApplicationKey, Error>> {
+        let key = std::fs::read_to_string(&self.path)
+            .map_err(Error::IOError)
+            .and_then(|s| s.trim().parse());
+        Box::pin(futures::future::ready(key))
+    }
+}
+
+/// Gets the key from an async function, e.g. one querying a secrets manager
+pub struct FnCredentials<F> {
+    f: F,
+}
+
+impl<F, Fut> FnCredentials<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<ApplicationKey, Error>> + Send + 'static,
+{
+    pub fn new(f: F) -> FnCredentials<F> {
+        FnCredentials { f }
+    }
+}
+
+impl<F, Fut> CredentialsProvider for FnCredentials<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<ApplicationKey, Error>> + Send + 'static,
+{
+    fn application_key(&self) -> BoxFuture<'_, Result<ApplicationKey, Error>> {
+        Box::pin((self.f)())
+    }
+}
@@ -0,0 +1,203 @@
+//! A client that keeps itself authorized
+//!
+//! The [api][crate::api] calls take a [B2Auth] that has to be managed by the caller. \
+//! [B2Client] instead gets its keys from a [CredentialsProvider] and re-authorizes when the token expires.
+use crate::api::{b2_authorize_account_version, ApiVersion, B2Auth, DEFAULT_API_ENDPOINT};
+#[cfg(feature = "util_readers")]
+use crate::utils::{BytesStreamExt, BytesStreamLimited, RateLimiter};
+use crate::Error;
+use bytes::Bytes;
+use futures::Future;
+use reqwest::{Client, Response};
+use std::sync::{Arc, RwLock};
+
+#[cfg(feature = "util_readers")]
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+mod bucket;
+pub use self::bucket::*;
+mod config;
+pub use self::config::*;
+mod credentials;
+pub use self::credentials::*;
+mod options;
+pub use self::options::*;
+
+struct Inner {
+    http: Client,
+    endpoint: String,
+    credentials: Box<dyn CredentialsProvider>,
+    auth: RwLock<B2Auth>,
+    #[cfg(feature = "util_readers")]
+    upload_limiter: RateLimiter,
+    #[cfg(feature = "util_readers")]
+    download_limiter: RateLimiter,
+}
+
+/// An authorized connection to B2, which re-authorizes when needed
+///
+/// Cloning is cheap and clones share the authorization.
+#[derive(Clone)]
+pub struct B2Client {
+    inner: Arc<Inner>,
+}
+
+impl B2Client {
+    /// Authorizes with the keys from 'credentials'
+    pub async fn new<C: CredentialsProvider + 'static>(
+        http: Client,
+        credentials: C,
+    ) -> Result<B2Client, Error> {
+        B2Client::with_endpoint(http, credentials, DEFAULT_API_ENDPOINT).await
+    }
+
+    /// Same as [new][B2Client::new], but authorizing against another endpoint, see [b2_authorize_account_at][crate::api::b2_authorize_account_at]
+    pub async fn with_endpoint<C: CredentialsProvider + 'static, E: Into<String>>(
+        http: Client,
+        credentials: C,
+        endpoint: E,
+    ) -> Result<B2Client, Error> {
+        let endpoint = endpoint.into();
+        let key = credentials.application_key().await?;
+        let auth = b2_authorize_account_version(&http, &endpoint, ApiVersion::V2, key).await?;
+        Ok(B2Client {
+            inner: Arc::new(Inner {
+                http,
+                endpoint,
+                credentials: Box::new(credentials),
+                auth: RwLock::new(auth),
+                #[cfg(feature = "util_readers")]
+                upload_limiter: RateLimiter::unlimited(),
+                #[cfg(feature = "util_readers")]
+                download_limiter: RateLimiter::unlimited(),
+            }),
+        })
+    }
+
+    /// The underlying HTTP client
+    pub fn http(&self) -> &Client {
+        &self.inner.http
+    }
+
+    /// The current authorization
+    pub fn auth(&self) -> B2Auth {
+        self.inner.auth.read().unwrap().clone()
+    }
+
+    /// The limit shared by all upload bodies made through this client, unlimited by default
+    ///
+    /// Only file data is limited, the JSON API calls are never held back
+    #[cfg(feature = "util_readers")]
+    pub fn upload_limiter(&self) -> &RateLimiter {
+        &self.inner.upload_limiter
+    }
+
+    /// The limit shared by all download streams made through this client, unlimited by default
+    #[cfg(feature = "util_readers")]
+    pub fn download_limiter(&self) -> &RateLimiter {
+        &self.inner.download_limiter
+    }
+
+    // A body for uploading 'data', limited by the upload limiter
+    #[cfg(feature = "util_readers")]
+    pub(crate) fn upload_body(&self, data: Bytes) -> reqwest::Body {
+        if self.inner.upload_limiter.rate().is_none() {
+            return data.into();
+        }
+        // Small chunks keep the limit smooth, slicing doesn't copy
+        let chunks: Vec<Result<Bytes, std::io::Error>> = (0..data.len())
+            .step_by(UPLOAD_CHUNK_SIZE)
+            .map(|start| Ok(data.slice(start..data.len().min(start + UPLOAD_CHUNK_SIZE))))
+            .collect();
+        reqwest::Body::wrap_stream(
+            futures::stream::iter(chunks).limited(self.inner.upload_limiter.clone()),
+        )
+    }
+
+    #[cfg(not(feature = "util_readers"))]
+    pub(crate) fn upload_body(&self, data: Bytes) -> reqwest::Body {
+        data.into()
+    }
+
+    // 'resp' with its body limited by the download limiter
+    #[cfg(feature = "util_readers")]
+    pub(crate) fn download_body(&self, resp: Response) -> Response {
+        if self.inner.download_limiter.rate().is_none() {
+            return resp;
+        }
+        let mut builder = http::Response::builder()
+            .status(resp.status())
+            .version(resp.version());
+        for (name, value) in resp.headers() {
+            builder = builder.header(name, value);
+        }
+        let body =
+            BytesStreamLimited::wrap(resp.bytes_stream(), self.inner.download_limiter.clone());
+        builder
+            .body(reqwest::Body::wrap_stream(body))
+            .unwrap()
+            .into()
+    }
+
+    #[cfg(not(feature = "util_readers"))]
+    pub(crate) fn download_body(&self, resp: Response) -> Response {
+        resp
+    }
+
+    /// Makes all further calls against the given API version, including re-authorization
+    pub fn set_api_version(&self, api_version: ApiVersion) {
+        self.inner.auth.write().unwrap().api_version = api_version;
+    }
+
+    /// Gets fresh keys from the credentials provider and authorizes again
+    ///
+    /// The API version of the current authorization is kept
+    pub async fn reauthorize(&self) -> Result<B2Auth, Error> {
+        let key = self.inner.credentials.application_key().await?;
+        let api_version = self.auth().api_version;
+        let auth =
+            b2_authorize_account_version(&self.inner.http, &self.inner.endpoint, api_version, key)
+                .await?;
+        *self.inner.auth.write().unwrap() = auth.clone();
+        Ok(auth)
+    }
+
+    /// Runs an API call with the current authorization, re-authorizing and retrying once if the token expired
+    ///
+    /// ```rust,no_run
+    /// # use raze::client::*;
+    /// # async fn f(client: B2Client) -> Result<(), raze::Error> {
+    /// let bucket_id = raze::api::BucketId::new("bucket_id");
+    /// let upauth = client
+    ///     .call(|http, auth| {
+    ///         let bucket_id = bucket_id.clone();
+    ///         async move { raze::api::b2_get_upload_url(&http, &auth, &bucket_id).await }
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn call<F, Fut, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: Fn(Client, B2Auth) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        match f(self.inner.http.clone(), self.auth()).await {
+            Err(e) if is_expired_auth(&e) => {
+                let auth = self.reauthorize().await?;
+                f(self.inner.http.clone(), auth).await
+            }
+            res => res,
+        }
+    }
+}
+
+// Errors meaning the account authorization token has to be renewed
+fn is_expired_auth(e: &Error) -> bool {
+    match e {
+        Error::B2Error(e) => {
+            e.status == 401 && (e.code == "expired_auth_token" || e.code == "bad_auth_token")
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,164 @@
+use crate::client::{B2Client, CredentialsProvider};
+use crate::utils::{recommended_client_builder, user_agent_for};
+use crate::Error;
+use reqwest::{Client, Proxy};
+
+/// Which TLS implementation the HTTP client uses, see [ClientOptions::tls]
+///
+/// Each backend needs its feature, 'native-tls' is enabled by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TlsBackend {
+    /// Whatever reqwest picks from the enabled features
+    #[default]
+    Default,
+    /// The platform's TLS library, using the system's root certificates
+    #[cfg(feature = "native-tls")]
+    Native,
+    /// rustls, using the bundled Mozilla root certificates
+    #[cfg(feature = "rustls-tls")]
+    Rustls,
+}
+
+/// Network settings for the HTTP client of a [B2Client], for environments with proxies or TLS interception
+///
+/// Starts from the settings of [recommended_client][crate::utils::recommended_client].
+///
+/// ```rust,no_run
+/// # use raze::client::*;
+/// # async fn f() -> Result<(), raze::Error> {
+/// let corporate_ca = std::fs::read("/etc/ssl/corporate-ca.pem").map_err(raze::Error::IOError)?;
+/// let client = ClientOptions::new()
+///     .proxy("http://proxy.internal:3128")
+///     .root_certificates_pem(corporate_ca)
+///     .app_name("my-backup/2.0")
+///     .connect(EnvCredentials::new())
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    proxy: Option<String>,
+    root_certificates: Vec<Vec<u8>>,
+    tls: TlsBackend,
+    app_name: Option<String>,
+    endpoint: Option<String>,
+}
+
+impl ClientOptions {
+    pub fn new() -> ClientOptions {
+        ClientOptions::default()
+    }
+
+    /// Sends all requests through the proxy at 'url', e.g. "http://proxy.internal:3128"
+    ///
+    /// Without this, the 'HTTPS_PROXY' and 'HTTP_PROXY' environment variables are used
+    pub fn proxy<T: Into<String>>(mut self, url: T) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Trusts the certificates in a PEM bundle in addition to the default roots
+    ///
+    /// Needed when a proxy intercepts TLS with its own certificate authority
+    pub fn root_certificates_pem<T: Into<Vec<u8>>>(mut self, pem: T) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Picks the TLS implementation, see [TlsBackend]
+    pub fn tls(mut self, tls: TlsBackend) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Identifies the application in the User-Agent, see [user_agent_for]
+    pub fn app_name<T: Into<String>>(mut self, app_name: T) -> Self {
+        self.app_name = Some(app_name.into());
+        self
+    }
+
+    /// Authorizes against another endpoint than [DEFAULT_API_ENDPOINT][crate::api::DEFAULT_API_ENDPOINT]
+    pub fn endpoint<T: Into<String>>(mut self, endpoint: T) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Builds the HTTP client
+    ///
+    /// Returns a [ConfigError][Error::ConfigError] if the proxy URL or a certificate is invalid
+    pub fn http_client(&self) -> Result<Client, Error> {
+        let mut builder = recommended_client_builder();
+        if let Some(app) = self.app_name.as_deref() {
+            builder = builder.user_agent(user_agent_for(app));
+        }
+        if let Some(url) = self.proxy.as_deref() {
+            let proxy = Proxy::all(url)
+                .map_err(|e| Error::ConfigError(format!("invalid proxy '{}': {}", url, e)))?;
+            builder = builder.proxy(proxy);
+        }
+        #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+        for pem in &self.root_certificates {
+            let certificates = reqwest::Certificate::from_pem_bundle(pem)
+                .map_err(|e| Error::ConfigError(format!("invalid root certificate: {}", e)))?;
+            if certificates.is_empty() {
+                return Err(Error::ConfigError(
+                    "no certificates found in PEM bundle".to_string(),
+                ));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        #[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+        if !self.root_certificates.is_empty() {
+            return Err(Error::ConfigError(
+                "root certificates need the 'native-tls' or 'rustls-tls' feature".to_string(),
+            ));
+        }
+        builder = match self.tls {
+            TlsBackend::Default => builder,
+            #[cfg(feature = "native-tls")]
+            TlsBackend::Native => builder.use_native_tls(),
+            #[cfg(feature = "rustls-tls")]
+            TlsBackend::Rustls => builder.use_rustls_tls(),
+        };
+        builder.build().map_err(Error::ReqwestError)
+    }
+
+    /// Builds the HTTP client and authorizes a [B2Client] with it
+    pub async fn connect<C: CredentialsProvider + 'static>(
+        &self,
+        credentials: C,
+    ) -> Result<B2Client, Error> {
+        let http = self.http_client()?;
+        match self.endpoint.as_deref() {
+            Some(endpoint) => B2Client::with_endpoint(http, credentials, endpoint).await,
+            None => B2Client::new(http, credentials).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_client() {
+        assert!(ClientOptions::new()
+            .proxy("http://proxy.internal:3128")
+            .app_name("test/1.0")
+            .http_client()
+            .is_ok());
+        assert!(matches!(
+            ClientOptions::new().proxy("not a url").http_client(),
+            Err(Error::ConfigError(_))
+        ));
+        assert!(matches!(
+            ClientOptions::new()
+                .root_certificates_pem("garbage")
+                .http_client(),
+            Err(Error::ConfigError(_))
+        ));
+    }
+}
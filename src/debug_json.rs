@@ -0,0 +1,172 @@
+//! Logging the JSON of failed API calls, to find out why B2 rejected them
+//!
+//! Off by default, turned on with [set_debug_json] or by setting the 'RAZE_DEBUG_JSON' environment variable
+//! to something other than "0". \
+//! While on, every call B2 answers with an error, or with a body that isn't what the call expects,
+//! writes its request and response JSON to stderr, or passes them to the function set with [set_debug_json_hook].
+//!
+//! ```rust
+//! raze::debug_json::set_debug_json(true);
+//! ```
+//!
+//! Authorization tokens and application keys are replaced with "REDACTED" first, so the output can be shared. \
+//! Only JSON request bodies are logged, not uploaded data.
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
+
+// Environment variable turning the logging on when set_debug_json wasn't called
+const DEBUG_VAR: &str = "RAZE_DEBUG_JSON";
+
+// Fields holding secrets, in requests and responses
+const SECRET_FIELDS: [&str; 2] = ["authorizationToken", "applicationKey"];
+
+/// An API call that failed, with its secrets redacted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedCall {
+    /// Name of the API call, e.g. "b2_list_file_names"
+    pub call: String,
+    pub status: u16,
+    /// The JSON sent, None for calls without a JSON body
+    pub request: Option<String>,
+    pub response: String,
+}
+
+impl fmt::Display for FailedCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} failed with status {}", self.call, self.status)?;
+        if let Some(request) = &self.request {
+            writeln!(f, "  request: {}", request)?;
+        }
+        write!(f, "  response: {}", self.response)
+    }
+}
+
+type DebugJsonHook = Arc<dyn Fn(&FailedCall) + Send + Sync>;
+
+// None until set_debug_json is called, the environment variable decides until then
+static ENABLED: RwLock<Option<bool>> = RwLock::new(None);
+static DEBUG_JSON_HOOK: RwLock<Option<DebugJsonHook>> = RwLock::new(None);
+
+/// Turns the logging on or off, overriding the 'RAZE_DEBUG_JSON' environment variable
+pub fn set_debug_json(enabled: bool) {
+    *ENABLED.write().unwrap() = Some(enabled);
+}
+
+/// Whether failed calls are logged
+pub fn is_enabled() -> bool {
+    static FROM_ENV: OnceLock<bool> = OnceLock::new();
+    ENABLED.read().unwrap().unwrap_or_else(|| {
+        *FROM_ENV
+            .get_or_init(|| matches!(std::env::var(DEBUG_VAR), Ok(v) if !v.is_empty() && v != "0"))
+    })
+}
+
+/// Sends failed calls to 'hook' instead of stderr, e.g. to route them to a logger, replacing the previous hook
+///
+/// The hook is only called while the logging is on
+pub fn set_debug_json_hook<F: Fn(&FailedCall) + Send + Sync + 'static>(hook: F) {
+    *DEBUG_JSON_HOOK.write().unwrap() = Some(Arc::new(hook));
+}
+
+/// Removes the function set by [set_debug_json_hook], logging to stderr again
+pub fn clear_debug_json_hook() {
+    *DEBUG_JSON_HOOK.write().unwrap() = None;
+}
+
+// The body of 'request' if the logging is on and it is JSON, kept before the request is sent
+pub(crate) fn request_json(request: &reqwest::Request) -> Option<Vec<u8>> {
+    if !is_enabled() {
+        return None;
+    }
+    let body = request.body()?.as_bytes()?;
+    // Uploaded data is never a JSON object, so this skips copying it
+    if body.first() != Some(&b'{') {
+        return None;
+    }
+    Some(body.to_vec())
+}
+
+// Logs a failed call, if the logging is on
+pub(crate) fn log_failure(call: &str, status: u16, request: Option<&[u8]>, response: &[u8]) {
+    if !is_enabled() {
+        return;
+    }
+    let failed = FailedCall {
+        call: call.to_string(),
+        status,
+        request: request.map(|body| redact_tokens(&String::from_utf8_lossy(body))),
+        response: redact_tokens(&String::from_utf8_lossy(response)),
+    };
+    // Not holding the lock while calling the hook, so it may replace itself
+    let hook = DEBUG_JSON_HOOK.read().unwrap().clone();
+    match hook {
+        Some(hook) => hook(&failed),
+        None => eprintln!("{}", failed),
+    }
+}
+
+// Replaces the value of every secret field in a JSON body, leaves bodies that aren't JSON as they are
+pub(crate) fn redact_tokens(body: &str) -> String {
+    fn walk(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if SECRET_FIELDS.contains(&key.as_str()) && value.is_string() {
+                        *value = serde_json::Value::from("REDACTED");
+                    } else {
+                        walk(value);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(walk),
+            _ => {}
+        }
+    }
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            walk(&mut value);
+            value.to_string()
+        }
+        Err(_) => body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_log_failure() {
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let sink = logged.clone();
+        // Other tests may fail calls at the same time, only keep this one
+        set_debug_json_hook(move |failed| {
+            if failed.call == "b2_debug_json_test" {
+                sink.lock().unwrap().push(failed.clone());
+                // Used to deadlock, as the hook was called under the lock
+                if failed.status == 500 {
+                    clear_debug_json_hook();
+                }
+            }
+        });
+        let request = br#"{"bucketId":"b","authorizationToken":"secret"}"#;
+        let response = br#"{"status":400,"code":"bad_request","applicationKey":"K001secret"}"#;
+
+        set_debug_json(false);
+        log_failure("b2_debug_json_test", 400, Some(request), response);
+        set_debug_json(true);
+        log_failure("b2_debug_json_test", 400, Some(request), response);
+        log_failure("b2_debug_json_test", 500, None, b"{}");
+        set_debug_json(false);
+        assert!(DEBUG_JSON_HOOK.read().unwrap().is_none());
+
+        let logged = logged.lock().unwrap();
+        assert_eq!(logged.len(), 2);
+        let text = logged[0].to_string();
+        assert!(!text.contains("secret"));
+        assert!(text.contains(r#""bucketId":"b""#));
+        assert!(text.starts_with("b2_debug_json_test failed with status 400"));
+        assert_eq!(redact_tokens("<html>"), "<html>");
+    }
+}
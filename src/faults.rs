@@ -0,0 +1,241 @@
+//! Injects the failures B2 produces under load, to test retry and backoff settings
+//!
+//! While a [FaultInjector] is set with [set_fault_injector], each [api][crate::api] call fails
+//! with the configured probabilities instead of (or, for truncated bodies, after) reaching B2.
+//!
+//! ```rust
+//! use raze::faults::{set_fault_injector, Fault, FaultInjector};
+//! set_fault_injector(
+//!     FaultInjector::new()
+//!         .with(Fault::ServiceUnavailable, 0.2)
+//!         .with(Fault::TruncatedBody, 0.05)
+//!         .only_calls(&["b2_upload_file", "b2_upload_part"])
+//!         .with_seed(42),
+//! );
+//! ```
+//!
+//! The injector is global, like [set_metrics][crate::metrics::set_metrics], and is only meant for tests.
+use crate::utils::sleep;
+use crate::Error;
+use bytes::Bytes;
+use reqwest::{Client, Request, Response};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// A failure [FaultInjector] can produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// 429 "too_many_requests" with a 'Retry-After' of one second, without sending the request
+    TooManyRequests,
+    /// 500 "internal_error", without sending the request
+    InternalError,
+    /// 503 "service_unavailable", without sending the request
+    ServiceUnavailable,
+    /// The request is sent, but the connection breaks halfway through the response body
+    TruncatedBody,
+    /// Nothing happens for the [timeout][FaultInjector::with_timeout], then the call fails with [TimedOut][std::io::ErrorKind::TimedOut]
+    Timeout,
+}
+
+impl Fault {
+    fn status(self) -> Option<(u16, &'static str)> {
+        match self {
+            Fault::TooManyRequests => Some((429, "too_many_requests")),
+            Fault::InternalError => Some((500, "internal_error")),
+            Fault::ServiceUnavailable => Some((503, "service_unavailable")),
+            Fault::TruncatedBody | Fault::Timeout => None,
+        }
+    }
+}
+
+/// Decides which calls fail and how, see the [module documentation][crate::faults]
+#[derive(Debug)]
+pub struct FaultInjector {
+    faults: Vec<(Fault, f64)>,
+    calls: Option<Vec<String>>,
+    timeout: Duration,
+    rng: Mutex<u64>,
+    injected: AtomicUsize,
+}
+
+impl Default for FaultInjector {
+    fn default() -> Self {
+        FaultInjector {
+            faults: Vec::new(),
+            calls: None,
+            timeout: Duration::from_secs(1),
+            rng: Mutex::new(0x2545_f491_4f6c_dd1d),
+            injected: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl FaultInjector {
+    /// An injector that doesn't fail anything yet
+    pub fn new() -> FaultInjector {
+        FaultInjector::default()
+    }
+
+    /// Makes 'fault' happen for a share 'rate' (0.0 to 1.0) of the calls
+    ///
+    /// Rates of several faults add up, so they should not exceed 1.0 together
+    pub fn with(mut self, fault: Fault, rate: f64) -> Self {
+        self.faults.push((fault, rate.clamp(0.0, 1.0)));
+        self
+    }
+
+    /// Only injects faults into the given calls, e.g. "b2_upload_file"
+    pub fn only_calls(mut self, calls: &[&str]) -> Self {
+        self.calls = Some(calls.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// How long a [Timeout][Fault::Timeout] hangs before failing, 1 second by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Seeds the random choices, so a test sees the same faults on every run
+    pub fn with_seed(self, seed: u64) -> Self {
+        // xorshift gets stuck on 0
+        *self.rng.lock().unwrap() = seed.max(1);
+        self
+    }
+
+    /// How many faults were injected so far
+    pub fn injected(&self) -> usize {
+        self.injected.load(Ordering::Relaxed)
+    }
+
+    // xorshift64, mapped to [0, 1)
+    fn roll(&self) -> f64 {
+        let mut state = self.rng.lock().unwrap();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        (*state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn pick(&self, call: &str) -> Option<Fault> {
+        if let Some(calls) = &self.calls {
+            if !calls.iter().any(|c| c == call) {
+                return None;
+            }
+        }
+        let mut roll = self.roll();
+        for (fault, rate) in &self.faults {
+            if roll < *rate {
+                self.injected.fetch_add(1, Ordering::Relaxed);
+                return Some(*fault);
+            }
+            roll -= rate;
+        }
+        None
+    }
+
+    pub(crate) async fn inject(
+        &self,
+        fault: Fault,
+        call: &str,
+        client: &Client,
+        request: Request,
+    ) -> Result<Response, Error> {
+        if let Some((status, code)) = fault.status() {
+            let body = serde_json::json!({
+                "status": status,
+                "code": code,
+                "message": format!("fault injected into {}", call),
+            });
+            return Ok(http::Response::builder()
+                .status(status)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(reqwest::header::RETRY_AFTER, "1")
+                .body(body.to_string())
+                .unwrap()
+                .into());
+        }
+        if fault == Fault::Timeout {
+            sleep(self.timeout).await;
+            return Err(Error::IOError(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("fault injected into {}", call),
+            )));
+        }
+        let resp = crate::metrics::transport(call, client, request).await?;
+        let mut builder = http::Response::builder().status(resp.status());
+        for (name, value) in resp.headers() {
+            builder = builder.header(name, value);
+        }
+        let bytes = resp.bytes().await.map_err(Error::ReqwestError)?;
+        let half = bytes.slice(..bytes.len() / 2);
+        let chunks: Vec<Result<Bytes, std::io::Error>> = vec![
+            Ok(half),
+            Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "connection closed by fault injection",
+            )),
+        ];
+        Ok(builder
+            .body(reqwest::Body::wrap_stream(futures::stream::iter(chunks)))
+            .unwrap()
+            .into())
+    }
+}
+
+static FAULT_INJECTOR: RwLock<Option<Arc<FaultInjector>>> = RwLock::new(None);
+
+/// Starts injecting faults into every API call, replacing the previous injector
+///
+/// Returns the injector, e.g. to check how many faults it [injected][FaultInjector::injected]
+pub fn set_fault_injector(injector: FaultInjector) -> Arc<FaultInjector> {
+    let injector = Arc::new(injector);
+    *FAULT_INJECTOR.write().unwrap() = Some(injector.clone());
+    injector
+}
+
+/// Removes the injector set by [set_fault_injector]
+pub fn clear_fault_injector() {
+    *FAULT_INJECTOR.write().unwrap() = None;
+}
+
+/// The fault the current injector picks for 'call', if any
+pub(crate) fn pick(call: &str) -> Option<(Arc<FaultInjector>, Fault)> {
+    let injector = FAULT_INJECTOR.read().unwrap().clone()?;
+    let fault = injector.pick(call)?;
+    Some((injector, fault))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick() {
+        let injector = FaultInjector::new()
+            .with(Fault::ServiceUnavailable, 0.5)
+            .only_calls(&["b2_upload_file"])
+            .with_seed(7);
+        let picks: Vec<_> = (0..1000).map(|_| injector.pick("b2_upload_file")).collect();
+        let failed = picks.iter().filter(|p| p.is_some()).count();
+        assert!((400..600).contains(&failed), "{} failures", failed);
+        assert_eq!(injector.injected(), failed);
+        assert_eq!(injector.pick("b2_list_buckets"), None);
+        assert_eq!(FaultInjector::new().pick("b2_upload_file"), None);
+    }
+
+    #[tokio::test]
+    async fn test_inject_status() {
+        let injector = FaultInjector::new();
+        let client = Client::new();
+        let request = client.post("http://localhost/").build().unwrap();
+        let resp = injector
+            .inject(Fault::TooManyRequests, "b2_upload_file", &client, request)
+            .await
+            .unwrap();
+        let e = Error::from_response(resp, |_| {}).await;
+        assert!(matches!(&e, Error::B2Error(api) if api.status == 429));
+        assert!(e.is_transient());
+    }
+}
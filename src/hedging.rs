@@ -0,0 +1,145 @@
+//! Hedged requests, cutting the tail latency of small read-only calls
+//!
+//! With a [HedgePolicy] set, a call that hasn't answered after the policy's delay is sent a second time,
+//! and whichever response arrives first is used. The slower request is dropped. \
+//! Only idempotent calls should be hedged, by default the listing and file info calls in [HEDGED_CALLS].
+//!
+//! ```rust
+//! use raze::hedging::{set_hedge_policy, HedgePolicy};
+//! use std::time::Duration;
+//! set_hedge_policy(HedgePolicy::new(Duration::from_millis(300)));
+//! ```
+//!
+//! Each hedged request costs another transaction, so the delay should be well above the usual latency,
+//! e.g. around its 95th percentile as reported by [metrics][crate::metrics].
+use crate::utils::sleep;
+use futures::future::{select, Either};
+use futures::Future;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// The calls [HedgePolicy::new] hedges, all of which only read
+pub const HEDGED_CALLS: [&str; 5] = [
+    "b2_list_buckets",
+    "b2_list_file_names",
+    "b2_list_file_versions",
+    "b2_get_file_info",
+    "b2_get_bucket_notification_rules",
+];
+
+/// Which calls are hedged, and after how long
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HedgePolicy {
+    delay: Duration,
+    calls: Vec<String>,
+}
+
+impl HedgePolicy {
+    /// Hedges the [HEDGED_CALLS] once they have been waiting for 'delay'
+    pub fn new(delay: Duration) -> HedgePolicy {
+        HedgePolicy {
+            delay,
+            calls: HEDGED_CALLS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// Replaces the hedged calls, which must all be safe to send twice
+    pub fn with_calls(mut self, calls: &[&str]) -> Self {
+        self.calls = calls.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+}
+
+static HEDGE_POLICY: RwLock<Option<HedgePolicy>> = RwLock::new(None);
+
+/// Starts hedging calls following 'policy', replacing the previous policy
+pub fn set_hedge_policy(policy: HedgePolicy) {
+    *HEDGE_POLICY.write().unwrap() = Some(policy);
+}
+
+/// Stops hedging, removing the policy set by [set_hedge_policy]
+pub fn clear_hedge_policy() {
+    *HEDGE_POLICY.write().unwrap() = None;
+}
+
+/// How long 'call' may take before it is hedged, None if it isn't
+pub(crate) fn delay_for(call: &str) -> Option<Duration> {
+    let policy = HEDGE_POLICY.read().unwrap();
+    let policy = policy.as_ref()?;
+    policy
+        .calls
+        .iter()
+        .any(|c| c == call)
+        .then_some(policy.delay)
+}
+
+/// Runs 'first', starting 'second' if it takes longer than 'delay', and returns the first success
+///
+/// If one of them fails, the other one is awaited instead
+pub(crate) async fn race<T, E, F, G, Fut>(delay: Duration, first: F, second: G) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    G: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    futures::pin_mut!(first);
+    let first = match select(first, sleep(delay)).await {
+        Either::Left((res, _)) => return res,
+        Either::Right((_, first)) => first,
+    };
+    let second = second();
+    futures::pin_mut!(second);
+    match select(first, second).await {
+        Either::Left((Ok(res), _)) | Either::Right((Ok(res), _)) => Ok(res),
+        Either::Left((Err(_), other)) => other.await,
+        Either::Right((Err(_), other)) => other.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_race() {
+        let slow = async {
+            sleep(Duration::from_millis(500)).await;
+            Ok::<_, ()>(1)
+        };
+        let res = race(Duration::from_millis(10), slow, || async { Ok(2) }).await;
+        assert_eq!(res, Ok(2));
+
+        let hedged = AtomicBool::new(false);
+        let res = race(
+            Duration::from_millis(500),
+            async { Ok::<_, ()>(1) },
+            || async {
+                hedged.store(true, Ordering::Relaxed);
+                Ok(2)
+            },
+        )
+        .await;
+        assert_eq!(res, Ok(1));
+        assert!(!hedged.load(Ordering::Relaxed));
+
+        // A failed hedge doesn't hide the slower success
+        let slow = async {
+            sleep(Duration::from_millis(50)).await;
+            Ok(1)
+        };
+        let res = race(Duration::from_millis(10), slow, || async { Err(()) }).await;
+        assert_eq!(res, Ok(1));
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = HedgePolicy::new(Duration::from_millis(200)).with_calls(&["b2_get_file_info"]);
+        assert_eq!(policy.calls, ["b2_get_file_info"]);
+        assert_eq!(policy.delay(), Duration::from_millis(200));
+    }
+}
@@ -17,7 +17,8 @@
 //! async fn main() {
 //!     let client = reqwest::ClientBuilder::new().build().unwrap();
 //!     let auth = b2_authorize_account(&client, std::env::var("B2_TEST_KEY_STRING").unwrap()).await.unwrap();
-//!     let upauth = b2_get_upload_url(&client, &auth, std::env::var("B2_TEST_BUCKET_ID").unwrap()).await.unwrap();
+//!     let bucket_id = BucketId::new(std::env::var("B2_TEST_BUCKET_ID").unwrap());
+//!     let upauth = b2_get_upload_url(&client, &auth, &bucket_id).await.unwrap();
 //!     let file = tokio::fs::File::open("tests/resources/simple_text_file.txt").await.unwrap();
 //!     let metadata = file.metadata().await.unwrap();
 //!     let size = metadata.len();
@@ -44,11 +45,41 @@
 
 /// Raw API bindings, mostly 1:1 with official API
 pub mod api;
+/// Limiting how often API calls are made
+#[cfg(feature = "util_streams")]
+pub mod call_limits;
+/// Content-addressed blob storage
+#[cfg(feature = "cas")]
+pub mod cas;
+/// High-level client handling (re-)authorization
+#[cfg(feature = "utils")]
+pub mod client;
+/// Logging the JSON of failed calls
+pub mod debug_json;
+/// Injecting B2 failures for resilience tests
+#[cfg(feature = "faults")]
+pub mod faults;
+/// Sending slow read-only calls twice
+#[cfg(feature = "util_streams")]
+pub mod hedging;
+/// Hooks for recording API calls
+pub mod metrics;
+/// Adapter for the object_store crate
+#[cfg(feature = "object_store")]
+pub mod object_store;
+/// Recording and replaying B2 responses for tests
+#[cfg(feature = "replay")]
+pub mod replay;
+/// Bindings for the S3-compatible API
+#[cfg(feature = "s3")]
+pub mod s3;
 /// Various helper functions to assist with common tasks
 pub mod utils;
 
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::{Arc, RwLock};
 
 #[derive(Debug)]
 /// The various kinds of errors this crate may return
@@ -61,6 +92,37 @@ pub enum Error {
     SerdeError(serde_json::Error),
     /// API related errors, returned by the B2 backend
     B2Error(B2ApiError),
+    /// A storage, download or transaction cap of the account was reached
+    ///
+    /// Retrying won't help until the cap is raised at <https://secure.backblaze.com/caps_alerts.htm>,
+    /// see [set_cap_exceeded_hook] to be notified when this happens
+    CapExceeded(B2ApiError),
+    /// Missing or invalid configuration, e.g. credentials that aren't set
+    ConfigError(String),
+    /// The Sha1 B2 reports for an uploaded file differs from the one computed locally
+    ///
+    /// The data was corrupted on the way, so the upload should be repeated
+    ChecksumMismatch {
+        file_name: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+type CapExceededHook = Arc<dyn Fn(&B2ApiError) + Send + Sync>;
+
+static CAP_EXCEEDED_HOOK: RwLock<Option<CapExceededHook>> = RwLock::new(None);
+
+/// Sets a function that is called every time B2 reports that a cap was exceeded, replacing the previous one
+///
+/// Useful for long-running jobs, which can pause and ask the user to raise their caps instead of failing every request
+pub fn set_cap_exceeded_hook<F: Fn(&B2ApiError) + Send + Sync + 'static>(hook: F) {
+    *CAP_EXCEEDED_HOOK.write().unwrap() = Some(Arc::new(hook));
+}
+
+/// Removes the function set by [set_cap_exceeded_hook]
+pub fn clear_cap_exceeded_hook() {
+    *CAP_EXCEEDED_HOOK.write().unwrap() = None;
 }
 
 impl Error {
@@ -75,25 +137,129 @@ impl Error {
             Ok(v) => v,
             Err(e) => return Error::SerdeError(e),
         };
-        Error::B2Error(deserialized)
+        Error::from_api_error(deserialized)
+    }
+
+    /// Picks the variant for an API error, calling the cap exceeded hook if needed
+    fn from_api_error(error: B2ApiError) -> Error {
+        // Plain "cap_exceeded" as well as "download_cap_exceeded" etc.
+        if error.status == 403 && error.code.ends_with("cap_exceeded") {
+            // Not holding the lock while calling the hook, so it may replace itself
+            let hook = CAP_EXCEEDED_HOOK.read().unwrap().clone();
+            if let Some(hook) = hook {
+                hook(&error);
+            }
+            return Error::CapExceeded(error);
+        }
+        Error::B2Error(error)
+    }
+
+    /// Whether this error is a [CapExceeded][Error::CapExceeded]
+    pub fn is_cap_exceeded(&self) -> bool {
+        matches!(self, Error::CapExceeded(_))
+    }
+
+    /// Whether retrying the request that caused this error may succeed
+    ///
+    /// True for connection problems, timeouts and the B2 statuses 408, 429, 500 and 503 \
+    /// See [Error Handling](https://www.backblaze.com/b2/docs/calling.html#error_handling)
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::ReqwestError(e) => {
+                e.is_timeout() || e.is_connect() || e.is_request() || e.is_body()
+            }
+            Error::IOError(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            Error::SerdeError(_) => false,
+            Error::B2Error(e) => matches!(e.status, 408 | 429 | 500 | 503),
+            Error::CapExceeded(_) | Error::ConfigError(_) | Error::ChecksumMismatch { .. } => false,
+        }
     }
 
     /// Same as from_string but works directly on a reqwest::Response
-    async fn from_response(resp: reqwest::Response) -> Error {
-        match resp.text().await {
-            Ok(s) => Error::from_json(&s),
+    ///
+    /// The request id is taken from the response headers, see [B2ApiError::request_id] \
+    /// 'inspect' sees the body before it is parsed, for the [debug_json] log
+    async fn from_response<F: FnOnce(&str)>(resp: reqwest::Response, inspect: F) -> Error {
+        let request_id = metrics::request_id(resp.headers()).map(str::to_string);
+        let mut e = match resp.text().await {
+            Ok(s) => {
+                inspect(&s);
+                Error::from_json(&s)
+            }
             Err(e) => Error::ReqwestError(e),
+        };
+        if let Error::B2Error(api) | Error::CapExceeded(api) = &mut e {
+            api.request_id = request_id;
         }
+        e
     }
 }
 
-// Helper method for figuring out if an error was a Serde or API error
-// Takes the json-str, return either a B2 API error or a Serde error
-fn handle_b2error_kinds(n: &str) -> Error {
-    let _b2err: B2ApiError = match serde_json::from_str(&n) {
-        Ok(v) => return Error::B2Error(v),
-        Err(e) => return Error::SerdeError(e),
-    };
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ReqwestError(e) => write!(f, "HTTP error: {}", e),
+            Error::IOError(e) => write!(f, "IO error: {}", e),
+            Error::SerdeError(e) => write!(f, "(De)Serialization error: {}", e),
+            Error::B2Error(e) => write!(f, "{}", e),
+            Error::ConfigError(e) => write!(f, "Configuration error: {}", e),
+            Error::ChecksumMismatch {
+                file_name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for {}: expected Sha1 {}, B2 reported {}",
+                file_name, expected, actual
+            ),
+            Error::CapExceeded(e) => write!(
+                f,
+                "A cap of the account was exceeded, raise it at https://secure.backblaze.com/caps_alerts.htm. {}",
+                e
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ReqwestError(e) => Some(e),
+            Error::IOError(e) => Some(e),
+            Error::SerdeError(e) => Some(e),
+            Error::B2Error(_)
+            | Error::CapExceeded(_)
+            | Error::ConfigError(_)
+            | Error::ChecksumMismatch { .. } => None,
+        }
+    }
+}
+
+// Deserializes the body of a successful call straight from its bytes, without copying it into a String first
+//
+// A body that isn't a T is returned as the B2 error it holds if it is one, otherwise as the SerdeError of parsing the T,
+// and logged as a failure of 'call' to the debug_json log
+pub(crate) async fn parse_response<T: DeserializeOwned>(
+    call: &str,
+    resp: reqwest::Response,
+) -> Result<T, Error> {
+    let status = resp.status().as_u16();
+    let body = resp.bytes().await.map_err(Error::ReqwestError)?;
+    serde_json::from_slice(&body).map_err(|e| {
+        debug_json::log_failure(call, status, None, &body);
+        match serde_json::from_slice::<B2ApiError>(&body) {
+            Ok(api) => Error::from_api_error(api),
+            Err(_) => Error::SerdeError(e),
+        }
+    })
 }
 
 #[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Ord, PartialOrd)]
@@ -111,6 +277,11 @@ pub struct B2ApiError {
     pub code: String,
     /// A human-readable error message describing what went wrong
     pub message: String,
+    /// The id B2 assigned to the failed request, if it sent one
+    ///
+    /// Include it when contacting Backblaze support about the error
+    #[serde(skip)]
+    pub request_id: Option<String>,
 }
 
 impl fmt::Debug for B2ApiError {
@@ -119,7 +290,11 @@ impl fmt::Debug for B2ApiError {
             f,
             "B2ApiError: error code {} - {}. Message: {}",
             self.status, self.code, self.message
-        )
+        )?;
+        if let Some(id) = &self.request_id {
+            write!(f, ". Request id: {}", id)?;
+        }
+        Ok(())
     }
 }
 
@@ -129,6 +304,38 @@ impl fmt::Display for B2ApiError {
             f,
             "A B2 API Error occurred. Error code {} - {}. Error message: {}",
             self.status, self.code, self.message
-        )
+        )?;
+        if let Some(id) = &self.request_id {
+            write!(f, ". Request id: {}", id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cap_exceeded() {
+        let err = Error::from_json(
+            r#"{"status":403,"code":"download_cap_exceeded","message":"Cannot download file, download bandwidth or transaction (Class B) cap exceeded."}"#,
+        );
+        assert!(err.is_cap_exceeded());
+        assert!(!err.is_transient());
+        let err = Error::from_json(r#"{"status":403,"code":"access_denied","message":""}"#);
+        assert!(!err.is_cap_exceeded());
+    }
+
+    #[test]
+    fn test_hook_can_clear_itself() {
+        set_cap_exceeded_hook(|error: &B2ApiError| {
+            if error.message == "hook test" {
+                clear_cap_exceeded_hook();
+            }
+        });
+        let err = Error::from_json(r#"{"status":403,"code":"cap_exceeded","message":"hook test"}"#);
+        assert!(err.is_cap_exceeded());
+        assert!(CAP_EXCEEDED_HOOK.read().unwrap().is_none());
     }
 }
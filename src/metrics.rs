@@ -0,0 +1,288 @@
+//! Hooks for recording what is sent to B2, e.g. to chart call rates, latencies and errors
+//!
+//! Every [api][crate::api] call reports a [CallRecord] to the [Metrics] set with [set_metrics],
+//! so everything built on top of the calls is covered as well.
+//!
+//! Wrap work in [with_correlation_id] to tie the calls it makes to e.g. a job or an incoming request.
+use crate::Error;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Request, RequestBuilder, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::task::futures::TaskLocalFuture;
+
+// Response headers that may hold the id of a request, the S3 compatible API always sends one
+const REQUEST_ID_HEADERS: [&str; 2] = ["x-bz-request-id", "x-amz-request-id"];
+
+/// The id of the request a response belongs to, if B2 sent one
+pub(crate) fn request_id(headers: &HeaderMap) -> Option<&str> {
+    REQUEST_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+}
+
+/// What happened during a single request to B2
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallRecord<'a> {
+    /// Name of the API call, e.g. "b2_upload_file"
+    pub call: &'a str,
+    /// Time until the response headers arrived, downloads may take longer to finish
+    pub latency: Duration,
+    /// HTTP status of the response, None if no response was received
+    pub status: Option<u16>,
+    /// The 'code' B2 gave for a failed call, e.g. "expired_auth_token"
+    pub error_code: Option<&'a str>,
+    /// Size of the request body, as given by its Content-Length
+    pub bytes_sent: u64,
+    /// Size of the response body, as given by its Content-Length
+    pub bytes_received: u64,
+    /// The id B2 gave the request, if it sent one
+    pub request_id: Option<&'a str>,
+    /// The id set with [with_correlation_id] for the work this call is part of
+    pub correlation_id: Option<&'a str>,
+}
+
+impl CallRecord<'_> {
+    /// Whether the call succeeded
+    pub fn is_success(&self) -> bool {
+        matches!(self.status, Some(s) if (200..300).contains(&s))
+    }
+}
+
+/// Receives a [CallRecord] for every request, see [set_metrics]
+///
+/// Implemented for closures taking a `&CallRecord`
+pub trait Metrics: Send + Sync {
+    fn record(&self, record: &CallRecord<'_>);
+}
+
+impl<F: Fn(&CallRecord<'_>) + Send + Sync> Metrics for F {
+    fn record(&self, record: &CallRecord<'_>) {
+        self(record)
+    }
+}
+
+static METRICS: RwLock<Option<Arc<dyn Metrics>>> = RwLock::new(None);
+
+/// Sets the [Metrics] every request is reported to, replacing the previous one
+///
+/// ```rust
+/// raze::metrics::set_metrics(|record: &raze::metrics::CallRecord| {
+///     println!("{} took {:?}", record.call, record.latency);
+/// });
+/// ```
+pub fn set_metrics<M: Metrics + 'static>(metrics: M) {
+    *METRICS.write().unwrap() = Some(Arc::new(metrics));
+}
+
+/// Removes the [Metrics] set by [set_metrics]
+pub fn clear_metrics() {
+    *METRICS.write().unwrap() = None;
+}
+
+tokio::task_local! {
+    static CORRELATION_ID: Arc<str>;
+}
+
+/// The correlation id of the current task, see [with_correlation_id]
+pub fn correlation_id() -> Option<String> {
+    CORRELATION_ID.try_with(|id| id.to_string()).ok()
+}
+
+/// Runs 'future' with a correlation id, which shows up in the [CallRecord] of every call made by it
+///
+/// The id is task-local, so tasks spawned by 'future' need to be wrapped as well
+///
+/// ```rust,no_run
+/// # async fn f(client: reqwest::Client, auth: raze::api::B2Auth) {
+/// let bucket_id = raze::api::BucketId::new("bucket_id");
+/// let upauth = raze::metrics::with_correlation_id(
+///     "nightly-backup-42",
+///     raze::api::b2_get_upload_url(&client, &auth, &bucket_id),
+/// )
+/// .await;
+/// # }
+/// ```
+pub fn with_correlation_id<T: Into<String>, F: Future>(id: T, future: F) -> WithCorrelationId<F> {
+    WithCorrelationId {
+        inner: Box::pin(CORRELATION_ID.scope(Arc::from(id.into()), future)),
+    }
+}
+
+/// Future returned by [with_correlation_id]
+pub struct WithCorrelationId<F> {
+    inner: Pin<Box<TaskLocalFuture<Arc<str>, F>>>,
+}
+
+impl<F: Future> Future for WithCorrelationId<F> {
+    type Output = F::Output;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+fn report(record: CallRecord<'_>) {
+    // Not holding the lock while recording, so the hook may call set_metrics itself
+    let metrics = METRICS.read().unwrap().clone();
+    if let Some(metrics) = metrics {
+        metrics.record(&record);
+    }
+}
+
+// The Content-Length of a request, falling back to the size of an in-memory body
+fn request_size(request: &reqwest::Request) -> u64 {
+    request
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .or_else(|| {
+            request
+                .body()
+                .and_then(|b| b.as_bytes())
+                .map(|b| b.len() as u64)
+        })
+        .unwrap_or(0)
+}
+
+// Sends the request, a second time if the hedge policy says so
+async fn execute(call: &str, client: &Client, request: Request) -> Result<Response, Error> {
+    #[cfg(feature = "util_streams")]
+    if let Some(delay) = crate::hedging::delay_for(call) {
+        if let Some(second) = request.try_clone() {
+            let first = attempt(call, client, request);
+            return crate::hedging::race(delay, first, || attempt(call, client, second)).await;
+        }
+    }
+    attempt(call, client, request).await
+}
+
+// Sends the request, unless the injector set with faults::set_fault_injector fails it
+async fn attempt(call: &str, client: &Client, request: Request) -> Result<Response, Error> {
+    #[cfg(feature = "faults")]
+    if let Some((injector, fault)) = crate::faults::pick(call) {
+        return injector.inject(fault, call, client, request).await;
+    }
+    transport(call, client, request).await
+}
+
+// Sends the request, or hands it to the cassette set with replay::set_cassette
+#[cfg_attr(not(feature = "replay"), allow(unused_variables))]
+pub(crate) async fn transport(
+    call: &str,
+    client: &Client,
+    request: Request,
+) -> Result<Response, Error> {
+    #[cfg(feature = "replay")]
+    if let Some(cassette) = crate::replay::current() {
+        return cassette.execute(call, client, request).await;
+    }
+    client.execute(request).await.map_err(Error::ReqwestError)
+}
+
+/// Sends a request for the API call 'call', turning unsuccessful responses into errors
+///
+/// The request is reported to the [Metrics], if any
+pub(crate) async fn send(call: &str, request: RequestBuilder) -> Result<Response, Error> {
+    let (client, request) = request.build_split();
+    let request = request.map_err(Error::ReqwestError)?;
+    let bytes_sent = request_size(&request);
+    let request_json = crate::debug_json::request_json(&request);
+    let correlation_id = correlation_id();
+    #[cfg(feature = "util_streams")]
+    crate::call_limits::wait_for_slot(call).await;
+    let start = Instant::now();
+    let res = execute(call, &client, request).await;
+    let mut record = CallRecord {
+        call,
+        latency: start.elapsed(),
+        status: None,
+        error_code: None,
+        bytes_sent,
+        bytes_received: 0,
+        request_id: None,
+        correlation_id: correlation_id.as_deref(),
+    };
+    let resp = match res {
+        Ok(resp) => resp,
+        Err(e) => {
+            report(record);
+            return Err(e);
+        }
+    };
+    record.status = Some(resp.status().as_u16());
+    record.bytes_received = resp.content_length().unwrap_or(0);
+    if resp.status().is_success() {
+        record.request_id = request_id(resp.headers());
+        report(record);
+        return Ok(resp);
+    }
+    let status = resp.status().as_u16();
+    let e = Error::from_response(resp, |body| {
+        crate::debug_json::log_failure(call, status, request_json.as_deref(), body.as_bytes())
+    })
+    .await;
+    if let Error::B2Error(api) | Error::CapExceeded(api) = &e {
+        record.error_code = Some(&api.code);
+        record.request_id = api.request_id.as_deref();
+    }
+    report(record);
+    Err(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_correlation_id() {
+        let id = with_correlation_id("job-1", async {
+            tokio::task::yield_now().await;
+            correlation_id()
+        })
+        .await;
+        assert_eq!(id.as_deref(), Some("job-1"));
+        assert_eq!(correlation_id(), None);
+    }
+
+    #[test]
+    fn test_hook_can_replace_itself() {
+        // Other tests may make calls at the same time, only react to this one
+        set_metrics(|record: &CallRecord| {
+            if record.call == "b2_metrics_test" {
+                clear_metrics();
+            }
+        });
+        report(CallRecord {
+            call: "b2_metrics_test",
+            latency: Duration::ZERO,
+            status: Some(200),
+            error_code: None,
+            bytes_sent: 0,
+            bytes_received: 0,
+            request_id: None,
+            correlation_id: None,
+        });
+        assert!(METRICS.read().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_request_size() {
+        let client = reqwest::Client::new();
+        let json = client.post("http://localhost/").body("{}").build().unwrap();
+        assert_eq!(request_size(&json), 2);
+        let upload = client
+            .post("http://localhost/")
+            .header(reqwest::header::CONTENT_LENGTH, 1040)
+            .build()
+            .unwrap();
+        assert_eq!(request_size(&upload), 1040);
+        assert_eq!(
+            request_size(&client.get("http://localhost/").build().unwrap()),
+            0
+        );
+    }
+}
@@ -0,0 +1,613 @@
+//! Adapter implementing the [object_store](https://docs.rs/object_store) crate's [ObjectStore] trait on top of raze
+//!
+//! This lets tools built on `object_store`, e.g. DataFusion or Parquet writers, read from and write to a B2 bucket. \
+//! Object paths map 1:1 to B2 file names.
+//!
+//! Some operations have no direct B2 counterpart and are emulated:
+//! * Deleting hides the file, like deleting on a versioned S3 bucket
+//! * [PutMode::Create] and [copy_if_not_exists][ObjectStore::copy_if_not_exists] check for existence first, so they are **not atomic**
+//! * Multipart uploads use the large-file API, every part but the last must be at least 'absolute_minimum_part_size' bytes. \
+//!   B2 needs at least 2 parts for a large file, so an upload of a single part is stored as a regular file instead
+use crate::api::{
+    b2_cancel_large_file, b2_copy_file, b2_download_file_by_name, b2_finish_large_file,
+    b2_get_upload_part_url, b2_get_upload_url, b2_hide_file, b2_list_file_names,
+    b2_start_large_file, b2_upload_file, b2_upload_part, B2Auth, B2CopyFileParams,
+    B2DownloadFileByNameParams, B2FileInfo, BucketId, FileId, FileParameters, ListFileNamesRequest,
+    MetadataDirective, PartParameters, Sha1Variant, StartLargeFileParameters,
+};
+use crate::Error;
+use ::object_store::path::Path;
+use ::object_store::{
+    Attribute, GetOptions, GetRange, GetResult, GetResultPayload, ListResult, MultipartUpload,
+    ObjectMeta, ObjectStore, PutMode, PutMultipartOpts, PutOptions, PutPayload, PutResult,
+    Result as StoreResult, UploadPart,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{TimeZone, Utc};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::{Client, Response};
+use sha1::Sha1;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+const STORE: &str = "B2";
+
+/// An [ObjectStore] backed by a single B2 bucket
+///
+/// Note that the B2Auth expires after 24 hours, after which a new store has to be created
+#[derive(Debug, Clone)]
+pub struct B2ObjectStore {
+    client: Client,
+    auth: B2Auth,
+    bucket_id: BucketId,
+    bucket_name: String,
+}
+
+impl B2ObjectStore {
+    /// Both the id and the name of the bucket are needed, as B2 uses the name for downloads and the id for everything else
+    pub fn new<T: Into<BucketId>, Q: Into<String>>(
+        client: Client,
+        auth: B2Auth,
+        bucket_id: T,
+        bucket_name: Q,
+    ) -> B2ObjectStore {
+        B2ObjectStore {
+            client,
+            auth,
+            bucket_id: bucket_id.into(),
+            bucket_name: bucket_name.into(),
+        }
+    }
+
+    // Finds the current version of a file by listing from its name
+    async fn find(&self, location: &Path) -> StoreResult<B2FileInfo> {
+        let name = location.as_ref();
+        let res = b2_list_file_names(
+            &self.client,
+            &self.auth,
+            ListFileNamesRequest::new(self.bucket_id.clone())
+                .start_file_name(name)
+                .max_file_count(1),
+        )
+        .await
+        .map_err(|e| store_error(e, name))?;
+        match res.items.into_iter().next() {
+            Some(info) if info.file_name == name => Ok(info),
+            _ => Err(::object_store::Error::NotFound {
+                path: name.to_string(),
+                source: "no such file".into(),
+            }),
+        }
+    }
+
+    async fn exists(&self, location: &Path) -> StoreResult<bool> {
+        match self.find(location).await {
+            Ok(_) => Ok(true),
+            Err(::object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl fmt::Display for B2ObjectStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "B2ObjectStore({})", self.bucket_name)
+    }
+}
+
+// Maps 404s to NotFound, everything else is reported as a generic error
+fn store_error(err: Error, path: &str) -> ::object_store::Error {
+    match err {
+        Error::B2Error(ref e) if e.status == 404 => ::object_store::Error::NotFound {
+            path: path.to_string(),
+            source: Box::new(err),
+        },
+        _ => ::object_store::Error::Generic {
+            store: STORE,
+            source: Box::new(err),
+        },
+    }
+}
+
+fn meta_from_info(info: &B2FileInfo) -> ObjectMeta {
+    ObjectMeta {
+        location: Path::from(info.file_name.as_str()),
+        last_modified: Utc
+            .timestamp_millis_opt(info.upload_timestamp as i64)
+            .single()
+            .unwrap_or_default(),
+        size: info.content_length as usize,
+        e_tag: info.file_id.clone().map(String::from),
+        version: info.file_id.clone().map(String::from),
+    }
+}
+
+// Builds the metadata of a download from its headers
+// Returns the metadata along with the range of the object contained in the response
+fn meta_from_response(location: &Path, resp: &Response) -> (ObjectMeta, Range<usize>) {
+    let header = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let content_length = resp.content_length().unwrap_or(0) as usize;
+    // "bytes <start>-<end>/<total>"
+    let content_range = header("content-range").and_then(|r| {
+        let (range, total) = r.strip_prefix("bytes ")?.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        Some((
+            start.parse::<usize>().ok()?..end.parse::<usize>().ok()? + 1,
+            total.parse::<usize>().ok()?,
+        ))
+    });
+    let (range, size) = match content_range {
+        Some((range, total)) => (range, total),
+        None => (0..content_length, content_length),
+    };
+    let file_id = header("x-bz-file-id");
+    let meta = ObjectMeta {
+        location: location.clone(),
+        last_modified: header("x-bz-upload-timestamp")
+            .and_then(|t| t.parse::<i64>().ok())
+            .and_then(|t| Utc.timestamp_millis_opt(t).single())
+            .unwrap_or_default(),
+        size,
+        e_tag: file_id.clone(),
+        version: file_id,
+    };
+    (meta, range)
+}
+
+fn range_header(range: &GetRange) -> String {
+    match range {
+        GetRange::Bounded(r) => format!("bytes={}-{}", r.start, r.end.saturating_sub(1)),
+        GetRange::Offset(o) => format!("bytes={}-", o),
+        GetRange::Suffix(n) => format!("bytes=-{}", n),
+    }
+}
+
+// Uploads 'payload' as a regular file, for payloads that fit in one request
+async fn upload_small(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+    name: &str,
+    content_type: Option<&str>,
+    payload: PutPayload,
+) -> Result<B2FileInfo, Error> {
+    let sha1 = sha1_of(&payload);
+    let size = payload.content_length() as u64;
+    let upauth = b2_get_upload_url(client, auth, bucket_id).await?;
+    b2_upload_file(
+        client,
+        &upauth,
+        Bytes::from(payload),
+        FileParameters {
+            file_path: name,
+            file_size: size,
+            content_type,
+            content_sha1: Sha1Variant::Precomputed(&sha1),
+            last_modified_millis: now_millis(),
+        },
+    )
+    .await
+}
+
+fn put_result(info: B2FileInfo) -> PutResult {
+    PutResult {
+        e_tag: info.file_id.clone().map(String::from),
+        version: info.file_id.map(String::from),
+    }
+}
+
+fn sha1_of(payload: &PutPayload) -> String {
+    let mut hasher = Sha1::new();
+    for chunk in payload {
+        hasher.update(chunk);
+    }
+    hasher.hexdigest()
+}
+
+// A prefix matches whole path segments, so "a/b" lists "a/b/c" but not "a/bc"
+fn prefix_string(prefix: Option<&Path>) -> String {
+    match prefix {
+        Some(p) if !p.as_ref().is_empty() => format!("{}/", p.as_ref()),
+        _ => String::new(),
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[async_trait]
+impl ObjectStore for B2ObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> StoreResult<PutResult> {
+        match opts.mode {
+            PutMode::Overwrite => {}
+            PutMode::Create => {
+                if self.exists(location).await? {
+                    return Err(::object_store::Error::AlreadyExists {
+                        path: location.to_string(),
+                        source: "file already exists".into(),
+                    });
+                }
+            }
+            PutMode::Update(_) => return Err(::object_store::Error::NotImplemented),
+        }
+        let content_type = opts
+            .attributes
+            .get(&Attribute::ContentType)
+            .map(|v| v.to_string());
+        let name = location.as_ref();
+        let info = upload_small(
+            &self.client,
+            &self.auth,
+            &self.bucket_id,
+            name,
+            content_type.as_deref(),
+            payload,
+        )
+        .await
+        .map_err(|e| store_error(e, name))?;
+        Ok(put_result(info))
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> StoreResult<Box<dyn MultipartUpload>> {
+        let content_type = opts
+            .attributes
+            .get(&Attribute::ContentType)
+            .map(|v| v.to_string());
+        let name = location.as_ref();
+        let started = b2_start_large_file(
+            &self.client,
+            &self.auth,
+            StartLargeFileParameters {
+                bucket_id: &self.bucket_id,
+                file_name: name,
+                content_type: content_type.as_deref(),
+                file_info: None,
+            },
+        )
+        .await
+        .map_err(|e| store_error(e, name))?;
+        Ok(Box::new(B2MultipartUpload {
+            client: self.client.clone(),
+            auth: Arc::new(self.auth.clone()),
+            bucket_id: self.bucket_id.clone(),
+            file_id: started.file_id.unwrap_or_default(),
+            file_name: name.to_string(),
+            content_type,
+            next_part_number: 1,
+            first_part: Arc::new(Mutex::new(None)),
+            part_sha1s: Arc::new(Mutex::new(BTreeMap::new())),
+        }))
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> StoreResult<GetResult> {
+        if options.if_match.is_some()
+            || options.if_none_match.is_some()
+            || options.if_modified_since.is_some()
+            || options.if_unmodified_since.is_some()
+            || options.version.is_some()
+        {
+            return Err(::object_store::Error::NotImplemented);
+        }
+        if options.head {
+            let meta = meta_from_info(&self.find(location).await?);
+            return Ok(GetResult {
+                payload: GetResultPayload::Stream(futures::stream::empty().boxed()),
+                range: 0..meta.size,
+                meta,
+                attributes: Default::default(),
+            });
+        }
+
+        let name = location.as_ref();
+        let resp = b2_download_file_by_name(
+            &self.client,
+            &self.auth,
+            B2DownloadFileByNameParams {
+                bucket_name: self.bucket_name.clone(),
+                file_name: name.to_string(),
+                authorization: None,
+                download_host: None,
+                omit_authorization: false,
+                range: options.range.as_ref().map(range_header),
+            },
+        )
+        .await
+        .map_err(|e| store_error(e, name))?;
+        let (meta, range) = meta_from_response(location, &resp);
+        let stream = resp
+            .bytes_stream()
+            .map_err(|e| store_error(Error::ReqwestError(e), ""))
+            .boxed();
+        Ok(GetResult {
+            payload: GetResultPayload::Stream(stream),
+            meta,
+            range,
+            attributes: Default::default(),
+        })
+    }
+
+    async fn head(&self, location: &Path) -> StoreResult<ObjectMeta> {
+        Ok(meta_from_info(&self.find(location).await?))
+    }
+
+    async fn delete(&self, location: &Path) -> StoreResult<()> {
+        // Deleting something that doesn't exist is not an error
+        if !self.exists(location).await? {
+            return Ok(());
+        }
+        let name = location.as_ref();
+        b2_hide_file(&self.client, &self.auth, &self.bucket_id, name)
+            .await
+            .map_err(|e| store_error(e, name))?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, StoreResult<ObjectMeta>> {
+        let prefix = prefix_string(prefix);
+        futures::stream::try_unfold(Some(prefix.clone()), move |start| {
+            let prefix = prefix.clone();
+            async move {
+                let start = match start {
+                    Some(s) => s,
+                    None => return Ok::<_, ::object_store::Error>(None),
+                };
+                let res = b2_list_file_names(
+                    &self.client,
+                    &self.auth,
+                    ListFileNamesRequest::new(self.bucket_id.clone())
+                        .start_file_name(start)
+                        .prefix(&prefix)
+                        .max_file_count(1000),
+                )
+                .await
+                .map_err(|e| store_error(e, &prefix))?;
+                // Names are sorted, so once one doesn't match the prefix, none of the following will
+                let past_prefix = res
+                    .items
+                    .last()
+                    .map(|f| !f.file_name.starts_with(&prefix))
+                    .unwrap_or(true);
+                let metas: Vec<StoreResult<ObjectMeta>> = res
+                    .items
+                    .iter()
+                    .filter(|f| f.file_name.starts_with(&prefix))
+                    .map(|f| Ok(meta_from_info(f)))
+                    .collect();
+                let next = if past_prefix {
+                    None
+                } else {
+                    res.next_file_name
+                };
+                Ok(Some((futures::stream::iter(metas), next)))
+            }
+        })
+        .try_flatten()
+        .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> StoreResult<ListResult> {
+        let prefix = prefix_string(prefix);
+        let mut common_prefixes: Vec<String> = Vec::new();
+        let mut objects = Vec::new();
+        let mut start = Some(prefix.clone());
+        while let Some(start_name) = start.take() {
+            let res = b2_list_file_names(
+                &self.client,
+                &self.auth,
+                ListFileNamesRequest::new(self.bucket_id.clone())
+                    .start_file_name(start_name)
+                    .prefix(&prefix)
+                    .max_file_count(1000),
+            )
+            .await
+            .map_err(|e| store_error(e, &prefix))?;
+            start = res.next_file_name;
+            let mut last_in_dir = false;
+            for f in &res.items {
+                if !f.file_name.starts_with(&prefix) {
+                    start = None;
+                    break;
+                }
+                let rest = &f.file_name[prefix.len()..];
+                last_in_dir = match rest.find('/') {
+                    Some(i) => {
+                        let dir = &f.file_name[..prefix.len() + i];
+                        if common_prefixes.last().map(String::as_str) != Some(dir) {
+                            common_prefixes.push(dir.to_string());
+                        }
+                        true
+                    }
+                    None => {
+                        objects.push(meta_from_info(f));
+                        false
+                    }
+                };
+            }
+            // If the page ended inside a "directory", skip past the rest of it
+            // '0' is the character right after '/', so "dir0" sorts after everything in "dir/"
+            if last_in_dir && start.is_some() {
+                start = common_prefixes.last().map(|dir| format!("{}0", dir));
+            }
+        }
+        Ok(ListResult {
+            common_prefixes: common_prefixes
+                .iter()
+                .map(|p| Path::from(p.as_str()))
+                .collect(),
+            objects,
+        })
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> StoreResult<()> {
+        let source = self.find(from).await?;
+        let name = to.as_ref();
+        b2_copy_file(
+            &self.client,
+            &self.auth,
+            B2CopyFileParams {
+                source_file_id: source.file_id.unwrap_or_default(),
+                destination_bucket_id: None,
+                file_name: name.to_string(),
+                range: None,
+                metadata_directive: MetadataDirective::Copy,
+                content_type: None,
+                file_info: None,
+            },
+        )
+        .await
+        .map_err(|e| store_error(e, name))?;
+        Ok(())
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> StoreResult<()> {
+        if self.exists(to).await? {
+            return Err(::object_store::Error::AlreadyExists {
+                path: to.to_string(),
+                source: "file already exists".into(),
+            });
+        }
+        self.copy(from, to).await
+    }
+}
+
+/// A large file being uploaded through [ObjectStore::put_multipart]
+struct B2MultipartUpload {
+    client: Client,
+    auth: Arc<B2Auth>,
+    bucket_id: BucketId,
+    file_id: FileId,
+    file_name: String,
+    content_type: Option<String>,
+    next_part_number: u32,
+    // The first part, held back until a second one arrives, as B2 won't finish a large file of a single part
+    first_part: Arc<Mutex<Option<PutPayload>>>,
+    // Sha1 of every finished part, by part number
+    part_sha1s: Arc<Mutex<BTreeMap<u32, String>>>,
+}
+
+impl fmt::Debug for B2MultipartUpload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("B2MultipartUpload")
+            .field("file_id", &self.file_id)
+            .field("file_name", &self.file_name)
+            .field("next_part_number", &self.next_part_number)
+            .finish()
+    }
+}
+
+impl B2MultipartUpload {
+    // Uploads part 'part_number' of the large file, recording its sha1
+    fn upload_part(
+        &self,
+        part_number: u32,
+        data: PutPayload,
+    ) -> impl std::future::Future<Output = StoreResult<()>> + Send + 'static {
+        let client = self.client.clone();
+        let auth = self.auth.clone();
+        let file_id = self.file_id.clone();
+        let file_name = self.file_name.clone();
+        let part_sha1s = self.part_sha1s.clone();
+        async move {
+            let sha1 = sha1_of(&data);
+            let size = data.content_length() as u64;
+            let upauth = b2_get_upload_part_url(&client, &auth, &file_id)
+                .await
+                .map_err(|e| store_error(e, &file_name))?;
+            b2_upload_part(
+                &client,
+                &upauth,
+                Bytes::from(data),
+                PartParameters {
+                    part_number,
+                    part_size: size,
+                    content_sha1: Sha1Variant::Precomputed(&sha1),
+                },
+            )
+            .await
+            .map_err(|e| store_error(e, &file_name))?;
+            part_sha1s.lock().unwrap().insert(part_number, sha1);
+            Ok(())
+        }
+    }
+}
+
+#[async_trait]
+impl MultipartUpload for B2MultipartUpload {
+    fn put_part(&mut self, data: PutPayload) -> UploadPart {
+        // Parts are numbered in the order they are submitted, regardless of when they finish
+        let part_number = self.next_part_number;
+        self.next_part_number += 1;
+        if part_number == 1 {
+            *self.first_part.lock().unwrap() = Some(data);
+            return Box::pin(async { Ok(()) });
+        }
+        let first = self
+            .first_part
+            .lock()
+            .unwrap()
+            .take()
+            .map(|first| self.upload_part(1, first));
+        let part = self.upload_part(part_number, data);
+        Box::pin(async move {
+            match first {
+                Some(first) => futures::future::try_join(first, part).await.map(|_| ()),
+                None => part.await,
+            }
+        })
+    }
+
+    async fn complete(&mut self) -> StoreResult<PutResult> {
+        // Fewer than 2 parts, the large file is dropped and the data uploaded as a regular file
+        if self.next_part_number <= 2 {
+            let payload = self.first_part.lock().unwrap().take().unwrap_or_default();
+            b2_cancel_large_file(&self.client, &self.auth, &self.file_id)
+                .await
+                .map_err(|e| store_error(e, &self.file_name))?;
+            let info = upload_small(
+                &self.client,
+                &self.auth,
+                &self.bucket_id,
+                &self.file_name,
+                self.content_type.as_deref(),
+                payload,
+            )
+            .await
+            .map_err(|e| store_error(e, &self.file_name))?;
+            return Ok(put_result(info));
+        }
+        let sha1s: Vec<String> = self.part_sha1s.lock().unwrap().values().cloned().collect();
+        let info = b2_finish_large_file(&self.client, &self.auth, &self.file_id, &sha1s)
+            .await
+            .map_err(|e| store_error(e, &self.file_name))?;
+        Ok(put_result(info))
+    }
+
+    async fn abort(&mut self) -> StoreResult<()> {
+        b2_cancel_large_file(&self.client, &self.auth, &self.file_id)
+            .await
+            .map_err(|e| store_error(e, &self.file_name))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+//! Records real B2 responses to a JSON file and replays them later, for tests that run without credentials
+//!
+//! While a [Cassette] is set with [set_cassette], every [api][crate::api] call goes through it:
+//! a recording cassette sends the request and stores the response,
+//! a replaying one answers with the first unused stored response for the same call, method, path and JSON body.
+//!
+//! ```rust,no_run
+//! use raze::replay::{clear_cassette, set_cassette, Cassette};
+//! # async fn f() -> Result<(), raze::Error> {
+//! // Record with RAZE_RECORD=1 and real credentials once, replay everywhere else
+//! set_cassette(Cassette::from_env("tests/fixtures/list_files.json")?);
+//! // ... make calls ...
+//! if let Some(cassette) = clear_cassette() {
+//!     cassette.save()?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Hosts are not compared, and authorization tokens and application keys in requests and responses
+//! are replaced with "REDACTED" before saving. \
+//! The cassette is global, so tests using different cassettes must not run at the same time.
+use crate::debug_json::redact_tokens;
+use crate::Error;
+use reqwest::{Client, Request, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+// Environment variable switching Cassette::from_env to recording
+const RECORD_VAR: &str = "RAZE_RECORD";
+
+/// A request and the response B2 gave to it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Interaction {
+    /// Name of the API call, e.g. "b2_list_file_names"
+    pub call: String,
+    pub method: String,
+    /// Path and query of the URL, without the host
+    pub path: String,
+    /// The request body, if it was in memory and valid UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The response body if it is valid UTF-8, otherwise see 'body_base64'
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+
+impl Interaction {
+    // Whether 'request' can be answered with this recorded interaction
+    fn matches(&self, request: &Interaction) -> bool {
+        self.call == request.call
+            && self.method == request.method
+            && self.path == request.path
+            && (self.request_body.is_none() || self.request_body == request.request_body)
+    }
+
+    fn body_bytes(&self) -> Result<Vec<u8>, Error> {
+        match (&self.body, &self.body_base64) {
+            (Some(body), _) => Ok(body.clone().into_bytes()),
+            (None, Some(encoded)) => base64::decode(encoded)
+                .map_err(|e| Error::ConfigError(format!("invalid recorded body: {}", e))),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+
+    fn to_response(&self) -> Result<Response, Error> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        builder
+            .body(self.body_bytes()?)
+            .map(Response::from)
+            .map_err(|e| Error::ConfigError(format!("invalid recorded response: {}", e)))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Recording {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Default)]
+struct Tape {
+    interactions: Vec<Interaction>,
+    // Which interactions a replay has used up
+    used: Vec<bool>,
+}
+
+/// A set of recorded interactions, see the [module documentation][crate::replay]
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    tape: Mutex<Tape>,
+}
+
+impl Cassette {
+    /// A cassette that sends requests to B2 and records them, to be written to 'path' by [save][Cassette::save]
+    pub fn record<P: Into<PathBuf>>(path: P) -> Cassette {
+        Cassette {
+            path: path.into(),
+            mode: Mode::Record,
+            tape: Mutex::new(Tape::default()),
+        }
+    }
+
+    /// A cassette answering requests from the recording at 'path', without contacting B2
+    pub fn replay<P: Into<PathBuf>>(path: P) -> Result<Cassette, Error> {
+        let path = path.into();
+        let json = std::fs::read_to_string(&path).map_err(Error::IOError)?;
+        let recording: Recording = serde_json::from_str(&json).map_err(Error::SerdeError)?;
+        let used = vec![false; recording.interactions.len()];
+        Ok(Cassette {
+            path,
+            mode: Mode::Replay,
+            tape: Mutex::new(Tape {
+                interactions: recording.interactions,
+                used,
+            }),
+        })
+    }
+
+    /// Records if the 'RAZE_RECORD' environment variable is set to something other than "0", replays otherwise
+    pub fn from_env<P: Into<PathBuf>>(path: P) -> Result<Cassette, Error> {
+        match std::env::var(RECORD_VAR) {
+            Ok(v) if !v.is_empty() && v != "0" => Ok(Cassette::record(path)),
+            _ => Cassette::replay(path),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.mode == Mode::Record
+    }
+
+    /// Where the recording is read from or written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The interactions recorded so far, or loaded for replaying
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.tape.lock().unwrap().interactions.clone()
+    }
+
+    /// Writes the recorded interactions to the cassette's path, does nothing when replaying
+    pub fn save(&self) -> Result<(), Error> {
+        if !self.is_recording() {
+            return Ok(());
+        }
+        let recording = Recording {
+            interactions: self.interactions(),
+        };
+        let json = serde_json::to_string_pretty(&recording).map_err(Error::SerdeError)?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(Error::IOError)?;
+        }
+        std::fs::write(&self.path, json).map_err(Error::IOError)
+    }
+
+    pub(crate) async fn execute(
+        &self,
+        call: &str,
+        client: &Client,
+        request: Request,
+    ) -> Result<Response, Error> {
+        let mut interaction = Interaction {
+            call: call.to_string(),
+            method: request.method().to_string(),
+            path: match request.url().query() {
+                Some(query) => format!("{}?{}", request.url().path(), query),
+                None => request.url().path().to_string(),
+            },
+            request_body: request
+                .body()
+                .and_then(|b| b.as_bytes())
+                .and_then(|b| std::str::from_utf8(b).ok())
+                // Redacted when replaying as well, so the bodies still match
+                .map(redact_tokens),
+            status: 0,
+            headers: BTreeMap::new(),
+            body: None,
+            body_base64: None,
+        };
+        if self.mode == Mode::Replay {
+            let mut tape = self.tape.lock().unwrap();
+            let Tape { interactions, used } = &mut *tape;
+            let index = (0..interactions.len())
+                .find(|i| !used[*i] && interactions[*i].matches(&interaction))
+                .ok_or_else(|| {
+                    Error::ConfigError(format!(
+                        "no recorded response for {} {} ({}) in {}",
+                        interaction.method,
+                        interaction.path,
+                        call,
+                        self.path.display()
+                    ))
+                })?;
+            used[index] = true;
+            return interactions[index].to_response();
+        }
+
+        let resp = client.execute(request).await.map_err(Error::ReqwestError)?;
+        interaction.status = resp.status().as_u16();
+        interaction.headers = resp
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let bytes = resp.bytes().await.map_err(Error::ReqwestError)?;
+        match std::str::from_utf8(&bytes) {
+            Ok(text) => interaction.body = Some(redact_tokens(text)),
+            Err(_) => interaction.body_base64 = Some(base64::encode(&bytes)),
+        }
+        let resp = interaction.to_response()?;
+        self.tape.lock().unwrap().interactions.push(interaction);
+        Ok(resp)
+    }
+}
+
+static CASSETTE: RwLock<Option<Arc<Cassette>>> = RwLock::new(None);
+
+/// Routes every API call through 'cassette', replacing the previous one
+pub fn set_cassette(cassette: Cassette) {
+    *CASSETTE.write().unwrap() = Some(Arc::new(cassette));
+}
+
+/// Removes the cassette set by [set_cassette], returning it so a recording can be [saved][Cassette::save]
+///
+/// Returns None if there was none, or if calls using it are still running
+pub fn clear_cassette() -> Option<Cassette> {
+    let cassette = CASSETTE.write().unwrap().take()?;
+    Arc::try_unwrap(cassette).ok()
+}
+
+pub(crate) fn current() -> Option<Arc<Cassette>> {
+    CASSETTE.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replay() {
+        let path = std::env::temp_dir().join(format!("raze-replay-{}.json", std::process::id()));
+        let recording = Recording {
+            interactions: vec![Interaction {
+                call: "b2_list_buckets".to_string(),
+                method: "POST".to_string(),
+                path: "/b2api/v2/b2_list_buckets".to_string(),
+                request_body: None,
+                status: 401,
+                headers: BTreeMap::new(),
+                body: Some(r#"{"status":401,"code":"bad_auth_token","message":""}"#.to_string()),
+                body_base64: None,
+            }],
+        };
+        std::fs::write(&path, serde_json::to_string(&recording).unwrap()).unwrap();
+        let cassette = Cassette::replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let client = Client::new();
+        let request = client
+            .post("https://api000.backblazeb2.com/b2api/v2/b2_list_buckets")
+            .body("{}")
+            .build()
+            .unwrap();
+        let resp = cassette
+            .execute("b2_list_buckets", &client, request.try_clone().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 401);
+        assert!(resp.text().await.unwrap().contains("bad_auth_token"));
+        // Each recorded response is only used once
+        assert!(matches!(
+            cassette.execute("b2_list_buckets", &client, request).await,
+            Err(Error::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_redact_tokens() {
+        let body = r#"{"accountId":"a","authorizationToken":"secret","allowed":{"authorizationToken":"x"}}"#;
+        let redacted = redact_tokens(body);
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.contains("\"accountId\":\"a\""));
+        assert_eq!(redact_tokens("not json"), "not json");
+        // b2_create_key returns the new key once, it must not end up in a recording
+        let key = r#"{"applicationKeyId":"id","applicationKey":"K001secret","keyName":"k"}"#;
+        let redacted = redact_tokens(key);
+        assert!(!redacted.contains("K001secret"));
+        assert!(redacted.contains(r#""applicationKeyId":"id""#));
+    }
+}
@@ -0,0 +1,420 @@
+//! Minimal client for B2's [S3-compatible API](https://www.backblaze.com/b2/docs/s3_compatible_api.html)
+//!
+//! Only the handful of object operations that have no native B2 counterpart worth
+//! pulling a full AWS SDK in for are implemented. \
+//! Requests are signed with [AWS Signature Version 4](https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-authenticating-requests.html)
+//! using the application key id and application key as credentials.
+use crate::api::B2Auth;
+use crate::{B2ApiError, Error};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Method, Response};
+use std::time::Duration;
+
+mod signing;
+use self::signing::{authorization_header, presigned_query, uri_encode, AmzDate, CanonicalRequest};
+pub use self::signing::{S3Credentials, MAX_PRESIGNED_EXPIRY_SECS, UNSIGNED_PAYLOAD};
+
+/// Endpoint and credentials for the S3-compatible API - Required by the calls in this module
+#[derive(Clone, Eq, PartialEq)]
+pub struct S3Auth {
+    /// Base URL, e.g. "https://s3.us-west-004.backblazeb2.com"
+    pub endpoint: String,
+    /// Region used for signing, e.g. "us-west-004"
+    pub region: String,
+    pub credentials: S3Credentials,
+}
+
+impl S3Auth {
+    /// Creates an S3Auth for the given endpoint, deriving the region from its host name
+    pub fn new<T: AsRef<str>>(endpoint: T, credentials: S3Credentials) -> S3Auth {
+        let endpoint = endpoint.as_ref().trim_end_matches('/').to_string();
+        // B2 endpoints look like "s3.<region>.backblazeb2.com"
+        let region = host_of(&endpoint)
+            .strip_prefix("s3.")
+            .and_then(|h| h.split('.').next())
+            .unwrap_or("us-east-1")
+            .to_string();
+        S3Auth {
+            endpoint,
+            region,
+            credentials,
+        }
+    }
+
+    /// Creates an S3Auth using the 's3ApiUrl' returned by [b2_authorize_account][crate::api::b2_authorize_account]
+    pub fn from_b2_auth(auth: &B2Auth, credentials: S3Credentials) -> S3Auth {
+        S3Auth::new(&auth.s3_api_url, credentials)
+    }
+
+    // Path-style path for an object, e.g. "/bucket/some/key.txt"
+    fn object_path(&self, bucket_name: &str, key: &str) -> String {
+        format!(
+            "/{}/{}",
+            uri_encode(bucket_name, true),
+            uri_encode(key, false)
+        )
+    }
+}
+
+// Strips the scheme and anything after the authority from a URL
+fn host_of(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+/// A part uploaded with [upload_part], as needed by [complete_multipart_upload]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct S3CompletedPart {
+    pub part_number: u32,
+    pub etag: String,
+}
+
+// Signs and sends a request, returning an error if it didn't succeed
+async fn send_signed(
+    client: &Client,
+    auth: &S3Auth,
+    method: Method,
+    path: &str,
+    query: &[(String, String)],
+    mut headers: HeaderMap,
+    body: Option<reqwest::Body>,
+) -> Result<Response, Error> {
+    let date = AmzDate::now();
+    let signed = vec![
+        ("host".to_string(), host_of(&auth.endpoint).to_string()),
+        (
+            "x-amz-content-sha256".to_string(),
+            UNSIGNED_PAYLOAD.to_string(),
+        ),
+        ("x-amz-date".to_string(), date.date_time.clone()),
+    ];
+    let canonical = CanonicalRequest {
+        method: method.as_str(),
+        path,
+        query,
+        headers: &signed,
+        payload_hash: UNSIGNED_PAYLOAD,
+    };
+    headers.insert("x-amz-content-sha256", UNSIGNED_PAYLOAD.parse().unwrap());
+    headers.insert("x-amz-date", date.date_time.parse().unwrap());
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        authorization_header(&auth.credentials, &auth.region, &date, &canonical)
+            .parse()
+            .unwrap(),
+    );
+
+    let query_string = canonical.canonical_query();
+    let url = if query_string.is_empty() {
+        format!("{}{}", auth.endpoint, path)
+    } else {
+        format!("{}{}?{}", auth.endpoint, path, query_string)
+    };
+
+    let mut req = client.request(method, url).headers(headers);
+    if let Some(body) = body {
+        req = req.body(body);
+    }
+    let resp = match req.send().await {
+        Ok(v) => v,
+        Err(e) => return Err(Error::ReqwestError(e)),
+    };
+    if !resp.status().is_success() {
+        return Err(s3_error_from_response(resp).await);
+    }
+    Ok(resp)
+}
+
+// The S3 API reports errors as XML: <Error><Code>..</Code><Message>..</Message></Error>
+// These are turned into a regular B2Error so callers only have to handle one kind of API error
+async fn s3_error_from_response(resp: Response) -> Error {
+    let status = resp.status().as_u16();
+    match resp.text().await {
+        Ok(s) => s3_error_from_xml(status, &s),
+        Err(e) => Error::ReqwestError(e),
+    }
+}
+
+fn s3_error_from_xml(status: u16, xml: &str) -> Error {
+    Error::B2Error(B2ApiError {
+        status,
+        code: xml_value(xml, "Code").unwrap_or_default(),
+        message: xml_value(xml, "Message").unwrap_or_default(),
+        request_id: xml_value(xml, "RequestId"),
+    })
+}
+
+// Extracts the text of the first <tag>..</tag> element
+// The S3 responses we care about are tiny and flat, so this is all the XML support needed
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(
+        xml[start..end]
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+fn etag_of(resp: &Response) -> String {
+    resp.headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Generates a presigned URL for the given method and object
+///
+/// Anyone holding the URL can perform the request without credentials until it expires \
+/// 'expires_in' is clamped to at most one week, see [MAX_PRESIGNED_EXPIRY_SECS]
+///
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-query-string-auth.html>
+pub fn presigned_url(
+    auth: &S3Auth,
+    method: Method,
+    bucket_name: &str,
+    key: &str,
+    expires_in: Duration,
+) -> String {
+    let path = auth.object_path(bucket_name, key);
+    let query = presigned_query(
+        &auth.credentials,
+        &auth.region,
+        &AmzDate::now(),
+        method.as_str(),
+        &path,
+        host_of(&auth.endpoint),
+        expires_in.as_secs(),
+    );
+    format!("{}{}?{}", auth.endpoint, path, query)
+}
+
+/// Generates a presigned URL that allows uploading an object with a plain HTTP PUT
+///
+/// This enables e.g. browsers to upload directly to B2 without ever seeing your credentials \
+/// The uploader may set any 'Content-Type', as only the host is signed
+pub fn presigned_put_object_url(
+    auth: &S3Auth,
+    bucket_name: &str,
+    key: &str,
+    expires_in: Duration,
+) -> String {
+    presigned_url(auth, Method::PUT, bucket_name, key, expires_in)
+}
+
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/API_PutObject.html>
+///
+/// 'content_length' **has to match the size of the body** \
+/// If 'content_type' is None, "b2/x-auto" is used, letting B2 pick one based on the extension \
+/// Returns the ETag of the object
+pub async fn put_object<B: Into<reqwest::Body>>(
+    client: &Client,
+    auth: &S3Auth,
+    bucket_name: &str,
+    key: &str,
+    body: B,
+    content_length: u64,
+    content_type: Option<&str>,
+) -> Result<String, Error> {
+    let mut headers = HeaderMap::new();
+    headers.insert(reqwest::header::CONTENT_LENGTH, content_length.into());
+    headers.insert(
+        reqwest::header::CONTENT_TYPE,
+        content_type.unwrap_or("b2/x-auto").parse().unwrap(),
+    );
+    let resp = send_signed(
+        client,
+        auth,
+        Method::PUT,
+        &auth.object_path(bucket_name, key),
+        &[],
+        headers,
+        Some(body.into()),
+    )
+    .await?;
+    Ok(etag_of(&resp))
+}
+
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/API_GetObject.html>
+///
+/// Returns the raw [Response] so the body can be streamed
+pub async fn get_object(
+    client: &Client,
+    auth: &S3Auth,
+    bucket_name: &str,
+    key: &str,
+) -> Result<Response, Error> {
+    send_signed(
+        client,
+        auth,
+        Method::GET,
+        &auth.object_path(bucket_name, key),
+        &[],
+        HeaderMap::new(),
+        None,
+    )
+    .await
+}
+
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteObject.html>
+///
+/// Note that like on S3, this hides the file on B2 rather than deleting every version
+pub async fn delete_object(
+    client: &Client,
+    auth: &S3Auth,
+    bucket_name: &str,
+    key: &str,
+) -> Result<(), Error> {
+    send_signed(
+        client,
+        auth,
+        Method::DELETE,
+        &auth.object_path(bucket_name, key),
+        &[],
+        HeaderMap::new(),
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/API_CreateMultipartUpload.html>
+///
+/// Returns the upload id needed for the other multipart calls
+pub async fn create_multipart_upload(
+    client: &Client,
+    auth: &S3Auth,
+    bucket_name: &str,
+    key: &str,
+    content_type: Option<&str>,
+) -> Result<String, Error> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        reqwest::header::CONTENT_TYPE,
+        content_type.unwrap_or("b2/x-auto").parse().unwrap(),
+    );
+    let resp = send_signed(
+        client,
+        auth,
+        Method::POST,
+        &auth.object_path(bucket_name, key),
+        &[("uploads".to_string(), "".to_string())],
+        headers,
+        None,
+    )
+    .await?;
+    let response_string = match resp.text().await {
+        Ok(s) => s,
+        Err(e) => return Err(Error::ReqwestError(e)),
+    };
+    match xml_value(&response_string, "UploadId") {
+        Some(id) => Ok(id),
+        None => Err(s3_error_from_xml(200, &response_string)),
+    }
+}
+
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/API_UploadPart.html>
+///
+/// 'part_number' starts at 1 \
+/// 'content_length' **has to match the size of the body** \
+/// Returns the part's ETag, needed for [complete_multipart_upload]
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_part<B: Into<reqwest::Body>>(
+    client: &Client,
+    auth: &S3Auth,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+    part_number: u32,
+    body: B,
+    content_length: u64,
+) -> Result<S3CompletedPart, Error> {
+    let mut headers = HeaderMap::new();
+    headers.insert(reqwest::header::CONTENT_LENGTH, content_length.into());
+    let resp = send_signed(
+        client,
+        auth,
+        Method::PUT,
+        &auth.object_path(bucket_name, key),
+        &[
+            ("partNumber".to_string(), part_number.to_string()),
+            ("uploadId".to_string(), upload_id.to_string()),
+        ],
+        headers,
+        Some(body.into()),
+    )
+    .await?;
+    Ok(S3CompletedPart {
+        part_number,
+        etag: etag_of(&resp),
+    })
+}
+
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/API_CompleteMultipartUpload.html>
+///
+/// 'parts' must contain every uploaded part, in order of part number
+pub async fn complete_multipart_upload(
+    client: &Client,
+    auth: &S3Auth,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+    parts: &[S3CompletedPart],
+) -> Result<(), Error> {
+    let mut req_body = String::from("<CompleteMultipartUpload>");
+    for part in parts {
+        req_body.push_str(&format!(
+            "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+            part.part_number,
+            part.etag.replace('&', "&amp;").replace('"', "&quot;")
+        ));
+    }
+    req_body.push_str("</CompleteMultipartUpload>");
+
+    let resp = send_signed(
+        client,
+        auth,
+        Method::POST,
+        &auth.object_path(bucket_name, key),
+        &[("uploadId".to_string(), upload_id.to_string())],
+        HeaderMap::new(),
+        Some(req_body.into()),
+    )
+    .await?;
+    // This call may fail after sending a 200 OK, in which case the body contains an error
+    let response_string = match resp.text().await {
+        Ok(s) => s,
+        Err(e) => return Err(Error::ReqwestError(e)),
+    };
+    if response_string.contains("<Error>") {
+        return Err(s3_error_from_xml(200, &response_string));
+    }
+    Ok(())
+}
+
+/// <https://docs.aws.amazon.com/AmazonS3/latest/API/API_AbortMultipartUpload.html>
+pub async fn abort_multipart_upload(
+    client: &Client,
+    auth: &S3Auth,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+) -> Result<(), Error> {
+    send_signed(
+        client,
+        auth,
+        Method::DELETE,
+        &auth.object_path(bucket_name, key),
+        &[("uploadId".to_string(), upload_id.to_string())],
+        HeaderMap::new(),
+        None,
+    )
+    .await?;
+    Ok(())
+}
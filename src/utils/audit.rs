@@ -0,0 +1,219 @@
+use crate::api::{
+    b2_download_file_by_name, Action, B2Auth, B2DownloadFileByNameParams, B2FileInfo, BucketId,
+    FileId,
+};
+use crate::utils::{download_stream, list_all_files_stream};
+use crate::Error;
+use futures::StreamExt;
+use reqwest::Client;
+
+/// How much of each file [audit_bucket] reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditMode {
+    /// Download every file and check its Sha1, see [download_stream]
+    Full,
+    /// Only download the first 'bytes' of every file, checking it can be read and has the listed size
+    ///
+    /// Much cheaper than a full audit, but corrupted data isn't noticed
+    Sample { bytes: u64 },
+}
+
+/// What is wrong with a file found by [audit_bucket]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditProblemKind {
+    /// The downloaded data doesn't match the Sha1 B2 has for it
+    Corrupted { expected: String, actual: String },
+    /// The file is listed but can't be downloaded
+    Missing,
+    /// The downloaded size differs from the listed 'content_length'
+    SizeMismatch { expected: u64, actual: u64 },
+    /// The file was read, but B2 has no Sha1 to check it against, e.g. a large file without 'large_file_sha1'
+    Unverifiable,
+    /// Downloading failed for another reason
+    Failed(String),
+}
+
+/// A file [audit_bucket] found a problem with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditProblem {
+    pub file_name: String,
+    pub file_id: Option<FileId>,
+    pub kind: AuditProblemKind,
+}
+
+/// The result of [audit_bucket]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Number of files checked
+    pub checked: u64,
+    /// Number of bytes downloaded
+    pub bytes_read: u64,
+    pub problems: Vec<AuditProblem>,
+}
+
+impl AuditReport {
+    /// Whether every file passed
+    ///
+    /// [Unverifiable][AuditProblemKind::Unverifiable] files count as problems
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Checks the integrity of every file in a bucket, for periodic verification of archives
+///
+/// Files are listed with [list_all_files_stream] and downloaded one at a time, as specified by 'mode'. \
+/// Problems with single files end up in the [AuditReport], only failing to list the bucket returns an error. \
+/// Downloads are retried up to 'max_retries' times, see [download_stream].
+///
+/// Since downloads are by name, 'bucket_name' has to be the name of the bucket with id 'bucket_id'.
+pub async fn audit_bucket<T: Into<BucketId>, Q: Into<String>>(
+    client: Client,
+    auth: B2Auth,
+    bucket_id: T,
+    bucket_name: Q,
+    mode: AuditMode,
+    max_retries: u32,
+) -> Result<AuditReport, Error> {
+    let bucket_name = bucket_name.into();
+    let mut report = AuditReport::default();
+    let files = list_all_files_stream(client.clone(), auth.clone(), bucket_id, 1000);
+    futures::pin_mut!(files);
+    while let Some(info) = files.next().await {
+        let info = info?;
+        // Folders only show up when listing with a delimiter, but don't hold data either way
+        if info.action != Action::Upload {
+            continue;
+        }
+        let params = B2DownloadFileByNameParams {
+            bucket_name: bucket_name.clone(),
+            file_name: info.file_name.clone(),
+            authorization: None,
+            download_host: None,
+            omit_authorization: false,
+            range: sample_range(&info, mode),
+        };
+        let (read, res) = match params.range {
+            Some(_) => sample_file(&client, &auth, params, &info).await,
+            None => read_file(&client, &auth, params, max_retries, &info).await,
+        };
+        report.checked += 1;
+        report.bytes_read += read;
+        if let Err(kind) = res {
+            report.problems.push(AuditProblem {
+                file_name: info.file_name.clone(),
+                file_id: info.file_id.clone(),
+                kind,
+            });
+        }
+    }
+    Ok(report)
+}
+
+// The 'Range' to request in the given mode, or None to read the whole file
+fn sample_range(info: &B2FileInfo, mode: AuditMode) -> Option<String> {
+    match mode {
+        AuditMode::Sample { bytes } if bytes > 0 && bytes < info.content_length => {
+            Some(format!("bytes=0-{}", bytes - 1))
+        }
+        _ => None,
+    }
+}
+
+// The total size from a 'Content-Range' header such as "bytes 0-99/1234"
+fn content_range_total(value: &str) -> Option<u64> {
+    value.rsplit('/').next()?.parse().ok()
+}
+
+fn to_problem(e: Error) -> AuditProblemKind {
+    match e {
+        Error::ChecksumMismatch {
+            expected, actual, ..
+        } => AuditProblemKind::Corrupted { expected, actual },
+        Error::B2Error(e) if e.status == 404 => AuditProblemKind::Missing,
+        e => AuditProblemKind::Failed(e.to_string()),
+    }
+}
+
+async fn read_file(
+    client: &Client,
+    auth: &B2Auth,
+    params: B2DownloadFileByNameParams,
+    max_retries: u32,
+    info: &B2FileInfo,
+) -> (u64, Result<(), AuditProblemKind>) {
+    let stream = download_stream(client.clone(), auth.clone(), params, max_retries);
+    futures::pin_mut!(stream);
+    let mut read = 0;
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => read += chunk.len() as u64,
+            Err(e) => return (read, Err(to_problem(e))),
+        }
+    }
+    if read != info.content_length {
+        return (
+            read,
+            Err(AuditProblemKind::SizeMismatch {
+                expected: info.content_length,
+                actual: read,
+            }),
+        );
+    }
+    match info.whole_file_sha1() {
+        Some(_) => (read, Ok(())),
+        None => (read, Err(AuditProblemKind::Unverifiable)),
+    }
+}
+
+async fn sample_file(
+    client: &Client,
+    auth: &B2Auth,
+    params: B2DownloadFileByNameParams,
+    info: &B2FileInfo,
+) -> (u64, Result<(), AuditProblemKind>) {
+    let resp = match b2_download_file_by_name(client, auth, params).await {
+        Ok(resp) => resp,
+        Err(e) => return (0, Err(to_problem(e))),
+    };
+    let total = resp
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(content_range_total);
+    let read = match resp.bytes().await {
+        Ok(data) => data.len() as u64,
+        Err(e) => return (0, Err(to_problem(Error::ReqwestError(e)))),
+    };
+    match total {
+        Some(total) if total != info.content_length => (
+            read,
+            Err(AuditProblemKind::SizeMismatch {
+                expected: info.content_length,
+                actual: total,
+            }),
+        ),
+        _ => (read, Ok(())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_file;
+
+    #[test]
+    fn test_sample_range() {
+        let mut info = test_file("a.txt");
+        info.content_length = 1000;
+        assert_eq!(
+            sample_range(&info, AuditMode::Sample { bytes: 100 }).as_deref(),
+            Some("bytes=0-99")
+        );
+        // Sampling at least the whole file is the same as a full read
+        assert_eq!(sample_range(&info, AuditMode::Sample { bytes: 1000 }), None);
+        assert_eq!(sample_range(&info, AuditMode::Full), None);
+        assert_eq!(content_range_total("bytes 0-99/1000"), Some(1000));
+        assert_eq!(content_range_total("bytes 0-99/*"), None);
+    }
+}
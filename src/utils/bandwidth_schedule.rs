@@ -0,0 +1,200 @@
+use crate::utils::RateLimiter;
+use crate::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// Monday to Friday
+    pub const WORKDAYS: [Weekday; 5] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+    ];
+    /// Saturday and Sunday
+    pub const WEEKEND: [Weekday; 2] = [Weekday::Saturday, Weekday::Sunday];
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    fn previous(self) -> Weekday {
+        Weekday::ALL[(self as usize + 6) % 7]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ScheduleRule {
+    days: u8,
+    start: u32,
+    end: u32,
+    rate: Option<usize>,
+}
+
+impl ScheduleRule {
+    fn matches(&self, day: Weekday, minute: u32) -> bool {
+        let on = |day: Weekday| self.days & day.bit() != 0;
+        if self.start <= self.end {
+            on(day) && minute >= self.start && minute < self.end
+        } else {
+            // Past midnight, the rule belongs to the day it started on
+            (on(day) && minute >= self.start) || (on(day.previous()) && minute < self.end)
+        }
+    }
+}
+
+/// Bandwidth limits by time of day and day of the week, e.g. 5 MB/s during office hours and unlimited overnight
+///
+/// The first matching rule wins, the default rate applies outside all rules.
+/// Times are in UTC unless an offset is set with [with_utc_offset][BandwidthSchedule::with_utc_offset]. \
+/// Apply it to a [RateLimiter] with [follow][BandwidthSchedule::follow].
+///
+/// ```rust
+/// # use raze::utils::{BandwidthSchedule, Weekday};
+/// let schedule = BandwidthSchedule::new(None)
+///     .with_utc_offset(60)
+///     .with_rule(&Weekday::WORKDAYS, (9, 0), (17, 30), Some(5_000_000))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandwidthSchedule {
+    default: Option<usize>,
+    utc_offset_minutes: i32,
+    rules: Vec<ScheduleRule>,
+}
+
+impl BandwidthSchedule {
+    /// A schedule with no rules, always using 'default' (None is unlimited)
+    pub fn new(default: Option<usize>) -> BandwidthSchedule {
+        BandwidthSchedule {
+            default,
+            utc_offset_minutes: 0,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Interprets the rules in a time zone 'minutes' ahead of UTC, e.g. 60 for UTC+1
+    pub fn with_utc_offset(mut self, minutes: i32) -> Self {
+        self.utc_offset_minutes = minutes;
+        self
+    }
+
+    /// Uses 'rate' on 'days' from 'start' until 'end', both given as (hour, minute)
+    ///
+    /// If 'end' is before 'start' the rule runs past midnight into the next day. \
+    /// Returns a [ConfigError][Error::ConfigError] if 'days' is empty, an hour is past 23 or a minute past 59
+    pub fn with_rule(
+        mut self,
+        days: &[Weekday],
+        start: (u32, u32),
+        end: (u32, u32),
+        rate: Option<usize>,
+    ) -> Result<Self, Error> {
+        if days.is_empty() {
+            return Err(Error::ConfigError(
+                "a rule needs at least one day".to_string(),
+            ));
+        }
+        for (hour, minute) in [start, end] {
+            if hour > 23 || minute > 59 {
+                return Err(Error::ConfigError(format!(
+                    "{}:{:02} is not a time of day",
+                    hour, minute
+                )));
+            }
+        }
+        self.rules.push(ScheduleRule {
+            days: days.iter().fold(0, |bits, day| bits | day.bit()),
+            start: start.0 * 60 + start.1,
+            end: end.0 * 60 + end.1,
+            rate,
+        });
+        Ok(self)
+    }
+
+    /// The rate in bytes per second at 'time', None if unlimited
+    pub fn rate_at(&self, time: SystemTime) -> Option<usize> {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let minutes = secs.div_euclid(60) + self.utc_offset_minutes as i64;
+        let days = minutes.div_euclid(24 * 60);
+        // 1970-01-01 was a Thursday
+        let day = Weekday::ALL[(days + 3).rem_euclid(7) as usize];
+        let minute = minutes.rem_euclid(24 * 60) as u32;
+        self.rate_on(day, minute)
+    }
+
+    fn rate_on(&self, day: Weekday, minute: u32) -> Option<usize> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(day, minute))
+            .map_or(self.default, |rule| rule.rate)
+    }
+
+    /// Keeps the rate of 'limiter' in line with the schedule, checking every minute
+    ///
+    /// Abort the returned task to stop following the schedule
+    pub fn follow(self, limiter: RateLimiter) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                limiter.set_rate(self.rate_at(SystemTime::now()));
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_at() {
+        let schedule = BandwidthSchedule::new(None)
+            .with_rule(&Weekday::WORKDAYS, (9, 0), (17, 0), Some(5_000_000))
+            .unwrap()
+            .with_rule(&[Weekday::Sunday], (22, 0), (6, 0), Some(1_000))
+            .unwrap();
+        let at = |secs: u64| schedule.rate_at(UNIX_EPOCH + Duration::from_secs(secs));
+        // 1970-01-01 10:00 UTC, a Thursday
+        assert_eq!(at(10 * 3600), Some(5_000_000));
+        assert_eq!(at(18 * 3600), None);
+        // Sunday 23:00 and the following Monday 05:00
+        assert_eq!(at(3 * 86400 + 23 * 3600), Some(1_000));
+        assert_eq!(at(4 * 86400 + 5 * 3600), Some(1_000));
+        assert_eq!(at(4 * 86400 + 7 * 3600), None);
+        // 08:30 UTC is 09:30 at UTC+1
+        let schedule = schedule.with_utc_offset(60);
+        assert_eq!(
+            schedule.rate_at(UNIX_EPOCH + Duration::from_secs(8 * 3600 + 1800)),
+            Some(5_000_000)
+        );
+        let invalid = BandwidthSchedule::new(None).with_rule(&Weekday::ALL, (9, 0), (24, 0), None);
+        assert!(matches!(invalid, Err(Error::ConfigError(_))));
+        let invalid = BandwidthSchedule::new(None).with_rule(&[], (9, 0), (17, 0), None);
+        assert!(matches!(invalid, Err(Error::ConfigError(_))));
+    }
+}
@@ -0,0 +1,150 @@
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Identifies a block of a file read by [B2DownloadReader][crate::utils::B2DownloadReader]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockKey {
+    /// "<bucket name>/<file name>"
+    pub file: Arc<str>,
+    /// Id of the version the block was read from, once it is known
+    pub file_id: Option<Arc<str>>,
+    /// Size of the file, telling apart versions of different sizes
+    pub file_size: u64,
+    pub block_size: u64,
+    /// Which block, the first one starting at byte 0
+    pub index: u64,
+}
+
+/// Where [B2DownloadReader][crate::utils::B2DownloadReader] keeps the blocks it fetched
+///
+/// Readers sharing a cache reuse each other's blocks, e.g. when several tasks read the footer of the same Parquet file. \
+/// Blocks are keyed by name and file id, so a replaced file never reuses the blocks of its old version.
+pub trait BlockCache: Send + Sync {
+    /// The block for 'key', if it is cached
+    fn get(&self, key: &BlockKey) -> Option<Bytes>;
+
+    /// Stores a block, evicting others as needed
+    fn insert(&self, key: BlockKey, block: Bytes);
+
+    /// Whether 'key' is cached, without counting as a use
+    fn contains(&self, key: &BlockKey) -> bool;
+}
+
+/// Which block [MemoryBlockCache] evicts when it is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// The one used the longest time ago, good for revisiting headers and indexes
+    Lru,
+    /// The one fetched first, good for mostly sequential reads
+    Fifo,
+}
+
+/// A [BlockCache] in memory, holding blocks up to a total size
+#[derive(Debug)]
+pub struct MemoryBlockCache {
+    max_bytes: u64,
+    policy: EvictionPolicy,
+    // Next to be evicted at the front
+    blocks: Mutex<(VecDeque<(BlockKey, Bytes)>, u64)>,
+}
+
+impl MemoryBlockCache {
+    /// A least recently used cache of at most 'max_bytes'
+    pub fn new(max_bytes: u64) -> MemoryBlockCache {
+        MemoryBlockCache {
+            max_bytes,
+            policy: EvictionPolicy::Lru,
+            blocks: Mutex::new((VecDeque::new(), 0)),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Total size of the cached blocks in bytes
+    pub fn size(&self) -> u64 {
+        self.blocks.lock().unwrap().1
+    }
+}
+
+impl BlockCache for MemoryBlockCache {
+    fn get(&self, key: &BlockKey) -> Option<Bytes> {
+        let mut guard = self.blocks.lock().unwrap();
+        let blocks = &mut guard.0;
+        let pos = blocks.iter().position(|(k, _)| k == key)?;
+        let block = blocks[pos].1.clone();
+        if self.policy == EvictionPolicy::Lru {
+            let entry = blocks.remove(pos)?;
+            blocks.push_back(entry);
+        }
+        Some(block)
+    }
+
+    fn insert(&self, key: BlockKey, block: Bytes) {
+        let mut guard = self.blocks.lock().unwrap();
+        let (blocks, total) = &mut *guard;
+        if let Some(pos) = blocks.iter().position(|(k, _)| *k == key) {
+            if let Some((_, old)) = blocks.remove(pos) {
+                *total -= old.len() as u64;
+            }
+        }
+        *total += block.len() as u64;
+        blocks.push_back((key, block));
+        while *total > self.max_bytes {
+            match blocks.pop_front() {
+                Some((_, old)) => *total -= old.len() as u64,
+                None => break,
+            }
+        }
+    }
+
+    fn contains(&self, key: &BlockKey) -> bool {
+        self.blocks.lock().unwrap().0.iter().any(|(k, _)| k == key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(index: u64) -> BlockKey {
+        BlockKey {
+            file: "bucket/data.parquet".into(),
+            file_id: Some("4_z1".into()),
+            file_size: 100,
+            block_size: 10,
+            index,
+        }
+    }
+
+    #[test]
+    fn test_eviction_policies() {
+        let block = Bytes::from_static(&[0; 10]);
+        for (policy, kept) in [(EvictionPolicy::Lru, 0), (EvictionPolicy::Fifo, 1)] {
+            let cache = MemoryBlockCache::new(20).with_policy(policy);
+            cache.insert(key(0), block.clone());
+            cache.insert(key(1), block.clone());
+            assert!(cache.get(&key(0)).is_some());
+            cache.insert(key(2), block.clone());
+            assert!(cache.contains(&key(kept)), "{:?}", policy);
+            assert!(!cache.contains(&key(1 - kept)), "{:?}", policy);
+            assert!(cache.contains(&key(2)));
+            assert_eq!(cache.size(), 20);
+        }
+    }
+
+    #[test]
+    fn test_versions_dont_share_blocks() {
+        let cache = MemoryBlockCache::new(100);
+        cache.insert(key(0), Bytes::from_static(b"old"));
+        let replaced = BlockKey {
+            file_id: Some("4_z2".into()),
+            ..key(0)
+        };
+        assert!(cache.get(&replaced).is_none());
+        assert!(cache.get(&key(0)).is_some());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::api::Sha1Variant;
+use crate::utils::{reader_to_stream, BytesStreamHashAtEnd, SpooledBody};
+use crate::Error;
+use bytes::Bytes;
+use futures::TryStreamExt;
+use std::path::{Path, PathBuf};
+
+/// Something that can create the same upload body any number of times, so failed uploads can be retried
+///
+/// Used by [upload_body_with_retry][crate::utils::upload_body_with_retry]. \
+/// Implemented for [Bytes], [SpooledBody] and [PathBody].
+pub trait BodyProvider {
+    /// A new body, sending the data from the start
+    ///
+    /// For [Sha1Variant::HexAtEnd], the body has to end with the Sha1, see [BytesStreamHashAtEnd]
+    fn body(&self) -> reqwest::Body;
+
+    /// The length of the data, not counting a Sha1 at the end
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How the body's Sha1 is passed to B2
+    fn content_sha1(&self) -> Sha1Variant<'_>;
+
+    /// Stored as 'src_last_modified_millis', 0 if unknown
+    fn last_modified_millis(&self) -> u64 {
+        0
+    }
+}
+
+/// The Sha1 is appended to the body, so it doesn't have to be computed up front
+impl BodyProvider for Bytes {
+    fn body(&self) -> reqwest::Body {
+        let stream = futures::stream::iter(vec![Ok(self.clone())]);
+        reqwest::Body::wrap_stream(BytesStreamHashAtEnd::wrap(stream))
+    }
+
+    fn len(&self) -> u64 {
+        Bytes::len(self) as u64
+    }
+
+    fn content_sha1(&self) -> Sha1Variant<'_> {
+        Sha1Variant::HexAtEnd
+    }
+}
+
+impl BodyProvider for SpooledBody {
+    fn body(&self) -> reqwest::Body {
+        SpooledBody::body(self)
+    }
+
+    fn len(&self) -> u64 {
+        SpooledBody::len(self)
+    }
+
+    fn content_sha1(&self) -> Sha1Variant<'_> {
+        Sha1Variant::Precomputed(self.sha1())
+    }
+}
+
+/// A local file, opened again for every body
+///
+/// The file must not change while it is being uploaded
+#[derive(Debug, Clone)]
+pub struct PathBody {
+    path: PathBuf,
+    len: u64,
+    last_modified_millis: u64,
+}
+
+impl PathBody {
+    /// Reads the length and modification time of the file at 'path'
+    pub async fn new<P: AsRef<Path>>(path: P) -> Result<PathBody, Error> {
+        let path = path.as_ref().to_path_buf();
+        let metadata = tokio::fs::metadata(&path).await.map_err(Error::IOError)?;
+        let last_modified_millis = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Ok(PathBody {
+            path,
+            len: metadata.len(),
+            last_modified_millis,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl BodyProvider for PathBody {
+    fn body(&self) -> reqwest::Body {
+        let path = self.path.clone();
+        let stream = futures::stream::once(async move { tokio::fs::File::open(path).await })
+            .map_ok(reader_to_stream)
+            .try_flatten();
+        reqwest::Body::wrap_stream(BytesStreamHashAtEnd::wrap(stream))
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn content_sha1(&self) -> Sha1Variant<'_> {
+        Sha1Variant::HexAtEnd
+    }
+
+    fn last_modified_millis(&self) -> u64 {
+        self.last_modified_millis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_path_body() {
+        let path = std::env::temp_dir().join(format!("raze-path-body-{}", std::process::id()));
+        tokio::fs::write(&path, b"hello world").await.unwrap();
+        let body = PathBody::new(&path).await.unwrap();
+        assert_eq!(body.len(), 11);
+        assert!(body.last_modified_millis() > 0);
+        assert_eq!(body.content_sha1(), Sha1Variant::HexAtEnd);
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        let bytes = Bytes::from_static(b"hello");
+        assert_eq!(BodyProvider::len(&bytes), 5);
+        assert_eq!(bytes.last_modified_millis(), 0);
+    }
+}
@@ -0,0 +1,117 @@
+use bytes::{Bytes, BytesMut};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+struct PoolState {
+    free: Vec<BytesMut>,
+    // Buffers handed out and not yet released
+    outstanding: usize,
+    waiters: Vec<Waker>,
+}
+
+/// A fixed number of reusable part buffers, shared between uploads
+///
+/// At most 'buffer_count' buffers exist at a time, so memory usage stays around 'buffer_count' × part size
+/// no matter how many files are being uploaded. Uploads wait for a free buffer when all of them are in use. \
+/// Buffers are allocated on first use and reused afterwards, instead of allocating a new buffer for each part.
+///
+/// Cloning the pool gives another handle to the same buffers.
+#[derive(Clone)]
+pub struct BufferPool {
+    max_buffers: usize,
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl BufferPool {
+    /// Creates a pool of at most 'buffer_count' (at least 1) buffers
+    pub fn new(buffer_count: usize) -> BufferPool {
+        BufferPool {
+            max_buffers: buffer_count.max(1),
+            state: Arc::new(Mutex::new(PoolState {
+                free: Vec::new(),
+                outstanding: 0,
+                waiters: Vec::new(),
+            })),
+        }
+    }
+
+    /// Takes an empty buffer with room for at least 'capacity' bytes, waiting if all buffers are in use
+    pub fn poll_acquire(&self, cx: &mut Context<'_>, capacity: usize) -> Poll<BytesMut> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(mut buffer) = state.free.pop() {
+            state.outstanding += 1;
+            buffer.reserve(capacity);
+            return Poll::Ready(buffer);
+        }
+        if state.outstanding + state.free.len() < self.max_buffers {
+            state.outstanding += 1;
+            return Poll::Ready(BytesMut::with_capacity(capacity));
+        }
+        state.waiters.push(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Returns a buffer to the pool
+    pub fn release(&self, mut buffer: BytesMut) {
+        buffer.clear();
+        let mut state = self.state.lock().unwrap();
+        state.outstanding = state.outstanding.saturating_sub(1);
+        state.free.push(buffer);
+        for waker in state.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Returns a frozen buffer to the pool
+    ///
+    /// If other references to the data still exist, its memory is freed once they are dropped
+    /// and a new buffer is allocated in its place later
+    pub fn release_bytes(&self, bytes: Bytes) {
+        match bytes.try_into_mut() {
+            Ok(buffer) => self.release(buffer),
+            Err(_) => {
+                let mut state = self.state.lock().unwrap();
+                state.outstanding = state.outstanding.saturating_sub(1);
+                for waker in state.waiters.drain(..) {
+                    waker.wake();
+                }
+            }
+        }
+    }
+
+    /// How many buffers are currently in use
+    pub fn in_use(&self) -> usize {
+        self.state.lock().unwrap().outstanding
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker;
+
+    #[test]
+    fn test_buffer_pool() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let pool = BufferPool::new(2);
+        let a = match pool.poll_acquire(&mut cx, 16) {
+            Poll::Ready(b) => b,
+            Poll::Pending => panic!("pool should have a free buffer"),
+        };
+        let b = match pool.poll_acquire(&mut cx, 16) {
+            Poll::Ready(b) => b,
+            Poll::Pending => panic!("pool should have a free buffer"),
+        };
+        assert!(pool.poll_acquire(&mut cx, 16).is_pending());
+
+        let ptr = a.as_ptr();
+        pool.release_bytes(a.freeze());
+        match pool.poll_acquire(&mut cx, 16) {
+            Poll::Ready(reused) => assert_eq!(reused.as_ptr(), ptr),
+            Poll::Pending => panic!("released buffer should be reused"),
+        }
+        pool.release(b);
+        assert_eq!(pool.in_use(), 1);
+    }
+}
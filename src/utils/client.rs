@@ -0,0 +1,67 @@
+use futures::future::join_all;
+use reqwest::{Client, ClientBuilder};
+use std::time::Duration;
+
+/// User-Agent sent by [recommended_client]
+pub const USER_AGENT: &str = concat!("raze/", env!("CARGO_PKG_VERSION"));
+
+/// The User-Agent for an application built on this crate, e.g. "raze/0.4.1 my-backup/2.0"
+///
+/// Backblaze asks integrations to identify themselves, 'app' should be the name and version of the application
+pub fn user_agent_for(app: &str) -> String {
+    format!("{} {}", USER_AGENT, app.trim())
+}
+
+/// Builds a [Client] configured for talking to B2
+///
+/// * Connections are established within 10 seconds, or fail
+/// * Idle connections are kept for 90 seconds, so consecutive calls to the same host skip the TLS handshake
+/// * At most 32 idle connections are kept per host, which fits many concurrent uploads
+/// * TCP keep-alive probes are sent every 60 seconds, so long uploads aren't dropped by middleboxes
+/// * The User-Agent is [USER_AGENT]
+///
+/// No overall request timeout is set, as uploads and downloads of large files can take a long time.
+pub fn recommended_client() -> Client {
+    recommended_client_builder().build().unwrap()
+}
+
+/// Same as [recommended_client], but identifying the application in the User-Agent, see [user_agent_for]
+pub fn recommended_client_for(app: &str) -> Client {
+    recommended_client_builder()
+        .user_agent(user_agent_for(app))
+        .build()
+        .unwrap()
+}
+
+/// The settings of [recommended_client], to change or add to before building
+pub fn recommended_client_builder() -> ClientBuilder {
+    Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(32)
+        .tcp_keepalive(Duration::from_secs(60))
+        .user_agent(USER_AGENT)
+}
+
+/// Opens connections to the given URLs ahead of time, e.g. the 'api_url' of a [B2Auth][crate::api::B2Auth]
+/// and the 'upload_url' of an [UploadAuth][crate::api::UploadAuth]
+///
+/// Sends a HEAD request to each URL concurrently, which resolves DNS and completes the TLS handshake,
+/// leaving an idle connection in the client's pool for the first real request. \
+/// Responses are discarded, so errors are ignored.
+pub async fn warm_up<T: AsRef<str>>(client: &Client, urls: &[T]) {
+    join_all(urls.iter().map(|url| client.head(url.as_ref()).send())).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_agent_for() {
+        assert_eq!(
+            user_agent_for(" my-backup/2.0 "),
+            format!("raze/{} my-backup/2.0", env!("CARGO_PKG_VERSION"))
+        );
+    }
+}
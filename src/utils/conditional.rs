@@ -0,0 +1,67 @@
+use crate::api::{b2_upload_file, B2Auth, B2FileInfo, FileParameters, UploadAuth};
+use crate::utils::get_file_by_name;
+use crate::Error;
+use reqwest::Client;
+
+/// What a conditional upload such as [upload_if_absent] did
+#[derive(Debug, Clone)]
+pub enum UploadOutcome {
+    /// The file was uploaded
+    Uploaded(B2FileInfo),
+    /// An identical file already existed, so nothing was uploaded
+    Skipped(B2FileInfo),
+    /// A file with the same name already existed, so nothing was uploaded
+    AlreadyExists(B2FileInfo),
+}
+
+impl UploadOutcome {
+    /// The uploaded or already existing file
+    pub fn file_info(&self) -> &B2FileInfo {
+        match self {
+            UploadOutcome::Uploaded(info)
+            | UploadOutcome::Skipped(info)
+            | UploadOutcome::AlreadyExists(info) => info,
+        }
+    }
+
+    /// Whether nothing was uploaded
+    pub fn was_skipped(&self) -> bool {
+        !matches!(self, UploadOutcome::Uploaded(_))
+    }
+}
+
+/// Uploads a file, unless the bucket already has a current version with the same name
+///
+/// Returns [AlreadyExists][UploadOutcome::AlreadyExists] instead of creating another version,
+/// which suits content-addressed names where an existing file is known to be identical. \
+/// The check is a separate call, see [get_file_by_name], so two concurrent uploads of the same name can both succeed.
+pub async fn upload_if_absent<B: Into<reqwest::Body>>(
+    client: &Client,
+    auth: &B2Auth,
+    upload_auth: &UploadAuth,
+    body: B,
+    params: FileParameters<'_>,
+) -> Result<UploadOutcome, Error> {
+    let existing = get_file_by_name(client, auth, &upload_auth.bucket_id, params.file_path).await?;
+    match existing {
+        Some(info) => Ok(UploadOutcome::AlreadyExists(info)),
+        None => b2_upload_file(client, upload_auth, body, params)
+            .await
+            .map(UploadOutcome::Uploaded),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_file;
+
+    #[test]
+    fn test_upload_outcome() {
+        let info = test_file("sha1/aaf4");
+        assert!(!UploadOutcome::Uploaded(info.clone()).was_skipped());
+        let outcome = UploadOutcome::AlreadyExists(info);
+        assert!(outcome.was_skipped());
+        assert_eq!(outcome.file_info().file_name, "sha1/aaf4");
+    }
+}
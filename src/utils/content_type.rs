@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+/// Content type B2 uses to pick a type on its own, based on the file extension
+pub const AUTO_CONTENT_TYPE: &str = "b2/x-auto";
+
+/// Picks the Content-Type of a file from its name
+///
+/// Custom mappings are checked first, by full file name and then by extension (case-insensitive). \
+/// With the `mime_guess` feature, the extension is then looked up in its database. \
+/// If nothing matches, [content_type_for][ContentTypeDetector::content_type_for] falls back to [AUTO_CONTENT_TYPE].
+///
+/// Full file names are useful for files without an extension, e.g. "Makefile" or "LICENSE".
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ContentTypeDetector {
+    by_name: HashMap<String, String>,
+    by_extension: HashMap<String, String>,
+}
+
+impl ContentTypeDetector {
+    pub fn new() -> ContentTypeDetector {
+        ContentTypeDetector::default()
+    }
+
+    /// Maps an extension, without the leading '.', to a content type
+    pub fn with_extension<T: AsRef<str>, Q: Into<String>>(
+        mut self,
+        extension: T,
+        content_type: Q,
+    ) -> Self {
+        self.by_extension.insert(
+            extension.as_ref().trim_start_matches('.').to_lowercase(),
+            content_type.into(),
+        );
+        self
+    }
+
+    /// Maps a full file name (the part after the last '/') to a content type
+    pub fn with_file_name<T: Into<String>, Q: Into<String>>(
+        mut self,
+        file_name: T,
+        content_type: Q,
+    ) -> Self {
+        self.by_name.insert(file_name.into(), content_type.into());
+        self
+    }
+
+    /// The detected content type, or None if it is unknown
+    ///
+    /// Both '/' and '\' are treated as path separators
+    pub fn detect(&self, path: &str) -> Option<String> {
+        let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        if let Some(content_type) = self.by_name.get(name) {
+            return Some(content_type.clone());
+        }
+        // A leading '.' marks a hidden file, not an extension
+        let extension = match name.rfind('.') {
+            Some(i) if i > 0 => name[i + 1..].to_lowercase(),
+            _ => return None,
+        };
+        if let Some(content_type) = self.by_extension.get(&extension) {
+            return Some(content_type.clone());
+        }
+        guess(&extension)
+    }
+
+    /// The detected content type, falling back to [AUTO_CONTENT_TYPE]
+    pub fn content_type_for(&self, path: &str) -> String {
+        self.detect(path)
+            .unwrap_or_else(|| AUTO_CONTENT_TYPE.to_string())
+    }
+}
+
+#[cfg(feature = "mime_guess")]
+fn guess(extension: &str) -> Option<String> {
+    mime_guess::from_ext(extension)
+        .first_raw()
+        .map(|s| s.to_string())
+}
+
+#[cfg(not(feature = "mime_guess"))]
+fn guess(_extension: &str) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_mappings() {
+        let detector = ContentTypeDetector::new()
+            .with_extension(".Parquet", "application/vnd.apache.parquet")
+            .with_file_name("Makefile", "text/x-makefile");
+        assert_eq!(
+            detector.detect("data/part-0.PARQUET").as_deref(),
+            Some("application/vnd.apache.parquet")
+        );
+        assert_eq!(
+            detector.detect("src\\Makefile").as_deref(),
+            Some("text/x-makefile")
+        );
+        assert_eq!(detector.detect(".gitignore"), None);
+        assert_eq!(detector.content_type_for("LICENSE"), AUTO_CONTENT_TYPE);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::api::{
+    b2_copy_file, b2_delete_file_version, b2_get_file_info, Action, B2Auth, B2CopyFileParams,
+    B2FileInfo, BucketId, FileId, MetadataDirective,
+};
+use crate::Error;
+use reqwest::Client;
+use std::collections::HashMap;
+
+/// Copies the file version 'source_file_id' to 'file_name', keeping its content type and file info
+///
+/// With 'destination_bucket_id' None, the copy goes into the source's bucket. \
+/// B2 copies at most 5GB in one call.
+pub async fn copy_preserving(
+    client: &Client,
+    auth: &B2Auth,
+    source_file_id: &FileId,
+    file_name: &str,
+    destination_bucket_id: Option<&BucketId>,
+) -> Result<B2FileInfo, Error> {
+    b2_copy_file(
+        client,
+        auth,
+        B2CopyFileParams {
+            source_file_id: source_file_id.clone(),
+            destination_bucket_id: destination_bucket_id.cloned(),
+            file_name: file_name.to_string(),
+            range: None,
+            metadata_directive: MetadataDirective::Copy,
+            content_type: None,
+            file_info: None,
+        },
+    )
+    .await
+}
+
+/// Copies 'source' to 'file_name' in the same bucket, with a new content type and/or changed file info
+///
+/// B2 only takes new metadata together with a content type, and drops any file info that isn't sent again,
+/// so 'content_type' None keeps the source's content type and 'transform' changes a copy of the source's file info. \
+/// E.g. `|info| { info.insert("author".into(), "me".into()); }` adds an attribute and keeps the others,
+/// while `|info| info.clear()` removes them all.
+///
+/// 'source' must be an uploaded file, as returned by a listing or [get_file_by_name][crate::utils::get_file_by_name].
+pub async fn copy_with_new_metadata<F>(
+    client: &Client,
+    auth: &B2Auth,
+    source: &B2FileInfo,
+    file_name: &str,
+    content_type: Option<&str>,
+    transform: F,
+) -> Result<B2FileInfo, Error>
+where
+    F: FnOnce(&mut HashMap<String, String>),
+{
+    let params = replace_params(source, file_name, content_type, transform)?;
+    b2_copy_file(client, auth, params).await
+}
+
+/// The result of [update_file_metadata]
+#[derive(Debug)]
+pub struct MetadataUpdate {
+    /// The new version, carrying the changed metadata
+    pub file: B2FileInfo,
+    /// Why the old version couldn't be deleted, None if it was
+    ///
+    /// The update itself succeeded either way, the old version just stays around as the previous version
+    pub delete_error: Option<Error>,
+}
+
+/// Changes the content type and/or file info of the file version 'file_id' named 'file_name'
+///
+/// B2 can't change metadata in place, so the version is copied onto its own name with the new metadata
+/// (see [copy_with_new_metadata]) and then deleted. The returned version has a new 'file_id' and upload timestamp,
+/// other versions of the file are left alone. \
+/// If deleting the old version fails, it stays as the previous version and the error is returned in
+/// [delete_error][MetadataUpdate::delete_error].
+///
+/// Only files up to 5GB can be updated, as that is the most B2 copies in one call.
+pub async fn update_file_metadata<F>(
+    client: &Client,
+    auth: &B2Auth,
+    file_name: &str,
+    file_id: &FileId,
+    content_type: Option<&str>,
+    transform: F,
+) -> Result<MetadataUpdate, Error>
+where
+    F: FnOnce(&mut HashMap<String, String>),
+{
+    let source = b2_get_file_info(client, auth, file_id).await?;
+    if source.file_name != file_name {
+        return Err(Error::ConfigError(format!(
+            "{} is a version of {}, not {}",
+            file_id, source.file_name, file_name
+        )));
+    }
+    let file =
+        copy_with_new_metadata(client, auth, &source, file_name, content_type, transform).await?;
+    let delete_error = b2_delete_file_version(client, auth, file_name, file_id)
+        .await
+        .err();
+    Ok(MetadataUpdate { file, delete_error })
+}
+
+// The parameters copying 'source' with replaced metadata, starting from its current metadata
+fn replace_params<F>(
+    source: &B2FileInfo,
+    file_name: &str,
+    content_type: Option<&str>,
+    transform: F,
+) -> Result<B2CopyFileParams, Error>
+where
+    F: FnOnce(&mut HashMap<String, String>),
+{
+    let source_file_id = match (&source.file_id, &source.action) {
+        (Some(file_id), Action::Upload) => file_id.clone(),
+        _ => {
+            return Err(Error::ConfigError(format!(
+                "{} is not an uploaded file",
+                source.file_name
+            )))
+        }
+    };
+    let mut file_info: HashMap<String, String> = source
+        .file_info
+        .clone()
+        .map(HashMap::from)
+        .unwrap_or_default();
+    transform(&mut file_info);
+    let content_type = content_type
+        .or(source.content_type.as_deref())
+        .unwrap_or("b2/x-auto");
+    Ok(B2CopyFileParams {
+        source_file_id,
+        destination_bucket_id: None,
+        file_name: file_name.to_string(),
+        range: None,
+        metadata_directive: MetadataDirective::Replace,
+        content_type: Some(content_type.to_string()),
+        file_info: Some(file_info),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_file;
+
+    #[test]
+    fn test_replace_params() {
+        let mut source = test_file("notes.txt");
+        source.content_length = 5;
+        source.file_id = Some("f1".into());
+        source.file_info = Some([("src_last_modified_millis", "1600000000000")].into());
+        let params = replace_params(&source, "notes.md", Some("text/markdown"), |info| {
+            info.insert("author".to_string(), "me".to_string());
+        })
+        .unwrap();
+        assert_eq!(params.metadata_directive, MetadataDirective::Replace);
+        assert_eq!(params.content_type.as_deref(), Some("text/markdown"));
+        let info = params.file_info.unwrap();
+        assert_eq!(info["author"], "me");
+        assert_eq!(info["src_last_modified_millis"], "1600000000000");
+
+        // Only changing the file info keeps the content type, as B2 needs one
+        let params = replace_params(&source, "notes.txt", None, |info| info.clear()).unwrap();
+        assert_eq!(params.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(params.file_info, Some(HashMap::new()));
+
+        let mut hidden = source.clone();
+        hidden.action = Action::Hide;
+        assert!(matches!(
+            replace_params(&hidden, "notes.txt", None, |_| {}),
+            Err(Error::ConfigError(_))
+        ));
+    }
+}
@@ -0,0 +1,323 @@
+use crate::api::{b2_download_file_by_name, B2Auth, B2DownloadFileByNameParams, B2FileInfo};
+use crate::Error;
+use bytes::Bytes;
+use reqwest::{Client, StatusCode};
+use sha1::Sha1;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Suffix of entries still being written
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// A read-through cache of downloaded files and ranges in a local directory
+///
+/// Entries are keyed by file id and range. Since a file version never changes, they stay valid until evicted,
+/// the least recently used going first once the cache grows past 'max_bytes'. \
+/// Downloads are checked before they are cached: the response must be for the requested version,
+/// and whole files must match their Sha1, which is checked again whenever they are read from disk.
+///
+/// Caching is best-effort, failing to read or write the directory only means downloading again.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<Index>,
+}
+
+// What is in the cache, and when it was last used
+#[derive(Debug, Default)]
+struct Index {
+    // Key to size and last use
+    entries: HashMap<String, (u64, u64)>,
+    total: u64,
+    clock: u64,
+}
+
+impl Index {
+    // Marks 'key' as used, returns whether it is cached
+    fn touch(&mut self, key: &str) -> bool {
+        self.clock += 1;
+        match self.entries.get_mut(key) {
+            Some((_, last_used)) => {
+                *last_used = self.clock;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Adds an entry, returning the keys evicted to stay within 'max_bytes'
+    fn insert(&mut self, key: String, size: u64, max_bytes: u64) -> Vec<String> {
+        self.remove(&key);
+        self.clock += 1;
+        self.entries.insert(key.clone(), (size, self.clock));
+        self.total += size;
+        let mut evicted = Vec::new();
+        while self.total > max_bytes {
+            let oldest = self
+                .entries
+                .iter()
+                .filter(|(k, _)| **k != key)
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(k, _)| k.clone());
+            match oldest {
+                Some(oldest) => {
+                    self.remove(&oldest);
+                    evicted.push(oldest);
+                }
+                None => break,
+            }
+        }
+        evicted
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((size, _)) = self.entries.remove(key) {
+            self.total -= size;
+        }
+    }
+}
+
+impl DiskCache {
+    /// Uses the directory 'dir' as cache, creating it if needed and keeping what is already in it
+    ///
+    /// Existing entries count as used in the order they were written
+    pub fn open<P: Into<PathBuf>>(dir: P, max_bytes: u64) -> Result<DiskCache, Error> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(Error::IOError)?;
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(&dir).map_err(Error::IOError)? {
+            let entry = entry.map_err(Error::IOError)?;
+            let key = entry.file_name().to_string_lossy().into_owned();
+            let metadata = entry.metadata().map_err(Error::IOError)?;
+            if !metadata.is_file() {
+                continue;
+            }
+            if key.ends_with(TEMP_SUFFIX) {
+                // Left over from an interrupted write
+                let _ = std::fs::remove_file(entry.path());
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+            found.push((modified, key, metadata.len()));
+        }
+        found.sort();
+        let cache = DiskCache {
+            dir,
+            max_bytes,
+            index: Mutex::new(Index::default()),
+        };
+        for (_, key, size) in found {
+            // The limit may be lower than when the entries were written
+            for evicted in cache.index_insert(key, size) {
+                let _ = std::fs::remove_file(cache.dir.join(evicted));
+            }
+        }
+        Ok(cache)
+    }
+
+    /// The directory the entries are stored in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Total size of the cached entries in bytes
+    pub fn size(&self) -> u64 {
+        self.index.lock().unwrap().total
+    }
+
+    /// Removes every entry
+    pub fn clear(&self) -> Result<(), Error> {
+        let keys: Vec<String> = self.index.lock().unwrap().entries.keys().cloned().collect();
+        for key in keys {
+            self.index.lock().unwrap().remove(&key);
+            match std::fs::remove_file(self.dir.join(&key)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(Error::IOError(e))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the bytes 'range' of 'file' (everything if None), from the cache or else downloaded and cached
+    ///
+    /// 'file' must be an uploaded version from 'bucket_name'. Since downloads are by name,
+    /// a file replaced by a newer version can't be fetched anymore and fails with a [ConfigError][Error::ConfigError].
+    pub async fn fetch(
+        &self,
+        client: &Client,
+        auth: &B2Auth,
+        bucket_name: &str,
+        file: &B2FileInfo,
+        range: Option<Range<u64>>,
+    ) -> Result<Bytes, Error> {
+        let file_id = file.file_id.as_ref().ok_or_else(|| {
+            Error::ConfigError(format!("{} has no file id to cache it by", file.file_name))
+        })?;
+        if matches!(&range, Some(r) if r.start >= r.end) {
+            return Err(Error::ConfigError("empty download range".to_string()));
+        }
+        let key = cache_key(file_id.as_ref(), range.as_ref());
+
+        if let Some(data) = self.read(&key).await {
+            if range.is_some() || check_sha1(file, &data).is_ok() {
+                return Ok(data);
+            }
+            self.remove(&key).await;
+        }
+
+        let params = B2DownloadFileByNameParams {
+            bucket_name: bucket_name.to_string(),
+            file_name: file.file_name.clone(),
+            authorization: None,
+            download_host: None,
+            omit_authorization: false,
+            range: range
+                .as_ref()
+                .map(|r| format!("bytes={}-{}", r.start, r.end - 1)),
+        };
+        let resp = b2_download_file_by_name(client, auth, params).await?;
+        let served = resp
+            .headers()
+            .get("x-bz-file-id")
+            .and_then(|v| v.to_str().ok());
+        if served != Some(file_id.as_ref()) {
+            return Err(Error::ConfigError(format!(
+                "{} is no longer the current version of {}",
+                file_id, file.file_name
+            )));
+        }
+        let partial = resp.status() == StatusCode::PARTIAL_CONTENT;
+        let mut data = resp.bytes().await.map_err(Error::ReqwestError)?;
+        match &range {
+            // The server sent the whole file
+            Some(r) if !partial => {
+                let end = (r.end as usize).min(data.len());
+                data = data.slice((r.start as usize).min(end)..end);
+            }
+            Some(_) => {}
+            None => check_sha1(file, &data)?,
+        }
+        self.write(key, &data).await;
+        Ok(data)
+    }
+
+    async fn read(&self, key: &str) -> Option<Bytes> {
+        if !self.index.lock().unwrap().touch(key) {
+            return None;
+        }
+        match tokio::fs::read(self.dir.join(key)).await {
+            Ok(data) => Some(data.into()),
+            Err(_) => {
+                self.index.lock().unwrap().remove(key);
+                None
+            }
+        }
+    }
+
+    async fn write(&self, key: String, data: &[u8]) {
+        if data.len() as u64 > self.max_bytes {
+            return;
+        }
+        // Unique, so concurrent writes of the same entry never share a temporary file
+        static TEMPS: AtomicUsize = AtomicUsize::new(0);
+        let temp = self.dir.join(format!(
+            "{}.{}-{}{}",
+            key,
+            std::process::id(),
+            TEMPS.fetch_add(1, Ordering::Relaxed),
+            TEMP_SUFFIX
+        ));
+        let path = self.dir.join(&key);
+        if tokio::fs::write(&temp, data).await.is_err()
+            || tokio::fs::rename(&temp, &path).await.is_err()
+        {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return;
+        }
+        for evicted in self.index_insert(key, data.len() as u64) {
+            let _ = tokio::fs::remove_file(self.dir.join(evicted)).await;
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        self.index.lock().unwrap().remove(key);
+        let _ = tokio::fs::remove_file(self.dir.join(key)).await;
+    }
+
+    fn index_insert(&self, key: String, size: u64) -> Vec<String> {
+        self.index.lock().unwrap().insert(key, size, self.max_bytes)
+    }
+}
+
+// File name of the entry for 'range' of the version 'file_id'
+//
+// Bytes that don't belong in a file name are escaped as "~xx", so different ids never share an entry
+fn cache_key(file_id: &str, range: Option<&Range<u64>>) -> String {
+    let mut id = String::with_capacity(file_id.len());
+    for b in file_id.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => id.push(b as char),
+            _ => id.push_str(&format!("~{:02x}", b)),
+        }
+    }
+    match range {
+        Some(r) => format!("{}.{}-{}", id, r.start, r.end),
+        None => id,
+    }
+}
+
+// Whether 'data' is the whole of 'file', if B2 has a Sha1 for it
+fn check_sha1(file: &B2FileInfo, data: &[u8]) -> Result<(), Error> {
+    let expected = match file.whole_file_sha1() {
+        Some(sha1) => sha1,
+        None => return Ok(()),
+    };
+    let actual = Sha1::from(data).hexdigest();
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(Error::ChecksumMismatch {
+            file_name: file.file_name.clone(),
+            expected: expected.to_string(),
+            actual,
+        })
+    }
+}
+
+impl std::fmt::Debug for DiskCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("DiskCache")
+            .field("dir", &self.dir)
+            .field("max_bytes", &self.max_bytes)
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_eviction() {
+        let mut index = Index::default();
+        assert!(index.insert("a".into(), 40, 100).is_empty());
+        assert!(index.insert("b".into(), 40, 100).is_empty());
+        assert!(index.touch("a"));
+        // "b" is the least recently used now
+        assert_eq!(index.insert("c".into(), 40, 100), vec!["b".to_string()]);
+        assert_eq!(index.total, 80);
+        assert!(!index.touch("b"));
+        // Replacing an entry doesn't count it twice
+        assert!(index.insert("c".into(), 50, 100).is_empty());
+        assert_eq!(index.total, 90);
+
+        assert_eq!(cache_key("4_z27/x", Some(&(0..10))), "4_z27~2fx.0-10");
+        assert_ne!(cache_key("a/b", None), cache_key("a_b", None));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::api::encoding::encode_segment;
+use crate::api::{
+    b2_get_download_authorization, B2Auth, B2DownloadAuth, B2GetDownloadAuthParams, BucketId,
+};
+use crate::Error;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// A [B2DownloadAuth] that knows when it expires
+///
+/// B2 computes the expiry with its own clock, so comparing against the local wall clock breaks when the clocks differ. \
+/// Instead, the validity is measured with a monotonic clock from just before the token was requested,
+/// minus a safety margin (30 seconds by default), so the token is always considered expired a bit early.
+#[derive(Debug, Clone)]
+pub struct ExpiringDownloadAuth {
+    params: B2GetDownloadAuthParams,
+    download_auth: B2DownloadAuth,
+    issued_at: Instant,
+    issued_at_system: SystemTime,
+    margin: Duration,
+}
+
+impl ExpiringDownloadAuth {
+    /// Requests a new download authorization with [b2_get_download_authorization]
+    pub async fn fetch(
+        client: &Client,
+        auth: &B2Auth,
+        params: B2GetDownloadAuthParams,
+    ) -> Result<ExpiringDownloadAuth, Error> {
+        let issued_at = Instant::now();
+        let issued_at_system = SystemTime::now();
+        let download_auth = b2_get_download_authorization(client, auth, params.clone()).await?;
+        Ok(ExpiringDownloadAuth {
+            params,
+            download_auth,
+            issued_at,
+            issued_at_system,
+            margin: Duration::from_secs(30),
+        })
+    }
+
+    /// Sets how long before the actual expiry the token is treated as expired
+    pub fn with_margin(mut self, margin: Duration) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// The current token
+    pub fn download_auth(&self) -> &B2DownloadAuth {
+        &self.download_auth
+    }
+
+    fn valid_for(&self) -> Duration {
+        Duration::from_secs(self.params.valid_duration_in_seconds as u64)
+            .saturating_sub(self.margin)
+    }
+
+    /// When the token should be considered expired, by the local clock
+    pub fn expires_at(&self) -> SystemTime {
+        self.issued_at_system + self.valid_for()
+    }
+
+    /// How long the token remains usable
+    pub fn remaining(&self) -> Duration {
+        self.valid_for().saturating_sub(self.issued_at.elapsed())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining() == Duration::ZERO
+    }
+
+    /// Mints a new token with the same parameters if the current one expires within 'needed'
+    ///
+    /// Note that a token can never outlive the 'valid_duration_in_seconds' it was requested with
+    pub async fn refresh_if_needed(
+        &mut self,
+        client: &Client,
+        auth: &B2Auth,
+        needed: Duration,
+    ) -> Result<&B2DownloadAuth, Error> {
+        if self.remaining() <= needed {
+            let fresh = ExpiringDownloadAuth::fetch(client, auth, self.params.clone()).await?;
+            self.download_auth = fresh.download_auth;
+            self.issued_at = fresh.issued_at;
+            self.issued_at_system = fresh.issued_at_system;
+        }
+        Ok(&self.download_auth)
+    }
+
+    /// Returns a URL for downloading 'file_name' that stays valid for at least 'valid_for',
+    /// minting a new token first if the current one would expire sooner
+    ///
+    /// The token is passed as the 'Authorization' query parameter, so the URL works without extra headers
+    pub async fn url_for<T: AsRef<str>, Q: AsRef<str>>(
+        &mut self,
+        client: &Client,
+        auth: &B2Auth,
+        bucket_name: T,
+        file_name: Q,
+        valid_for: Duration,
+    ) -> Result<String, Error> {
+        let token = &self
+            .refresh_if_needed(client, auth, valid_for)
+            .await?
+            .authorization_token;
+        Ok(format!(
+            "{}?Authorization={}",
+            auth.public_url_for(bucket_name, file_name),
+            encode_segment(token)
+        ))
+    }
+}
+
+/// Reuses download authorizations per bucket and file name prefix
+///
+/// Minting a token for every share link costs a class C transaction each time. \
+/// This cache hands out an unexpired token that covers the requested prefix instead,
+/// so a token for "photos/" is also used for "photos/2021/", and only asks B2 for a new one when needed.
+pub struct DownloadAuthCache {
+    valid_duration_in_seconds: u32,
+    entries: Mutex<HashMap<(String, String), ExpiringDownloadAuth>>,
+}
+
+impl DownloadAuthCache {
+    /// Creates an empty cache, minting tokens valid for 'valid_duration_in_seconds' (1 to 604800)
+    pub fn new(valid_duration_in_seconds: u32) -> DownloadAuthCache {
+        DownloadAuthCache {
+            valid_duration_in_seconds,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // A cached token for the bucket covering 'prefix' that stays valid for 'needed', if any
+    fn lookup(&self, bucket_id: &str, prefix: &str, needed: Duration) -> Option<B2DownloadAuth> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, auth| !auth.is_expired());
+        entries
+            .iter()
+            .filter(|((bucket, cached_prefix), auth)| {
+                bucket == bucket_id
+                    && prefix.starts_with(cached_prefix.as_str())
+                    && auth.remaining() > needed
+            })
+            .map(|(_, auth)| auth.download_auth().clone())
+            .next()
+    }
+
+    /// Returns a token for files starting with 'prefix' in the given bucket, valid for at least 'needed'
+    ///
+    /// 'needed' must be shorter than the validity the cache mints tokens with, or a new token is requested every time
+    pub async fn get<Q: AsRef<str>>(
+        &self,
+        client: &Client,
+        auth: &B2Auth,
+        bucket_id: &BucketId,
+        prefix: Q,
+        needed: Duration,
+    ) -> Result<B2DownloadAuth, Error> {
+        let prefix = prefix.as_ref();
+        if let Some(download_auth) = self.lookup(bucket_id, prefix, needed) {
+            return Ok(download_auth);
+        }
+        let fresh = ExpiringDownloadAuth::fetch(
+            client,
+            auth,
+            B2GetDownloadAuthParams {
+                bucket_id: bucket_id.clone(),
+                file_name_prefix: prefix.to_string(),
+                valid_duration_in_seconds: self.valid_duration_in_seconds,
+            },
+        )
+        .await?;
+        let download_auth = fresh.download_auth().clone();
+        self.entries
+            .lock()
+            .unwrap()
+            .insert((bucket_id.to_string(), prefix.to_string()), fresh);
+        Ok(download_auth)
+    }
+
+    /// Number of cached tokens, expired ones included
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets every cached token
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expiring(valid_duration_in_seconds: u32) -> ExpiringDownloadAuth {
+        ExpiringDownloadAuth {
+            params: B2GetDownloadAuthParams {
+                bucket_id: "bucket".into(),
+                file_name_prefix: String::new(),
+                valid_duration_in_seconds,
+            },
+            download_auth: B2DownloadAuth {
+                bucket_id: "bucket".into(),
+                file_name_prefix: String::new(),
+                authorization_token: "token".to_string(),
+            },
+            issued_at: Instant::now(),
+            issued_at_system: SystemTime::now(),
+            margin: Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn test_expiry_margin() {
+        let auth = expiring(3600);
+        assert!(!auth.is_expired());
+        assert!(auth.remaining() <= Duration::from_secs(3570));
+        assert!(auth.expires_at() <= SystemTime::now() + Duration::from_secs(3570));
+        // Shorter than the margin, so it's expired right away
+        assert!(expiring(20).is_expired());
+    }
+
+    #[test]
+    fn test_cache_lookup_by_prefix() {
+        let cache = DownloadAuthCache::new(3600);
+        cache.entries.lock().unwrap().insert(
+            ("bucket".to_string(), "photos/".to_string()),
+            expiring(3600),
+        );
+        let needed = Duration::from_secs(60);
+        assert!(cache.lookup("bucket", "photos/2021/", needed).is_some());
+        assert!(cache.lookup("bucket", "videos/", needed).is_none());
+        assert!(cache.lookup("other", "photos/", needed).is_none());
+        assert!(cache
+            .lookup("bucket", "photos/", Duration::from_secs(7200))
+            .is_none());
+    }
+}
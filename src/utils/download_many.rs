@@ -0,0 +1,351 @@
+use crate::api::{Action, B2Auth, B2DownloadFileByNameParams, B2FileInfo};
+use crate::utils::download_stream;
+use crate::utils::download_stream::download_version_stream;
+use crate::Error;
+use futures::{Stream, StreamExt, TryStreamExt};
+use reqwest::Client;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+
+/// Applies one 'X-Bz-Info' attribute to a restored file, see [RestoreOptions::with_info_handler]
+pub type InfoHandler = Arc<dyn Fn(&Path, &str) -> std::io::Result<()> + Send + Sync>;
+
+/// Which of the metadata stored with a file is applied to it when it is written to disk
+///
+/// By default only the modification time is restored, from 'src_last_modified_millis'
+#[derive(Clone)]
+pub struct RestoreOptions {
+    modified_time: bool,
+    handlers: Vec<(String, InfoHandler)>,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        RestoreOptions {
+            modified_time: true,
+            handlers: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for RestoreOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RestoreOptions")
+            .field("modified_time", &self.modified_time)
+            .field(
+                "handlers",
+                &self.handlers.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl RestoreOptions {
+    pub fn new() -> RestoreOptions {
+        RestoreOptions::default()
+    }
+
+    /// Whether to set the modification time from 'src_last_modified_millis'
+    pub fn with_modified_time(mut self, restore: bool) -> Self {
+        self.modified_time = restore;
+        self
+    }
+
+    /// Calls 'handler' with the path and value whenever a file has the file info 'key'
+    ///
+    /// Use this for attributes stored at upload time, such as owners or extended attributes
+    pub fn with_info_handler<T, F>(mut self, key: T, handler: F) -> Self
+    where
+        T: Into<String>,
+        F: Fn(&Path, &str) -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        self.handlers.push((key.into(), Arc::new(handler)));
+        self
+    }
+
+    /// Sets the permissions from the file info 'key', holding the mode as octal digits, e.g. "644"
+    #[cfg(unix)]
+    pub fn with_unix_mode<T: Into<String>>(self, key: T) -> Self {
+        self.with_info_handler(key, |path, value| {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = u32::from_str_radix(value, 8).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid mode '{}'", value),
+                )
+            })?;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        })
+    }
+}
+
+/// Applies the metadata of 'info' to the local file at 'path' following 'options'
+///
+/// [download_many] calls this for every file, use it directly when writing downloads to disk yourself
+pub fn restore_metadata<P: AsRef<Path>>(
+    path: P,
+    info: &B2FileInfo,
+    options: &RestoreOptions,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let modified = info.modified();
+    if options.modified_time && modified > 0 {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|f| f.set_modified(UNIX_EPOCH + Duration::from_millis(modified)))
+            .map_err(Error::IOError)?;
+    }
+    if let Some(file_info) = &info.file_info {
+        for (key, handler) in &options.handlers {
+            if let Some(value) = file_info.get(key) {
+                handler(path, value).map_err(Error::IOError)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What happened to one file of [download_many]
+#[derive(Debug)]
+pub struct DownloadResult {
+    /// The file, None if the listing it came from failed
+    pub file: Option<B2FileInfo>,
+    /// Where the file was written
+    pub result: Result<PathBuf, Error>,
+}
+
+/// Downloads every file from 'files' into the directory 'dest', at most 'concurrency' at a time
+///
+/// 'files' is usually a listing such as [list_all_files_stream][crate::utils::list_all_files_stream],
+/// versions that aren't uploaded files (e.g. hide markers) are skipped. \
+/// Files are downloaded by name, which always gives the latest version. Only the first version of each name is downloaded,
+/// and one that turns out not to be the latest fails with a [ConfigError][Error::ConfigError] instead of getting the newer content. \
+/// Each file is written to its name below 'dest', creating directories as needed,
+/// and gets the modification time stored as 'src_last_modified_millis' if there is one,
+/// see [download_many_with] to restore other metadata. \
+/// Files are downloaded with [download_stream], so interrupted downloads are resumed up to 'max_retries' times
+/// and checked against their Sha1. They are written to a uniquely named temporary "<name>.raze-download-..." next to the target
+/// and only renamed once complete, so a failed download never leaves a partial file under its real name.
+///
+/// Returns a result for every file in the order they finished, failures don't stop the other downloads.
+/// Names that would end up outside of 'dest', e.g. containing "..", fail with a [ConfigError][Error::ConfigError].
+pub async fn download_many<S, P>(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_name: &str,
+    files: S,
+    dest: P,
+    concurrency: usize,
+    max_retries: u32,
+) -> Vec<DownloadResult>
+where
+    S: Stream<Item = Result<B2FileInfo, Error>>,
+    P: AsRef<Path>,
+{
+    download_many_with(
+        client,
+        auth,
+        bucket_name,
+        files,
+        dest,
+        concurrency,
+        max_retries,
+        &RestoreOptions::default(),
+    )
+    .await
+}
+
+/// Same as [download_many], restoring metadata following 'options'
+///
+/// A file whose metadata can't be restored is still kept, but reported as failed
+#[allow(clippy::too_many_arguments)]
+pub async fn download_many_with<S, P>(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_name: &str,
+    files: S,
+    dest: P,
+    concurrency: usize,
+    max_retries: u32,
+    options: &RestoreOptions,
+) -> Vec<DownloadResult>
+where
+    S: Stream<Item = Result<B2FileInfo, Error>>,
+    P: AsRef<Path>,
+{
+    let dest = dest.as_ref();
+    let mut seen = HashSet::new();
+    files
+        .filter(move |item| {
+            let keep = match item {
+                Ok(info) => info.action == Action::Upload && seen.insert(info.file_name.clone()),
+                Err(_) => true,
+            };
+            async move { keep }
+        })
+        .map(|item| async move {
+            match item {
+                Ok(info) => {
+                    let result = download_file(client, auth, bucket_name, &info, dest, max_retries)
+                        .await
+                        .and_then(|path| restore_metadata(&path, &info, options).map(|_| path));
+                    DownloadResult {
+                        file: Some(info),
+                        result,
+                    }
+                }
+                Err(e) => DownloadResult {
+                    file: None,
+                    result: Err(e),
+                },
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+async fn download_file(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_name: &str,
+    info: &B2FileInfo,
+    dest: &Path,
+    max_retries: u32,
+) -> Result<PathBuf, Error> {
+    let path = local_path(dest, &info.file_name).ok_or_else(|| {
+        Error::ConfigError(format!(
+            "'{}' can't be stored below the destination",
+            info.file_name
+        ))
+    })?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(Error::IOError)?;
+    }
+    // Unique, so downloads of the same name never write to the same temporary file
+    static PARTIALS: AtomicUsize = AtomicUsize::new(0);
+    let mut partial = path.clone().into_os_string();
+    partial.push(format!(
+        ".raze-download-{}-{}",
+        std::process::id(),
+        PARTIALS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let partial = PathBuf::from(partial);
+
+    let res = write_file(client, auth, bucket_name, info, &partial, max_retries).await;
+    if let Err(e) = res {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    tokio::fs::rename(&partial, &path)
+        .await
+        .map_err(Error::IOError)?;
+    Ok(path)
+}
+
+async fn write_file(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_name: &str,
+    info: &B2FileInfo,
+    path: &Path,
+    max_retries: u32,
+) -> Result<(), Error> {
+    let params = B2DownloadFileByNameParams {
+        bucket_name: bucket_name.to_string(),
+        file_name: info.file_name.clone(),
+        authorization: None,
+        download_host: None,
+        omit_authorization: false,
+        range: None,
+    };
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(Error::IOError)?;
+    let chunks = match &info.file_id {
+        Some(file_id) => {
+            download_version_stream(client.clone(), auth.clone(), params, file_id, max_retries)
+                .left_stream()
+        }
+        None => download_stream(client.clone(), auth.clone(), params, max_retries).right_stream(),
+    };
+    futures::pin_mut!(chunks);
+    while let Some(chunk) = chunks.try_next().await? {
+        file.write_all(&chunk).await.map_err(Error::IOError)?;
+    }
+    file.flush().await.map_err(Error::IOError)
+}
+
+// Where 'file_name' goes below 'dest', None if it would escape it
+fn local_path(dest: &Path, file_name: &str) -> Option<PathBuf> {
+    let mut path = dest.to_path_buf();
+    let mut segments = 0;
+    for segment in file_name.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return None,
+            s if s.contains('\\') || s.contains(':') => return None,
+            s => path.push(s),
+        }
+        segments += 1;
+    }
+    (segments > 0).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_file;
+
+    #[test]
+    fn test_restore_metadata() {
+        let path = std::env::temp_dir().join(format!("raze-restore-{}", std::process::id()));
+        std::fs::write(&path, b"hello").unwrap();
+        let mut info = test_file("hello.txt");
+        info.content_length = 5;
+        info.file_info = Some(
+            [
+                ("src_last_modified_millis", "1600000000000"),
+                ("owner", "me"),
+            ]
+            .into(),
+        );
+        let seen = Arc::new(std::sync::Mutex::new(None));
+        let options = {
+            let seen = seen.clone();
+            RestoreOptions::new().with_info_handler("owner", move |_, value| {
+                *seen.lock().unwrap() = Some(value.to_string());
+                Ok(())
+            })
+        };
+        restore_metadata(&path, &info, &options).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            modified,
+            UNIX_EPOCH + Duration::from_millis(1_600_000_000_000)
+        );
+        assert_eq!(seen.lock().unwrap().as_deref(), Some("me"));
+    }
+
+    #[test]
+    fn test_local_path() {
+        let dest = Path::new("restore");
+        assert_eq!(
+            local_path(dest, "photos/2021/cat.jpg"),
+            Some(dest.join("photos").join("2021").join("cat.jpg"))
+        );
+        assert_eq!(local_path(dest, "/a//b"), Some(dest.join("a").join("b")));
+        assert_eq!(local_path(dest, "photos/../../etc/passwd"), None);
+        assert_eq!(local_path(dest, "c:\\windows"), None);
+        assert_eq!(local_path(dest, "/"), None);
+    }
+}
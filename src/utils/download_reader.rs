@@ -0,0 +1,332 @@
+use crate::api::{b2_download_file_by_name, B2Auth, B2DownloadFileByNameParams};
+use crate::utils::{BlockCache, BlockKey, MemoryBlockCache};
+use crate::Error;
+use bytes::Bytes;
+use futures::Future;
+use reqwest::Client;
+use std::io::{Error as IoError, SeekFrom};
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+use tokio::task::JoinHandle;
+
+// The file being read, shared with the spawned fetches
+struct Source {
+    client: Client,
+    auth: B2Auth,
+    bucket_name: String,
+    file_name: String,
+    // Taken from the first response, every block must come from the same version
+    file_id: OnceLock<Arc<str>>,
+}
+
+/// An [AsyncRead] + [AsyncSeek] over a file on B2, for parsers that need random access
+///
+/// The file is fetched in blocks with ranged requests. The most recently used blocks are cached
+/// and the blocks after the current position are fetched in the background (readahead),
+/// so both sequential and random access patterns work well. \
+/// Defaults are 1 MiB blocks, 8 cached blocks and 1 block of readahead.
+///
+/// The cache can be replaced with any [BlockCache] using [with_block_cache][Self::with_block_cache],
+/// e.g. a larger [MemoryBlockCache] shared between readers, so reading the central directory of a zip
+/// or the footer of a Parquet file again doesn't fetch the same ranges twice.
+///
+/// Must be used from within a tokio runtime.
+pub struct B2DownloadReader {
+    source: Arc<Source>,
+    size: u64,
+    position: u64,
+    block_size: u64,
+    cache_blocks: usize,
+    readahead_blocks: u64,
+    // Created on first use if none was given, as its size depends on the block size
+    cache: Option<Arc<dyn BlockCache>>,
+    // The block read last, kept even if the cache evicts it
+    current: Option<(u64, Bytes)>,
+    file_key: Arc<str>,
+    in_flight: Vec<(u64, JoinHandle<Result<Bytes, Error>>)>,
+}
+
+impl B2DownloadReader {
+    /// Creates a reader for the given file
+    ///
+    /// 'size' is the size of the file in bytes, e.g. the 'content_length' of its [B2FileInfo][crate::api::B2FileInfo]
+    pub fn new<T: Into<String>, Q: Into<String>>(
+        client: Client,
+        auth: B2Auth,
+        bucket_name: T,
+        file_name: Q,
+        size: u64,
+    ) -> B2DownloadReader {
+        let bucket_name = bucket_name.into();
+        let file_name = file_name.into();
+        B2DownloadReader {
+            file_key: format!("{}/{}", bucket_name, file_name).into(),
+            source: Arc::new(Source {
+                client,
+                auth,
+                bucket_name,
+                file_name,
+                file_id: OnceLock::new(),
+            }),
+            size,
+            position: 0,
+            block_size: 1024 * 1024,
+            cache_blocks: 8,
+            readahead_blocks: 1,
+            cache: None,
+            current: None,
+            in_flight: Vec::new(),
+        }
+    }
+
+    /// Sets the size of each ranged request in bytes (at least 1)
+    pub fn with_block_size(mut self, block_size: u64) -> Self {
+        self.block_size = block_size.max(1);
+        self.current = None;
+        self
+    }
+
+    /// Sets how many blocks are kept in memory (at least 1), unless a cache is set with [with_block_cache][Self::with_block_cache]
+    pub fn with_cache_blocks(mut self, cache_blocks: usize) -> Self {
+        self.cache_blocks = cache_blocks.max(1);
+        self
+    }
+
+    /// Sets how many blocks after the current one are fetched in the background
+    pub fn with_readahead(mut self, readahead_blocks: u64) -> Self {
+        self.readahead_blocks = readahead_blocks;
+        self
+    }
+
+    /// Pins the reader to a version of the file, e.g. the 'file_id' of its [B2FileInfo][crate::api::B2FileInfo]
+    ///
+    /// Reads fail with a [ConfigError][Error::ConfigError] once the name refers to another version. \
+    /// Without it, the version of the first block fetched is used, and blocks can't be found in a shared cache until then.
+    pub fn with_file_id<T: Into<String>>(self, file_id: T) -> Self {
+        let _ = self.source.file_id.set(file_id.into().into());
+        self
+    }
+
+    /// Sets the cache for fetched blocks, which may be shared with other readers
+    pub fn with_block_cache(mut self, cache: Arc<dyn BlockCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The size of the file in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn cache(&mut self) -> Arc<dyn BlockCache> {
+        let max_bytes = self.cache_blocks as u64 * self.block_size;
+        self.cache
+            .get_or_insert_with(|| Arc::new(MemoryBlockCache::new(max_bytes)))
+            .clone()
+    }
+
+    fn key(&self, index: u64) -> BlockKey {
+        BlockKey {
+            file: self.file_key.clone(),
+            file_id: self.source.file_id.get().cloned(),
+            file_size: self.size,
+            block_size: self.block_size,
+            index,
+        }
+    }
+
+    fn cached(&mut self, index: u64) -> Option<Bytes> {
+        if let Some((i, block)) = &self.current {
+            if *i == index {
+                return Some(block.clone());
+            }
+        }
+        let block = self.cache().get(&self.key(index))?;
+        self.current = Some((index, block.clone()));
+        Some(block)
+    }
+
+    fn insert(&mut self, index: u64, block: Bytes) {
+        let key = self.key(index);
+        self.cache().insert(key, block);
+    }
+
+    // Starts fetching a block unless it is already being fetched
+    fn start_fetch(&mut self, index: u64) {
+        let start = index * self.block_size;
+        if start >= self.size || self.in_flight.iter().any(|(i, _)| *i == index) {
+            return;
+        }
+        let end = (start + self.block_size).min(self.size) - 1;
+        let source = self.source.clone();
+        self.in_flight
+            .push((index, tokio::spawn(fetch(source, start, end))));
+    }
+
+    // Polls every in-flight fetch, caching the finished ones
+    // Ready once the 'wanted' block is cached, errors are only reported for that block
+    fn poll_fetches(&mut self, cx: &mut Context<'_>, wanted: u64) -> Poll<Result<(), IoError>> {
+        let mut i = 0;
+        while i < self.in_flight.len() {
+            let (index, handle) = &mut self.in_flight[i];
+            let index = *index;
+            match Pin::new(handle).poll(cx) {
+                Poll::Pending => i += 1,
+                Poll::Ready(res) => {
+                    self.in_flight.swap_remove(i);
+                    match res {
+                        Ok(Ok(block)) => {
+                            if index == wanted {
+                                self.current = Some((index, block.clone()));
+                            }
+                            self.insert(index, block)
+                        }
+                        Ok(Err(e)) if index == wanted => {
+                            return Poll::Ready(Err(IoError::other(e)))
+                        }
+                        Err(e) if index == wanted => return Poll::Ready(Err(IoError::other(e))),
+                        // A failed readahead is simply fetched again when it is needed
+                        _ => {}
+                    }
+                }
+            }
+        }
+        if matches!(&self.current, Some((i, _)) if *i == wanted) {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+async fn fetch(source: Arc<Source>, start: u64, end: u64) -> Result<Bytes, Error> {
+    let resp = b2_download_file_by_name(
+        &source.client,
+        &source.auth,
+        B2DownloadFileByNameParams {
+            bucket_name: source.bucket_name.clone(),
+            file_name: source.file_name.clone(),
+            authorization: None,
+            download_host: None,
+            omit_authorization: false,
+            range: Some(format!("bytes={}-{}", start, end)),
+        },
+    )
+    .await?;
+    let file_id = resp
+        .headers()
+        .get("x-bz-file-id")
+        .and_then(|v| v.to_str().ok());
+    check_file_id(&source.file_id, &source.file_name, file_id)?;
+    resp.bytes().await.map_err(Error::ReqwestError)
+}
+
+// Pins the first file id seen, a block of another version would corrupt the data read
+fn check_file_id(
+    pinned: &OnceLock<Arc<str>>,
+    file_name: &str,
+    file_id: Option<&str>,
+) -> Result<(), Error> {
+    let file_id = match file_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let pinned = pinned.get_or_init(|| file_id.into());
+    if **pinned != *file_id {
+        return Err(Error::ConfigError(format!(
+            "{} is not the latest version of '{}'",
+            pinned, file_name
+        )));
+    }
+    Ok(())
+}
+
+impl AsyncRead for B2DownloadReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<(), IoError>> {
+        let this = self.get_mut();
+        if this.position >= this.size || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let index = this.position / this.block_size;
+        loop {
+            if let Some(block) = this.cached(index) {
+                let offset = (this.position - index * this.block_size) as usize;
+                // A short block means the file is smaller than expected, which is treated as EOF
+                if offset >= block.len() {
+                    return Poll::Ready(Ok(()));
+                }
+                let n = (block.len() - offset).min(buf.remaining());
+                buf.put_slice(&block[offset..offset + n]);
+                this.position += n as u64;
+                for ahead in 1..=this.readahead_blocks {
+                    if !this.cache().contains(&this.key(index + ahead)) {
+                        this.start_fetch(index + ahead);
+                    }
+                }
+                return Poll::Ready(Ok(()));
+            }
+            this.start_fetch(index);
+            futures::ready!(this.poll_fetches(cx, index))?;
+        }
+    }
+}
+
+impl AsyncSeek for B2DownloadReader {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> Result<(), IoError> {
+        let this = self.get_mut();
+        let new = match position {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => this.size.checked_add_signed(n),
+            SeekFrom::Current(n) => this.position.checked_add_signed(n),
+        };
+        match new {
+            Some(n) => {
+                this.position = n;
+                Ok(())
+            }
+            None => Err(IoError::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<u64, IoError>> {
+        Poll::Ready(Ok(self.position))
+    }
+}
+
+impl Drop for B2DownloadReader {
+    fn drop(&mut self) {
+        // Readahead that will never be read doesn't need to finish
+        for (_, handle) in &self.in_flight {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_file_id() {
+        let pinned = OnceLock::new();
+        assert!(check_file_id(&pinned, "a.txt", None).is_ok());
+        assert!(pinned.get().is_none());
+        assert!(check_file_id(&pinned, "a.txt", Some("4_z1")).is_ok());
+        assert!(check_file_id(&pinned, "a.txt", Some("4_z1")).is_ok());
+        match check_file_id(&pinned, "a.txt", Some("4_z2")) {
+            Err(Error::ConfigError(msg)) => {
+                assert_eq!(msg, "4_z1 is not the latest version of 'a.txt'")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
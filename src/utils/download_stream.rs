@@ -0,0 +1,211 @@
+use crate::api::{b2_download_file_by_name, B2Auth, B2DownloadFileByNameParams, FileId};
+use crate::utils::expected_sha1;
+use crate::Error;
+use bytes::Bytes;
+use futures::Stream;
+use reqwest::{Client, Response, StatusCode};
+use sha1::Sha1;
+use std::time::Duration;
+
+struct DownloadState {
+    client: Client,
+    auth: B2Auth,
+    params: B2DownloadFileByNameParams,
+    max_retries: u32,
+    // Bytes received so far, which is where a retry resumes from
+    offset: u64,
+    // Failures since the last received chunk
+    failures: u32,
+    resp: Option<Response>,
+    done: bool,
+    // Sha1 of the bytes received so far, and the one B2 has for the file
+    hasher: Sha1,
+    expected_sha1: Option<String>,
+    // The file the first response came from, or the one asked for, every response must come from it
+    file_id: Option<String>,
+}
+
+impl DownloadState {
+    // Decides whether to retry after an error, sleeping before the retry
+    async fn should_retry(&mut self, e: &Error) -> bool {
+        self.resp = None;
+        if !e.is_transient() || self.failures >= self.max_retries {
+            return false;
+        }
+        self.failures += 1;
+        crate::utils::sleep(Duration::from_millis(
+            250 * 2u64.pow((self.failures - 1).min(8)),
+        ))
+        .await;
+        true
+    }
+
+    // Checks the received bytes against the Sha1 B2 has, if it has one
+    fn verify(&self) -> Result<(), Error> {
+        let expected = match self.expected_sha1.as_ref() {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+        let actual = self.hasher.hexdigest();
+        if actual.eq_ignore_ascii_case(expected) {
+            Ok(())
+        } else {
+            Err(Error::ChecksumMismatch {
+                file_name: self.params.file_name.clone(),
+                expected: expected.clone(),
+                actual,
+            })
+        }
+    }
+
+    async fn next_chunk(&mut self) -> Option<Result<Bytes, Error>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let resp = match self.resp.as_mut() {
+                Some(resp) => resp,
+                None => {
+                    let mut params = self.params.clone();
+                    params.range = match self.offset {
+                        0 => None,
+                        offset => Some(format!("bytes={}-", offset)),
+                    };
+                    match b2_download_file_by_name(&self.client, &self.auth, params).await {
+                        Ok(resp)
+                            if self.offset > 0 && resp.status() != StatusCode::PARTIAL_CONTENT =>
+                        {
+                            // Continuing a full response would repeat the start of the file
+                            self.done = true;
+                            return Some(Err(Error::IOError(std::io::Error::other(
+                                "server ignored the range of a resumed download",
+                            ))));
+                        }
+                        Ok(resp) => {
+                            let file_id = resp
+                                .headers()
+                                .get("x-bz-file-id")
+                                .and_then(|v| v.to_str().ok())
+                                .map(str::to_string);
+                            if self.offset > 0 && file_id != self.file_id {
+                                // The name now refers to a newer upload, whose bytes can't continue the old one
+                                self.done = true;
+                                return Some(Err(Error::IOError(std::io::Error::other(
+                                    "file was replaced while resuming its download",
+                                ))));
+                            }
+                            if let Some(wanted) = self
+                                .file_id
+                                .as_ref()
+                                .filter(|w| file_id.as_ref() != Some(*w))
+                            {
+                                // Downloads by name always get the latest version
+                                self.done = true;
+                                return Some(Err(Error::ConfigError(format!(
+                                    "{} is not the latest version of '{}'",
+                                    wanted, self.params.file_name
+                                ))));
+                            }
+                            if self.offset == 0 {
+                                self.expected_sha1 = expected_sha1(resp.headers());
+                                self.file_id = file_id;
+                            }
+                            self.resp.insert(resp)
+                        }
+                        // The failure happened after the last byte, so there is nothing left
+                        Err(Error::B2Error(e)) if self.offset > 0 && e.status == 416 => {
+                            self.done = true;
+                            return self.verify().err().map(Err);
+                        }
+                        Err(e) => {
+                            if self.should_retry(&e).await {
+                                continue;
+                            }
+                            self.done = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+            };
+            match resp.chunk().await {
+                Ok(Some(chunk)) => {
+                    self.offset += chunk.len() as u64;
+                    self.failures = 0;
+                    self.hasher.update(&chunk);
+                    return Some(Ok(chunk));
+                }
+                Ok(None) => {
+                    self.done = true;
+                    return self.verify().err().map(Err);
+                }
+                Err(e) => {
+                    let e = Error::ReqwestError(e);
+                    if self.should_retry(&e).await {
+                        continue;
+                    }
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Downloads a file as a stream of [Bytes], transparently resuming after transient failures
+///
+/// When the connection fails, the download is restarted from the last received byte with a 'Range' request,
+/// so consumers never see connection resets. \
+/// Up to 'max_retries' consecutive failures are retried with exponential backoff, starting at 250ms and capped at 64s. \
+/// Errors that aren't [transient][Error::is_transient], or too many failures, end the stream with that error.
+///
+/// The whole file is downloaded, the 'range' of 'params' is ignored. \
+/// Retries are pinned to the file id of the first response, if the name was uploaded again in the meantime
+/// the stream ends with an error instead of mixing the bytes of two files. \
+/// Once all bytes are received they are checked against the Sha1 B2 has for the file,
+/// or the 'large_file_sha1' file info for large files, see [expected_sha1]. \
+/// On a mismatch the stream ends with a [ChecksumMismatch][Error::ChecksumMismatch] after the last chunk.
+pub fn download_stream(
+    client: Client,
+    auth: B2Auth,
+    params: B2DownloadFileByNameParams,
+    max_retries: u32,
+) -> impl Stream<Item = Result<Bytes, Error>> {
+    download_stream_from(client, auth, params, None, max_retries)
+}
+
+// Same as download_stream, failing with a ConfigError if 'file_id' isn't the version the name downloads
+pub(crate) fn download_version_stream(
+    client: Client,
+    auth: B2Auth,
+    params: B2DownloadFileByNameParams,
+    file_id: &FileId,
+    max_retries: u32,
+) -> impl Stream<Item = Result<Bytes, Error>> {
+    download_stream_from(client, auth, params, Some(file_id.to_string()), max_retries)
+}
+
+fn download_stream_from(
+    client: Client,
+    auth: B2Auth,
+    params: B2DownloadFileByNameParams,
+    file_id: Option<String>,
+    max_retries: u32,
+) -> impl Stream<Item = Result<Bytes, Error>> {
+    let state = DownloadState {
+        client,
+        auth,
+        params,
+        max_retries,
+        offset: 0,
+        failures: 0,
+        resp: None,
+        done: false,
+        hasher: Sha1::new(),
+        expected_sha1: None,
+        file_id,
+    };
+    futures::stream::unfold(state, |mut state| async move {
+        let item = state.next_chunk().await?;
+        Some((item, state))
+    })
+}
@@ -0,0 +1,70 @@
+use crate::api::{
+    b2_list_buckets, b2_update_bucket_with, B2Auth, BucketId, BucketResult, ListBucketParams,
+    ServerSideEncryption, UpdateBucketRequest,
+};
+use crate::Error;
+use reqwest::Client;
+
+/// Makes B2 encrypt new files in the bucket with SSE-B2 by default
+///
+/// Existing files are not re-encrypted
+pub async fn enable_default_encryption(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+) -> Result<BucketResult, Error> {
+    set_default_encryption(client, auth, bucket_id, ServerSideEncryption::sse_b2()).await
+}
+
+/// Stops encrypting new files in the bucket by default
+pub async fn disable_default_encryption(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+) -> Result<BucketResult, Error> {
+    set_default_encryption(client, auth, bucket_id, ServerSideEncryption::none()).await
+}
+
+async fn set_default_encryption(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+    encryption: ServerSideEncryption,
+) -> Result<BucketResult, Error> {
+    b2_update_bucket_with(
+        client,
+        auth,
+        UpdateBucketRequest::new(bucket_id.clone()).default_server_side_encryption(encryption),
+    )
+    .await
+}
+
+/// Reads the default encryption of a bucket
+///
+/// Returns a [ConfigError][Error::ConfigError] if the bucket doesn't exist or the key isn't allowed to read its encryption settings
+pub async fn default_encryption(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+) -> Result<ServerSideEncryption, Error> {
+    let bucket = b2_list_buckets(
+        client,
+        auth,
+        ListBucketParams {
+            bucket_id: Some(bucket_id.clone()),
+            bucket_name: None,
+            bucket_types: None,
+        },
+    )
+    .await?
+    .into_iter()
+    .next()
+    .ok_or_else(|| Error::ConfigError(format!("bucket {} not found", bucket_id)))?;
+    match bucket.default_server_side_encryption {
+        Some(sse) if sse.is_client_authorized_to_read => Ok(sse.value.unwrap_or_default()),
+        _ => Err(Error::ConfigError(format!(
+            "not allowed to read the encryption settings of bucket {}",
+            bucket_id
+        ))),
+    }
+}
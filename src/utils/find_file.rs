@@ -0,0 +1,28 @@
+use crate::api::{b2_list_file_names, Action, B2Auth, B2FileInfo, BucketId, ListFileNamesRequest};
+use crate::Error;
+use reqwest::Client;
+
+/// Looks up the current version of a file by its exact name
+///
+/// Returns None if there is no such file, or if it is hidden. \
+/// This is a single [b2_list_file_names] call starting at 'file_name', so it is a class C transaction.
+pub async fn get_file_by_name(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+    file_name: &str,
+) -> Result<Option<B2FileInfo>, Error> {
+    let page = b2_list_file_names(
+        client,
+        auth,
+        ListFileNamesRequest::new(bucket_id.clone())
+            .start_file_name(file_name)
+            .max_file_count(1),
+    )
+    .await?;
+    // Listing starts at the first name >= 'file_name', which may be another file
+    Ok(page
+        .items
+        .into_iter()
+        .find(|f| f.file_name == file_name && f.action == Action::Upload))
+}
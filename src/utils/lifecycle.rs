@@ -0,0 +1,144 @@
+use crate::api::{Action, B2FileInfo, FileId, LifecycleRule};
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// What the lifecycle rules of a bucket will do to one file version, as predicted by [predict_lifecycle]
+///
+/// Times are in milliseconds since the epoch, like 'upload_timestamp'. None means it never happens under the current rules.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VersionFate {
+    pub file_id: Option<FileId>,
+    /// When the version gets hidden, only set for the current version of a file
+    pub hide_at: Option<u64>,
+    /// When the version gets deleted, or cancelled for unfinished large files
+    pub delete_at: Option<u64>,
+}
+
+/// The rule applying to 'file_name', the one with the longest matching prefix
+pub fn matching_rule<'a>(rules: &'a [LifecycleRule], file_name: &str) -> Option<&'a LifecycleRule> {
+    rules
+        .iter()
+        .filter(|r| file_name.starts_with(&r.file_name_prefix))
+        .max_by_key(|r| r.file_name_prefix.len())
+}
+
+/// Predicts when B2 will hide and delete the versions of a file, following the documented lifecycle semantics
+///
+/// 'versions' are all versions of one file name, e.g. from [Bucket::versions_of][crate::client::Bucket::versions_of], in any order.
+/// The result is in the same order. \
+/// * The current upload is hidden 'days_from_uploading_to_hiding' after it was uploaded
+/// * A version stops being current when a newer version is uploaded or it is hidden,
+///   and is deleted 'days_from_hiding_to_deleting' after that
+/// * A hide marker is deleted along with the last version it hides
+/// * Unfinished large files are cancelled 'days_from_starting_to_canceling_unfinished_large_files' after starting
+///
+/// B2 applies rules once a day, so the actual time can be up to a day later than predicted.
+pub fn predict_lifecycle(rules: &[LifecycleRule], versions: &[B2FileInfo]) -> Vec<VersionFate> {
+    let rule = versions
+        .first()
+        .and_then(|v| matching_rule(rules, &v.file_name));
+    let days = |d: Option<u32>| d.map(|d| d as u64 * DAY_MILLIS);
+    let hide_after = rule.and_then(|r| days(r.days_from_uploading_to_hiding));
+    let delete_after = rule.and_then(|r| days(r.days_from_hiding_to_deleting));
+    let cancel_after =
+        rule.and_then(|r| days(r.days_from_starting_to_canceling_unfinished_large_files));
+
+    // Indices of uploads and hide markers, newest first
+    let mut chain: Vec<usize> = (0..versions.len())
+        .filter(|&i| matches!(versions[i].action, Action::Upload | Action::Hide))
+        .collect();
+    chain.sort_by(|&a, &b| {
+        versions[b]
+            .upload_timestamp
+            .cmp(&versions[a].upload_timestamp)
+    });
+
+    let mut fates: Vec<VersionFate> = versions
+        .iter()
+        .map(|v| VersionFate {
+            file_id: v.file_id.clone(),
+            hide_at: None,
+            delete_at: match v.action {
+                Action::Start => cancel_after.map(|c| v.upload_timestamp + c),
+                _ => None,
+            },
+        })
+        .collect();
+    for (pos, &i) in chain.iter().enumerate() {
+        let version = &versions[i];
+        // When this version stopped, or will stop, being the current one
+        let superseded_at = match pos {
+            0 if version.action == Action::Upload => {
+                let hide_at = hide_after.map(|h| version.upload_timestamp + h);
+                fates[i].hide_at = hide_at;
+                hide_at
+            }
+            0 => None,
+            _ => Some(versions[chain[pos - 1]].upload_timestamp),
+        };
+        if version.action == Action::Upload || pos > 0 {
+            fates[i].delete_at = superseded_at.zip(delete_after).map(|(s, d)| s + d);
+        }
+    }
+    // A current hide marker goes away once everything it hides is deleted
+    if let Some(&top) = chain.first() {
+        if versions[top].action == Action::Hide && delete_after.is_some() {
+            fates[top].delete_at = chain[1..]
+                .iter()
+                .map(|&i| fates[i].delete_at)
+                .try_fold(versions[top].upload_timestamp, |max, d| {
+                    d.map(|d| max.max(d))
+                });
+        }
+    }
+    fates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_file;
+
+    fn version(id: &str, action: Action, timestamp: u64) -> B2FileInfo {
+        let mut file = test_file("logs/a.txt");
+        file.file_id = Some(id.into());
+        file.action = action;
+        file.upload_timestamp = timestamp;
+        file
+    }
+
+    #[test]
+    fn test_predict_lifecycle() {
+        let rules = vec![
+            LifecycleRule {
+                file_name_prefix: "".to_string(),
+                ..Default::default()
+            },
+            LifecycleRule {
+                file_name_prefix: "logs/".to_string(),
+                days_from_uploading_to_hiding: Some(7),
+                days_from_hiding_to_deleting: Some(1),
+                ..Default::default()
+            },
+        ];
+        let versions = vec![
+            version("old", Action::Upload, 0),
+            version("new", Action::Upload, DAY_MILLIS),
+        ];
+        let fates = predict_lifecycle(&rules, &versions);
+        // The old version stopped being current when the new one was uploaded
+        assert_eq!(fates[0].hide_at, None);
+        assert_eq!(fates[0].delete_at, Some(2 * DAY_MILLIS));
+        assert_eq!(fates[1].hide_at, Some(8 * DAY_MILLIS));
+        assert_eq!(fates[1].delete_at, Some(9 * DAY_MILLIS));
+
+        let versions = vec![
+            version("hide", Action::Hide, DAY_MILLIS),
+            version("old", Action::Upload, 0),
+        ];
+        let fates = predict_lifecycle(&rules, &versions);
+        assert_eq!(fates[1].delete_at, Some(2 * DAY_MILLIS));
+        assert_eq!(fates[0].delete_at, Some(2 * DAY_MILLIS));
+        assert_eq!(predict_lifecycle(&[], &versions)[0].delete_at, None);
+    }
+}
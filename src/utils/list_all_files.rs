@@ -1,8 +1,8 @@
 use std::borrow::Cow;
 use std::collections::VecDeque;
 
-use crate::api::{b2_list_file_names, ListFilesResult};
-use crate::api::{B2Auth, B2FileInfo};
+use crate::api::{b2_list_file_names, ListFileNamesRequest, ListFilesResult};
+use crate::api::{B2Auth, B2FileInfo, BucketId};
 use crate::Error;
 use futures::Stream;
 use reqwest::Client;
@@ -13,7 +13,7 @@ use reqwest::Client;
 /// The recommended value for `batch_size` is the maximum value possible: 1000.
 ///
 /// <https://www.backblaze.com/b2/docs/b2_list_file_names.html>
-pub fn list_all_files_stream<T: Into<Cow<'static, str>>>(
+pub fn list_all_files_stream<T: Into<BucketId>>(
     client: Client,
     auth: B2Auth,
     bucket_id: T,
@@ -22,7 +22,7 @@ pub fn list_all_files_stream<T: Into<Cow<'static, str>>>(
     struct ListAllFilesSeed {
         client: Client,
         auth: B2Auth,
-        bucket_id: Cow<'static, str>,
+        bucket_id: BucketId,
         batch_size: u32,
         next_file_name: Option<Cow<'static, str>>,
         batch: VecDeque<B2FileInfo>,
@@ -37,25 +37,22 @@ pub fn list_all_files_stream<T: Into<Cow<'static, str>>>(
                 let res = b2_list_file_names(
                     &seed.client,
                     &seed.auth,
-                    &seed.bucket_id,
-                    file_name_str,
-                    seed.batch_size,
+                    ListFileNamesRequest::new(seed.bucket_id.clone())
+                        .start_file_name(file_name_str.as_ref())
+                        .max_file_count(seed.batch_size),
                 )
                 .await;
                 match res {
                     Ok(ListFilesResult {
-                        files,
+                        items: files,
                         next_file_name,
+                        ..
                     }) => {
                         let mut iter = files.into_iter();
                         let front = iter.next();
                         seed.batch.extend(iter);
                         seed.next_file_name = next_file_name.map(Cow::from);
-                        if let Some(front) = front {
-                            Some((Ok(front), seed))
-                        } else {
-                            None
-                        }
+                        front.map(|front| (Ok(front), seed))
                     }
                     Err(err) => Some((Err(err), seed)),
                 }
@@ -76,3 +73,59 @@ pub fn list_all_files_stream<T: Into<Cow<'static, str>>>(
         inner,
     )
 }
+
+/// Like [list_all_files_stream], but fetches up to 'prefetch' pages ahead in a background task
+///
+/// The next page is requested while the current one is consumed, so the latency of each call overlaps with processing
+/// the previous page, which adds up for large buckets. \
+/// The pages wait in a channel holding at most 'prefetch' of them (at least 1), so a slow consumer holds back the listing
+/// and at most 'prefetch' + 1 pages of `batch_size` files are in memory at once. \
+/// The stream ends after the first error, and the listing stops once the stream is dropped.
+///
+/// Must be called from within a tokio runtime.
+#[cfg(feature = "util_readers")]
+pub fn list_all_files_prefetched<T: Into<BucketId>>(
+    client: Client,
+    auth: B2Auth,
+    bucket_id: T,
+    batch_size: u32,
+    prefetch: usize,
+) -> impl Stream<Item = Result<B2FileInfo, Error>> {
+    use futures::future::{select, Either};
+    use futures::StreamExt;
+
+    let bucket_id = bucket_id.into();
+    let (sender, receiver) = tokio::sync::mpsc::channel(prefetch.max(1));
+    tokio::spawn(async move {
+        let mut next_file_name = Some(String::new());
+        while let Some(start) = next_file_name.take() {
+            let request = ListFileNamesRequest::new(bucket_id.clone())
+                .start_file_name(start)
+                .max_file_count(batch_size);
+            let list = Box::pin(b2_list_file_names(&client, &auth, request));
+            // Stops right away if the stream was dropped while the page was fetched
+            let page = match select(list, Box::pin(sender.closed())).await {
+                Either::Left((page, _)) => page,
+                Either::Right(_) => return,
+            };
+            let page = page.map(|result| {
+                next_file_name = result.next_file_name;
+                result.items
+            });
+            let failed = page.is_err();
+            if sender.send(page).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|page| (page, receiver))
+    })
+    .flat_map(|page| {
+        let items: Vec<Result<B2FileInfo, Error>> = match page {
+            Ok(files) => files.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        };
+        futures::stream::iter(items)
+    })
+}
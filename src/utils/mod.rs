@@ -1,9 +1,171 @@
-#[cfg(feature = "util_readers")]
+mod content_type;
+pub use self::content_type::*;
+mod part_size;
+pub use self::part_size::*;
+mod verify;
+pub use self::verify::*;
+mod find_file;
+pub use self::find_file::*;
+mod copy;
+pub use self::copy::*;
+mod conditional;
+pub use self::conditional::*;
+mod encryption;
+pub use self::encryption::*;
+mod lifecycle;
+pub use self::lifecycle::*;
+mod notifications;
+pub use self::notifications::*;
+
+#[cfg(feature = "util_streams")]
+mod timer;
+#[cfg(feature = "util_streams")]
+pub use self::timer::*;
+#[cfg(feature = "util_streams")]
 mod readers;
-#[cfg(feature = "util_readers")]
+#[cfg(feature = "util_streams")]
 pub use self::readers::*;
+#[cfg(feature = "util_streams")]
+mod rate_limiter;
+#[cfg(feature = "util_streams")]
+pub use self::rate_limiter::*;
+#[cfg(feature = "util_readers")]
+mod bandwidth_schedule;
+#[cfg(feature = "util_readers")]
+pub use self::bandwidth_schedule::*;
+#[cfg(feature = "util_readers")]
+mod buffer_pool;
+#[cfg(feature = "util_readers")]
+pub use self::buffer_pool::*;
+#[cfg(feature = "util_readers")]
+mod upload_writer;
+#[cfg(feature = "util_readers")]
+pub use self::upload_writer::*;
+#[cfg(feature = "util_readers")]
+mod download_reader;
+#[cfg(feature = "util_readers")]
+pub use self::download_reader::*;
+#[cfg(feature = "util_readers")]
+mod block_cache;
+#[cfg(feature = "util_readers")]
+pub use self::block_cache::*;
+#[cfg(feature = "util_readers")]
+mod upload_pool;
+#[cfg(feature = "util_readers")]
+pub use self::upload_pool::*;
+#[cfg(feature = "util_readers")]
+mod upload_retry;
+#[cfg(feature = "util_readers")]
+pub use self::upload_retry::*;
+#[cfg(feature = "util_readers")]
+mod upload_sink;
+#[cfg(feature = "util_readers")]
+pub use self::upload_sink::*;
+#[cfg(feature = "util_readers")]
+mod download_stream;
+#[cfg(feature = "util_readers")]
+pub use self::download_stream::*;
+#[cfg(feature = "util_readers")]
+mod download_many;
+#[cfg(feature = "util_readers")]
+pub use self::download_many::*;
+#[cfg(feature = "util_readers")]
+mod disk_cache;
+#[cfg(feature = "util_readers")]
+pub use self::disk_cache::*;
+#[cfg(feature = "util_readers")]
+mod upload_path;
+#[cfg(feature = "util_readers")]
+pub use self::upload_path::*;
+#[cfg(feature = "util_readers")]
+mod sync;
+#[cfg(feature = "util_readers")]
+pub use self::sync::*;
+#[cfg(feature = "util_readers")]
+mod sync_manifest;
+#[cfg(feature = "util_readers")]
+pub use self::sync_manifest::*;
+#[cfg(feature = "filters")]
+mod sync_filter;
+#[cfg(feature = "filters")]
+pub use self::sync_filter::*;
+#[cfg(feature = "util_readers")]
+mod spooled_body;
+#[cfg(feature = "util_readers")]
+pub use self::spooled_body::*;
+#[cfg(feature = "util_readers")]
+mod body_provider;
+#[cfg(feature = "util_readers")]
+pub use self::body_provider::*;
+#[cfg(feature = "util_readers")]
+mod upload_stream;
+#[cfg(feature = "util_readers")]
+pub use self::upload_stream::*;
+#[cfg(feature = "util_readers")]
+mod tee;
+#[cfg(feature = "util_readers")]
+pub use self::tee::*;
+#[cfg(feature = "util_readers")]
+mod writers;
+#[cfg(feature = "util_readers")]
+pub use self::writers::*;
+#[cfg(feature = "util_readers")]
+mod shutdown;
+#[cfg(feature = "util_readers")]
+pub use self::shutdown::*;
+#[cfg(feature = "util_readers")]
+mod transfer_stats;
+#[cfg(feature = "util_readers")]
+pub use self::transfer_stats::*;
+#[cfg(feature = "zip")]
+mod remote_zip;
+#[cfg(feature = "zip")]
+pub use self::remote_zip::*;
+
+#[cfg(feature = "utils")]
+mod client;
+#[cfg(feature = "utils")]
+pub use self::client::*;
+
+#[cfg(feature = "utils")]
+mod download_auth;
+#[cfg(feature = "utils")]
+pub use self::download_auth::*;
+
+#[cfg(feature = "utils")]
+mod resume_auth;
+#[cfg(feature = "utils")]
+pub use self::resume_auth::*;
 
 #[cfg(feature = "utils")]
 mod list_all_files;
 #[cfg(feature = "utils")]
 pub use self::list_all_files::*;
+
+#[cfg(feature = "utils")]
+mod snapshots;
+#[cfg(feature = "utils")]
+pub use self::snapshots::*;
+
+#[cfg(feature = "utils")]
+mod ordering;
+#[cfg(feature = "utils")]
+pub use self::ordering::*;
+
+#[cfg(feature = "utils")]
+mod part_manifest;
+#[cfg(feature = "utils")]
+pub use self::part_manifest::*;
+
+#[cfg(all(feature = "utils", feature = "util_readers"))]
+mod audit;
+#[cfg(all(feature = "utils", feature = "util_readers"))]
+pub use self::audit::*;
+#[cfg(all(feature = "utils", feature = "util_readers"))]
+mod transfer_manager;
+#[cfg(all(feature = "utils", feature = "util_readers"))]
+pub use self::transfer_manager::*;
+#[cfg(all(feature = "utils", feature = "util_readers"))]
+mod transfer_journal;
+#[cfg(all(feature = "utils", feature = "util_readers"))]
+pub use self::transfer_journal::*;
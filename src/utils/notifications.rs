@@ -0,0 +1,21 @@
+use crate::api::{
+    b2_get_bucket_notification_rules, b2_set_bucket_notification_rules, B2Auth, BucketId,
+    BucketNotificationRules,
+};
+use crate::Error;
+use reqwest::Client;
+
+/// Makes B2 send a test event to the webhooks of the bucket's enabled notification rules
+///
+/// B2 has no separate call for this, instead it sends a [TestEvent][crate::api::B2EventType::TestEvent]
+/// to every enabled webhook whenever rules are saved. This saves the current rules again unchanged. \
+/// Fails if a webhook doesn't accept the event, so this checks an endpoint end-to-end.
+pub async fn send_test_event(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+) -> Result<BucketNotificationRules, Error> {
+    let current = b2_get_bucket_notification_rules(client, auth, bucket_id).await?;
+    b2_set_bucket_notification_rules(client, auth, bucket_id, &current.event_notification_rules)
+        .await
+}
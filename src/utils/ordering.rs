@@ -0,0 +1,83 @@
+use crate::api::B2FileInfo;
+use crate::Error;
+use futures::{Stream, StreamExt};
+use std::cmp::Ordering;
+
+/// Collects the 'n' greatest files of a listing according to 'cmp', greatest first
+///
+/// Only 'n' files are kept in memory at a time, so this works on listings of any size,
+/// e.g. from [list_all_files_stream][crate::utils::list_all_files_stream]. \
+/// Files comparing equal keep the order of the listing. Stops at the first error.
+pub async fn top_n_by<S, F>(stream: S, n: usize, mut cmp: F) -> Result<Vec<B2FileInfo>, Error>
+where
+    S: Stream<Item = Result<B2FileInfo, Error>>,
+    F: FnMut(&B2FileInfo, &B2FileInfo) -> Ordering,
+{
+    futures::pin_mut!(stream);
+    let mut top: Vec<B2FileInfo> = Vec::with_capacity(n);
+    while let Some(info) = stream.next().await {
+        let info = info?;
+        // 'top' is sorted greatest first, insert after every file that isn't smaller
+        let pos = top.partition_point(|f| cmp(f, &info) != Ordering::Less);
+        if pos < n {
+            top.insert(pos, info);
+            top.truncate(n);
+        }
+    }
+    Ok(top)
+}
+
+/// The 'n' most recently uploaded files, newest first
+pub async fn newest_files<S>(stream: S, n: usize) -> Result<Vec<B2FileInfo>, Error>
+where
+    S: Stream<Item = Result<B2FileInfo, Error>>,
+{
+    top_n_by(stream, n, B2FileInfo::cmp_by_upload_time).await
+}
+
+/// The 'n' most recently modified files, newest first, see [B2FileInfo::modified]
+pub async fn recently_modified_files<S>(stream: S, n: usize) -> Result<Vec<B2FileInfo>, Error>
+where
+    S: Stream<Item = Result<B2FileInfo, Error>>,
+{
+    top_n_by(stream, n, B2FileInfo::cmp_by_modified).await
+}
+
+/// The 'n' largest files, largest first
+pub async fn largest_files<S>(stream: S, n: usize) -> Result<Vec<B2FileInfo>, Error>
+where
+    S: Stream<Item = Result<B2FileInfo, Error>>,
+{
+    top_n_by(stream, n, B2FileInfo::cmp_by_size).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_file;
+
+    fn file(name: &str, size: u64, timestamp: u64) -> B2FileInfo {
+        let mut file = test_file(name);
+        file.content_length = size;
+        file.upload_timestamp = timestamp;
+        file
+    }
+
+    #[tokio::test]
+    async fn test_top_n() {
+        let files = vec![
+            file("a", 5, 30),
+            file("b", 50, 10),
+            file("c", 1, 20),
+            file("d", 9, 40),
+        ];
+        let stream = || futures::stream::iter(files.clone().into_iter().map(Ok));
+        let names = |v: Vec<B2FileInfo>| v.into_iter().map(|f| f.file_name).collect::<Vec<_>>();
+        assert_eq!(names(newest_files(stream(), 2).await.unwrap()), ["d", "a"]);
+        assert_eq!(
+            names(largest_files(stream(), 3).await.unwrap()),
+            ["b", "d", "a"]
+        );
+        assert!(newest_files(stream(), 0).await.unwrap().is_empty());
+    }
+}
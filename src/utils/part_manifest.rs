@@ -0,0 +1,193 @@
+use crate::api::{b2_finish_large_file, B2Auth, B2FileInfo, FileId, UploadPartResult};
+use crate::utils::MAX_PARTS;
+use crate::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Reasons a part can be rejected by a [PartManifest]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PartManifestError {
+    /// Parts must be added in order, starting at 1
+    OutOfOrder { expected: u32, got: u32 },
+    /// Every part except the last must be at least the minimum part size
+    PartTooSmall {
+        part_number: u32,
+        size: u64,
+        minimum: u64,
+    },
+    /// The part belongs to a different large file
+    WrongFile { expected: FileId, got: FileId },
+    /// A large file has at most [MAX_PARTS] parts
+    TooManyParts,
+}
+
+impl fmt::Display for PartManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartManifestError::OutOfOrder { expected, got } => {
+                write!(f, "expected part {}, got part {}", expected, got)
+            }
+            PartManifestError::PartTooSmall {
+                part_number,
+                size,
+                minimum,
+            } => write!(
+                f,
+                "part {} is {} bytes, but only the last part may be smaller than {} bytes",
+                part_number, size, minimum
+            ),
+            PartManifestError::WrongFile { expected, got } => {
+                write!(f, "part of file {} added to manifest of {}", got, expected)
+            }
+            PartManifestError::TooManyParts => {
+                write!(f, "a large file can have at most {} parts", MAX_PARTS)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PartManifestError {}
+
+/// The parts uploaded so far for a large file, in order
+///
+/// Serializes to and from JSON, so an interrupted upload can be resumed from the next part. \
+/// Once every part is added, [finish][PartManifest::finish] calls [b2_finish_large_file] with the collected SHA1s.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PartManifest {
+    file_id: FileId,
+    minimum_part_size: u64,
+    part_sha1_array: Vec<String>,
+    part_sizes: Vec<u64>,
+}
+
+impl PartManifest {
+    /// Creates an empty manifest for the large file 'file_id'
+    ///
+    /// 'minimum_part_size' is usually the 'absolute_minimum_part_size' of the [B2Auth]
+    pub fn new<T: Into<FileId>>(file_id: T, minimum_part_size: u64) -> PartManifest {
+        PartManifest {
+            file_id: file_id.into(),
+            minimum_part_size,
+            part_sha1_array: Vec::new(),
+            part_sizes: Vec::new(),
+        }
+    }
+
+    /// Adds the next part
+    ///
+    /// Since only the last part may be smaller than the minimum, a small part is accepted,
+    /// but adding another part after it fails
+    pub fn add_part<T: Into<String>>(
+        &mut self,
+        part_number: u32,
+        size: u64,
+        sha1: T,
+    ) -> Result<(), PartManifestError> {
+        let expected = self.next_part_number();
+        if part_number != expected {
+            return Err(PartManifestError::OutOfOrder {
+                expected,
+                got: part_number,
+            });
+        }
+        if part_number > MAX_PARTS {
+            return Err(PartManifestError::TooManyParts);
+        }
+        if let Some(&last) = self.part_sizes.last() {
+            if last < self.minimum_part_size {
+                return Err(PartManifestError::PartTooSmall {
+                    part_number: part_number - 1,
+                    size: last,
+                    minimum: self.minimum_part_size,
+                });
+            }
+        }
+        self.part_sha1_array.push(sha1.into());
+        self.part_sizes.push(size);
+        Ok(())
+    }
+
+    /// Adds the result of [b2_upload_part][crate::api::b2_upload_part], see [add_part][PartManifest::add_part]
+    pub fn add_result(&mut self, result: &UploadPartResult) -> Result<(), PartManifestError> {
+        if result.file_id != self.file_id {
+            return Err(PartManifestError::WrongFile {
+                expected: self.file_id.clone(),
+                got: result.file_id.clone(),
+            });
+        }
+        self.add_part(
+            result.part_number,
+            result.content_length,
+            result.content_sha1.clone(),
+        )
+    }
+
+    /// The id of the large file
+    pub fn file_id(&self) -> &FileId {
+        &self.file_id
+    }
+
+    /// The number the next part should have
+    pub fn next_part_number(&self) -> u32 {
+        self.part_sizes.len() as u32 + 1
+    }
+
+    /// Total size of the parts added so far
+    pub fn total_size(&self) -> u64 {
+        self.part_sizes.iter().sum()
+    }
+
+    /// The SHA1s of the parts, as expected by [b2_finish_large_file]
+    pub fn part_sha1_array(&self) -> &[String] {
+        &self.part_sha1_array
+    }
+
+    /// Serializes the manifest to JSON
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(Error::SerdeError)
+    }
+
+    /// Reads a manifest previously written by [to_json][PartManifest::to_json]
+    pub fn from_json(json: &str) -> Result<PartManifest, Error> {
+        serde_json::from_str(json).map_err(Error::SerdeError)
+    }
+
+    /// Finishes the large file with the parts in this manifest
+    pub async fn finish(&self, client: &Client, auth: &B2Auth) -> Result<B2FileInfo, Error> {
+        b2_finish_large_file(client, auth, &self.file_id, &self.part_sha1_array).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_manifest() {
+        let mut manifest = PartManifest::new("4_z_large", 100);
+        assert_eq!(
+            manifest.add_part(2, 100, "a"),
+            Err(PartManifestError::OutOfOrder {
+                expected: 1,
+                got: 2
+            })
+        );
+        manifest.add_part(1, 100, "a").unwrap();
+        manifest.add_part(2, 50, "b").unwrap();
+        assert_eq!(
+            manifest.add_part(3, 100, "c"),
+            Err(PartManifestError::PartTooSmall {
+                part_number: 2,
+                size: 50,
+                minimum: 100
+            })
+        );
+        assert_eq!(manifest.total_size(), 150);
+        assert_eq!(manifest.part_sha1_array(), ["a", "b"]);
+
+        let json = manifest.to_json().unwrap();
+        assert_eq!(PartManifest::from_json(&json).unwrap(), manifest);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::api::B2Auth;
+
+/// Most parts a large file may have
+pub const MAX_PARTS: u32 = 10000;
+/// Largest part B2 accepts, 5 GB
+pub const MAX_PART_SIZE: u64 = 5_000_000_000;
+
+/// Picks the part size used for uploading a large file
+///
+/// The part size is the preferred size (the 'recommended_part_size' by default), raised if needed
+/// so the file fits in [MAX_PARTS] parts, and kept between 'absolute_minimum_part_size' and [MAX_PART_SIZE]. \
+/// Larger parts mean fewer requests, smaller parts mean less memory and less to redo when a part fails.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PartSizePolicy {
+    pub recommended_part_size: u64,
+    pub absolute_minimum_part_size: u64,
+    /// Used instead of 'recommended_part_size' if set
+    pub preferred_part_size: Option<u64>,
+}
+
+impl PartSizePolicy {
+    /// Uses the part sizes returned by [b2_authorize_account][crate::api::b2_authorize_account]
+    pub fn from_auth(auth: &B2Auth) -> PartSizePolicy {
+        PartSizePolicy {
+            recommended_part_size: auth.recommended_part_size as u64,
+            absolute_minimum_part_size: auth.absolute_minimum_part_size as u64,
+            preferred_part_size: None,
+        }
+    }
+
+    /// Sets the part size to use when the file size allows it
+    pub fn with_preferred_part_size(mut self, part_size: u64) -> Self {
+        self.preferred_part_size = Some(part_size);
+        self
+    }
+
+    /// The part size for a file of 'file_size' bytes
+    ///
+    /// Use 0 if the size is not known up front, which limits the file to [MAX_PARTS] times the preferred size
+    pub fn part_size_for(&self, file_size: u64) -> u64 {
+        let preferred = self
+            .preferred_part_size
+            .unwrap_or(self.recommended_part_size);
+        let needed = file_size.div_ceil(MAX_PARTS as u64);
+        preferred
+            .max(needed)
+            .max(self.absolute_minimum_part_size)
+            .clamp(1, MAX_PART_SIZE)
+    }
+
+    /// How many parts a file of 'file_size' bytes is split into
+    pub fn part_count(&self, file_size: u64) -> u64 {
+        let part_size = self.part_size_for(file_size);
+        file_size.div_ceil(part_size).max(1)
+    }
+
+    /// Whether a file of 'file_size' bytes needs more than one part
+    pub fn is_large_file(&self, file_size: u64) -> bool {
+        self.part_count(file_size) > 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_size_for() {
+        let policy = PartSizePolicy {
+            recommended_part_size: 100_000_000,
+            absolute_minimum_part_size: 5_000_000,
+            preferred_part_size: None,
+        };
+        assert_eq!(policy.part_size_for(0), 100_000_000);
+        assert_eq!(policy.part_count(250_000_000), 3);
+        assert!(!policy.is_large_file(100_000_000));
+        // 2 TB doesn't fit in 10000 parts of 100 MB
+        assert_eq!(policy.part_size_for(2_000_000_000_000), 200_000_000);
+
+        let policy = policy.with_preferred_part_size(1000);
+        assert_eq!(policy.part_size_for(0), 5_000_000);
+    }
+}
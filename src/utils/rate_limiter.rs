@@ -0,0 +1,168 @@
+use crate::utils::readers::{max_chunk, split_chunk};
+use crate::utils::{sleep, Sleep, DEFAULT_CHUNK_DURATION};
+use bytes::Bytes;
+use futures::{ready, Stream};
+use pin_project::pin_project;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// A bandwidth limit that can be shared by any number of streams
+///
+/// Unlike [BytesStreamThrottled][crate::utils::BytesStreamThrottled], which limits a single stream,
+/// all streams wrapped with the same limiter (see [BytesStreamLimited]) share its rate. \
+/// Cloning is cheap and clones share the limit, which can be changed at any time with [set_rate][RateLimiter::set_rate].
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    state: Arc<Mutex<LimiterState>>,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    rate: Option<usize>,
+    // When the bytes reserved so far have been sent
+    next_free: Instant,
+}
+
+impl Default for LimiterState {
+    fn default() -> Self {
+        LimiterState {
+            rate: None,
+            next_free: Instant::now(),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Allows 'bytes_per_second' across all streams, 0 means unlimited
+    pub fn new(bytes_per_second: usize) -> RateLimiter {
+        let limiter = RateLimiter::default();
+        limiter.set_rate(Some(bytes_per_second));
+        limiter
+    }
+
+    /// A limiter that lets everything through until a rate is set
+    pub fn unlimited() -> RateLimiter {
+        RateLimiter::default()
+    }
+
+    /// The current limit in bytes per second, None if unlimited
+    pub fn rate(&self) -> Option<usize> {
+        self.state.lock().unwrap().rate
+    }
+
+    /// Changes the limit, None or 0 removes it
+    pub fn set_rate(&self, bytes_per_second: Option<usize>) {
+        let mut state = self.state.lock().unwrap();
+        state.rate = bytes_per_second.filter(|r| *r > 0);
+        if state.rate.is_none() {
+            state.next_free = Instant::now();
+        }
+    }
+
+    /// Reserves 'bytes' of the bandwidth, returning when they may be sent
+    pub fn reserve(&self, bytes: usize) -> Instant {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        match state.rate {
+            None => now,
+            Some(rate) => {
+                let start = state.next_free.max(now);
+                state.next_free = start + Duration::from_secs_f64(bytes as f64 / rate as f64);
+                start
+            }
+        }
+    }
+}
+
+/// Wraps a [Stream] of [Bytes], holding back every chunk until a shared [RateLimiter] allows it
+///
+/// While a limit is set, chunks are split so each takes about [DEFAULT_CHUNK_DURATION] at the current rate,
+/// see [with_chunk_duration][BytesStreamLimited::with_chunk_duration]
+#[pin_project]
+pub struct BytesStreamLimited<R> {
+    #[pin]
+    inner: R,
+    limiter: RateLimiter,
+    chunk_duration: Duration,
+    pending: Option<Bytes>,
+    // What is left of a chunk that was split
+    rest: Option<Bytes>,
+    sleep: Option<Sleep>,
+}
+
+impl<R> BytesStreamLimited<R> {
+    pub fn wrap(inner: R, limiter: RateLimiter) -> Self {
+        Self {
+            inner,
+            limiter,
+            chunk_duration: DEFAULT_CHUNK_DURATION,
+            pending: None,
+            rest: None,
+            sleep: None,
+        }
+    }
+
+    /// Splits chunks so each takes about 'duration' to send, [Duration::ZERO] keeps chunks as they are
+    pub fn with_chunk_duration(mut self, duration: Duration) -> Self {
+        self.chunk_duration = duration;
+        self
+    }
+}
+
+impl<R, E> Stream for BytesStreamLimited<R>
+where
+    R: Stream<Item = Result<Bytes, E>>,
+{
+    type Item = Result<Bytes, E>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if this.pending.is_none() {
+            let bytes = match this.rest.take() {
+                Some(bytes) => bytes,
+                None => match ready!(this.inner.poll_next(cx)) {
+                    Some(Ok(bytes)) => bytes,
+                    other => return Poll::Ready(other),
+                },
+            };
+            let chunk_duration = *this.chunk_duration;
+            let max = this
+                .limiter
+                .rate()
+                .and_then(|rate| max_chunk(rate as f64, chunk_duration));
+            let bytes = split_chunk(bytes, max, this.rest);
+            let at = this.limiter.reserve(bytes.len());
+            let now = Instant::now();
+            if at <= now {
+                return Poll::Ready(Some(Ok(bytes)));
+            }
+            *this.sleep = Some(sleep(at - now));
+            *this.pending = Some(bytes);
+        }
+        if let Some(sleep) = this.sleep.as_mut() {
+            ready!(sleep.as_mut().poll(cx));
+            *this.sleep = None;
+        }
+        Poll::Ready(this.pending.take().map(Ok))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        let limiter = RateLimiter::new(1000);
+        let start = limiter.reserve(500);
+        assert!(start <= Instant::now());
+        // The next 500 bytes have to wait for the first ones, on every clone
+        let next = limiter.clone().reserve(500);
+        assert!(next >= start + Duration::from_millis(499));
+        limiter.set_rate(None);
+        assert_eq!(limiter.rate(), None);
+        assert!(limiter.reserve(1_000_000) <= Instant::now());
+        assert_eq!(RateLimiter::new(0).rate(), None);
+    }
+}
@@ -1,22 +1,80 @@
-///! Different `Read` wrappers, useful for file uploading.
-///! These can be composed to combine their effects
+//! Different `Read` wrappers, useful for file uploading.
+//! These can be composed to combine their effects
+//!
+//! All wrappers work on a [Stream] of [Result<Bytes, std::io::Error>]. \
+//! Use `reader_to_stream` to get one from a tokio `AsyncRead`, and `stream_to_reader` to turn it back into one.
+//!
+//! The wrappers themselves only need the 'util_streams' feature and work with any async runtime,
+//! delays go through [sleep][crate::utils::sleep]. The conversions from and to readers need 'util_readers', which brings in tokio.
+#[cfg(feature = "util_readers")]
+use crate::utils::TransferStats;
+use crate::utils::{sleep, BytesStreamLimited, RateLimiter, Sleep};
 use bytes::Bytes;
-use futures::{ready, Stream, TryStreamExt};
+#[cfg(feature = "util_readers")]
+use bytes::BytesMut;
+use digest::DynDigest;
+use futures::channel::oneshot;
+#[cfg(feature = "util_readers")]
+use futures::TryStreamExt;
+use futures::{ready, Stream};
 use pin_project::pin_project;
 use sha1::Sha1;
 use std::io::Error as IoError;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Duration;
-use tokio::{
-    io::AsyncRead,
-    time::{Instant, Sleep},
-};
+#[cfg(feature = "util_readers")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+#[cfg(feature = "util_readers")]
 use tokio_util::codec::{BytesCodec, FramedRead};
+#[cfg(feature = "util_readers")]
+use tokio_util::io::StreamReader;
+
+/// Digests computed by [BytesStreamHashAtEnd] or [AsyncWriteHashing][crate::utils::AsyncWriteHashing], available once the stream or writer is done
+///
+/// Cloning is cheap, and clones see the same results
+#[derive(Clone, Default, Debug)]
+pub struct Digests {
+    results: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Digests {
+    /// The digest with the given name as lowercase hexadecimal, or None if the stream isn't done yet
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.results
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, hex)| hex.clone())
+    }
+
+    /// The Sha1 that was appended to the stream
+    pub fn sha1(&self) -> Option<String> {
+        self.get("sha1")
+    }
+
+    /// Whether the stream is done and the digests are available
+    pub fn is_done(&self) -> bool {
+        !self.results.lock().unwrap().is_empty()
+    }
+
+    /// All digests as (name, hex) pairs, e.g. to record them in file info
+    pub fn all(&self) -> Vec<(String, String)> {
+        self.results.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set(&self, results: Vec<(String, String)>) {
+        *self.results.lock().unwrap() = results;
+    }
+}
 
 /// Wraps an [Stream] of [Result<Bytes, std::io::Error>], computing the Sha1 hash along the way and returning it when the inner stream is done
 ///
-/// The hash is returned as 40 hexadecimal digits
+/// The hash is returned as 40 hexadecimal digits \
+/// Other digests, such as MD5 or SHA-256, can be computed in the same pass with [with_digest][BytesStreamHashAtEnd::with_digest],
+/// and read through [digests][BytesStreamHashAtEnd::digests] after the upload. Only the Sha1 is appended to the stream.
 #[pin_project]
 pub struct BytesStreamHashAtEnd<R>
 where
@@ -25,6 +83,9 @@ where
     #[pin]
     inner: R,
     hash: Sha1,
+    extra: Vec<(String, Box<dyn DynDigest + Send + Sync>)>,
+    digests: Digests,
+    sha1_sender: Option<oneshot::Sender<String>>,
     done: bool,
 }
 
@@ -36,9 +97,38 @@ where
         Self {
             inner,
             hash: Sha1::new(),
+            extra: Vec::new(),
+            digests: Digests::default(),
+            sha1_sender: None,
             done: false,
         }
     }
+
+    /// Also computes 'digest', e.g. `sha2::Sha256::new()`, available under 'name' once the stream is done
+    pub fn with_digest<N, D>(mut self, name: N, digest: D) -> Self
+    where
+        N: Into<String>,
+        D: DynDigest + Send + Sync + 'static,
+    {
+        self.extra.push((name.into(), Box::new(digest)));
+        self
+    }
+
+    /// A handle to the digests, which can be kept after the stream is moved into a body
+    pub fn digests(&self) -> Digests {
+        self.digests.clone()
+    }
+
+    /// Returns a receiver that gets the Sha1, as 40 hexadecimal digits, once the stream is done
+    ///
+    /// Useful with [Sha1Variant::HexAtEnd][crate::api::Sha1Variant::HexAtEnd] to store the checksum without reading the file again. \
+    /// The receiver is cancelled if the stream is dropped early, e.g. because the upload failed. \
+    /// Calling this again replaces the previous receiver.
+    pub fn sha1_receiver(&mut self) -> oneshot::Receiver<String> {
+        let (sender, receiver) = oneshot::channel();
+        self.sha1_sender = Some(sender);
+        receiver
+    }
 }
 
 impl<R> Stream for BytesStreamHashAtEnd<R>
@@ -48,31 +138,49 @@ where
     type Item = Result<Bytes, IoError>;
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.project();
+        // The inner stream already ended, and may not be polled again
+        if *this.done {
+            return Poll::Ready(None);
+        }
         let bytes: Option<Result<Bytes, IoError>> = ready!(this.inner.poll_next(cx));
         match bytes {
             Some(Ok(bytes)) => {
                 this.hash.update(&bytes);
+                for (_, digest) in this.extra.iter_mut() {
+                    digest.update(&bytes);
+                }
                 Poll::Ready(Some(Ok(bytes)))
             }
             None => {
-                if !*this.done {
-                    let digest = this.hash.hexdigest();
-                    let digest_bytes = Bytes::copy_from_slice(digest.as_bytes());
-                    *this.done = true;
-                    Poll::Ready(Some(Ok(digest_bytes)))
-                } else {
-                    Poll::Ready(None)
+                let digest = this.hash.hexdigest();
+                let digest_bytes = Bytes::copy_from_slice(digest.as_bytes());
+                if let Some(sender) = this.sha1_sender.take() {
+                    let _ = sender.send(digest.clone());
                 }
+                let mut results = vec![("sha1".to_string(), digest)];
+                results.extend(
+                    this.extra
+                        .iter_mut()
+                        .map(|(name, d)| (name.clone(), hex::encode(d.finalize_reset()))),
+                );
+                this.digests.set(results);
+                *this.done = true;
+                Poll::Ready(Some(Ok(digest_bytes)))
             }
             other => Poll::Ready(other),
         }
     }
 }
 
+/// How long sending a single chunk of a throttled stream should take, see [BytesStreamThrottled::with_chunk_duration]
+pub const DEFAULT_CHUNK_DURATION: Duration = Duration::from_millis(100);
+
 /// Wraps an [Stream] of [Result<Bytes, std::io::Error>], limiting the bandwidth it can use. \
 /// Useful for limiting upload bandwidth.
 ///
-/// bandwidth: maximum bytes per second \
+/// bandwidth: maximum bytes per second, 0 is unlimited \
+/// Chunks are split so each takes about [DEFAULT_CHUNK_DURATION] at that bandwidth,
+/// which keeps the send rate smooth instead of sending a large chunk at once and then sleeping for a long time.
 #[pin_project]
 pub struct BytesStreamThrottled<R>
 where
@@ -81,7 +189,12 @@ where
     #[pin]
     inner: R,
     bandwidth: f32,
-    sleep: Pin<Box<Sleep>>,
+    chunk_duration: Duration,
+    // What is left of a chunk that was split
+    rest: Option<Bytes>,
+    sleep: Option<Sleep>,
+    #[cfg(feature = "util_readers")]
+    stats: Option<TransferStats>,
 }
 
 impl<R> BytesStreamThrottled<R>
@@ -92,9 +205,42 @@ where
         Self {
             inner: reader,
             bandwidth: bandwidth as f32,
-            sleep: Box::pin(tokio::time::sleep_until(Instant::now())),
+            chunk_duration: DEFAULT_CHUNK_DURATION,
+            rest: None,
+            sleep: None,
+            #[cfg(feature = "util_readers")]
+            stats: None,
         }
     }
+
+    /// Splits chunks so each takes about 'duration' to send, [Duration::ZERO] keeps chunks as they are
+    pub fn with_chunk_duration(mut self, duration: Duration) -> Self {
+        self.chunk_duration = duration;
+        self
+    }
+
+    /// Records every chunk in 'stats', after it was let through
+    #[cfg(feature = "util_readers")]
+    pub fn with_stats(mut self, stats: TransferStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+}
+
+// The largest chunk that takes at most 'duration' at 'bytes_per_second', None if chunks aren't split
+pub(crate) fn max_chunk(bytes_per_second: f64, duration: Duration) -> Option<usize> {
+    if duration.is_zero() {
+        return None;
+    }
+    Some(((bytes_per_second * duration.as_secs_f64()) as usize).max(1))
+}
+
+// Splits off everything after 'max' bytes into 'rest'
+pub(crate) fn split_chunk(mut bytes: Bytes, max: Option<usize>, rest: &mut Option<Bytes>) -> Bytes {
+    if let Some(max) = max.filter(|m| bytes.len() > *m) {
+        *rest = Some(bytes.split_off(max));
+    }
+    bytes
 }
 
 impl<R> Stream for BytesStreamThrottled<R>
@@ -103,32 +249,245 @@ where
 {
     type Item = Result<Bytes, IoError>;
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        use futures::Future;
         let this = self.project();
-        ready!(this.sleep.as_mut().poll(cx));
+        if let Some(sleep) = this.sleep.as_mut() {
+            ready!(sleep.as_mut().poll(cx));
+            *this.sleep = None;
+        }
+        let bytes = match this.rest.take() {
+            Some(bytes) => bytes,
+            None => match ready!(this.inner.poll_next(cx)) {
+                Some(Ok(bytes)) => bytes,
+                other => return Poll::Ready(other),
+            },
+        };
+        // Without a bandwidth, chunks are passed through as they are
+        let bytes = if *this.bandwidth > 0.0 {
+            let max = max_chunk(*this.bandwidth as f64, *this.chunk_duration);
+            let bytes = split_chunk(bytes, max, this.rest);
+            let sleep_duration: f32 = (bytes.len() as f32) / *this.bandwidth;
+            *this.sleep = Some(sleep(Duration::from_secs_f32(sleep_duration)));
+            bytes
+        } else {
+            bytes
+        };
+        #[cfg(feature = "util_readers")]
+        if let Some(stats) = this.stats {
+            stats.record(bytes.len() as u64);
+        }
+        Poll::Ready(Some(Ok(bytes)))
+    }
+}
+
+/// Wraps an [Stream] of [Result<Bytes, std::io::Error>], reporting the total number of bytes passed through so far
+///
+/// 'on_progress' is called after every chunk, e.g. to update a progress bar
+#[pin_project]
+pub struct BytesStreamProgress<R, F>
+where
+    R: Stream<Item = Result<Bytes, IoError>>,
+    F: FnMut(u64),
+{
+    #[pin]
+    inner: R,
+    on_progress: F,
+    total: u64,
+    #[cfg(feature = "util_readers")]
+    stats: Option<TransferStats>,
+}
+
+impl<R, F> BytesStreamProgress<R, F>
+where
+    R: Stream<Item = Result<Bytes, IoError>>,
+    F: FnMut(u64),
+{
+    pub fn wrap(inner: R, on_progress: F) -> Self {
+        Self {
+            inner,
+            on_progress,
+            total: 0,
+            #[cfg(feature = "util_readers")]
+            stats: None,
+        }
+    }
+
+    /// Also records every chunk in 'stats', for speed and time left on top of the total
+    #[cfg(feature = "util_readers")]
+    pub fn with_stats(mut self, stats: TransferStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+}
+
+impl<R, F> Stream for BytesStreamProgress<R, F>
+where
+    R: Stream<Item = Result<Bytes, IoError>>,
+    F: FnMut(u64),
+{
+    type Item = Result<Bytes, IoError>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
         let res: Option<Result<Bytes, IoError>> = ready!(this.inner.poll_next(cx));
         if let Some(Ok(bytes)) = &res {
-            let read_amount = bytes.len();
-            let sleep_duration: f32 = (read_amount as f32) / *this.bandwidth;
-            this.sleep
-                .as_mut()
-                .reset(Instant::now() + Duration::from_secs_f32(sleep_duration));
+            *this.total += bytes.len() as u64;
+            (this.on_progress)(*this.total);
+            #[cfg(feature = "util_readers")]
+            if let Some(stats) = this.stats {
+                stats.record(bytes.len() as u64);
+            }
+        }
+        Poll::Ready(res)
+    }
+}
+
+/// Wraps an [Stream] of [Result<Bytes, std::io::Error>], recording every chunk in a [TransferStats]
+///
+/// Wrap it more than once to record the same chunks in several stats, e.g. per transfer and overall
+#[cfg(feature = "util_readers")]
+#[pin_project]
+pub struct BytesStreamStats<R>
+where
+    R: Stream<Item = Result<Bytes, IoError>>,
+{
+    #[pin]
+    inner: R,
+    stats: TransferStats,
+}
+
+#[cfg(feature = "util_readers")]
+impl<R> BytesStreamStats<R>
+where
+    R: Stream<Item = Result<Bytes, IoError>>,
+{
+    pub fn wrap(inner: R, stats: TransferStats) -> Self {
+        Self { inner, stats }
+    }
+}
+
+#[cfg(feature = "util_readers")]
+impl<R> Stream for BytesStreamStats<R>
+where
+    R: Stream<Item = Result<Bytes, IoError>>,
+{
+    type Item = Result<Bytes, IoError>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let res = ready!(this.inner.poll_next(cx));
+        if let Some(Ok(bytes)) = &res {
+            this.stats.record(bytes.len() as u64);
         }
         Poll::Ready(res)
     }
 }
 
+/// Chains the stream wrappers as methods
+///
+/// ```rust,no_run
+/// # use raze::utils::*;
+/// let stream = reader_to_stream(&b"hello"[..])
+///     .progress(|total| println!("{} bytes", total))
+///     .throttled(5000)
+///     .hash_at_end();
+/// let body = reqwest::Body::wrap_stream(stream);
+/// ```
+pub trait BytesStreamExt: Stream<Item = Result<Bytes, IoError>> + Sized {
+    /// See [BytesStreamHashAtEnd]
+    fn hash_at_end(self) -> BytesStreamHashAtEnd<Self> {
+        BytesStreamHashAtEnd::wrap(self)
+    }
+
+    /// See [BytesStreamThrottled]
+    fn throttled(self, bandwidth: usize) -> BytesStreamThrottled<Self> {
+        BytesStreamThrottled::wrap(self, bandwidth)
+    }
+
+    /// See [BytesStreamLimited]
+    fn limited(self, limiter: RateLimiter) -> BytesStreamLimited<Self> {
+        BytesStreamLimited::wrap(self, limiter)
+    }
+
+    /// See [BytesStreamProgress]
+    fn progress<F: FnMut(u64)>(self, on_progress: F) -> BytesStreamProgress<Self, F> {
+        BytesStreamProgress::wrap(self, on_progress)
+    }
+
+    /// See [BytesStreamStats]
+    #[cfg(feature = "util_readers")]
+    fn stats(self, stats: TransferStats) -> BytesStreamStats<Self> {
+        BytesStreamStats::wrap(self, stats)
+    }
+}
+
+impl<S: Stream<Item = Result<Bytes, IoError>>> BytesStreamExt for S {}
+
 /// Wrap an [AsyncRead] into a [Stream] of [Result<Bytes, IoError>].
+#[cfg(feature = "util_readers")]
 pub fn reader_to_stream<R: AsyncRead + Send + Sync + 'static>(
     file: R,
 ) -> impl Stream<Item = Result<Bytes, IoError>> {
-    let stream = FramedRead::new(file, BytesCodec::new()).map_ok(bytes::BytesMut::freeze);
-    stream
+    FramedRead::new(file, BytesCodec::new()).map_ok(bytes::BytesMut::freeze)
+}
+
+/// Chunk size used by [body_from_reader]
+#[cfg(feature = "util_readers")]
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Same as [reader_to_stream], but with chunks of 'chunk_size' bytes (at least 1), only the last one may be smaller
+///
+/// [reader_to_stream] hands out whatever a single read returns, often 8KiB or less,
+/// which adds per-chunk overhead on fast disks. Chunks of 64-512KiB are usually a good fit for uploads. \
+/// The buffer is reused for the next chunk once the previous ones have been dropped, e.g. after being sent.
+#[cfg(feature = "util_readers")]
+pub fn reader_to_stream_with_chunk_size<R: AsyncRead + Send + Sync + 'static>(
+    reader: R,
+    chunk_size: usize,
+) -> impl Stream<Item = Result<Bytes, IoError>> {
+    let chunk_size = chunk_size.max(1);
+    let state = (Box::pin(reader), BytesMut::new());
+    futures::stream::try_unfold(state, move |(mut reader, mut buffer)| async move {
+        buffer.reserve(chunk_size);
+        while buffer.len() < chunk_size {
+            let remaining = (chunk_size - buffer.len()) as u64;
+            if (&mut reader).take(remaining).read_buf(&mut buffer).await? == 0 {
+                break;
+            }
+        }
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+        let chunk = buffer.split().freeze();
+        Ok(Some((chunk, (reader, buffer))))
+    })
+}
+
+/// Turn a [Stream] of [Result<Bytes, IoError>] back into an [AsyncRead], e.g. to pass it to code expecting a reader
+#[cfg(feature = "util_readers")]
+pub fn stream_to_reader<S: Stream<Item = Result<Bytes, IoError>>>(stream: S) -> impl AsyncRead {
+    StreamReader::new(stream)
+}
+
+/// Turn an [AsyncRead], such as a file, into a body for [b2_upload_file][crate::api::b2_upload_file]
+///
+/// Sends chunks of [DEFAULT_CHUNK_SIZE], see [body_from_reader_with_chunk_size] to pick another size. \
+/// Wrap the stream from [reader_to_stream_with_chunk_size] instead when hashing or throttling is needed
+#[cfg(feature = "util_readers")]
+pub fn body_from_reader<R: AsyncRead + Send + Sync + 'static>(reader: R) -> reqwest::Body {
+    body_from_reader_with_chunk_size(reader, DEFAULT_CHUNK_SIZE)
+}
+
+/// Same as [body_from_reader], with chunks of 'chunk_size' bytes, see [reader_to_stream_with_chunk_size]
+#[cfg(feature = "util_readers")]
+pub fn body_from_reader_with_chunk_size<R: AsyncRead + Send + Sync + 'static>(
+    reader: R,
+    chunk_size: usize,
+) -> reqwest::Body {
+    reqwest::Body::wrap_stream(reader_to_stream_with_chunk_size(reader, chunk_size))
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::time::Instant;
 
     use futures::AsyncReadExt;
 
@@ -156,6 +515,162 @@ mod tests {
         assert_eq!(appended_hash, computed_hash);
     }
 
+    #[tokio::test]
+    async fn test_compose_and_read_back() {
+        use tokio::io::AsyncReadExt;
+        let mut seen = 0;
+        let stream = reader_to_stream(&b"hello this is a test"[..])
+            .progress(|total| seen = total)
+            .hash_at_end();
+        let mut buf = Vec::new();
+        stream_to_reader(stream)
+            .read_to_end(&mut buf)
+            .await
+            .unwrap();
+        assert_eq!(
+            &buf[20..],
+            "f291f60cafb2ef2e0013f5a5889b1da5af4b4657".as_bytes()
+        );
+        assert_eq!(seen, 20);
+    }
+
+    #[tokio::test]
+    async fn test_extra_digests() {
+        use sha2::Digest;
+        let mut stream = BytesStreamHashAtEnd::wrap(reader_to_stream(&b"hello this is a test"[..]))
+            .with_digest("sha256", sha2::Sha256::new());
+        let sha1 = stream.sha1_receiver();
+        let digests = stream.digests();
+        assert!(!digests.is_done());
+        let _: Vec<Bytes> = stream.try_collect().await.unwrap();
+        assert_eq!(
+            sha1.await.unwrap(),
+            "f291f60cafb2ef2e0013f5a5889b1da5af4b4657"
+        );
+        assert_eq!(
+            digests.sha1().unwrap(),
+            "f291f60cafb2ef2e0013f5a5889b1da5af4b4657"
+        );
+        assert_eq!(
+            digests.get("sha256").unwrap(),
+            hex::encode(sha2::Sha256::digest(b"hello this is a test"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chunk_size() {
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let chunks: Vec<Bytes> =
+            reader_to_stream_with_chunk_size(std::io::Cursor::new(data.clone()), 300)
+                .try_collect()
+                .await
+                .unwrap();
+        let sizes: Vec<usize> = chunks.iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, [300, 300, 300, 100]);
+        assert_eq!(chunks.concat(), data);
+        let empty: Vec<Bytes> = reader_to_stream_with_chunk_size(&b""[..], 300)
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_hash_at_end_polled_after_end() {
+        use futures::StreamExt;
+        // Unfold panics when polled again after it ended
+        let inner = futures::stream::unfold(false, |sent| async move {
+            if sent {
+                None
+            } else {
+                Some((Ok(Bytes::from_static(b"hello")), true))
+            }
+        });
+        let mut stream = Box::pin(BytesStreamHashAtEnd::wrap(inner));
+        futures::executor::block_on(async {
+            assert_eq!(stream.next().await.unwrap().unwrap(), "hello");
+            assert_eq!(
+                stream.next().await.unwrap().unwrap(),
+                "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"
+            );
+            assert!(stream.next().await.is_none());
+            assert!(stream.next().await.is_none());
+        });
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_hash_at_end(
+            chunks in proptest::collection::vec(proptest::collection::vec(proptest::num::u8::ANY, 0..64), 0..16),
+            read_size in 1usize..48,
+        ) {
+            use tokio::io::AsyncReadExt;
+            let content: Vec<u8> = chunks.concat();
+            let inner = futures::stream::iter(
+                chunks.into_iter().map(|c| Ok::<_, IoError>(Bytes::from(c))),
+            );
+            let mut reader = Box::pin(stream_to_reader(BytesStreamHashAtEnd::wrap(inner)));
+            let mut out = Vec::new();
+            let mut buf = vec![0; read_size];
+            futures::executor::block_on(async {
+                loop {
+                    let n = reader.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    out.extend_from_slice(&buf[..n]);
+                }
+                // Reading again after the end must keep returning nothing
+                assert_eq!(reader.read(&mut buf).await.unwrap(), 0);
+            });
+            let mut hasher = Sha1::new();
+            hasher.update(&content);
+            proptest::prop_assert_eq!(&out[..content.len()], &content[..]);
+            let digest = hasher.hexdigest();
+            proptest::prop_assert_eq!(&out[content.len()..], digest.as_bytes());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_throttled_splits_chunks() {
+        let inner = futures::stream::iter(vec![Ok(Bytes::from(vec![0u8; 250]))]);
+        let chunks: Vec<Bytes> = BytesStreamThrottled::wrap(inner, 10_000)
+            .with_chunk_duration(Duration::from_millis(10))
+            .try_collect()
+            .await
+            .unwrap();
+        let sizes: Vec<usize> = chunks.iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, [100, 100, 50]);
+        assert_eq!(max_chunk(10.0, Duration::ZERO), None);
+        assert_eq!(max_chunk(1.0, Duration::from_millis(1)), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_stats_layers() {
+        use futures::TryStreamExt;
+        let (overall, single) = (TransferStats::new(None), TransferStats::new(Some(20)));
+        let _: Vec<Bytes> = reader_to_stream(&b"hello this is a test"[..])
+            .stats(overall.clone())
+            .stats(single.clone())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(overall.snapshot().bytes, 20);
+        assert_eq!(single.snapshot().bytes, 20);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_stream() {
+        let inner = futures::stream::iter(vec![Ok(Bytes::from(vec![0u8; 250]))]);
+        let chunks: Vec<Bytes> = BytesStreamThrottled::wrap(inner, 0)
+            .with_chunk_duration(Duration::from_millis(10))
+            .try_collect()
+            .await
+            .unwrap();
+        let sizes: Vec<usize> = chunks.iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, [250]);
+    }
+
     #[tokio::test]
     async fn test_thrrottled_read() {
         // Test reading 512 bytes at a bandwidth of 256 bytes / sec. Should complete in around 2 secs.
@@ -0,0 +1,300 @@
+use crate::api::{B2Auth, B2FileInfo, BucketId};
+use crate::utils::{prefixed_file_name, upload_stream, B2DownloadReader};
+use crate::Error;
+use async_zip::base::read::{WithoutEntry, ZipEntryReader};
+use async_zip::error::ZipError;
+use async_zip::tokio::read::seek::ZipFileReader;
+use bytes::Bytes;
+use futures::io::{AsyncBufRead as FuturesBufRead, AsyncReadExt};
+use futures::{Stream, StreamExt};
+use reqwest::Client;
+use std::io::{Error as IoError, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use tokio::io::{AsyncBufRead, AsyncSeek, AsyncWriteExt, BufReader};
+
+// Size of the chunks entries are read in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// An entry of a [RemoteZip]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntryInfo {
+    /// Position in the central directory
+    pub index: usize,
+    /// Path inside the archive, with '/' as separator
+    pub name: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub is_dir: bool,
+}
+
+/// A zip archive on B2, read through a [B2DownloadReader] to extract single entries without downloading the whole file
+///
+/// Opening reads the central directory at the end of the archive, each extracted entry then only fetches its own data. \
+/// Stored and deflated entries are supported, and every entry is checked against its CRC32.
+///
+/// ```rust,no_run
+/// # use raze::utils::*;
+/// # async fn f(client: reqwest::Client, auth: raze::api::B2Auth) -> Result<(), raze::Error> {
+/// let reader = B2DownloadReader::new(client.clone(), auth.clone(), "my-bucket", "photos.zip", 1_000_000_000);
+/// let mut zip = RemoteZip::open(reader).await?;
+/// let bucket_id = "bucket_id".into();
+/// let uploaded = zip
+///     .extract_to_b2(&client, &auth, &bucket_id, "photos/", |e| e.name.ends_with(".jpg"))
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RemoteZip<R = BufReader<B2DownloadReader>> {
+    reader: ZipFileReader<R>,
+}
+
+impl RemoteZip {
+    /// Opens the zip file read by 'reader'
+    pub async fn open(reader: B2DownloadReader) -> Result<RemoteZip, Error> {
+        // Zip headers are read in many small pieces
+        RemoteZip::from_reader(BufReader::new(reader)).await
+    }
+}
+
+impl<R: AsyncBufRead + AsyncSeek + Unpin> RemoteZip<R> {
+    /// Opens a zip file from any seekable reader, e.g. a local file
+    pub async fn from_reader(reader: R) -> Result<RemoteZip<R>, Error> {
+        let reader = ZipFileReader::with_tokio(reader).await.map_err(zip_error)?;
+        Ok(RemoteZip { reader })
+    }
+
+    /// Every entry of the archive, including directories
+    pub fn entries(&self) -> Vec<ZipEntryInfo> {
+        self.reader
+            .file()
+            .entries()
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let filename = entry.filename();
+                let name = match filename.as_str() {
+                    Ok(name) => name.to_string(),
+                    Err(_) => String::from_utf8_lossy(filename.as_bytes()).into_owned(),
+                };
+                ZipEntryInfo {
+                    index,
+                    is_dir: entry.dir().unwrap_or_else(|_| name.ends_with('/')),
+                    name,
+                    size: entry.uncompressed_size(),
+                    compressed_size: entry.compressed_size(),
+                }
+            })
+            .collect()
+    }
+
+    /// Extracts the entries for which 'filter' returns true into 'dir', returning the paths of the extracted files
+    ///
+    /// Fails with a [ConfigError][Error::ConfigError] before extracting anything if a selected entry
+    /// would end up outside of 'dir', e.g. "../x" or an absolute path. \
+    /// A file that fails to extract, including failing its CRC32 check, is removed again.
+    pub async fn extract_to_dir<F>(&mut self, dir: &Path, filter: F) -> Result<Vec<PathBuf>, Error>
+    where
+        F: Fn(&ZipEntryInfo) -> bool,
+    {
+        let mut extracted = Vec::new();
+        for (entry, relative) in self.selected(filter)? {
+            let path = dir.join(relative);
+            if entry.is_dir {
+                tokio::fs::create_dir_all(&path)
+                    .await
+                    .map_err(Error::IOError)?;
+                continue;
+            }
+            let stream = self.entry_stream(entry.index).await?;
+            if let Err(e) = write_file(&path, stream).await {
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(Error::IOError(e));
+            }
+            extracted.push(path);
+        }
+        Ok(extracted)
+    }
+
+    /// Uploads the entries for which 'filter' returns true to 'bucket_id', named 'prefix' followed by their path in the archive
+    ///
+    /// Entries are streamed through [upload_stream], so they can be of any size and nothing is stored locally.
+    /// An entry failing its CRC32 check is cancelled instead of uploaded. \
+    /// Directories are skipped, as B2 has none. Entry paths are checked like in [extract_to_dir][Self::extract_to_dir].
+    pub async fn extract_to_b2<F>(
+        &mut self,
+        client: &Client,
+        auth: &B2Auth,
+        bucket_id: &BucketId,
+        prefix: &str,
+        filter: F,
+    ) -> Result<Vec<B2FileInfo>, Error>
+    where
+        F: Fn(&ZipEntryInfo) -> bool,
+    {
+        let mut uploaded = Vec::new();
+        for (entry, relative) in self.selected(filter)? {
+            if entry.is_dir {
+                continue;
+            }
+            let stream = self.entry_stream(entry.index).await?;
+            let info = upload_stream(
+                client.clone(),
+                auth.clone(),
+                bucket_id.clone(),
+                prefixed_file_name(prefix, relative),
+                stream,
+            )
+            .await?;
+            uploaded.push(info);
+        }
+        Ok(uploaded)
+    }
+
+    // The entries matching 'filter' with their relative paths, failing if one would escape the target
+    fn selected<F>(&self, filter: F) -> Result<Vec<(ZipEntryInfo, PathBuf)>, Error>
+    where
+        F: Fn(&ZipEntryInfo) -> bool,
+    {
+        self.entries()
+            .into_iter()
+            .filter(|e| filter(e))
+            .map(|e| match safe_path(&e.name) {
+                Some(relative) => Ok((e, relative)),
+                None => Err(Error::ConfigError(format!(
+                    "zip entry {} would be extracted outside of the target",
+                    e.name
+                ))),
+            })
+            .collect()
+    }
+
+    async fn entry_stream(
+        &mut self,
+        index: usize,
+    ) -> Result<impl Stream<Item = Result<Bytes, IoError>> + '_, Error> {
+        let crc32 = self.reader.file().entries()[index].crc32();
+        let reader = self
+            .reader
+            .reader_without_entry(index)
+            .await
+            .map_err(zip_error)?;
+        Ok(checked_chunks(reader, crc32))
+    }
+}
+
+// The data of an entry in chunks, ending with an error if it doesn't match 'crc32'
+fn checked_chunks<'a, T: FuturesBufRead + Unpin + 'a>(
+    reader: ZipEntryReader<'a, T, WithoutEntry>,
+    crc32: u32,
+) -> impl Stream<Item = Result<Bytes, IoError>> + 'a {
+    futures::stream::unfold(Some(reader), move |state| async move {
+        let mut reader = state?;
+        let mut buf = vec![0; CHUNK_SIZE];
+        match reader.read(&mut buf).await {
+            Ok(0) if reader.compute_hash() == crc32 => None,
+            Ok(0) => Some((
+                Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "zip entry doesn't match its CRC32",
+                )),
+                None,
+            )),
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(Bytes::from(buf)), Some(reader)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+async fn write_file<S>(path: &Path, stream: S) -> Result<(), IoError>
+where
+    S: Stream<Item = Result<Bytes, IoError>>,
+{
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut file = tokio::fs::File::create(path).await?;
+    futures::pin_mut!(stream);
+    while let Some(chunk) = stream.next().await {
+        file.write_all(&chunk?).await?;
+    }
+    file.flush().await
+}
+
+// 'name' as a relative path that stays within the directory it is joined to
+fn safe_path(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    let mut components = path.components().peekable();
+    components.peek()?;
+    if components.all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        Some(path.to_path_buf())
+    } else {
+        None
+    }
+}
+
+fn zip_error(e: ZipError) -> Error {
+    match e {
+        ZipError::UpstreamReadError(e) => Error::IOError(e),
+        e => Error::IOError(IoError::new(ErrorKind::InvalidData, e)),
+    }
+}
+
+impl<R: AsyncBufRead + AsyncSeek + Unpin> std::fmt::Debug for RemoteZip<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RemoteZip")
+            .field("entries", &self.reader.file().entries().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_zip::base::write::ZipFileWriter;
+    use async_zip::{Compression, ZipEntryBuilder};
+
+    #[tokio::test]
+    async fn test_extract_to_dir() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 7) as u8).collect();
+        let mut writer = ZipFileWriter::new(futures::io::Cursor::new(Vec::new()));
+        for (name, compression, content) in [
+            ("a.txt", Compression::Deflate, &data[..]),
+            ("sub/", Compression::Stored, &[][..]),
+            ("sub/b.bin", Compression::Stored, &b"hello"[..]),
+            ("../evil.txt", Compression::Stored, &b"!"[..]),
+        ] {
+            let entry = ZipEntryBuilder::new(name.to_string().into(), compression);
+            writer.write_entry_whole(entry, content).await.unwrap();
+        }
+        let archive = writer.close().await.unwrap().into_inner();
+
+        let mut zip = RemoteZip::from_reader(std::io::Cursor::new(archive))
+            .await
+            .unwrap();
+        let entries = zip.entries();
+        assert_eq!(entries.len(), 4);
+        assert!(entries[1].is_dir);
+        assert_eq!(entries[0].size, data.len() as u64);
+
+        let dir = std::env::temp_dir().join(format!("raze-zip-{}", std::process::id()));
+        assert!(matches!(
+            zip.extract_to_dir(&dir, |_| true).await,
+            Err(Error::ConfigError(_))
+        ));
+        let extracted = zip
+            .extract_to_dir(&dir, |e| !e.name.starts_with(".."))
+            .await
+            .unwrap();
+        assert_eq!(extracted, vec![dir.join("a.txt"), dir.join("sub/b.bin")]);
+        assert_eq!(std::fs::read(dir.join("a.txt")).unwrap(), data);
+        assert_eq!(std::fs::read(dir.join("sub/b.bin")).unwrap(), b"hello");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(safe_path("./x/y"), Some(PathBuf::from("./x/y")));
+        assert_eq!(safe_path("/etc/passwd"), None);
+        assert_eq!(safe_path(""), None);
+    }
+}
@@ -0,0 +1,26 @@
+use crate::api::{b2_authorize_account, B2Auth};
+use crate::client::CredentialsProvider;
+use crate::Error;
+use reqwest::Client;
+use std::path::Path;
+
+/// Loads a saved [B2Auth] from 'path', or authorizes again with the keys from 'credentials'
+/// if there is none or it is probably expired
+///
+/// A new authorization is saved to 'path', so short-lived processes like CLI invocations
+/// only call [b2_authorize_account] about once a day \
+/// Failing to save is not an error, the next run simply authorizes again
+pub async fn resume_or_reauthorize<P: AsRef<Path>, C: CredentialsProvider + ?Sized>(
+    client: &Client,
+    path: P,
+    credentials: &C,
+) -> Result<B2Auth, Error> {
+    if let Ok(auth) = B2Auth::load(&path) {
+        if !auth.is_probably_expired() {
+            return Ok(auth);
+        }
+    }
+    let auth = b2_authorize_account(client, credentials.application_key().await?).await?;
+    let _ = auth.save(&path);
+    Ok(auth)
+}
@@ -0,0 +1,173 @@
+use crate::utils::sleep;
+use futures::future::{select, Either, Future};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Running,
+    // No new work is started, running work may finish
+    Draining,
+    // The deadline passed, running work is dropped
+    Stopped,
+}
+
+/// Asks long-running work to stop, e.g. when a service receives SIGTERM
+///
+/// Once [shutdown][ShutdownSignal::shutdown] is called, nothing new is started, and work already running gets until the deadline to finish.
+/// Whatever is still running then is stopped. \
+/// Passed to [sync_dir][crate::utils::sync_dir] with [SyncOptions::with_shutdown][crate::utils::SyncOptions::with_shutdown],
+/// [TransferManager][crate::utils::TransferManager] and [UploadUrlPool][crate::utils::UploadUrlPool] have their own `shutdown`. \
+/// Cloning is cheap, and clones refer to the same signal.
+///
+/// ```rust,no_run
+/// # use raze::utils::*;
+/// # async fn f(signal: ShutdownSignal) {
+/// // Somewhere else, a clone was passed to sync_dir through SyncOptions::with_shutdown
+/// let drained = signal.shutdown(std::time::Duration::from_secs(30)).await;
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    phase: watch::Sender<Phase>,
+    // Pieces of work started and not yet done
+    active: watch::Sender<usize>,
+}
+
+// Counts as running work until dropped
+#[derive(Debug)]
+pub(crate) struct WorkGuard {
+    inner: Arc<Inner>,
+}
+
+impl Drop for WorkGuard {
+    fn drop(&mut self) {
+        self.inner.active.send_modify(|n| *n -= 1);
+    }
+}
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        ShutdownSignal {
+            inner: Arc::new(Inner {
+                phase: watch::channel(Phase::Running).0,
+                active: watch::channel(0).0,
+            }),
+        }
+    }
+}
+
+impl ShutdownSignal {
+    pub fn new() -> ShutdownSignal {
+        ShutdownSignal::default()
+    }
+
+    /// Whether [shutdown][ShutdownSignal::shutdown] was called
+    pub fn is_shutting_down(&self) -> bool {
+        *self.inner.phase.borrow() != Phase::Running
+    }
+
+    /// Stops new work from starting and waits up to 'deadline' for running work to finish
+    ///
+    /// Returns whether everything finished in time. If not, the work still running is stopped,
+    /// which may happen shortly after this returns.
+    pub async fn shutdown(&self, deadline: Duration) -> bool {
+        self.begin();
+        let mut active = self.inner.active.subscribe();
+        let drained = Box::pin(async move {
+            let _ = active.wait_for(|n| *n == 0).await;
+        });
+        match select(drained, sleep(deadline)).await {
+            Either::Left(_) => true,
+            Either::Right(_) => {
+                self.inner.phase.send_replace(Phase::Stopped);
+                false
+            }
+        }
+    }
+
+    // Stops new work from starting, without waiting
+    pub(crate) fn begin(&self) {
+        self.inner.phase.send_if_modified(|phase| {
+            let running = *phase == Phase::Running;
+            if running {
+                *phase = Phase::Draining;
+            }
+            running
+        });
+    }
+
+    // Registers a piece of work, None once shutting down
+    pub(crate) fn start(&self) -> Option<WorkGuard> {
+        // Holding the phase keeps shutdown from starting between the check and the count
+        let phase = self.inner.phase.borrow();
+        if *phase != Phase::Running {
+            return None;
+        }
+        self.inner.active.send_modify(|n| *n += 1);
+        Some(WorkGuard {
+            inner: self.inner.clone(),
+        })
+    }
+
+    // Completes once the deadline of a shutdown passed
+    pub(crate) async fn stopped(&self) {
+        let mut phase = self.inner.phase.subscribe();
+        let _ = phase.wait_for(|p| *p == Phase::Stopped).await;
+    }
+
+    /// Waits until no work is running, e.g. for the stopped work to be gone after a shutdown missed its deadline
+    pub async fn finished(&self) {
+        let mut active = self.inner.active.subscribe();
+        let _ = active.wait_for(|n| *n == 0).await;
+    }
+
+    // Runs 'work' unless shutting down, dropping it if the deadline passes. None if it didn't run to the end
+    pub(crate) async fn run<F: Future>(&self, work: F) -> Option<F::Output> {
+        let _guard = self.start()?;
+        futures::pin_mut!(work);
+        let stopped = Box::pin(self.stopped());
+        match select(work, stopped).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_signal() {
+        let signal = ShutdownSignal::new();
+        assert_eq!(signal.run(async { 1 }).await, Some(1));
+
+        let slow = tokio::spawn({
+            let signal = signal.clone();
+            async move { signal.run(sleep(Duration::from_secs(60))).await }
+        });
+        let quick = tokio::spawn({
+            let signal = signal.clone();
+            async move { signal.run(sleep(Duration::from_millis(20))).await }
+        });
+        tokio::task::yield_now().await;
+        while *signal.inner.active.borrow() < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert!(!signal.shutdown(Duration::from_millis(200)).await);
+        assert!(signal.is_shutting_down());
+        assert_eq!(quick.await.unwrap(), Some(()));
+        assert_eq!(slow.await.unwrap(), None);
+        signal.finished().await;
+        // Nothing new starts once shutting down
+        assert_eq!(signal.run(async { 1 }).await, None);
+        assert!(signal.shutdown(Duration::ZERO).await);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::api::{
+    b2_download_file_by_name, b2_list_buckets, B2Auth, B2BucketType, B2DownloadFileByNameParams,
+    B2FileInfo, BucketResult, ListBucketParams,
+};
+use crate::utils::list_all_files_stream;
+use crate::Error;
+use futures::TryStreamExt;
+use reqwest::{Client, Response};
+
+/// A snapshot generated by B2, stored in a [Snapshot][B2BucketType::Snapshot] bucket
+///
+/// Snapshot buckets are created and filled by B2 itself, so these are read-only from the API's point of view
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Snapshot {
+    pub bucket_name: String,
+    pub file: B2FileInfo,
+}
+
+impl Snapshot {
+    /// The name of the snapshot file, e.g. "my-snapshot.zip"
+    pub fn file_name(&self) -> &str {
+        &self.file.file_name
+    }
+
+    /// The size of the snapshot in bytes
+    pub fn size(&self) -> u64 {
+        self.file.content_length
+    }
+}
+
+/// Lists all buckets of type [B2BucketType::Snapshot] on the account
+///
+/// <https://www.backblaze.com/b2/docs/b2_list_buckets.html>
+pub async fn list_snapshot_buckets(
+    client: &Client,
+    auth: &B2Auth,
+) -> Result<Vec<BucketResult>, Error> {
+    // Without a 'bucketTypes' filter, B2 includes snapshot buckets in the response
+    let buckets = b2_list_buckets(
+        client,
+        auth,
+        ListBucketParams {
+            bucket_id: None,
+            bucket_name: None,
+            bucket_types: None,
+        },
+    )
+    .await?;
+    Ok(buckets
+        .into_iter()
+        .filter(|b| b.bucket_type == B2BucketType::Snapshot)
+        .collect())
+}
+
+/// Lists all snapshots in all snapshot buckets on the account
+///
+/// Uses [list_snapshot_buckets] and [list_all_files_stream] with the maximum batch size
+pub async fn list_snapshots(client: &Client, auth: &B2Auth) -> Result<Vec<Snapshot>, Error> {
+    let mut snapshots = Vec::new();
+    for BucketResult {
+        bucket_id,
+        bucket_name,
+        ..
+    } in list_snapshot_buckets(client, auth).await?
+    {
+        let files: Vec<B2FileInfo> =
+            list_all_files_stream(client.clone(), auth.clone(), bucket_id, 1000)
+                .try_collect()
+                .await?;
+        snapshots.extend(files.into_iter().map(|file| Snapshot {
+            bucket_name: bucket_name.clone(),
+            file,
+        }));
+    }
+    Ok(snapshots)
+}
+
+/// Downloads a snapshot obtained from [list_snapshots]
+///
+/// Returns the raw [Response], use e.g. [Response::bytes_stream] to write it to disk
+pub async fn download_snapshot(
+    client: &Client,
+    auth: &B2Auth,
+    snapshot: &Snapshot,
+) -> Result<Response, Error> {
+    b2_download_file_by_name(
+        client,
+        auth,
+        B2DownloadFileByNameParams {
+            bucket_name: snapshot.bucket_name.clone(),
+            file_name: snapshot.file.file_name.clone(),
+            authorization: None,
+            download_host: None,
+            omit_authorization: false,
+            range: None,
+        },
+    )
+    .await
+}
@@ -0,0 +1,196 @@
+use crate::utils::reader_to_stream;
+use crate::Error;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt, TryStreamExt};
+use sha1::Sha1;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWriteExt};
+
+/// Payloads up to this size are kept in memory by [SpooledBody::from_stream]
+pub const DEFAULT_SPOOL_THRESHOLD: usize = 8 * 1024 * 1024;
+
+/// A body of known length and Sha1 that can be sent any number of times
+///
+/// Built by reading a stream to the end, keeping it in memory while it is smaller than a threshold
+/// and spilling it to a temporary file beyond that. \
+/// This makes data that can only be read once, such as a pipe or a network stream,
+/// usable with [b2_upload_file][crate::api::b2_upload_file], which needs the length up front,
+/// and with retries, which need to send the body again.
+///
+/// Clones share the data, the temporary file is removed once the last clone is dropped.
+#[derive(Debug, Clone)]
+pub struct SpooledBody {
+    data: Spool,
+    len: u64,
+    sha1: String,
+}
+
+#[derive(Debug, Clone)]
+enum Spool {
+    Memory(Bytes),
+    File(Arc<SpoolFile>),
+}
+
+// Removes the temporary file when dropped
+#[derive(Debug)]
+struct SpoolFile {
+    path: PathBuf,
+}
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl SpooledBody {
+    /// Reads 'stream' to the end, keeping up to 'threshold' bytes in memory
+    ///
+    /// Larger streams are written to a file in [std::env::temp_dir]
+    pub async fn from_stream<S>(stream: S, threshold: usize) -> Result<SpooledBody, Error>
+    where
+        S: Stream<Item = Result<Bytes, IoError>>,
+    {
+        futures::pin_mut!(stream);
+        let mut hasher = Sha1::new();
+        let mut buffer = BytesMut::new();
+        let mut file: Option<(tokio::fs::File, SpoolFile)> = None;
+        let mut len = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(Error::IOError)?;
+            hasher.update(&chunk);
+            len += chunk.len() as u64;
+            if file.is_none() && buffer.len() + chunk.len() > threshold {
+                let spool = SpoolFile { path: spool_path() };
+                let mut f = tokio::fs::File::create(&spool.path)
+                    .await
+                    .map_err(Error::IOError)?;
+                f.write_all(&buffer).await.map_err(Error::IOError)?;
+                buffer = BytesMut::new();
+                file = Some((f, spool));
+            }
+            match file.as_mut() {
+                Some((f, _)) => f.write_all(&chunk).await.map_err(Error::IOError)?,
+                None => buffer.extend_from_slice(&chunk),
+            }
+        }
+        let data = match file {
+            Some((mut f, spool)) => {
+                f.flush().await.map_err(Error::IOError)?;
+                Spool::File(Arc::new(spool))
+            }
+            None => Spool::Memory(buffer.freeze()),
+        };
+        Ok(SpooledBody {
+            data,
+            len,
+            sha1: hasher.hexdigest(),
+        })
+    }
+
+    /// Same as [from_stream][SpooledBody::from_stream], reading from an [AsyncRead] such as stdin
+    pub async fn from_reader<R: AsyncRead + Send + Sync + 'static>(
+        reader: R,
+        threshold: usize,
+    ) -> Result<SpooledBody, Error> {
+        SpooledBody::from_stream(reader_to_stream(reader), threshold).await
+    }
+
+    /// The exact length in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The Sha1 of the data, as 40 hexadecimal digits
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
+    /// Whether the data is kept in memory rather than in a temporary file
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.data, Spool::Memory(_))
+    }
+
+    /// The path of the temporary file, if the data was spilled to disk
+    pub fn path(&self) -> Option<&Path> {
+        match &self.data {
+            Spool::Memory(_) => None,
+            Spool::File(file) => Some(&file.path),
+        }
+    }
+
+    /// Streams the data from the start, can be called again for every attempt
+    pub fn stream(&self) -> impl Stream<Item = Result<Bytes, IoError>> + Send + Sync + 'static {
+        match &self.data {
+            Spool::Memory(data) => futures::stream::iter(vec![Ok(data.clone())]).left_stream(),
+            Spool::File(file) => {
+                let file = file.clone();
+                futures::stream::once(async move { tokio::fs::File::open(&file.path).await })
+                    .map_ok(reader_to_stream)
+                    .try_flatten()
+                    .right_stream()
+            }
+        }
+    }
+
+    /// A new body with the data, can be called again for every attempt
+    pub fn body(&self) -> reqwest::Body {
+        match &self.data {
+            Spool::Memory(data) => data.clone().into(),
+            Spool::File(_) => reqwest::Body::wrap_stream(self.stream()),
+        }
+    }
+}
+
+// A file name in the temp dir that no other spool uses
+fn spool_path() -> PathBuf {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    std::env::temp_dir().join(format!(
+        "raze-spool-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks() -> impl Stream<Item = Result<Bytes, IoError>> {
+        futures::stream::iter(vec![
+            Ok(Bytes::from_static(b"hello ")),
+            Ok(Bytes::from_static(b"world")),
+        ])
+    }
+
+    async fn collect(body: &SpooledBody) -> Vec<u8> {
+        let parts: Vec<Bytes> = body.stream().try_collect().await.unwrap();
+        parts.concat()
+    }
+
+    #[tokio::test]
+    async fn test_spooled_body() {
+        let small = SpooledBody::from_stream(chunks(), 100).await.unwrap();
+        assert!(small.is_in_memory());
+        assert_eq!(small.len(), 11);
+        assert_eq!(small.sha1(), "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed");
+        assert_eq!(collect(&small).await, b"hello world");
+
+        let large = SpooledBody::from_stream(chunks(), 8).await.unwrap();
+        assert!(!large.is_in_memory());
+        assert_eq!(large.sha1(), small.sha1());
+        // Replaying reads the file from the start every time
+        assert_eq!(collect(&large).await, b"hello world");
+        assert_eq!(collect(&large).await, b"hello world");
+        let path = large.path().unwrap().to_path_buf();
+        drop(large);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,681 @@
+use crate::api::{
+    b2_upload_file_with_info, B2Auth, B2FileInfo, FileParameters, Sha1Variant, UploadAuth,
+};
+#[cfg(feature = "filters")]
+use crate::utils::SyncFilter;
+use crate::utils::{
+    get_file_by_name, prefixed_file_name, upload_path_dedup, ContentTypeDetector, ManifestEntry,
+    ShutdownSignal, SyncManifest, UploadOutcome, UploadUrlPool,
+};
+use crate::Error;
+use futures::StreamExt;
+use reqwest::Client;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// The 'file_info' key holding the target of a symlink stored with [SymlinkPolicy::StoreTarget]
+pub const SYMLINK_TARGET_INFO: &str = "src_symlink_target";
+
+/// The name of the object standing in for an empty directory, the same the B2 web interface uses
+pub const EMPTY_DIR_PLACEHOLDER: &str = ".bzEmpty";
+
+// Sha1 of zero bytes, for symlinks and placeholders
+const EMPTY_SHA1: &str = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+
+/// What [sync_dir] does with symbolic links
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Uploads what the link points to, as if it was there. Links to directories are walked, once each
+    Follow,
+    /// Leaves links out, noting them as skipped
+    Skip,
+    /// Uploads an empty file with the link's target in [SYMLINK_TARGET_INFO]
+    StoreTarget,
+}
+
+/// What [sync_dir] does with directories that have no entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyDirPolicy {
+    /// Leaves them out, as B2 has no directories
+    Skip,
+    /// Uploads an empty [EMPTY_DIR_PLACEHOLDER] file into them, so they show up in listings
+    Placeholder,
+}
+
+/// Settings for [sync_dir]
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    symlinks: SymlinkPolicy,
+    empty_dirs: EmptyDirPolicy,
+    concurrency: usize,
+    detector: ContentTypeDetector,
+    #[cfg(feature = "filters")]
+    filter: Option<SyncFilter>,
+    previous: Option<SyncManifest>,
+    manifest: Option<String>,
+    shutdown: Option<ShutdownSignal>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            symlinks: SymlinkPolicy::Skip,
+            empty_dirs: EmptyDirPolicy::Skip,
+            concurrency: 4,
+            detector: ContentTypeDetector::new(),
+            #[cfg(feature = "filters")]
+            filter: None,
+            previous: None,
+            manifest: None,
+            shutdown: None,
+        }
+    }
+}
+
+impl SyncOptions {
+    /// Skips symlinks and empty directories, uploading 4 files at a time
+    pub fn new() -> SyncOptions {
+        SyncOptions::default()
+    }
+
+    pub fn with_symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    pub fn with_empty_dirs(mut self, policy: EmptyDirPolicy) -> Self {
+        self.empty_dirs = policy;
+        self
+    }
+
+    /// How many uploads run at the same time
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Picks content types, see [ContentTypeDetector]
+    pub fn with_detector(mut self, detector: ContentTypeDetector) -> Self {
+        self.detector = detector;
+        self
+    }
+
+    /// Only syncs the paths 'filter' doesn't exclude
+    #[cfg(feature = "filters")]
+    pub fn with_filter(mut self, filter: SyncFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Trusts the manifest of an earlier run, see [SyncManifest::fetch]
+    ///
+    /// Files with the same name, size and modification time as in 'previous' are reported
+    /// as [Unmodified][SyncOutcome::Unmodified] without hashing them or asking B2
+    pub fn with_previous_manifest(mut self, previous: SyncManifest) -> Self {
+        self.previous = Some(previous);
+        self
+    }
+
+    /// Uploads a [SyncManifest] of the synced files as 'file_name' once everything else is done
+    ///
+    /// Entries that failed or were skipped aren't in it, and neither is the manifest itself
+    pub fn with_manifest<T: Into<String>>(mut self, file_name: T) -> Self {
+        self.manifest = Some(file_name.into());
+        self
+    }
+
+    /// Lets 'signal' stop the sync, e.g. when the service is asked to stop
+    ///
+    /// Once [shutdown][ShutdownSignal::shutdown] is called, no more uploads start, and the uploads still running
+    /// at its deadline are stopped. Those entries are [skipped][SyncOutcome::Skipped] and no manifest is uploaded.
+    /// A large file stopped halfway is left unfinished, to be cancelled with
+    /// [b2_cancel_large_file][crate::api::b2_cancel_large_file] or a lifecycle rule.
+    pub fn with_shutdown(mut self, signal: ShutdownSignal) -> Self {
+        self.shutdown = Some(signal);
+        self
+    }
+
+    // Whether the filter leaves out 'relative'
+    #[cfg_attr(not(feature = "filters"), allow(unused_variables))]
+    fn is_excluded(&self, relative: &Path, is_dir: bool) -> bool {
+        #[cfg(feature = "filters")]
+        if let Some(filter) = &self.filter {
+            return filter.is_excluded(relative, is_dir);
+        }
+        false
+    }
+}
+
+/// What [sync_dir] found at a local path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Symlink,
+    EmptyDir,
+    /// A directory that couldn't be read
+    Dir,
+    /// Sockets, pipes, devices and the like, which are never uploaded
+    Special,
+}
+
+/// What happened to one entry of [sync_dir]
+#[derive(Debug)]
+pub enum SyncOutcome {
+    Uploaded(B2FileInfo),
+    /// The bucket already had the same file
+    Unchanged(B2FileInfo),
+    /// The file matches the [previous manifest][SyncOptions::with_previous_manifest], so the bucket wasn't checked
+    Unmodified(ManifestEntry),
+    /// Left out, with the reason
+    Skipped(String),
+    Failed(Error),
+}
+
+/// One local entry handled by [sync_dir]
+#[derive(Debug)]
+pub struct SyncEntry {
+    /// Path relative to the synced directory
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    /// The file name in the bucket, None if it was never uploaded
+    pub file_name: Option<String>,
+    pub outcome: SyncOutcome,
+}
+
+/// The result of [sync_dir], with an entry for everything that was found
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub entries: Vec<SyncEntry>,
+    /// The result of uploading the manifest, if [one was asked for][SyncOptions::with_manifest]
+    pub manifest: Option<Result<B2FileInfo, Error>>,
+}
+
+impl SyncReport {
+    /// Entries that were uploaded
+    pub fn uploaded(&self) -> impl Iterator<Item = &SyncEntry> {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.outcome, SyncOutcome::Uploaded(_)))
+    }
+
+    /// Entries that failed, e.g. files that couldn't be read
+    pub fn failed(&self) -> impl Iterator<Item = &SyncEntry> {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.outcome, SyncOutcome::Failed(_)))
+    }
+
+    /// Whether nothing failed, including the manifest upload
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none() && !matches!(self.manifest, Some(Err(_)))
+    }
+}
+
+/// Uploads the directory 'dir' to the bucket of 'pool', placing every file under 'prefix'
+///
+/// Files are uploaded with [upload_path_dedup], so those already in the bucket with the same size and Sha1 are left alone. \
+/// Symlinks and empty directories are handled as set in 'options', sockets, pipes and devices are always skipped.
+/// With the "filters" feature, paths excluded by the options' filter are left out without a note. \
+/// Problems with single entries, like unreadable files or directories, are noted in the [SyncReport] and don't stop the sync.
+/// Nothing is ever deleted remotely.
+///
+/// The directory is walked before uploading starts, using blocking file system calls.
+pub async fn sync_dir<P: AsRef<Path>>(
+    client: &Client,
+    auth: &B2Auth,
+    pool: &UploadUrlPool,
+    dir: P,
+    prefix: &str,
+    options: &SyncOptions,
+) -> SyncReport {
+    let dir = dir.as_ref();
+    let mut found = Vec::new();
+    let mut visited = HashSet::new();
+    if let Ok(canonical) = std::fs::canonicalize(dir) {
+        visited.insert(canonical);
+    }
+    walk(dir, Path::new(""), options, &mut visited, &mut found);
+
+    let previous = options.previous.as_ref();
+    let results: Vec<(SyncEntry, Option<ManifestEntry>)> = futures::stream::iter(found)
+        .map(|found| async move {
+            match found {
+                Found::Upload { path, kind, target } => {
+                    let file_name = match kind {
+                        EntryKind::EmptyDir => {
+                            prefixed_file_name(prefix, path.join(EMPTY_DIR_PLACEHOLDER))
+                        }
+                        _ => prefixed_file_name(prefix, &path),
+                    };
+                    let local = dir.join(&path);
+                    let (size, mtime) = match kind {
+                        EntryKind::File => local_stat(&local),
+                        _ => (0, 0),
+                    };
+                    let unmodified = previous
+                        .and_then(|p| p.get(&file_name))
+                        .filter(|e| kind == EntryKind::File && e.size == size && e.mtime == mtime);
+                    let outcome = match unmodified {
+                        Some(entry) => SyncOutcome::Unmodified(entry.clone()),
+                        None => {
+                            let upload = sync_entry(
+                                client,
+                                auth,
+                                pool,
+                                &local,
+                                &file_name,
+                                target.as_deref(),
+                                kind,
+                                &options.detector,
+                            );
+                            let result = match &options.shutdown {
+                                Some(signal) => signal.run(upload).await,
+                                None => Some(upload.await),
+                            };
+                            match result {
+                                Some(Ok(UploadOutcome::Uploaded(info))) => {
+                                    SyncOutcome::Uploaded(info)
+                                }
+                                Some(Ok(UploadOutcome::Skipped(info)))
+                                | Some(Ok(UploadOutcome::AlreadyExists(info))) => {
+                                    SyncOutcome::Unchanged(info)
+                                }
+                                Some(Err(e)) => SyncOutcome::Failed(e),
+                                None => SyncOutcome::Skipped("shutting down".to_string()),
+                            }
+                        }
+                    };
+                    let manifest_entry = match &outcome {
+                        SyncOutcome::Uploaded(info) | SyncOutcome::Unchanged(info) => {
+                            Some(ManifestEntry::from_file_info(info, mtime))
+                        }
+                        SyncOutcome::Unmodified(entry) => Some(entry.clone()),
+                        _ => None,
+                    };
+                    let entry = SyncEntry {
+                        path,
+                        kind,
+                        file_name: Some(file_name),
+                        outcome,
+                    };
+                    (entry, manifest_entry)
+                }
+                Found::Skip { path, kind, reason } => {
+                    let entry = SyncEntry {
+                        path,
+                        kind,
+                        file_name: None,
+                        outcome: SyncOutcome::Skipped(reason),
+                    };
+                    (entry, None)
+                }
+                Found::Fail { path, kind, error } => {
+                    let entry = SyncEntry {
+                        path,
+                        kind,
+                        file_name: None,
+                        outcome: SyncOutcome::Failed(Error::IOError(error)),
+                    };
+                    (entry, None)
+                }
+            }
+        })
+        .buffer_unordered(options.concurrency)
+        .collect()
+        .await;
+
+    let mut entries = Vec::with_capacity(results.len());
+    let mut files = Vec::new();
+    for (entry, manifest_entry) in results {
+        entries.push(entry);
+        files.extend(manifest_entry);
+    }
+    let shut_down = options
+        .shutdown
+        .as_ref()
+        .is_some_and(|s| s.is_shutting_down());
+    let manifest = match &options.manifest {
+        Some(_) if shut_down => None,
+        Some(file_name) => {
+            Some(upload_manifest(pool, client, SyncManifest::new(files), file_name).await)
+        }
+        None => None,
+    };
+    SyncReport { entries, manifest }
+}
+
+async fn upload_manifest(
+    pool: &UploadUrlPool,
+    client: &Client,
+    manifest: SyncManifest,
+    file_name: &str,
+) -> Result<B2FileInfo, Error> {
+    let upload_auth = pool.acquire().await?;
+    let res = manifest.upload(client, &upload_auth, file_name).await;
+    pool.release(upload_auth, res.is_ok());
+    res
+}
+
+// Size and modification time in milliseconds of a local file, zeros if unknown
+fn local_stat(path: &Path) -> (u64, u64) {
+    match std::fs::metadata(path) {
+        Ok(metadata) => {
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+            (metadata.len(), mtime)
+        }
+        Err(_) => (0, 0),
+    }
+}
+
+// An entry found while walking, before anything is uploaded
+#[derive(Debug)]
+enum Found {
+    Upload {
+        path: PathBuf,
+        kind: EntryKind,
+        target: Option<String>,
+    },
+    Skip {
+        path: PathBuf,
+        kind: EntryKind,
+        reason: String,
+    },
+    Fail {
+        path: PathBuf,
+        kind: EntryKind,
+        error: std::io::Error,
+    },
+}
+
+// Collects everything below 'dir/relative', 'visited' holds the directories walked so far to break symlink loops
+fn walk(
+    dir: &Path,
+    relative: &Path,
+    options: &SyncOptions,
+    visited: &mut HashSet<PathBuf>,
+    found: &mut Vec<Found>,
+) {
+    let read = match std::fs::read_dir(dir.join(relative)) {
+        Ok(read) => read,
+        Err(error) => {
+            found.push(Found::Fail {
+                path: relative.to_path_buf(),
+                kind: EntryKind::Dir,
+                error,
+            });
+            return;
+        }
+    };
+    let mut empty = true;
+    for entry in read {
+        empty = false;
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                found.push(Found::Fail {
+                    path: relative.to_path_buf(),
+                    kind: EntryKind::Dir,
+                    error,
+                });
+                continue;
+            }
+        };
+        let path = relative.join(entry.file_name());
+        let full = entry.path();
+        let mut file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(error) => {
+                found.push(Found::Fail {
+                    path,
+                    kind: EntryKind::File,
+                    error,
+                });
+                continue;
+            }
+        };
+        if options.is_excluded(&path, file_type.is_dir()) {
+            continue;
+        }
+        if file_type.is_symlink() {
+            match options.symlinks {
+                SymlinkPolicy::Skip => {
+                    found.push(Found::Skip {
+                        path,
+                        kind: EntryKind::Symlink,
+                        reason: "symlink".to_string(),
+                    });
+                    continue;
+                }
+                SymlinkPolicy::StoreTarget => {
+                    match std::fs::read_link(&full) {
+                        Ok(target) => found.push(Found::Upload {
+                            path,
+                            kind: EntryKind::Symlink,
+                            target: Some(target.to_string_lossy().into_owned()),
+                        }),
+                        Err(error) => found.push(Found::Fail {
+                            path,
+                            kind: EntryKind::Symlink,
+                            error,
+                        }),
+                    }
+                    continue;
+                }
+                SymlinkPolicy::Follow => match std::fs::metadata(&full) {
+                    Ok(metadata) => file_type = metadata.file_type(),
+                    Err(error) => {
+                        // Usually a dangling link
+                        found.push(Found::Fail {
+                            path,
+                            kind: EntryKind::Symlink,
+                            error,
+                        });
+                        continue;
+                    }
+                },
+            }
+        }
+        if file_type.is_dir() {
+            // Followed links are only known to be directories now
+            if options.is_excluded(&path, true) {
+                continue;
+            }
+            let seen = std::fs::canonicalize(&full)
+                .map(|canonical| !visited.insert(canonical))
+                .unwrap_or(false);
+            if seen {
+                found.push(Found::Skip {
+                    path,
+                    kind: EntryKind::Dir,
+                    reason: "directory was already synced through another path".to_string(),
+                });
+            } else {
+                walk(dir, &path, options, visited, found);
+            }
+        } else if file_type.is_file() {
+            found.push(Found::Upload {
+                path,
+                kind: EntryKind::File,
+                target: None,
+            });
+        } else {
+            found.push(Found::Skip {
+                path,
+                kind: EntryKind::Special,
+                reason: "not a regular file".to_string(),
+            });
+        }
+    }
+    if empty && !relative.as_os_str().is_empty() {
+        match options.empty_dirs {
+            EmptyDirPolicy::Skip => found.push(Found::Skip {
+                path: relative.to_path_buf(),
+                kind: EntryKind::EmptyDir,
+                reason: "empty directory".to_string(),
+            }),
+            EmptyDirPolicy::Placeholder => found.push(Found::Upload {
+                path: relative.to_path_buf(),
+                kind: EntryKind::EmptyDir,
+                target: None,
+            }),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn sync_entry(
+    client: &Client,
+    auth: &B2Auth,
+    pool: &UploadUrlPool,
+    local: &Path,
+    file_name: &str,
+    target: Option<&str>,
+    kind: EntryKind,
+    detector: &ContentTypeDetector,
+) -> Result<UploadOutcome, Error> {
+    let upload_auth = pool.acquire().await?;
+    let res = match kind {
+        EntryKind::File => {
+            upload_path_dedup(client, auth, &upload_auth, local, file_name, detector).await
+        }
+        _ => upload_marker(client, auth, &upload_auth, file_name, target).await,
+    };
+    pool.release(upload_auth, res.is_ok());
+    res
+}
+
+// Uploads an empty file for a symlink or empty directory, unless an identical one is there
+async fn upload_marker(
+    client: &Client,
+    auth: &B2Auth,
+    upload_auth: &UploadAuth,
+    file_name: &str,
+    target: Option<&str>,
+) -> Result<UploadOutcome, Error> {
+    let existing = get_file_by_name(client, auth, &upload_auth.bucket_id, file_name).await?;
+    if let Some(existing) = existing {
+        let existing_target = existing
+            .file_info
+            .as_ref()
+            .and_then(|fi| fi.get(SYMLINK_TARGET_INFO));
+        if existing.content_length == 0 && existing_target == target {
+            return Ok(UploadOutcome::Skipped(existing));
+        }
+    }
+    let file_info: Vec<(&str, &str)> = target
+        .map(|t| (SYMLINK_TARGET_INFO, t))
+        .into_iter()
+        .collect();
+    b2_upload_file_with_info(
+        client,
+        upload_auth,
+        Vec::new(),
+        FileParameters {
+            file_path: file_name,
+            file_size: 0,
+            content_type: None,
+            content_sha1: Sha1Variant::Precomputed(EMPTY_SHA1),
+            last_modified_millis: 0,
+        },
+        &file_info,
+    )
+    .await
+    .map(UploadOutcome::Uploaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk_dir(dir: &Path, options: &SyncOptions) -> Vec<Found> {
+        let mut found = Vec::new();
+        let mut visited = HashSet::new();
+        visited.insert(std::fs::canonicalize(dir).unwrap());
+        walk(dir, Path::new(""), options, &mut visited, &mut found);
+        found.sort_by_key(|f| match f {
+            Found::Upload { path, .. } | Found::Skip { path, .. } | Found::Fail { path, .. } => {
+                path.clone()
+            }
+        });
+        found
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_policies() {
+        let dir = std::env::temp_dir().join(format!("raze-sync-walk-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("a/empty")).unwrap();
+        std::fs::write(dir.join("a/file.txt"), b"hello").unwrap();
+        std::os::unix::fs::symlink("file.txt", dir.join("a/link")).unwrap();
+        // A loop back to the top, only walked when following links
+        std::os::unix::fs::symlink("..", dir.join("a/up")).unwrap();
+
+        let skipping = walk_dir(&dir, &SyncOptions::new());
+        let storing = walk_dir(
+            &dir,
+            &SyncOptions::new()
+                .with_symlinks(SymlinkPolicy::StoreTarget)
+                .with_empty_dirs(EmptyDirPolicy::Placeholder),
+        );
+        let following = walk_dir(
+            &dir,
+            &SyncOptions::new().with_symlinks(SymlinkPolicy::Follow),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            &skipping[..],
+            [
+                Found::Skip {
+                    kind: EntryKind::EmptyDir,
+                    ..
+                },
+                Found::Upload {
+                    kind: EntryKind::File,
+                    ..
+                },
+                Found::Skip {
+                    kind: EntryKind::Symlink,
+                    ..
+                },
+                Found::Skip {
+                    kind: EntryKind::Symlink,
+                    ..
+                },
+            ]
+        ));
+        assert!(matches!(&storing[..], [
+            Found::Upload { kind: EntryKind::EmptyDir, target: None, .. },
+            Found::Upload { kind: EntryKind::File, .. },
+            Found::Upload { kind: EntryKind::Symlink, target: Some(t), .. },
+            Found::Upload { kind: EntryKind::Symlink, .. },
+        ] if t == "file.txt"));
+        assert!(matches!(
+            &following[..],
+            [
+                Found::Skip {
+                    kind: EntryKind::EmptyDir,
+                    ..
+                },
+                Found::Upload {
+                    kind: EntryKind::File,
+                    ..
+                },
+                Found::Upload {
+                    kind: EntryKind::File,
+                    ..
+                },
+                Found::Skip {
+                    kind: EntryKind::Dir,
+                    ..
+                },
+            ]
+        ));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::Error;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+/// Gitignore-style rules deciding which local paths [sync_dir][crate::utils::sync_dir] uploads
+///
+/// Paths are matched relative to the synced directory, so "/target" only matches at its top
+/// while "*.tmp" or "cache/" match at any depth. A pattern starting with '!' re-includes what an earlier one excluded. \
+/// If there are include patterns, only files matching one of them (or inside a matching directory) are uploaded.
+/// Excludes win over includes, and excluded directories aren't walked at all.
+#[derive(Debug, Clone)]
+pub struct SyncFilter {
+    exclude: Gitignore,
+    include: Option<Gitignore>,
+}
+
+impl SyncFilter {
+    /// Builds a filter from lines as they would appear in a .gitignore file
+    ///
+    /// Fails with a [ConfigError][Error::ConfigError] if a pattern is invalid
+    pub fn new<T: AsRef<str>>(exclude: &[T], include: &[T]) -> Result<SyncFilter, Error> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(compile(include)?)
+        };
+        Ok(SyncFilter {
+            exclude: compile(exclude)?,
+            include,
+        })
+    }
+
+    /// Reads the exclude patterns from a .gitignore-style file, without include patterns
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SyncFilter, Error> {
+        let text = std::fs::read_to_string(path).map_err(Error::IOError)?;
+        SyncFilter::new(&text.lines().collect::<Vec<_>>(), &[])
+    }
+
+    /// Whether the local path 'relative' should be left out
+    pub fn is_excluded<P: AsRef<Path>>(&self, relative: P, is_dir: bool) -> bool {
+        let relative = relative.as_ref();
+        if self
+            .exclude
+            .matched_path_or_any_parents(relative, is_dir)
+            .is_ignore()
+        {
+            return true;
+        }
+        match &self.include {
+            // Directories are walked to find included files inside them
+            Some(include) if !is_dir => !include
+                .matched_path_or_any_parents(relative, false)
+                .is_ignore(),
+            _ => false,
+        }
+    }
+}
+
+// Matches relative paths, as the root is empty
+fn compile<T: AsRef<str>>(lines: &[T]) -> Result<Gitignore, Error> {
+    let mut builder = GitignoreBuilder::new("");
+    for line in lines {
+        builder
+            .add_line(None, line.as_ref())
+            .map_err(|e| Error::ConfigError(format!("invalid filter pattern: {}", e)))?;
+    }
+    builder
+        .build()
+        .map_err(|e| Error::ConfigError(format!("invalid filter pattern: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let filter = SyncFilter::new(&["*.tmp", "/target", "cache/", "!keep.tmp"], &[]).unwrap();
+        assert!(filter.is_excluded("a.tmp", false));
+        assert!(filter.is_excluded("deep/down/a.tmp", false));
+        assert!(!filter.is_excluded("keep.tmp", false));
+        assert!(filter.is_excluded("target", true));
+        assert!(!filter.is_excluded("src/target", true));
+        assert!(filter.is_excluded("app/cache", true));
+        assert!(filter.is_excluded("app/cache/blob", false));
+        assert!(!filter.is_excluded("app/cache", false));
+        assert!(!filter.is_excluded("src/main.rs", false));
+
+        let filter = SyncFilter::new(&["raw/"], &["*.jpg", "docs/"]).unwrap();
+        assert!(!filter.is_excluded("photos/cat.jpg", false));
+        assert!(!filter.is_excluded("docs/readme.md", false));
+        assert!(!filter.is_excluded("photos", true));
+        assert!(filter.is_excluded("photos/cat.png", false));
+        assert!(filter.is_excluded("raw/cat.jpg", false));
+
+        assert!(matches!(
+            SyncFilter::new(&["a{b"], &[]),
+            Err(Error::ConfigError(_))
+        ));
+    }
+}
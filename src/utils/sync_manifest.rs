@@ -0,0 +1,156 @@
+use crate::api::{
+    b2_download_file_by_name, b2_upload_file, B2Auth, B2DownloadFileByNameParams, B2FileInfo,
+    FileId, FileParameters, Sha1Variant, UploadAuth,
+};
+use crate::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+
+/// One file recorded in a [SyncManifest]
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    /// The file name in the bucket
+    pub name: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    /// Modification time of the local file in milliseconds since the Unix epoch, 0 if unknown
+    pub mtime: u64,
+    /// The version the sync left in the bucket
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<FileId>,
+}
+
+impl ManifestEntry {
+    /// An entry for the uploaded or existing 'info' of a local file modified at 'mtime'
+    pub fn from_file_info(info: &B2FileInfo, mtime: u64) -> ManifestEntry {
+        ManifestEntry {
+            name: info.file_name.clone(),
+            size: info.content_length,
+            sha1: info.whole_file_sha1().map(str::to_string),
+            mtime,
+            file_id: info.file_id.clone(),
+        }
+    }
+}
+
+/// Every file a [sync_dir][crate::utils::sync_dir] run left in the bucket, with the exact versions
+///
+/// Stored next to the data (see [SyncOptions::with_manifest][crate::utils::SyncOptions::with_manifest]),
+/// it lets the next run skip files whose size and modification time didn't change without asking B2,
+/// and records which versions made up the bucket at that point for restoring it later.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncManifest {
+    /// When the manifest was created, in milliseconds since the Unix epoch
+    pub created: u64,
+    /// The files, sorted by name
+    pub files: Vec<ManifestEntry>,
+}
+
+impl SyncManifest {
+    /// A manifest of 'files', created now
+    pub fn new(mut files: Vec<ManifestEntry>) -> SyncManifest {
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        let created = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        SyncManifest { created, files }
+    }
+
+    /// The entry for the file named 'name', 'files' must be sorted
+    pub fn get(&self, name: &str) -> Option<&ManifestEntry> {
+        self.files
+            .binary_search_by(|e| e.name.as_str().cmp(name))
+            .ok()
+            .map(|i| &self.files[i])
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(Error::SerdeError)
+    }
+
+    pub fn from_json(json: &str) -> Result<SyncManifest, Error> {
+        let mut manifest: SyncManifest = serde_json::from_str(json).map_err(Error::SerdeError)?;
+        // Manifests written by other tools might not be sorted
+        manifest.files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(manifest)
+    }
+
+    /// Downloads the manifest stored as 'file_name', None if there is none
+    pub async fn fetch(
+        client: &Client,
+        auth: &B2Auth,
+        bucket_name: &str,
+        file_name: &str,
+    ) -> Result<Option<SyncManifest>, Error> {
+        let params = B2DownloadFileByNameParams {
+            bucket_name: bucket_name.to_string(),
+            file_name: file_name.to_string(),
+            authorization: None,
+            download_host: None,
+            omit_authorization: false,
+            range: None,
+        };
+        let resp = match b2_download_file_by_name(client, auth, params).await {
+            Ok(resp) => resp,
+            Err(Error::B2Error(e)) if e.status == 404 => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let json = resp.text().await.map_err(Error::ReqwestError)?;
+        SyncManifest::from_json(&json).map(Some)
+    }
+
+    /// Uploads the manifest as 'file_name'
+    pub async fn upload(
+        &self,
+        client: &Client,
+        upload_auth: &UploadAuth,
+        file_name: &str,
+    ) -> Result<B2FileInfo, Error> {
+        let json = self.to_json()?;
+        let sha1 = Sha1::from(json.as_bytes()).hexdigest();
+        b2_upload_file(
+            client,
+            upload_auth,
+            json.clone(),
+            FileParameters {
+                file_path: file_name,
+                file_size: json.len() as u64,
+                content_type: Some("application/json"),
+                content_sha1: Sha1Variant::Precomputed(&sha1),
+                last_modified_millis: self.created,
+            },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let entry = |name: &str| {
+            ManifestEntry {
+            name: name.to_string(),
+            size: 5,
+            sha1: Some("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d".to_string()),
+            mtime: 1_600_000_000_000,
+            file_id: Some("4_z27c88f1d182b150646ff0b16_f1004ba650fe24e6b_d20200911_m134418_c002_v0001109_t0009".into()),
+        }
+        };
+        let manifest = SyncManifest::new(vec![entry("b/c.txt"), entry("a.txt")]);
+        assert_eq!(manifest.files[0].name, "a.txt");
+        assert_eq!(manifest.get("b/c.txt"), Some(&entry("b/c.txt")));
+        assert_eq!(manifest.get("d.txt"), None);
+
+        let json = manifest.to_json().unwrap();
+        assert!(json.contains("\"fileId\""));
+        assert_eq!(SyncManifest::from_json(&json).unwrap(), manifest);
+    }
+}
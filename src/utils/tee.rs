@@ -0,0 +1,150 @@
+use bytes::{Buf, BytesMut};
+use futures::ready;
+use pin_project::pin_project;
+use std::io::Error as IoError;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Wraps an [AsyncRead], copying everything read from it into a second [AsyncWrite] 'sink'
+///
+/// Lets a single read of a file feed both an upload and e.g. a local cache file, a hasher or a progress tracker
+/// (see [CallbackSink] for the latter two). \
+/// The sink is flushed once the reader reaches its end. If the sink falls behind, reading waits for it,
+/// so it only ever buffers one read. Errors from the sink fail the read.
+///
+/// ```rust,no_run
+/// # use raze::utils::*;
+/// # async fn f() -> std::io::Result<()> {
+/// let file = tokio::fs::File::open("video.mp4").await?;
+/// let cache = tokio::fs::File::create("cache/video.mp4").await?;
+/// let body = body_from_reader(AsyncReadTee::new(file, cache));
+/// # Ok(())
+/// # }
+/// ```
+#[pin_project]
+#[derive(Debug)]
+pub struct AsyncReadTee<R, W> {
+    #[pin]
+    inner: R,
+    #[pin]
+    sink: W,
+    // Read, but not taken by the sink yet
+    pending: BytesMut,
+}
+
+impl<R: AsyncRead, W: AsyncWrite> AsyncReadTee<R, W> {
+    pub fn new(inner: R, sink: W) -> Self {
+        AsyncReadTee {
+            inner,
+            sink,
+            pending: BytesMut::new(),
+        }
+    }
+
+    pub fn sink(&self) -> &W {
+        &self.sink
+    }
+
+    /// The reader and the sink, e.g. to close a cache file once everything was read
+    pub fn into_inner(self) -> (R, W) {
+        (self.inner, self.sink)
+    }
+}
+
+impl<R: AsyncRead, W: AsyncWrite> AsyncRead for AsyncReadTee<R, W> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let mut this = self.project();
+        while !this.pending.is_empty() {
+            let written = ready!(this.sink.as_mut().poll_write(cx, &this.pending[..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(IoError::new(
+                    std::io::ErrorKind::WriteZero,
+                    "tee sink stopped accepting data",
+                )));
+            }
+            this.pending.advance(written);
+        }
+
+        let before = buf.filled().len();
+        ready!(this.inner.poll_read(cx, buf))?;
+        let read = &buf.filled()[before..];
+        if read.is_empty() && buf.remaining() > 0 {
+            return this.sink.poll_flush(cx);
+        }
+        // Hand the data over right away, whatever the sink doesn't take is written before the next read
+        let written = match this.sink.poll_write(cx, read) {
+            Poll::Ready(res) => res?,
+            Poll::Pending => 0,
+        };
+        this.pending.extend_from_slice(&read[written..]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// An [AsyncWrite] that passes everything written to it to a closure, for use with [AsyncReadTee]
+///
+/// ```rust
+/// # use raze::utils::*;
+/// let mut hasher = sha1::Sha1::new();
+/// let mut total = 0;
+/// let sink = CallbackSink::new(|data: &[u8]| {
+///     hasher.update(data);
+///     total += data.len();
+/// });
+/// ```
+pub struct CallbackSink<F: FnMut(&[u8])> {
+    callback: F,
+}
+
+impl<F: FnMut(&[u8])> CallbackSink<F> {
+    pub fn new(callback: F) -> Self {
+        CallbackSink { callback }
+    }
+}
+
+impl<F: FnMut(&[u8]) + Unpin> AsyncWrite for CallbackSink<F> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        (self.callback)(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_tee() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        let mut copy = Vec::new();
+        let mut total = 0;
+        let mut read = Vec::new();
+        {
+            let tee = AsyncReadTee::new(&data[..], &mut copy);
+            let mut counted =
+                AsyncReadTee::new(tee, CallbackSink::new(|d: &[u8]| total += d.len()));
+            counted.read_to_end(&mut read).await.unwrap();
+        }
+        assert_eq!(read, data);
+        assert_eq!(copy, data);
+        assert_eq!(total, data.len());
+    }
+}
@@ -0,0 +1,59 @@
+use futures::Future;
+use std::pin::Pin;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// A future that completes after some time, as returned by [sleep]
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send + Sync>>;
+
+/// Provides the delays used by the stream wrappers, rate limiters and retry helpers
+///
+/// Implemented for closures returning a [Sleep], see [set_timer]
+pub trait Timer: Send + Sync {
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+impl<F: Fn(Duration) -> Sleep + Send + Sync> Timer for F {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        self(duration)
+    }
+}
+
+static TIMER: RwLock<Option<Box<dyn Timer>>> = RwLock::new(None);
+
+/// Replaces the timer used by [sleep], e.g. with the one of another async runtime
+///
+/// ```rust,ignore
+/// raze::utils::set_timer(|d| Box::pin(async_std::task::sleep(d)) as raze::utils::Sleep);
+/// ```
+pub fn set_timer<T: Timer + 'static>(timer: T) {
+    *TIMER.write().unwrap() = Some(Box::new(timer));
+}
+
+/// Waits for 'duration'
+///
+/// Uses the timer set with [set_timer] if there is one. Otherwise, tokio's timer is used when called within a tokio runtime,
+/// and the runtime-independent [futures_timer] everywhere else.
+pub fn sleep(duration: Duration) -> Sleep {
+    if let Some(timer) = TIMER.read().unwrap().as_ref() {
+        return timer.sleep(duration);
+    }
+    #[cfg(feature = "util_readers")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Box::pin(tokio::time::sleep(duration));
+    }
+    Box::pin(futures_timer::Delay::new(duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_sleep_outside_tokio() {
+        let start = Instant::now();
+        futures::executor::block_on(sleep(Duration::from_millis(50)));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
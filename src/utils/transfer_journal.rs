@@ -0,0 +1,302 @@
+use crate::utils::{PartManifest, Transfer};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// An unfinished transfer recorded in a [TransferJournal]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub id: u64,
+    pub priority: i32,
+    pub transfer: Transfer,
+    /// The parts uploaded so far, if the transfer is the upload of a large file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub large_file: Option<PartManifest>,
+}
+
+// Compaction only pays off once the log has this many more lines than there are entries
+const COMPACT_SLACK: usize = 1000;
+
+// One change to the journal, stored as a line of JSON
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum Record {
+    Insert {
+        entry: Box<JournalEntry>,
+    },
+    #[serde(rename_all = "camelCase")]
+    LargeFile {
+        id: u64,
+        large_file: Option<PartManifest>,
+    },
+    Remove {
+        id: u64,
+    },
+}
+
+impl Record {
+    fn apply(self, entries: &mut BTreeMap<u64, JournalEntry>) {
+        match self {
+            Record::Insert { entry } => {
+                entries.insert(entry.id, *entry);
+            }
+            Record::LargeFile { id, large_file } => {
+                if let Some(entry) = entries.get_mut(&id) {
+                    entry.large_file = large_file;
+                }
+            }
+            Record::Remove { id } => {
+                entries.remove(&id);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct JournalState {
+    entries: BTreeMap<u64, JournalEntry>,
+    // Opened for appending on the first change
+    file: Option<File>,
+    // Lines in the file, compared to the number of entries to decide when to compact it
+    lines: usize,
+    // A failed write may have left a cut off line, so the next change rewrites the file instead of appending
+    broken: bool,
+}
+
+/// The queued and running transfers of a [TransferManager][crate::utils::TransferManager], kept in a file
+///
+/// Every change, such as a transfer being queued or finishing or a part of a large file being uploaded,
+/// is appended to the file as a line of JSON, so it always describes what is left to do
+/// and recording a change doesn't depend on how many transfers there are.
+/// The file is rewritten with only the unfinished transfers once it has grown well past them. \
+/// After a crash or restart, [TransferManager::resume][crate::utils::TransferManager::resume] queues the transfers again,
+/// and large files continue after their last uploaded part instead of starting over.
+#[derive(Debug)]
+pub struct TransferJournal {
+    path: PathBuf,
+    state: Mutex<JournalState>,
+}
+
+impl TransferJournal {
+    /// Opens the journal at 'path', with the transfers left in it by a previous run
+    ///
+    /// A missing file is an empty journal, it is only created once a transfer is queued
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<TransferJournal, Error> {
+        let path = path.into();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::IOError(e)),
+        };
+        let mut state = JournalState::default();
+        let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+        for (i, line) in lines.iter().enumerate() {
+            match serde_json::from_str::<Record>(line) {
+                Ok(record) => record.apply(&mut state.entries),
+                // A crash while appending leaves the last line cut off, the change it held never completed
+                Err(_) if i + 1 == lines.len() => state.broken = true,
+                Err(e) => return Err(Error::SerdeError(e)),
+            }
+        }
+        state.lines = lines.len();
+        // Appending to a cut off line would garble the next change too
+        state.broken |= !text.is_empty() && !text.ends_with('\n');
+        Ok(TransferJournal {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The unfinished transfers, in the order they were queued
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        self.state
+            .lock()
+            .unwrap()
+            .entries
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().entries.is_empty()
+    }
+
+    // The id after every recorded one
+    pub(crate) fn next_id(&self) -> u64 {
+        self.state
+            .lock()
+            .unwrap()
+            .entries
+            .keys()
+            .next_back()
+            .map_or(0, |id| id + 1)
+    }
+
+    pub(crate) fn insert(&self, entry: JournalEntry) -> Result<(), Error> {
+        self.insert_all(vec![entry])
+    }
+
+    // Records several transfers with a single write
+    pub(crate) fn insert_all(&self, entries: Vec<JournalEntry>) -> Result<(), Error> {
+        let records = entries
+            .into_iter()
+            .map(|entry| Record::Insert {
+                entry: Box::new(entry),
+            })
+            .collect();
+        self.append(records)
+    }
+
+    pub(crate) fn set_large_file(&self, id: u64, manifest: &PartManifest) -> Result<(), Error> {
+        self.append(vec![Record::LargeFile {
+            id,
+            large_file: Some(manifest.clone()),
+        }])
+    }
+
+    pub(crate) fn clear_large_file(&self, id: u64) -> Result<(), Error> {
+        let has_large_file = matches!(
+            self.state.lock().unwrap().entries.get(&id),
+            Some(entry) if entry.large_file.is_some()
+        );
+        if !has_large_file {
+            return Ok(());
+        }
+        self.append(vec![Record::LargeFile {
+            id,
+            large_file: None,
+        }])
+    }
+
+    pub(crate) fn remove(&self, id: u64) -> Result<(), Error> {
+        if !self.state.lock().unwrap().entries.contains_key(&id) {
+            return Ok(());
+        }
+        self.append(vec![Record::Remove { id }])
+    }
+
+    // Writes 'records' to the end of the file, then applies them
+    fn append(&self, records: Vec<Record>) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.broken {
+            // Rewriting drops the cut off line, along with everything else that is over
+            let mut entries = state.entries.clone();
+            for record in records {
+                record.apply(&mut entries);
+            }
+            self.rewrite(&entries)?;
+            state.lines = entries.len();
+            state.entries = entries;
+            state.broken = false;
+            return Ok(());
+        }
+        let mut lines = String::new();
+        for record in &records {
+            lines.push_str(&serde_json::to_string(record).map_err(Error::SerdeError)?);
+            lines.push('\n');
+        }
+        if let Err(e) = state.write(&self.path, lines.as_bytes()) {
+            state.file = None;
+            state.broken = true;
+            return Err(Error::IOError(e));
+        }
+        state.lines += records.len();
+        for record in records {
+            record.apply(&mut state.entries);
+        }
+        // The changes are recorded either way, a failed compaction is tried again on the next change
+        if state.lines > state.entries.len() * 2 + COMPACT_SLACK
+            && self.rewrite(&state.entries).is_ok()
+        {
+            state.file = None;
+            state.lines = state.entries.len();
+        }
+        Ok(())
+    }
+
+    // Replaces the file with one holding only 'entries'
+    //
+    // Writes a temporary file first, so a crash never leaves a half-written journal
+    fn rewrite(&self, entries: &BTreeMap<u64, JournalEntry>) -> Result<(), Error> {
+        let mut text = String::new();
+        for entry in entries.values() {
+            let record = Record::Insert {
+                entry: Box::new(entry.clone()),
+            };
+            text.push_str(&serde_json::to_string(&record).map_err(Error::SerdeError)?);
+            text.push('\n');
+        }
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        std::fs::write(&temp, text).map_err(Error::IOError)?;
+        std::fs::rename(&temp, &self.path).map_err(Error::IOError)
+    }
+}
+
+impl JournalState {
+    // Appends to the file at 'path', opening it on first use
+    fn write(&mut self, path: &Path, data: &[u8]) -> std::io::Result<()> {
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => self
+                .file
+                .insert(OpenOptions::new().create(true).append(true).open(path)?),
+        };
+        file.write_all(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal() {
+        let path = std::env::temp_dir().join(format!("raze-journal-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let journal = TransferJournal::open(&path).unwrap();
+        assert!(journal.is_empty());
+        assert_eq!(journal.next_id(), 0);
+        for id in [0, 1] {
+            let entry = JournalEntry {
+                id,
+                priority: 0,
+                transfer: Transfer::Upload {
+                    path: format!("file{}", id).into(),
+                    file_name: format!("backup/file{}", id),
+                },
+                large_file: None,
+            };
+            journal.insert(entry).unwrap();
+        }
+        let mut manifest = PartManifest::new("4_z_large", 100);
+        manifest.add_part(1, 100, "a").unwrap();
+        journal.set_large_file(1, &manifest).unwrap();
+        journal.remove(0).unwrap();
+
+        let reopened = TransferJournal::open(&path).unwrap();
+        let entries = reopened.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].large_file, Some(manifest));
+        assert_eq!(reopened.next_id(), 2);
+
+        // A change cut off by a crash is dropped, and the next one doesn't get appended to it
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"op":"remove","#).unwrap();
+        let reopened = TransferJournal::open(&path).unwrap();
+        assert_eq!(reopened.entries().len(), 1);
+        reopened.remove(1).unwrap();
+        assert!(TransferJournal::open(&path).unwrap().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
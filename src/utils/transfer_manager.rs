@@ -0,0 +1,865 @@
+use crate::api::{
+    b2_cancel_large_file, b2_get_upload_part_url, b2_start_large_file, b2_upload_file,
+    b2_upload_part, B2Auth, B2DownloadFileByNameParams, B2FileInfo, BucketId, FileParameters,
+    PartParameters, Sha1Variant, StartLargeFileParameters, LARGE_FILE_SHA1,
+};
+use crate::utils::download_stream::download_version_stream;
+use crate::utils::upload_retry::should_retry_upload;
+use crate::utils::upload_stream::from_io_error;
+use crate::utils::{
+    download_stream, file_sha1, reader_to_stream, restore_metadata, BytesStreamExt,
+    ContentTypeDetector, JournalEntry, PartManifest, RateLimiter, RestoreOptions, ShutdownSignal,
+    TransferJournal, TransferStats, UploadUrlPool,
+};
+use crate::Error;
+use bytes::Bytes;
+use futures::future::{select, AbortHandle, Abortable, Either};
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{Error as IoError, SeekFrom};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::watch;
+
+/// Something for a [TransferManager] to do
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum Transfer {
+    /// Uploads the local file 'path' as 'file_name'
+    #[serde(rename_all = "camelCase")]
+    Upload { path: PathBuf, file_name: String },
+    /// Downloads 'file' to the local 'path', restoring its modification time
+    ///
+    /// Fails with a [ConfigError][Error::ConfigError] if 'file' is no longer the latest version of its name
+    Download { file: B2FileInfo, path: PathBuf },
+}
+
+/// Where a transfer of a [TransferManager] is at
+#[derive(Debug, Clone)]
+pub enum TransferStatus {
+    Queued,
+    Running,
+    Uploaded(B2FileInfo),
+    Downloaded(PathBuf),
+    /// The transfer failed, after retrying if the error was transient
+    Failed(Arc<Error>),
+    Cancelled,
+}
+
+impl TransferStatus {
+    /// Whether the transfer is over, successfully or not
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, TransferStatus::Queued | TransferStatus::Running)
+    }
+}
+
+/// What [TransferManager::shutdown] does with large files whose upload didn't finish
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnfinishedLargeFiles {
+    /// Keeps them and their uploaded parts in the journal, so [resume][TransferManager::resume] continues them
+    Keep,
+    /// Cancels them with [b2_cancel_large_file], so their parts don't take up storage. Resumed uploads start over
+    Cancel,
+}
+
+// A queued or running transfer, shared by its handles and the manager
+struct Job {
+    id: u64,
+    priority: i32,
+    transfer: Transfer,
+    stats: TransferStats,
+    status: watch::Sender<TransferStatus>,
+    // Whether it was cancelled, and how to stop it once it runs
+    control: Mutex<(bool, Option<AbortHandle>)>,
+    // The parts uploaded so far, for large files
+    large_file: Mutex<Option<PartManifest>>,
+    journal: Option<Arc<TransferJournal>>,
+}
+
+/// Follows and controls a transfer queued on a [TransferManager]
+///
+/// Cloning is cheap, and clones refer to the same transfer
+#[derive(Clone)]
+pub struct TransferHandle {
+    job: Arc<Job>,
+}
+
+impl TransferHandle {
+    /// Tells transfers apart, in the order they were queued
+    pub fn id(&self) -> u64 {
+        self.job.id
+    }
+
+    pub fn transfer(&self) -> &Transfer {
+        &self.job.transfer
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.job.priority
+    }
+
+    /// Live progress of the transfer
+    pub fn stats(&self) -> &TransferStats {
+        &self.job.stats
+    }
+
+    pub fn status(&self) -> TransferStatus {
+        self.job.status.borrow().clone()
+    }
+
+    /// A receiver that sees every change of the status
+    pub fn subscribe(&self) -> watch::Receiver<TransferStatus> {
+        self.job.status.subscribe()
+    }
+
+    /// Cancels the transfer, dropping it from the queue or stopping it if it is running
+    ///
+    /// A download stopped halfway leaves no partial file behind. An upload of a large file stopped halfway
+    /// leaves it unfinished, to be cancelled with [b2_cancel_large_file][crate::api::b2_cancel_large_file] or a lifecycle rule.
+    pub fn cancel(&self) {
+        let mut control = self.job.control.lock().unwrap();
+        control.0 = true;
+        match control.1.take() {
+            Some(abort) => abort.abort(),
+            None => {
+                let cancelled = self.job.status.send_if_modified(|status| {
+                    let queued = !status.is_finished();
+                    if queued {
+                        *status = TransferStatus::Cancelled;
+                    }
+                    queued
+                });
+                if cancelled {
+                    self.job.forget();
+                }
+            }
+        }
+    }
+
+    /// Waits for the transfer to finish, returning its final status
+    pub async fn wait(&self) -> TransferStatus {
+        let mut status = self.subscribe();
+        loop {
+            let current = status.borrow_and_update().clone();
+            if current.is_finished() || status.changed().await.is_err() {
+                return current;
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for TransferHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TransferHandle")
+            .field("id", &self.job.id)
+            .field("priority", &self.job.priority)
+            .field("transfer", &self.job.transfer)
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+// A job waiting in the queue, the highest priority first, then in the order they were queued
+struct Queued(Arc<Job>);
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .priority
+            .cmp(&other.0.priority)
+            .then(other.0.id.cmp(&self.0.id))
+    }
+}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
+}
+
+impl Eq for Queued {}
+
+#[derive(Default)]
+struct Queue {
+    waiting: BinaryHeap<Queued>,
+    running: HashMap<u64, Arc<Job>>,
+    concurrency: usize,
+    next_id: u64,
+}
+
+struct Shared {
+    client: Client,
+    auth: B2Auth,
+    bucket_id: BucketId,
+    bucket_name: String,
+    pool: UploadUrlPool,
+    limiter: RateLimiter,
+    stats: TransferStats,
+    detector: ContentTypeDetector,
+    max_retries: u32,
+    journal: Option<Arc<TransferJournal>>,
+    queue: Mutex<Queue>,
+    shutdown: ShutdownSignal,
+}
+
+/// Runs uploads and downloads for one bucket from a queue, the ones with the highest priority first
+///
+/// At most [concurrency][TransferManager::set_concurrency] transfers run at a time (4 by default), and all of them share
+/// one bandwidth budget through a [RateLimiter]. Every transfer gets a [TransferHandle] to follow its progress or cancel it. \
+/// A failed transfer doesn't affect the others. Uploads are retried up to 'max_retries' times (3 by default) like
+/// [upload_with_retry][crate::utils::upload_with_retry], downloads resume after transient failures like [download_stream]. \
+/// Files larger than the 'recommended_part_size' of the [B2Auth] are uploaded as large files, one part at a time,
+/// so a retried or [resumed][TransferManager::resume] upload continues after the last finished part.
+///
+/// Must be used from within a tokio runtime.
+///
+/// ```rust,no_run
+/// # use raze::utils::*;
+/// # async fn f(client: reqwest::Client, auth: raze::api::B2Auth) {
+/// let manager = TransferManager::new(client, auth, "bucket_id", "my-bucket").with_bandwidth(Some(1_000_000));
+/// let handle = manager.enqueue(
+///     Transfer::Upload { path: "notes.txt".into(), file_name: "backup/notes.txt".into() },
+///     0,
+/// );
+/// println!("{:?}", handle.wait().await);
+/// # }
+/// ```
+pub struct TransferManager {
+    shared: Arc<Shared>,
+}
+
+impl TransferManager {
+    pub fn new<T: Into<BucketId>, Q: Into<String>>(
+        client: Client,
+        auth: B2Auth,
+        bucket_id: T,
+        bucket_name: Q,
+    ) -> TransferManager {
+        let bucket_id = bucket_id.into();
+        TransferManager {
+            shared: Arc::new(Shared {
+                pool: UploadUrlPool::new(client.clone(), auth.clone(), bucket_id.clone(), 8),
+                client,
+                auth,
+                bucket_id,
+                bucket_name: bucket_name.into(),
+                limiter: RateLimiter::unlimited(),
+                stats: TransferStats::new(None),
+                detector: ContentTypeDetector::default(),
+                max_retries: 3,
+                journal: None,
+                queue: Mutex::new(Queue {
+                    concurrency: 4,
+                    ..Queue::default()
+                }),
+                shutdown: ShutdownSignal::new(),
+            }),
+        }
+    }
+
+    /// Sets how many transfers run at a time (at least 1)
+    pub fn with_concurrency(self, concurrency: usize) -> Self {
+        self.set_concurrency(concurrency);
+        self
+    }
+
+    /// Limits the bandwidth of all transfers together in bytes per second, None for unlimited
+    pub fn with_bandwidth(self, bytes_per_second: Option<usize>) -> Self {
+        self.shared.limiter.set_rate(bytes_per_second);
+        self
+    }
+
+    /// Sets how often a failed upload is retried
+    ///
+    /// Panics if transfers were already queued
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.configure().max_retries = max_retries;
+        self
+    }
+
+    /// Sets how the content type of uploads is picked
+    ///
+    /// Panics if transfers were already queued
+    pub fn with_detector(mut self, detector: ContentTypeDetector) -> Self {
+        self.configure().detector = detector;
+        self
+    }
+
+    /// Records queued and running transfers in 'journal', so they can be [resumed][TransferManager::resume] after a restart
+    ///
+    /// Panics if transfers were already queued
+    pub fn with_journal(mut self, journal: TransferJournal) -> Self {
+        let shared = self.configure();
+        shared.queue.get_mut().unwrap().next_id = journal.next_id();
+        shared.journal = Some(Arc::new(journal));
+        self
+    }
+
+    fn configure(&mut self) -> &mut Shared {
+        Arc::get_mut(&mut self.shared).expect("TransferManager is configured before queueing")
+    }
+
+    /// Changes how many transfers run at a time, takes effect as transfers finish
+    pub fn set_concurrency(&self, concurrency: usize) {
+        self.shared.queue.lock().unwrap().concurrency = concurrency.max(1);
+        pump(&self.shared);
+    }
+
+    /// The limiter shared by all transfers, e.g. to change the bandwidth while they run
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.shared.limiter
+    }
+
+    /// Progress of all transfers together, without a total
+    pub fn stats(&self) -> &TransferStats {
+        &self.shared.stats
+    }
+
+    /// Number of transfers waiting to run
+    pub fn queued(&self) -> usize {
+        self.shared.queue.lock().unwrap().waiting.len()
+    }
+
+    /// Number of transfers running
+    pub fn running(&self) -> usize {
+        self.shared.queue.lock().unwrap().running.len()
+    }
+
+    /// Queues a transfer, transfers with a higher 'priority' run first
+    ///
+    /// With a journal, the transfer is recorded before it is queued. If that fails, the transfer fails right away,
+    /// and so does every transfer queued after [shutdown][TransferManager::shutdown] was called.
+    pub fn enqueue(&self, transfer: Transfer, priority: i32) -> TransferHandle {
+        let id = {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.next_id += 1;
+            queue.next_id - 1
+        };
+        let entry = JournalEntry {
+            id,
+            priority,
+            transfer,
+            large_file: None,
+        };
+        if self.shared.shutdown.is_shutting_down() {
+            return self.rejected(entry);
+        }
+        if let Some(journal) = &self.shared.journal {
+            if let Err(e) = journal.insert(entry.clone()) {
+                let job = self.job(entry);
+                job.status.send_replace(TransferStatus::Failed(Arc::new(e)));
+                return TransferHandle { job };
+            }
+        }
+        self.queue(entry)
+    }
+
+    /// Same as calling [enqueue][TransferManager::enqueue] for each transfer, recording them in the journal at once
+    pub fn enqueue_all<I: IntoIterator<Item = (Transfer, i32)>>(
+        &self,
+        transfers: I,
+    ) -> Vec<TransferHandle> {
+        let entries: Vec<JournalEntry> = {
+            let mut queue = self.shared.queue.lock().unwrap();
+            transfers
+                .into_iter()
+                .map(|(transfer, priority)| {
+                    queue.next_id += 1;
+                    JournalEntry {
+                        id: queue.next_id - 1,
+                        priority,
+                        transfer,
+                        large_file: None,
+                    }
+                })
+                .collect()
+        };
+        if self.shared.shutdown.is_shutting_down() {
+            return entries.into_iter().map(|e| self.rejected(e)).collect();
+        }
+        if let Some(journal) = &self.shared.journal {
+            if let Err(e) = journal.insert_all(entries.clone()) {
+                let e = Arc::new(e);
+                return entries
+                    .into_iter()
+                    .map(|entry| {
+                        let job = self.job(entry);
+                        job.status.send_replace(TransferStatus::Failed(e.clone()));
+                        TransferHandle { job }
+                    })
+                    .collect();
+            }
+        }
+        entries.into_iter().map(|e| self.queue(e)).collect()
+    }
+
+    /// Queues the transfers left in the journal by a previous run, in their original order
+    ///
+    /// Large files continue after the last part that was uploaded, the other transfers start over.
+    /// Call this once, before queueing new transfers, as each call queues the journaled transfers again.
+    pub fn resume(&self) -> Vec<TransferHandle> {
+        let entries = match &self.shared.journal {
+            Some(journal) => journal.entries(),
+            None => Vec::new(),
+        };
+        entries.into_iter().map(|e| self.queue(e)).collect()
+    }
+
+    fn job(&self, entry: JournalEntry) -> Arc<Job> {
+        let size = match &entry.transfer {
+            Transfer::Upload { path, .. } => std::fs::metadata(path).ok().map(|m| m.len()),
+            Transfer::Download { file, .. } => Some(file.content_length),
+        };
+        let mut job = Job::new(entry.id, entry.priority, entry.transfer, size);
+        job.large_file = Mutex::new(entry.large_file);
+        job.journal = self.shared.journal.clone();
+        Arc::new(job)
+    }
+
+    fn queue(&self, entry: JournalEntry) -> TransferHandle {
+        if self.shared.shutdown.is_shutting_down() {
+            return self.rejected(entry);
+        }
+        let job = self.job(entry);
+        self.shared
+            .queue
+            .lock()
+            .unwrap()
+            .waiting
+            .push(Queued(job.clone()));
+        pump(&self.shared);
+        TransferHandle { job }
+    }
+
+    // A transfer that fails right away, as the manager is shutting down
+    fn rejected(&self, entry: JournalEntry) -> TransferHandle {
+        let job = self.job(entry);
+        let error = Error::ConfigError("transfer manager is shutting down".to_string());
+        job.status
+            .send_replace(TransferStatus::Failed(Arc::new(error)));
+        TransferHandle { job }
+    }
+
+    /// Stops the manager, e.g. when the service is asked to stop
+    ///
+    /// Transfers queued afterwards fail right away, and waiting transfers are cancelled without starting.
+    /// Running transfers get until 'deadline' to finish, then they are cancelled too. \
+    /// Transfers cancelled this way stay in the journal, to be [resumed][TransferManager::resume] on the next start,
+    /// and large files keep their uploaded parts unless 'unfinished' is [Cancel][UnfinishedLargeFiles::Cancel]. \
+    /// Returns whether all running transfers finished in time, or the first error cancelling a large file.
+    pub async fn shutdown(
+        &self,
+        deadline: Duration,
+        unfinished: UnfinishedLargeFiles,
+    ) -> Result<bool, Error> {
+        self.shared.shutdown.begin();
+        let mut stopped: Vec<Arc<Job>> = {
+            let mut queue = self.shared.queue.lock().unwrap();
+            let running: Vec<Arc<Job>> = queue.running.values().cloned().collect();
+            queue
+                .waiting
+                .drain()
+                .map(|Queued(job)| job)
+                .chain(running)
+                .collect()
+        };
+        for job in &stopped {
+            job.status.send_if_modified(|status| {
+                let queued = matches!(status, TransferStatus::Queued);
+                if queued {
+                    *status = TransferStatus::Cancelled;
+                }
+                queued
+            });
+        }
+        let drained = self.shared.shutdown.shutdown(deadline).await;
+        if !drained {
+            // Stopped transfers are dropped quickly, this only waits for their last status
+            self.shared.shutdown.finished().await;
+        }
+
+        stopped.retain(|job| matches!(*job.status.borrow(), TransferStatus::Cancelled));
+        let mut result = Ok(drained);
+        if unfinished == UnfinishedLargeFiles::Cancel {
+            for job in stopped {
+                let manifest = job.large_file.lock().unwrap().take();
+                let manifest = match manifest {
+                    Some(manifest) => manifest,
+                    None => continue,
+                };
+                let cancelled = b2_cancel_large_file(
+                    &self.shared.client,
+                    &self.shared.auth,
+                    manifest.file_id(),
+                )
+                .await;
+                match cancelled {
+                    Ok(_) => {
+                        if let Some(journal) = &job.journal {
+                            let _ = journal.clear_large_file(job.id);
+                        }
+                    }
+                    Err(e) => {
+                        *job.large_file.lock().unwrap() = Some(manifest);
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+impl std::fmt::Debug for TransferManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TransferManager")
+            .field("bucket_name", &self.shared.bucket_name)
+            .field("queued", &self.queued())
+            .field("running", &self.running())
+            .finish()
+    }
+}
+
+impl Job {
+    fn new(id: u64, priority: i32, transfer: Transfer, size: Option<u64>) -> Job {
+        let (status, _) = watch::channel(TransferStatus::Queued);
+        Job {
+            id,
+            priority,
+            transfer,
+            stats: TransferStats::new(size),
+            status,
+            control: Mutex::new((false, None)),
+            large_file: Mutex::new(None),
+            journal: None,
+        }
+    }
+
+    // Records the parts uploaded so far
+    fn save_large_file(&self, manifest: &PartManifest) -> Result<(), Error> {
+        *self.large_file.lock().unwrap() = Some(manifest.clone());
+        match &self.journal {
+            Some(journal) => journal.set_large_file(self.id, manifest),
+            None => Ok(()),
+        }
+    }
+
+    // Drops the job from the journal once it is over
+    fn forget(&self) {
+        if let Some(journal) = &self.journal {
+            let _ = journal.remove(self.id);
+        }
+    }
+}
+
+// Starts queued jobs while there is room
+fn pump(shared: &Arc<Shared>) {
+    let mut queue = shared.queue.lock().unwrap();
+    while queue.running.len() < queue.concurrency {
+        let guard = match shared.shutdown.start() {
+            Some(guard) => guard,
+            None => break,
+        };
+        let job = match queue.waiting.pop() {
+            Some(Queued(job)) => job,
+            None => break,
+        };
+        let (abort, registration) = AbortHandle::new_pair();
+        {
+            let mut control = job.control.lock().unwrap();
+            if control.0 {
+                continue;
+            }
+            control.1 = Some(abort);
+        }
+        queue.running.insert(job.id, job.clone());
+        job.status.send_replace(TransferStatus::Running);
+        let shared = shared.clone();
+        let run = Abortable::new(run(shared.clone(), job.clone()), registration);
+        tokio::spawn(async move {
+            let run = AssertUnwindSafe(run).catch_unwind();
+            futures::pin_mut!(run);
+            let result = match select(run, Box::pin(shared.shutdown.stopped())).await {
+                Either::Left((result, _)) => Some(result),
+                Either::Right(_) => None,
+            };
+            // Transfers stopped by a shutdown stay in the journal
+            if result.is_some() {
+                job.forget();
+            }
+            let status = match result {
+                Some(Ok(Ok(status))) => status,
+                Some(Ok(Err(_))) | None => {
+                    if let Transfer::Download { path, .. } = &job.transfer {
+                        let _ = tokio::fs::remove_file(partial_path(path)).await;
+                    }
+                    TransferStatus::Cancelled
+                }
+                Some(Err(_)) => TransferStatus::Failed(Arc::new(Error::IOError(IoError::other(
+                    "transfer panicked",
+                )))),
+            };
+            job.control.lock().unwrap().1 = None;
+            job.status.send_replace(status);
+            shared.queue.lock().unwrap().running.remove(&job.id);
+            pump(&shared);
+            drop(guard);
+        });
+    }
+}
+
+async fn run(shared: Arc<Shared>, job: Arc<Job>) -> TransferStatus {
+    match &job.transfer {
+        Transfer::Upload { path, file_name } => {
+            let mut attempt = 0;
+            loop {
+                job.stats.restart();
+                match upload(&shared, &job, path, file_name).await {
+                    Ok(info) => return TransferStatus::Uploaded(info),
+                    Err(e) if attempt < shared.max_retries && should_retry_upload(&e) => {
+                        crate::utils::sleep(Duration::from_secs(1 << attempt.min(6))).await;
+                        attempt += 1;
+                    }
+                    Err(e) => return TransferStatus::Failed(Arc::new(e)),
+                }
+            }
+        }
+        Transfer::Download { file, path } => match download(&shared, &job, file, path).await {
+            Ok(()) => TransferStatus::Downloaded(path.clone()),
+            Err(e) => TransferStatus::Failed(Arc::new(e)),
+        },
+    }
+}
+
+// Limits 'stream' to the shared bandwidth and records it in the stats
+fn track<S>(shared: &Shared, job: &Job, stream: S) -> impl Stream<Item = Result<Bytes, IoError>>
+where
+    S: Stream<Item = Result<Bytes, IoError>>,
+{
+    stream
+        .limited(shared.limiter.clone())
+        .stats(shared.stats.clone())
+        .stats(job.stats.clone())
+}
+
+async fn upload(
+    shared: &Shared,
+    job: &Job,
+    path: &Path,
+    file_name: &str,
+) -> Result<B2FileInfo, Error> {
+    let file = tokio::fs::File::open(path).await.map_err(Error::IOError)?;
+    let metadata = file.metadata().await.map_err(Error::IOError)?;
+    let last_modified_millis = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let content_type = shared.detector.detect(&path.to_string_lossy());
+    let resuming = job.large_file.lock().unwrap().is_some();
+    if resuming || metadata.len() > shared.auth.recommended_part_size as u64 {
+        let mut file_info = HashMap::from([(
+            "src_last_modified_millis".to_string(),
+            last_modified_millis.to_string(),
+        )]);
+        // B2 only keeps per-part Sha1s for large files, so a new one gets the whole-file Sha1 from a first pass
+        if !resuming {
+            file_info.insert(LARGE_FILE_SHA1.to_string(), file_sha1(path).await?);
+        }
+        let params = StartLargeFileParameters {
+            bucket_id: &shared.bucket_id,
+            file_name,
+            content_type: content_type.as_deref(),
+            file_info: Some(file_info),
+        };
+        return upload_large(shared, job, path, metadata.len(), params).await;
+    }
+    let stream = track(shared, job, reader_to_stream(file));
+    let upload_auth = shared.pool.acquire().await?;
+    let res = b2_upload_file(
+        &shared.client,
+        &upload_auth,
+        reqwest::Body::wrap_stream(stream.hash_at_end()),
+        FileParameters {
+            file_path: file_name,
+            file_size: metadata.len(),
+            content_type: content_type.as_deref(),
+            content_sha1: Sha1Variant::HexAtEnd,
+            last_modified_millis,
+        },
+    )
+    .await;
+    shared.pool.release(upload_auth, res.is_ok());
+    res
+}
+
+// Uploads 'path' in parts, continuing after the parts in the job's manifest if it has one
+async fn upload_large(
+    shared: &Shared,
+    job: &Job,
+    path: &Path,
+    size: u64,
+    params: StartLargeFileParameters<'_>,
+) -> Result<B2FileInfo, Error> {
+    let existing = job.large_file.lock().unwrap().clone();
+    let mut manifest = match existing {
+        Some(manifest) => manifest,
+        None => {
+            let started = b2_start_large_file(&shared.client, &shared.auth, params).await?;
+            let file_name = started.file_name;
+            let file_id = started.file_id.ok_or_else(|| {
+                Error::ConfigError(format!("no file id for large file {}", file_name))
+            })?;
+            let manifest =
+                PartManifest::new(file_id, shared.auth.absolute_minimum_part_size as u64);
+            job.save_large_file(&manifest)?;
+            manifest
+        }
+    };
+    let mut offset = manifest.total_size();
+    if offset > size {
+        return Err(Error::ConfigError(format!(
+            "{} is smaller than the parts already uploaded",
+            path.display()
+        )));
+    }
+    job.stats.record(offset);
+    let part_auth =
+        b2_get_upload_part_url(&shared.client, &shared.auth, manifest.file_id()).await?;
+    let part_size = (shared.auth.recommended_part_size as u64).max(1);
+    while offset < size {
+        let len = part_size.min(size - offset);
+        let mut file = tokio::fs::File::open(path).await.map_err(Error::IOError)?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(Error::IOError)?;
+        let stream = track(shared, job, reader_to_stream(file.take(len))).hash_at_end();
+        let digests = stream.digests();
+        let result = b2_upload_part(
+            &shared.client,
+            &part_auth,
+            reqwest::Body::wrap_stream(stream),
+            PartParameters {
+                part_number: manifest.next_part_number(),
+                part_size: len,
+                content_sha1: Sha1Variant::HexAtEnd,
+            },
+        )
+        .await?;
+        let sha1 = digests.sha1().unwrap_or(result.content_sha1);
+        manifest
+            .add_part(result.part_number, len, sha1)
+            .map_err(|e| Error::ConfigError(e.to_string()))?;
+        job.save_large_file(&manifest)?;
+        offset += len;
+    }
+    manifest.finish(&shared.client, &shared.auth).await
+}
+
+async fn download(shared: &Shared, job: &Job, file: &B2FileInfo, path: &Path) -> Result<(), Error> {
+    let params = B2DownloadFileByNameParams {
+        bucket_name: shared.bucket_name.clone(),
+        file_name: file.file_name.clone(),
+        authorization: None,
+        download_host: None,
+        omit_authorization: false,
+        range: None,
+    };
+    // The listed version's metadata is restored afterwards, so the content must be of that version too
+    let stream = match &file.file_id {
+        Some(file_id) => download_version_stream(
+            shared.client.clone(),
+            shared.auth.clone(),
+            params,
+            file_id,
+            shared.max_retries,
+        )
+        .left_stream(),
+        None => download_stream(
+            shared.client.clone(),
+            shared.auth.clone(),
+            params,
+            shared.max_retries,
+        )
+        .right_stream(),
+    }
+    .map_err(IoError::other);
+    let stream = track(shared, job, stream);
+
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(Error::IOError)?;
+    }
+    let partial = partial_path(path);
+    if let Err(e) = write_stream(&partial, stream).await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(from_io_error(e));
+    }
+    tokio::fs::rename(&partial, path)
+        .await
+        .map_err(Error::IOError)?;
+    restore_metadata(path, file, &RestoreOptions::default())
+}
+
+async fn write_stream<S>(path: &Path, stream: S) -> Result<(), IoError>
+where
+    S: Stream<Item = Result<Bytes, IoError>>,
+{
+    let mut out = tokio::fs::File::create(path).await?;
+    futures::pin_mut!(stream);
+    while let Some(chunk) = stream.next().await {
+        out.write_all(&chunk?).await?;
+    }
+    out.flush().await
+}
+
+// Where a download is written until it is complete, same as download_many
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.to_path_buf().into_os_string();
+    partial.push(".raze-download");
+    PathBuf::from(partial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_order() {
+        let upload = |name: &str| Transfer::Upload {
+            path: name.into(),
+            file_name: name.into(),
+        };
+        let mut waiting = BinaryHeap::new();
+        for (id, priority) in [(0, 0), (1, 5), (2, 0), (3, 5), (4, -1)] {
+            waiting.push(Queued(Arc::new(Job::new(id, priority, upload("a"), None))));
+        }
+        let order: Vec<u64> = std::iter::from_fn(|| waiting.pop().map(|q| q.0.id)).collect();
+        assert_eq!(order, [1, 3, 0, 2, 4]);
+
+        // Cancelling a queued transfer finishes it right away
+        let handle = TransferHandle {
+            job: Arc::new(Job::new(0, 0, upload("a"), Some(10))),
+        };
+        assert!(!handle.status().is_finished());
+        handle.cancel();
+        assert!(matches!(handle.status(), TransferStatus::Cancelled));
+        assert_eq!(handle.stats().snapshot().total, Some(10));
+    }
+}
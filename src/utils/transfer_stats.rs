@@ -0,0 +1,215 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// How far back [TransferStats] looks to compute the current speed by default
+pub const DEFAULT_STATS_WINDOW: Duration = Duration::from_secs(5);
+
+/// The state of a transfer at one point, as published by [TransferStats]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TransferSnapshot {
+    /// Bytes transferred so far
+    pub bytes: u64,
+    /// Size of the whole transfer, if known
+    pub total: Option<u64>,
+    /// Average speed over the last few seconds
+    pub bytes_per_second: f64,
+    /// Time left at the current speed, None if the total is unknown or nothing moved lately
+    pub eta: Option<Duration>,
+    /// Time since the first bytes were transferred
+    pub elapsed: Duration,
+}
+
+/// Live statistics of a transfer, fed by [BytesStreamProgress][crate::utils::BytesStreamProgress]
+/// or [BytesStreamThrottled][crate::utils::BytesStreamThrottled] through their `with_stats`
+///
+/// Every chunk publishes a new [TransferSnapshot] on a [watch] channel, so a UI can
+/// [subscribe][TransferStats::subscribe] and redraw whenever it changes, or just read the current state with [snapshot][TransferStats::snapshot]. \
+/// Cloning is cheap, and clones update the same statistics.
+///
+/// ```rust,no_run
+/// # use raze::utils::*;
+/// # async fn f() {
+/// let stats = TransferStats::new(Some(20));
+/// let stream = reader_to_stream(&b"hello this is a test"[..])
+///     .throttled(5000)
+///     .with_stats(stats.clone());
+/// let mut updates = stats.subscribe();
+/// tokio::spawn(async move {
+///     while updates.changed().await.is_ok() {
+///         let s = *updates.borrow();
+///         println!("{} bytes, {:.0} B/s, {:?} left", s.bytes, s.bytes_per_second, s.eta);
+///     }
+/// });
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TransferStats {
+    state: Arc<Mutex<StatsState>>,
+    sender: Arc<watch::Sender<TransferSnapshot>>,
+}
+
+#[derive(Debug)]
+struct StatsState {
+    bytes: u64,
+    total: Option<u64>,
+    window: Duration,
+    start: Option<Instant>,
+    // Chunks within the window, oldest first
+    recent: VecDeque<(Instant, u64)>,
+}
+
+impl TransferStats {
+    /// Statistics for a transfer of 'total' bytes, None if the size isn't known
+    pub fn new(total: Option<u64>) -> TransferStats {
+        let (sender, _) = watch::channel(TransferSnapshot {
+            total,
+            ..TransferSnapshot::default()
+        });
+        TransferStats {
+            state: Arc::new(Mutex::new(StatsState {
+                bytes: 0,
+                total,
+                window: DEFAULT_STATS_WINDOW,
+                start: None,
+                recent: VecDeque::new(),
+            })),
+            sender: Arc::new(sender),
+        }
+    }
+
+    /// Sets how far back the speed is averaged, longer windows give steadier numbers
+    pub fn with_window(self, window: Duration) -> Self {
+        self.state.lock().unwrap().window = window;
+        self
+    }
+
+    /// A receiver that sees every new snapshot
+    pub fn subscribe(&self) -> watch::Receiver<TransferSnapshot> {
+        self.sender.subscribe()
+    }
+
+    /// The state right now
+    ///
+    /// Unlike the snapshots sent to subscribers, which are only made when bytes are transferred,
+    /// this notices a stalled transfer: its speed drops to 0 and its eta to None once the window has passed
+    pub fn snapshot(&self) -> TransferSnapshot {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> TransferSnapshot {
+        self.state.lock().unwrap().snapshot(now)
+    }
+
+    /// Counts 'bytes' more as transferred and publishes a new snapshot
+    ///
+    /// Called by the wrappers, only needed to count transfers they don't see
+    pub fn record(&self, bytes: u64) {
+        self.record_at(bytes, Instant::now());
+    }
+
+    /// Starts counting from zero again, e.g. when a failed transfer is retried from the start
+    pub fn restart(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.bytes = 0;
+        state.start = None;
+        state.recent.clear();
+        self.sender.send_replace(TransferSnapshot {
+            total: state.total,
+            ..TransferSnapshot::default()
+        });
+    }
+
+    fn record_at(&self, bytes: u64, now: Instant) {
+        let snapshot = self.state.lock().unwrap().record(bytes, now);
+        self.sender.send_replace(snapshot);
+    }
+}
+
+impl StatsState {
+    fn record(&mut self, bytes: u64, now: Instant) -> TransferSnapshot {
+        self.start.get_or_insert(now);
+        self.bytes += bytes;
+        self.recent.push_back((now, bytes));
+        self.snapshot(now)
+    }
+
+    // The state at 'now', forgetting chunks that fell out of the window
+    fn snapshot(&mut self, now: Instant) -> TransferSnapshot {
+        let start = match self.start {
+            Some(start) => start,
+            None => {
+                return TransferSnapshot {
+                    total: self.total,
+                    ..TransferSnapshot::default()
+                }
+            }
+        };
+        while let Some((at, _)) = self.recent.front() {
+            if now.duration_since(*at) > self.window {
+                self.recent.pop_front();
+            } else {
+                break;
+            }
+        }
+        let elapsed = now.duration_since(start);
+        // Early on, the window reaches back before the transfer started
+        let span = elapsed.min(self.window).as_secs_f64();
+        let recent: u64 = self.recent.iter().map(|(_, b)| b).sum();
+        let bytes_per_second = if span > 0.0 {
+            recent as f64 / span
+        } else {
+            0.0
+        };
+        let eta = match self.total {
+            Some(total) if bytes_per_second > 0.0 => Some(Duration::from_secs_f64(
+                total.saturating_sub(self.bytes) as f64 / bytes_per_second,
+            )),
+            _ => None,
+        };
+        TransferSnapshot {
+            bytes: self.bytes,
+            total: self.total,
+            bytes_per_second,
+            eta,
+            elapsed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_stats() {
+        let stats = TransferStats::new(Some(1000)).with_window(Duration::from_secs(2));
+        let mut updates = stats.subscribe();
+        let start = Instant::now();
+        stats.record_at(100, start);
+        assert_eq!(stats.snapshot_at(start).bytes_per_second, 0.0);
+        assert_eq!(stats.snapshot_at(start).eta, None);
+        stats.record_at(100, start + Duration::from_secs(1));
+        let s = stats.snapshot_at(start + Duration::from_secs(1));
+        assert_eq!(s.bytes, 200);
+        assert_eq!(s.bytes_per_second, 200.0);
+        assert_eq!(s.eta, Some(Duration::from_secs(4)));
+        assert!(updates.has_changed().unwrap());
+        assert_eq!(updates.borrow_and_update().bytes, 200);
+
+        // The first chunk falls out of the window
+        stats.record_at(300, start + Duration::from_secs(3));
+        let s = stats.snapshot_at(start + Duration::from_secs(3));
+        assert_eq!(s.bytes_per_second, 200.0);
+        assert_eq!(s.eta, Some(Duration::from_millis(2500)));
+        assert_eq!(s.elapsed, Duration::from_secs(3));
+        assert_eq!(updates.borrow().eta, Some(Duration::from_millis(2500)));
+
+        // Nothing moved for longer than the window
+        let s = stats.snapshot_at(start + Duration::from_secs(6));
+        assert_eq!(s.bytes_per_second, 0.0);
+        assert_eq!(s.eta, None);
+        assert_eq!(s.bytes, 500);
+    }
+}
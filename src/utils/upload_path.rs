@@ -0,0 +1,244 @@
+use crate::api::{
+    b2_copy_file, b2_delete_file_version, b2_upload_file, B2Auth, B2CopyFileParams, B2FileInfo,
+    FileParameters, MetadataDirective, Sha1Variant, UploadAuth,
+};
+use crate::utils::{
+    check_content_sha1, get_file_by_name, reader_to_stream, verify_upload, BytesStreamHashAtEnd,
+    ContentTypeDetector, UploadOutcome, VerifySha1,
+};
+use crate::Error;
+use futures::StreamExt;
+use reqwest::Client;
+use sha1::Sha1;
+use std::path::Path;
+
+/// Uploads a local file as 'file_name', streaming it from disk
+///
+/// The content type is picked from the local path by 'detector', see [ContentTypeDetector]. \
+/// The SHA1 is computed while uploading (see [Sha1Variant::HexAtEnd]) and the
+/// file's modification time is stored as 'src_last_modified_millis'.
+pub async fn upload_path<P: AsRef<Path>>(
+    client: &Client,
+    auth: &UploadAuth,
+    path: P,
+    file_name: &str,
+    detector: &ContentTypeDetector,
+) -> Result<B2FileInfo, Error> {
+    upload_path_verified(client, auth, path, file_name, detector, VerifySha1::Skip).await
+}
+
+/// Same as [upload_path], but checks the Sha1 B2 reports against the one computed while uploading
+///
+/// See [VerifySha1] for what happens on a mismatch
+pub async fn upload_path_verified<P: AsRef<Path>>(
+    client: &Client,
+    auth: &UploadAuth,
+    path: P,
+    file_name: &str,
+    detector: &ContentTypeDetector,
+    verify: VerifySha1<'_>,
+) -> Result<B2FileInfo, Error> {
+    let path = path.as_ref();
+    let file = tokio::fs::File::open(path).await.map_err(Error::IOError)?;
+    let metadata = file.metadata().await.map_err(Error::IOError)?;
+    let last_modified_millis = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let content_type = detector.detect(&path.to_string_lossy());
+
+    let stream = BytesStreamHashAtEnd::wrap(reader_to_stream(file));
+    let digests = stream.digests();
+    let info = b2_upload_file(
+        client,
+        auth,
+        reqwest::Body::wrap_stream(stream),
+        FileParameters {
+            file_path: file_name,
+            file_size: metadata.len(),
+            content_type: content_type.as_deref(),
+            content_sha1: Sha1Variant::HexAtEnd,
+            last_modified_millis,
+        },
+    )
+    .await?;
+    if let Some(sha1) = digests.sha1() {
+        verify_upload(client, &info, &sha1, verify).await?;
+    }
+    Ok(info)
+}
+
+/// Same as [upload_path], but skips the upload if the bucket already has a file with the same name, size and Sha1
+///
+/// The remote file is looked up with [get_file_by_name]. \
+/// The local file is only hashed when the sizes match, in which case it is read twice: once to hash it and once to upload it. \
+/// Large files without a 'large_file_sha1' can't be compared and are always uploaded again.
+pub async fn upload_path_dedup<P: AsRef<Path>>(
+    client: &Client,
+    auth: &B2Auth,
+    upload_auth: &UploadAuth,
+    path: P,
+    file_name: &str,
+    detector: &ContentTypeDetector,
+) -> Result<UploadOutcome, Error> {
+    let path = path.as_ref();
+    let size = tokio::fs::metadata(path)
+        .await
+        .map_err(Error::IOError)?
+        .len();
+    let existing = get_file_by_name(client, auth, &upload_auth.bucket_id, file_name).await?;
+    if let Some(existing) = existing.filter(|f| f.content_length == size) {
+        if let Some(remote) = existing.whole_file_sha1() {
+            if remote.eq_ignore_ascii_case(&file_sha1(path).await?) {
+                return Ok(UploadOutcome::Skipped(existing));
+            }
+        }
+    }
+    upload_path(client, upload_auth, path, file_name, detector)
+        .await
+        .map(UploadOutcome::Uploaded)
+}
+
+/// Uploads a local file so readers of 'file_name' see either the old or the complete new file, never a broken one
+///
+/// The file is first uploaded to a temporary name next to 'file_name' and its Sha1 is checked,
+/// then it is copied server-side to 'file_name' with [b2_copy_file] and the temporary version is deleted. \
+/// If anything fails before the copy, 'file_name' is left untouched and the temporary version is removed. \
+/// Deleting the temporary version after a successful copy is best-effort,
+/// leftovers are named "<file_name>.raze-tmp-<timestamp>".
+///
+/// Since B2 copies at most 5GB in one call, the file must not be larger than that.
+pub async fn upload_path_atomic<P: AsRef<Path>>(
+    client: &Client,
+    auth: &B2Auth,
+    upload_auth: &UploadAuth,
+    path: P,
+    file_name: &str,
+    detector: &ContentTypeDetector,
+) -> Result<B2FileInfo, Error> {
+    let temp = upload_path_verified(
+        client,
+        upload_auth,
+        path,
+        &temp_name(file_name),
+        detector,
+        VerifySha1::DeleteOnMismatch(auth),
+    )
+    .await?;
+    let temp_id = temp.file_id.clone().unwrap_or_default();
+    let res = b2_copy_file(
+        client,
+        auth,
+        B2CopyFileParams {
+            source_file_id: temp_id.clone(),
+            destination_bucket_id: None,
+            file_name: file_name.to_string(),
+            range: None,
+            metadata_directive: MetadataDirective::Copy,
+            content_type: None,
+            file_info: None,
+        },
+    )
+    .await;
+    let _ = b2_delete_file_version(client, auth, &temp.file_name, &temp_id).await;
+    let info = res?;
+    if let Some(sha1) = temp.whole_file_sha1() {
+        check_content_sha1(&info, sha1)?;
+    }
+    Ok(info)
+}
+
+/// Same as [upload_path], but names the file after the last component of 'path', placed under 'prefix'
+///
+/// Uploading "/home/me/dog.jpg" with the prefix "pictures/" creates "pictures/dog.jpg", see [prefixed_file_name]
+pub async fn upload_path_with_prefix<P: AsRef<Path>>(
+    client: &Client,
+    auth: &UploadAuth,
+    path: P,
+    prefix: &str,
+    detector: &ContentTypeDetector,
+) -> Result<B2FileInfo, Error> {
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::ConfigError(format!("'{}' does not name a file", path.display())))?;
+    let file_name = prefixed_file_name(prefix, Path::new(name));
+    upload_path(client, auth, path, &file_name, detector).await
+}
+
+/// Combines 'prefix' and a relative local path into a B2 file name
+///
+/// Windows separators ('\\') become '/', and empty or "." segments are dropped,
+/// so "pictures", "pictures/" and "pictures\\" all give "pictures/dog.jpg" for "dog.jpg". \
+/// The name is not percent-encoded, uploads encode it following B2's string encoding rules.
+pub fn prefixed_file_name<P: AsRef<Path>>(prefix: &str, relative: P) -> String {
+    let relative = relative.as_ref().to_string_lossy().replace('\\', "/");
+    let prefix = prefix.replace('\\', "/");
+    prefix
+        .split('/')
+        .chain(relative.split('/'))
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// The name a file is uploaded as before being swapped in by upload_path_atomic
+fn temp_name(file_name: &str) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!("{}.raze-tmp-{}", file_name, now)
+}
+
+/// Computes the hex Sha1 of a local file
+pub async fn file_sha1<P: AsRef<Path>>(path: P) -> Result<String, Error> {
+    let file = tokio::fs::File::open(path).await.map_err(Error::IOError)?;
+    let mut hasher = Sha1::new();
+    let chunks = reader_to_stream(file);
+    futures::pin_mut!(chunks);
+    while let Some(chunk) = chunks.next().await {
+        hasher.update(&chunk.map_err(Error::IOError)?);
+    }
+    Ok(hasher.hexdigest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_sha1() {
+        let path = std::env::temp_dir().join(format!("raze-file-sha1-{}", std::process::id()));
+        std::fs::write(&path, b"hello").unwrap();
+        let sha1 = file_sha1(&path).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sha1.unwrap(), "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d");
+    }
+
+    #[test]
+    fn test_prefixed_file_name() {
+        assert_eq!(
+            prefixed_file_name("pictures/", "dog.jpg"),
+            "pictures/dog.jpg"
+        );
+        assert_eq!(
+            prefixed_file_name("pictures", "dog.jpg"),
+            "pictures/dog.jpg"
+        );
+        assert_eq!(
+            prefixed_file_name("backup\\2024\\", "photos\\./cat.jpg"),
+            "backup/2024/photos/cat.jpg"
+        );
+        assert_eq!(prefixed_file_name("", "/dog.jpg"), "dog.jpg");
+    }
+
+    #[test]
+    fn test_temp_name() {
+        let name = temp_name("photos/cat.jpg");
+        assert!(name.starts_with("photos/cat.jpg.raze-tmp-"));
+        assert_ne!(name, "photos/cat.jpg");
+    }
+}
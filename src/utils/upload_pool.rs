@@ -0,0 +1,244 @@
+use crate::api::{b2_get_upload_url, B2Auth, BucketId, UploadAuth};
+use crate::utils::{ShutdownSignal, WorkGuard};
+use crate::Error;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Default)]
+struct PoolState {
+    idle: Vec<UploadAuth>,
+    // Uploads currently running against each host
+    busy: HashMap<String, usize>,
+}
+
+impl PoolState {
+    fn busy_count(&self, upauth: &UploadAuth) -> usize {
+        self.busy
+            .get(host_of(&upauth.upload_url))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn mark_busy(&mut self, upauth: &UploadAuth) {
+        *self
+            .busy
+            .entry(host_of(&upauth.upload_url).to_string())
+            .or_insert(0) += 1;
+    }
+}
+
+/// A pool of upload URLs for one bucket, spreading uploads across B2's upload hosts
+///
+/// Each upload URL points at a specific pod, so uploads sharing a host compete for its bandwidth. \
+/// [acquire][UploadUrlPool::acquire] prefers an idle URL on a host with no running uploads,
+/// and otherwise fetches a new URL, which is likely to point at a different host. \
+/// URLs are returned with [release][UploadUrlPool::release], and discarded if the upload failed, as recommended by Backblaze. \
+/// A [PooledUploadUrl] that is dropped without being released, e.g. because its task was aborted, counts as failed.
+pub struct UploadUrlPool {
+    client: Client,
+    auth: B2Auth,
+    bucket_id: BucketId,
+    max_idle: usize,
+    state: Mutex<PoolState>,
+    shutdown: ShutdownSignal,
+}
+
+impl UploadUrlPool {
+    /// Creates an empty pool for the given bucket, keeping at most 'max_idle' unused URLs
+    pub fn new<T: Into<BucketId>>(
+        client: Client,
+        auth: B2Auth,
+        bucket_id: T,
+        max_idle: usize,
+    ) -> UploadUrlPool {
+        UploadUrlPool {
+            client,
+            auth,
+            bucket_id: bucket_id.into(),
+            max_idle,
+            state: Mutex::new(PoolState::default()),
+            shutdown: ShutdownSignal::new(),
+        }
+    }
+
+    /// Takes an upload URL, preferring hosts without running uploads
+    ///
+    /// The URL should be handed back with [release][UploadUrlPool::release] once the upload is done. \
+    /// Fails with a [ConfigError][Error::ConfigError] once the pool is [shut down][UploadUrlPool::shutdown].
+    pub async fn acquire(&self) -> Result<PooledUploadUrl<'_>, Error> {
+        let guard = self
+            .shutdown
+            .start()
+            .ok_or_else(|| Error::ConfigError("upload URL pool is shut down".to_string()))?;
+        {
+            let mut state = self.state.lock().unwrap();
+            let best = state
+                .idle
+                .iter()
+                .enumerate()
+                .map(|(i, upauth)| (i, state.busy_count(upauth)))
+                .min_by_key(|(_, busy)| *busy);
+            // Reuse a URL if its host is free, or if the pool is full so a new URL couldn't be kept anyway
+            if let Some((i, busy)) = best {
+                if busy == 0 || state.idle.len() >= self.max_idle {
+                    let upauth = state.idle.swap_remove(i);
+                    state.mark_busy(&upauth);
+                    return Ok(PooledUploadUrl::new(self, upauth, guard));
+                }
+            }
+        }
+        let upauth = b2_get_upload_url(&self.client, &self.auth, &self.bucket_id).await?;
+        self.state.lock().unwrap().mark_busy(&upauth);
+        Ok(PooledUploadUrl::new(self, upauth, guard))
+    }
+
+    /// Hands back a URL taken with [acquire][UploadUrlPool::acquire]
+    ///
+    /// If 'succeeded' is false, the URL is discarded and a new one is fetched next time
+    pub fn release(&self, mut url: PooledUploadUrl<'_>, succeeded: bool) {
+        url.succeeded = succeeded;
+    }
+
+    fn put_back(&self, upauth: UploadAuth, succeeded: bool) {
+        let mut state = self.state.lock().unwrap();
+        let host = host_of(&upauth.upload_url);
+        if let Some(count) = state.busy.get_mut(host) {
+            *count -= 1;
+            if *count == 0 {
+                state.busy.remove(host);
+            }
+        }
+        if succeeded && state.idle.len() < self.max_idle && !self.shutdown.is_shutting_down() {
+            state.idle.push(upauth);
+        }
+    }
+
+    /// Stops handing out URLs and waits up to 'deadline' for the URLs in use to be released
+    ///
+    /// Returns whether all of them were released in time. The idle URLs are dropped right away.
+    /// The pool can't stop uploads itself, so those still running past the deadline are left to their owners.
+    pub async fn shutdown(&self, deadline: Duration) -> bool {
+        self.shutdown.begin();
+        self.state.lock().unwrap().idle.clear();
+        self.shutdown.shutdown(deadline).await
+    }
+
+    /// Number of running uploads per host
+    pub fn host_counts(&self) -> HashMap<String, usize> {
+        self.state.lock().unwrap().busy.clone()
+    }
+}
+
+/// An upload URL taken from an [UploadUrlPool], usable as an [UploadAuth]
+///
+/// Hand it back with [release][UploadUrlPool::release], dropping it discards the URL.
+pub struct PooledUploadUrl<'a> {
+    pool: &'a UploadUrlPool,
+    upauth: Option<UploadAuth>,
+    succeeded: bool,
+    // Dropped after the URL was put back, so a shutdown waits for it
+    _guard: WorkGuard,
+}
+
+impl<'a> PooledUploadUrl<'a> {
+    fn new(pool: &'a UploadUrlPool, upauth: UploadAuth, guard: WorkGuard) -> Self {
+        PooledUploadUrl {
+            pool,
+            upauth: Some(upauth),
+            succeeded: false,
+            _guard: guard,
+        }
+    }
+}
+
+impl Deref for PooledUploadUrl<'_> {
+    type Target = UploadAuth;
+
+    fn deref(&self) -> &UploadAuth {
+        self.upauth.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledUploadUrl<'_> {
+    fn drop(&mut self) {
+        if let Some(upauth) = self.upauth.take() {
+            self.pool.put_back(upauth, self.succeeded);
+        }
+    }
+}
+
+// The host of an upload URL, e.g. "pod-000-1016-09.backblaze.com"
+fn host_of(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upauth(host: &str) -> UploadAuth {
+        UploadAuth {
+            bucket_id: "bucket".into(),
+            upload_url: format!("https://{}/b2api/v2/b2_upload_file/bucket/c001", host),
+            authorization_token: "token".to_string(),
+        }
+    }
+
+    fn test_auth() -> B2Auth {
+        B2Auth {
+            account_id: Default::default(),
+            authorization_token: String::new(),
+            api_url: String::new(),
+            download_url: String::new(),
+            absolute_minimum_part_size: 0,
+            recommended_part_size: 0,
+            s3_api_url: String::new(),
+            issued_at: None,
+            api_version: Default::default(),
+            allowed: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prefers_free_hosts() {
+        let pool = UploadUrlPool::new(Client::new(), test_auth(), "bucket", 1);
+        {
+            let mut state = pool.state.lock().unwrap();
+            state.idle.push(upauth("pod-a"));
+            state.idle.push(upauth("pod-b"));
+            state.busy.insert("pod-a".to_string(), 1);
+        }
+        let first = pool.acquire().await.unwrap();
+        assert_eq!(host_of(&first.upload_url), "pod-b");
+        // Only the busy pod-a is left, but the pool is full, so it is reused instead of fetching a new URL
+        let second = pool.acquire().await.unwrap();
+        assert_eq!(host_of(&second.upload_url), "pod-a");
+        assert_eq!(pool.host_counts()["pod-a"], 2);
+
+        pool.release(second, false);
+        pool.release(first, true);
+        assert_eq!(pool.host_counts()["pod-a"], 1);
+        assert_eq!(pool.state.lock().unwrap().idle.len(), 1);
+
+        assert!(pool.shutdown(Duration::ZERO).await);
+        assert!(pool.state.lock().unwrap().idle.is_empty());
+        assert!(matches!(pool.acquire().await, Err(Error::ConfigError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_url_is_released() {
+        let pool = UploadUrlPool::new(Client::new(), test_auth(), "bucket", 1);
+        pool.state.lock().unwrap().idle.push(upauth("pod-a"));
+        let url = pool.acquire().await.unwrap();
+        assert_eq!(pool.host_counts()["pod-a"], 1);
+        // As if the task holding it was aborted mid-upload
+        drop(url);
+        assert!(pool.host_counts().is_empty());
+        assert!(pool.state.lock().unwrap().idle.is_empty());
+        assert!(pool.shutdown(Duration::ZERO).await);
+    }
+}
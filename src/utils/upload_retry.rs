@@ -0,0 +1,102 @@
+use crate::api::{b2_get_upload_url, b2_upload_file, B2Auth, B2FileInfo, BucketId, FileParameters};
+use crate::utils::BodyProvider;
+use crate::Error;
+use reqwest::Client;
+use std::time::Duration;
+
+// Whether an upload should be retried with a fresh upload URL
+// Upload URLs can also expire, which shows up as a 401 with 'expired_auth_token'
+pub(crate) fn should_retry_upload(e: &Error) -> bool {
+    match e {
+        Error::B2Error(e) if e.status == 401 => e.code == "expired_auth_token",
+        Error::B2Error(e) => e.status == 408 || e.status == 429 || e.status >= 500,
+        e => e.is_transient(),
+    }
+}
+
+/// Uploads a file, retrying with a fresh upload URL on failures as prescribed by
+/// [B2's integration checklist](https://www.backblaze.com/b2/docs/integration_checklist.html)
+///
+/// Since a body can only be sent once, 'make_body' is called to create a new one for every attempt. \
+/// Retries happen on 408, 429 and 5xx responses, expired upload URLs and broken connections,
+/// waiting 1 second before the first retry and doubling the wait each time, up to 64 seconds. \
+/// Up to 'max_retries' retries are made before the last error is returned.
+pub async fn upload_with_retry<F, B>(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+    params: FileParameters<'_>,
+    mut make_body: F,
+    max_retries: u32,
+) -> Result<B2FileInfo, Error>
+where
+    F: FnMut() -> B,
+    B: Into<reqwest::Body>,
+{
+    let mut attempt = 0;
+    loop {
+        let res = match b2_get_upload_url(client, auth, bucket_id).await {
+            Ok(upauth) => b2_upload_file(client, &upauth, make_body(), params.clone()).await,
+            Err(e) => Err(e),
+        };
+        match res {
+            Err(e) if attempt < max_retries && should_retry_upload(&e) => {
+                crate::utils::sleep(Duration::from_secs(1 << attempt.min(6))).await;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Same as [upload_with_retry], taking the body and its length and Sha1 from a [BodyProvider]
+pub async fn upload_body_with_retry<P: BodyProvider>(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+    file_name: &str,
+    content_type: Option<&str>,
+    provider: &P,
+    max_retries: u32,
+) -> Result<B2FileInfo, Error> {
+    let params = FileParameters {
+        file_path: file_name,
+        file_size: provider.len(),
+        content_type,
+        content_sha1: provider.content_sha1(),
+        last_modified_millis: provider.last_modified_millis(),
+    };
+    upload_with_retry(
+        client,
+        auth,
+        bucket_id,
+        params,
+        || provider.body(),
+        max_retries,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::B2ApiError;
+
+    fn b2_error(status: u16, code: &str) -> Error {
+        Error::B2Error(B2ApiError {
+            status,
+            code: code.to_string(),
+            message: String::new(),
+            request_id: None,
+        })
+    }
+
+    #[test]
+    fn test_should_retry_upload() {
+        assert!(should_retry_upload(&b2_error(503, "service_unavailable")));
+        assert!(should_retry_upload(&b2_error(408, "request_timeout")));
+        assert!(should_retry_upload(&b2_error(401, "expired_auth_token")));
+        assert!(!should_retry_upload(&b2_error(401, "unauthorized")));
+        assert!(!should_retry_upload(&b2_error(400, "bad_request")));
+    }
+}
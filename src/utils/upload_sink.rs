@@ -0,0 +1,166 @@
+use crate::api::{b2_upload_file, B2Auth, B2FileInfo, BucketId, FileParameters, Sha1Variant};
+use crate::utils::UploadUrlPool;
+use crate::Error;
+use futures::stream::FuturesUnordered;
+use futures::{Sink, Stream};
+use reqwest::Client;
+use std::io::Error as IoError;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::task::JoinHandle;
+
+// Owned copy of a FileParameters, so it can be moved into a task
+struct OwnedFileParameters {
+    file_path: String,
+    file_size: u64,
+    content_type: Option<String>,
+    content_sha1: Option<String>,
+    hex_at_end: bool,
+    last_modified_millis: u64,
+}
+
+impl OwnedFileParameters {
+    fn new(params: FileParameters<'_>) -> OwnedFileParameters {
+        let (content_sha1, hex_at_end) = match params.content_sha1 {
+            Sha1Variant::Precomputed(hash) => (Some(hash.to_string()), false),
+            Sha1Variant::HexAtEnd => (None, true),
+            Sha1Variant::DoNotVerify => (None, false),
+        };
+        OwnedFileParameters {
+            file_path: params.file_path.to_string(),
+            file_size: params.file_size,
+            content_type: params.content_type.map(|s| s.to_string()),
+            content_sha1,
+            hex_at_end,
+            last_modified_millis: params.last_modified_millis,
+        }
+    }
+
+    fn borrow(&self) -> FileParameters<'_> {
+        FileParameters {
+            file_path: &self.file_path,
+            file_size: self.file_size,
+            content_type: self.content_type.as_deref(),
+            content_sha1: match (&self.content_sha1, self.hex_at_end) {
+                (Some(hash), _) => Sha1Variant::Precomputed(hash),
+                (None, true) => Sha1Variant::HexAtEnd,
+                (None, false) => Sha1Variant::DoNotVerify,
+            },
+            last_modified_millis: self.last_modified_millis,
+        }
+    }
+}
+
+/// A [Sink] of `(FileParameters, Body)` pairs, each of which is uploaded as a file to one bucket
+///
+/// Up to 'max_concurrent' uploads run in the background at once. Once that limit is reached,
+/// [poll_ready][Sink::poll_ready] waits for one of them to finish, which applies backpressure to the stream feeding the sink. \
+/// Upload URLs are pooled and reused between uploads with an [UploadUrlPool], which spreads uploads across upload hosts.
+///
+/// The first failed upload is reported by the next call to the sink, after which the sink should not be used anymore. \
+/// Flushing or closing waits for every upload to finish, after which [take_uploaded][B2UploadSink::take_uploaded]
+/// returns the uploaded files.
+///
+/// Must be used from within a tokio runtime.
+pub struct B2UploadSink {
+    pool: Arc<UploadUrlPool>,
+    client: Client,
+    max_concurrent: usize,
+    in_flight: FuturesUnordered<JoinHandle<Result<B2FileInfo, Error>>>,
+    uploaded: Vec<B2FileInfo>,
+}
+
+impl B2UploadSink {
+    /// Creates a sink uploading to the given bucket, with at most 'max_concurrent' (at least 1) uploads at once
+    pub fn new<T: Into<BucketId>>(
+        client: Client,
+        auth: B2Auth,
+        bucket_id: T,
+        max_concurrent: usize,
+    ) -> B2UploadSink {
+        B2UploadSink {
+            pool: Arc::new(UploadUrlPool::new(
+                client.clone(),
+                auth,
+                bucket_id,
+                max_concurrent.max(1),
+            )),
+            client,
+            max_concurrent: max_concurrent.max(1),
+            in_flight: FuturesUnordered::new(),
+            uploaded: Vec::new(),
+        }
+    }
+
+    /// Takes the files uploaded so far, in the order they finished
+    pub fn take_uploaded(&mut self) -> Vec<B2FileInfo> {
+        std::mem::take(&mut self.uploaded)
+    }
+
+    // Collects every finished upload, stopping at the first error
+    fn poll_finished(&mut self, cx: &mut Context<'_>) -> Result<(), IoError> {
+        while let Poll::Ready(Some(res)) = Pin::new(&mut self.in_flight).poll_next(cx) {
+            match res {
+                Ok(Ok(info)) => self.uploaded.push(info),
+                Ok(Err(e)) => return Err(IoError::other(e)),
+                Err(e) => return Err(IoError::other(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn upload(
+    client: Client,
+    pool: Arc<UploadUrlPool>,
+    params: OwnedFileParameters,
+    body: reqwest::Body,
+) -> Result<B2FileInfo, Error> {
+    let upauth = pool.acquire().await?;
+    let res = b2_upload_file(&client, &upauth, body, params.borrow()).await;
+    pool.release(upauth, res.is_ok());
+    res
+}
+
+impl<'a> Sink<(FileParameters<'a>, reqwest::Body)> for B2UploadSink {
+    type Error = IoError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        this.poll_finished(cx)?;
+        if this.in_flight.len() < this.max_concurrent {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn start_send(
+        self: Pin<&mut Self>,
+        item: (FileParameters<'a>, reqwest::Body),
+    ) -> Result<(), Self::Error> {
+        let (params, body) = item;
+        let params = OwnedFileParameters::new(params);
+        let client = self.client.clone();
+        let pool = self.pool.clone();
+        self.get_mut()
+            .in_flight
+            .push(tokio::spawn(upload(client, pool, params, body)));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        this.poll_finished(cx)?;
+        if this.in_flight.is_empty() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        <Self as Sink<(FileParameters<'a>, reqwest::Body)>>::poll_flush(self, cx)
+    }
+}
@@ -0,0 +1,112 @@
+use crate::api::{
+    b2_upload_file, B2Auth, B2FileInfo, BucketId, FileParameters, Sha1Variant, UploadAuth,
+};
+use crate::utils::{B2UploadWriter, SpooledBody, DEFAULT_SPOOL_THRESHOLD};
+use crate::Error;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use reqwest::Client;
+use std::io::Error as IoError;
+use tokio::io::AsyncWriteExt;
+
+/// Uploads a stream of unknown length as 'file_name', e.g. the output of another program
+///
+/// The stream is written to a [B2UploadWriter], so only about two parts are held in memory
+/// and files of any size work. Streams of at most one part end up as a regular upload. \
+/// If the stream or an upload fails, the large file is cancelled so no parts are left behind.
+///
+/// Use [reader_to_stream] to upload from an [AsyncRead][tokio::io::AsyncRead] such as stdin.
+pub async fn upload_stream<T, Q, S>(
+    client: Client,
+    auth: B2Auth,
+    bucket_id: T,
+    file_name: Q,
+    stream: S,
+) -> Result<B2FileInfo, Error>
+where
+    T: Into<BucketId>,
+    Q: Into<String>,
+    S: Stream<Item = Result<Bytes, IoError>>,
+{
+    let mut writer = B2UploadWriter::new(client, auth, bucket_id, file_name);
+    futures::pin_mut!(stream);
+    let mut res = Ok(());
+    while let Some(chunk) = stream.next().await {
+        res = match chunk {
+            Ok(chunk) => writer.write_all(&chunk).await,
+            Err(e) => Err(e),
+        };
+        if res.is_err() {
+            break;
+        }
+    }
+    if res.is_ok() {
+        res = writer.shutdown().await;
+    }
+    match res {
+        Ok(()) => Ok(writer
+            .file_info()
+            .cloned()
+            .expect("finished upload has a file")),
+        Err(e) => {
+            let _ = writer.abort().await;
+            Err(from_io_error(e))
+        }
+    }
+}
+
+/// Same as [upload_stream], but reads the whole stream before uploading it once its length is known
+///
+/// The stream is buffered with a [SpooledBody], so payloads beyond [DEFAULT_SPOOL_THRESHOLD] go to a temporary file. \
+/// This takes a single [b2_upload_file] call, so the stream is limited to 5GB. \
+/// Useful when the data arrives slower than it can be uploaded, or an upload URL is already at hand.
+pub async fn upload_stream_spooled<S>(
+    client: &Client,
+    auth: &UploadAuth,
+    file_name: &str,
+    content_type: Option<&str>,
+    stream: S,
+) -> Result<B2FileInfo, Error>
+where
+    S: Stream<Item = Result<Bytes, IoError>>,
+{
+    let body = SpooledBody::from_stream(stream, DEFAULT_SPOOL_THRESHOLD).await?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    b2_upload_file(
+        client,
+        auth,
+        body.body(),
+        FileParameters {
+            file_path: file_name,
+            file_size: body.len(),
+            content_type,
+            content_sha1: Sha1Variant::Precomputed(body.sha1()),
+            last_modified_millis: now,
+        },
+    )
+    .await
+}
+
+// The writer reports B2 errors as io errors, this gets the original error back
+pub(crate) fn from_io_error(e: IoError) -> Error {
+    match e.get_ref().map(|inner| inner.is::<Error>()) {
+        Some(true) => *e.into_inner().unwrap().downcast::<Error>().unwrap(),
+        _ => Error::IOError(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_io_error() {
+        let e = IoError::other(Error::ConfigError("bad".to_string()));
+        assert!(matches!(from_io_error(e), Error::ConfigError(_)));
+        let e = IoError::other("plain");
+        assert!(matches!(from_io_error(e), Error::IOError(_)));
+    }
+}
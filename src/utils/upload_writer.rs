@@ -0,0 +1,515 @@
+use crate::api::{
+    b2_cancel_large_file, b2_finish_large_file, b2_get_upload_part_url, b2_get_upload_url,
+    b2_start_large_file, b2_upload_file, b2_upload_part, B2Auth, B2FileInfo, FileParameters,
+    PartParameters, Sha1Variant, StartLargeFileParameters, UploadPartAuth,
+};
+use crate::api::{BucketId, FileId};
+use crate::utils::{BufferPool, PartSizePolicy};
+use crate::Error;
+use bytes::{Bytes, BytesMut};
+use futures::Future;
+use reqwest::Client;
+use sha1::Sha1;
+use std::io::Error as IoError;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+use tokio::task::{JoinError, JoinHandle};
+
+// What a finished part upload hands back to the writer
+struct PartDone {
+    sha1: String,
+    // Upload URLs can be reused for the next part
+    upload_auth: UploadPartAuth,
+}
+
+// Settings shared with the spawned upload tasks
+struct Target {
+    client: Client,
+    auth: B2Auth,
+    bucket_id: BucketId,
+    file_name: String,
+    content_type: Option<String>,
+    // The large file once it is started, kept outside the part tasks so it can be cancelled even if they fail
+    file_id: Mutex<Option<FileId>>,
+}
+
+/// An [AsyncWrite] that uploads everything written to it as a single file on B2
+///
+/// Data is buffered until a full part is available, which is then uploaded in the background with the large-file API
+/// while writing continues. At most two parts are kept in memory at a time,
+/// use [with_buffer_pool][B2UploadWriter::with_buffer_pool] to bound memory across many writers. \
+/// Calling [shutdown][tokio::io::AsyncWriteExt::shutdown] uploads the last part and finishes the file,
+/// after which [file_info][B2UploadWriter::file_info] returns the result. \
+/// Files of at most one part are uploaded with a regular [b2_upload_file] instead, as a full part is only uploaded once more data follows.
+///
+/// The Sha1 of everything written is computed along the way and available from [sha1][B2UploadWriter::sha1]. \
+/// Large files only have per-part Sha1s on B2, and since the whole-file Sha1 is only known once the large file
+/// has been started, it isn't stored as [LARGE_FILE_SHA1][crate::api::LARGE_FILE_SHA1].
+/// Uploads of local files that need it can hash the file first, as [TransferManager][crate::utils::TransferManager] does.
+///
+/// Note that [flush][tokio::io::AsyncWriteExt::flush] does **not** upload partial parts, as B2 has a minimum part size.
+///
+/// Must be used from within a tokio runtime.
+pub struct B2UploadWriter {
+    target: Arc<Target>,
+    policy: PartSizePolicy,
+    expected_size: u64,
+    part_size: usize,
+    buffer: BytesMut,
+    pool: Option<BufferPool>,
+    // Whether 'buffer' was taken from the pool
+    pooled: bool,
+    upload_auth: Option<UploadPartAuth>,
+    part_sha1s: Vec<String>,
+    // Sha1 of everything written so far
+    hasher: Sha1,
+    sha1: Option<String>,
+    in_flight: Option<JoinHandle<Result<PartDone, Error>>>,
+    finishing: Option<JoinHandle<Result<B2FileInfo, Error>>>,
+    result: Option<B2FileInfo>,
+    // Set once a part or the finish failed, after which the upload can only be aborted
+    failed: bool,
+}
+
+impl B2UploadWriter {
+    /// Creates a writer uploading to 'file_name' in the given bucket, using the 'recommended_part_size' of the B2Auth
+    pub fn new<T: Into<BucketId>, Q: Into<String>>(
+        client: Client,
+        auth: B2Auth,
+        bucket_id: T,
+        file_name: Q,
+    ) -> B2UploadWriter {
+        let policy = PartSizePolicy::from_auth(&auth);
+        B2UploadWriter {
+            target: Arc::new(Target {
+                client,
+                auth,
+                bucket_id: bucket_id.into(),
+                file_name: file_name.into(),
+                content_type: None,
+                file_id: Mutex::new(None),
+            }),
+            part_size: policy.part_size_for(0) as usize,
+            policy,
+            expected_size: 0,
+            buffer: BytesMut::new(),
+            pool: None,
+            pooled: false,
+            upload_auth: None,
+            part_sha1s: Vec::new(),
+            hasher: Sha1::new(),
+            sha1: None,
+            in_flight: None,
+            finishing: None,
+            result: None,
+            failed: false,
+        }
+    }
+
+    /// Sets the preferred part size, which is raised to 'absolute_minimum_part_size' if it is smaller
+    ///
+    /// Memory usage is roughly twice the part size
+    pub fn with_part_size(self, part_size: usize) -> Self {
+        let policy = self.policy.with_preferred_part_size(part_size as u64);
+        self.with_part_size_policy(policy)
+    }
+
+    /// Sets the policy used to pick the part size, see [PartSizePolicy]
+    pub fn with_part_size_policy(mut self, policy: PartSizePolicy) -> Self {
+        self.policy = policy;
+        self.part_size = policy.part_size_for(self.expected_size) as usize;
+        self
+    }
+
+    /// Sets the expected size of the file
+    ///
+    /// Without it, files are limited to [MAX_PARTS][crate::utils::MAX_PARTS] parts of the preferred size,
+    /// with it the part size is raised so the file fits
+    pub fn with_expected_size(mut self, size: u64) -> Self {
+        self.expected_size = size;
+        self.part_size = self.policy.part_size_for(size) as usize;
+        self
+    }
+
+    /// Takes part buffers from a shared [BufferPool] instead of allocating them
+    ///
+    /// The writer holds up to two buffers at once, one being filled and one being uploaded
+    pub fn with_buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Sets the content type, "b2/x-auto" is used if this isn't called
+    pub fn with_content_type<T: Into<String>>(mut self, content_type: T) -> Self {
+        // Nothing has been shared with a task yet, so this never fails
+        if let Some(target) = Arc::get_mut(&mut self.target) {
+            target.content_type = Some(content_type.into());
+        }
+        self
+    }
+
+    /// The Sha1 of everything written, available once [shutdown][tokio::io::AsyncWriteExt::shutdown] has been called
+    pub fn sha1(&self) -> Option<&str> {
+        self.sha1.as_deref()
+    }
+
+    /// The uploaded file, available once [shutdown][tokio::io::AsyncWriteExt::shutdown] has completed
+    pub fn file_info(&self) -> Option<&B2FileInfo> {
+        self.result.as_ref()
+    }
+
+    /// Gives up on the upload, cancelling the large file if one was started
+    ///
+    /// Dropping the writer instead leaves the parts uploaded so far on B2 until they are cancelled
+    pub async fn abort(mut self) -> Result<(), Error> {
+        if let Some(handle) = self.in_flight.take() {
+            // Only the large file it may have started matters, not whether the part made it
+            let _ = handle.await;
+        }
+        match self.file_id() {
+            Some(file_id) => {
+                b2_cancel_large_file(&self.target.client, &self.target.auth, &file_id).await?;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // The large file, if one was started
+    fn file_id(&self) -> Option<FileId> {
+        self.target.file_id.lock().unwrap().clone()
+    }
+
+    // Takes the buffered data, along with the pool it has to be returned to
+    fn take_buffer(&mut self) -> (Bytes, Option<BufferPool>) {
+        if self.pooled {
+            self.pooled = false;
+            (std::mem::take(&mut self.buffer).freeze(), self.pool.clone())
+        } else {
+            (self.buffer.split().freeze(), None)
+        }
+    }
+
+    // Uploads the buffered data as the next part in the background
+    fn spawn_part(&mut self) {
+        let (data, pool) = self.take_buffer();
+        let part_number = self.part_sha1s.len() as u32 + 1;
+        let target = self.target.clone();
+        let upload_auth = self.upload_auth.take();
+        self.in_flight = Some(tokio::spawn(async move {
+            let res = upload_part(target, upload_auth, part_number, data.clone()).await;
+            if let Some(pool) = pool {
+                pool.release_bytes(data);
+            }
+            res
+        }));
+    }
+
+    // Makes sure the buffer being filled comes from the pool, if there is one
+    fn poll_buffer(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(pool) = self.pool.as_ref() {
+            if !self.pooled {
+                self.buffer = futures::ready!(pool.poll_acquire(cx, self.part_size));
+                self.pooled = true;
+            }
+        }
+        Poll::Ready(())
+    }
+
+    // Drives the in-flight part upload, if any
+    fn poll_in_flight(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        if let Some(handle) = self.in_flight.as_mut() {
+            // A finished JoinHandle must not be polled again, so it is dropped whatever the outcome
+            let res = futures::ready!(Pin::new(handle).poll(cx));
+            self.in_flight = None;
+            let done = self.check(res)?;
+            self.upload_auth = Some(done.upload_auth);
+            self.part_sha1s.push(done.sha1);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    // Unwraps the result of a task, remembering failures
+    fn check<T>(&mut self, res: Result<Result<T, Error>, JoinError>) -> Result<T, IoError> {
+        let res = match res {
+            Ok(Ok(value)) => return Ok(value),
+            Ok(Err(e)) => to_io_error(e),
+            Err(e) => to_io_error(e),
+        };
+        self.failed = true;
+        Err(res)
+    }
+}
+
+fn to_io_error<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> IoError {
+    IoError::other(e)
+}
+
+fn sha1_hex(data: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+    hasher.hexdigest()
+}
+
+async fn upload_part(
+    target: Arc<Target>,
+    upload_auth: Option<UploadPartAuth>,
+    part_number: u32,
+    data: Bytes,
+) -> Result<PartDone, Error> {
+    // The large file is only started once a second part is needed, so small files never become large files
+    let started = target.file_id.lock().unwrap().clone();
+    let file_id = match started {
+        Some(id) => id,
+        None => {
+            let file_id = b2_start_large_file(
+                &target.client,
+                &target.auth,
+                StartLargeFileParameters {
+                    bucket_id: &target.bucket_id,
+                    file_name: &target.file_name,
+                    content_type: target.content_type.as_deref(),
+                    file_info: None,
+                },
+            )
+            .await?
+            .file_id
+            .unwrap_or_default();
+            *target.file_id.lock().unwrap() = Some(file_id.clone());
+            file_id
+        }
+    };
+    let upload_auth = match upload_auth {
+        Some(a) => a,
+        None => b2_get_upload_part_url(&target.client, &target.auth, &file_id).await?,
+    };
+    let sha1 = sha1_hex(&data);
+    let size = data.len() as u64;
+    b2_upload_part(
+        &target.client,
+        &upload_auth,
+        data,
+        PartParameters {
+            part_number,
+            part_size: size,
+            content_sha1: Sha1Variant::Precomputed(&sha1),
+        },
+    )
+    .await?;
+    Ok(PartDone { sha1, upload_auth })
+}
+
+async fn upload_small_file(target: Arc<Target>, data: Bytes) -> Result<B2FileInfo, Error> {
+    let upauth = b2_get_upload_url(&target.client, &target.auth, &target.bucket_id).await?;
+    let sha1 = sha1_hex(&data);
+    let size = data.len() as u64;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    b2_upload_file(
+        &target.client,
+        &upauth,
+        data,
+        FileParameters {
+            file_path: &target.file_name,
+            file_size: size,
+            content_type: target.content_type.as_deref(),
+            content_sha1: Sha1Variant::Precomputed(&sha1),
+            last_modified_millis: now,
+        },
+    )
+    .await
+}
+
+impl AsyncWrite for B2UploadWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let this = self.get_mut();
+        if this.failed {
+            return Poll::Ready(Err(to_io_error(
+                "the upload failed, it can only be aborted",
+            )));
+        }
+        if this.finishing.is_some() || this.result.is_some() {
+            return Poll::Ready(Err(to_io_error("write after shutdown")));
+        }
+        // Poll errors are surfaced right away, even if there is room in the buffer
+        if let Poll::Ready(Err(e)) = this.poll_in_flight(cx) {
+            return Poll::Ready(Err(e));
+        }
+        // A full buffer is only uploaded once more data arrives,
+        // so a file of exactly one part is uploaded as a regular file instead of a large file of a single part
+        if this.buffer.len() >= this.part_size && !buf.is_empty() {
+            if this.in_flight.is_some() {
+                // Both the buffer and the upload slot are full, wait for the upload
+                return Poll::Pending;
+            }
+            this.spawn_part();
+        }
+        futures::ready!(this.poll_buffer(cx));
+        let n = buf.len().min(this.part_size - this.buffer.len());
+        this.buffer.extend_from_slice(&buf[..n]);
+        this.hasher.update(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        let this = self.get_mut();
+        loop {
+            if this.result.is_some() {
+                return Poll::Ready(Ok(()));
+            }
+            if this.failed {
+                return Poll::Ready(Err(to_io_error(
+                    "the upload failed, it can only be aborted",
+                )));
+            }
+            futures::ready!(this.poll_in_flight(cx))?;
+            if let Some(handle) = this.finishing.as_mut() {
+                let res = futures::ready!(Pin::new(handle).poll(cx));
+                this.finishing = None;
+                this.result = Some(this.check(res)?);
+                continue;
+            }
+            let target = this.target.clone();
+            if this.sha1.is_none() {
+                this.sha1 = Some(this.hasher.hexdigest());
+            }
+            match this.file_id() {
+                // Everything fit in one part, so upload it as a regular file
+                None => {
+                    let (data, pool) = this.take_buffer();
+                    this.finishing = Some(tokio::spawn(async move {
+                        let res = upload_small_file(target, data.clone()).await;
+                        if let Some(pool) = pool {
+                            pool.release_bytes(data);
+                        }
+                        res
+                    }));
+                }
+                Some(_) if !this.buffer.is_empty() => this.spawn_part(),
+                Some(file_id) => {
+                    let sha1s = std::mem::take(&mut this.part_sha1s);
+                    this.finishing = Some(tokio::spawn(async move {
+                        b2_finish_large_file(&target.client, &target.auth, &file_id, &sha1s).await
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for B2UploadWriter {
+    fn drop(&mut self) {
+        if let (Some(pool), true) = (self.pool.as_ref(), self.pooled) {
+            pool.release(std::mem::take(&mut self.buffer));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use tokio::io::AsyncWriteExt;
+
+    // Answers B2 calls by name from a local port, recording the calls made
+    fn serve(calls: Arc<Mutex<Vec<String>>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let call = line
+                    .split('/')
+                    .nth(3)
+                    .unwrap_or("")
+                    .split(' ')
+                    .next()
+                    .unwrap();
+                let call = call.to_string();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(v) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = v.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let (status, json) = match call.as_str() {
+                    "b2_start_large_file" => (
+                        200,
+                        r#"{"accountId": "a", "action": "start", "bucketId": "bucket",
+                        "contentLength": 0, "fileId": "4_zlarge", "fileName": "big.bin", "uploadTimestamp": 0}"#,
+                    ),
+                    "b2_cancel_large_file" => (
+                        200,
+                        r#"{"accountId": "a", "bucketId": "bucket",
+                        "fileId": "4_zlarge", "fileName": "big.bin"}"#,
+                    ),
+                    _ => (
+                        400,
+                        r#"{"status": 400, "code": "bad_request", "message": "no"}"#,
+                    ),
+                };
+                calls.lock().unwrap().push(call);
+                let resp = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    json.len(),
+                    json
+                );
+                reader.get_mut().write_all(resp.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_abort_after_failed_part() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let auth = B2Auth {
+            account_id: Default::default(),
+            authorization_token: String::new(),
+            api_url: serve(calls.clone()),
+            download_url: String::new(),
+            absolute_minimum_part_size: 1,
+            recommended_part_size: 4,
+            s3_api_url: String::new(),
+            issued_at: None,
+            api_version: Default::default(),
+            allowed: None,
+        };
+        let mut writer = B2UploadWriter::new(Client::new(), auth, "bucket", "big.bin");
+        // The large file is started, but getting a URL for its first part fails
+        assert!(writer.write_all(b"0123456789").await.is_err());
+        assert!(writer.write_all(b"more").await.is_err());
+        writer.abort().await.unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                "b2_start_large_file",
+                "b2_get_upload_part_url",
+                "b2_cancel_large_file"
+            ]
+        );
+    }
+}
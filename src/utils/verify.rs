@@ -0,0 +1,97 @@
+use crate::api::{b2_delete_file_version, B2Auth, B2FileInfo};
+use crate::Error;
+use reqwest::header::HeaderMap;
+use reqwest::Client;
+
+/// Whether upload helpers check the Sha1 B2 reports against the one computed locally
+#[derive(Debug, Clone, Copy)]
+pub enum VerifySha1<'a> {
+    /// Trust the upload
+    Skip,
+    /// Return a [ChecksumMismatch][Error::ChecksumMismatch] if the checksums differ
+    Check,
+    /// Same as Check, but also delete the uploaded version, so no corrupted data is left behind
+    DeleteOnMismatch(&'a B2Auth),
+}
+
+/// Compares the Sha1 of an uploaded file against the locally computed 'expected' Sha1
+///
+/// Returns a [ChecksumMismatch][Error::ChecksumMismatch] if they differ. \
+/// Large files are checked against their [LARGE_FILE_SHA1][crate::api::LARGE_FILE_SHA1] file info,
+/// large files without it can't be checked and always pass.
+pub fn check_content_sha1(info: &B2FileInfo, expected: &str) -> Result<(), Error> {
+    let actual = match info.whole_file_sha1() {
+        None => return Ok(()),
+        Some(sha1) => sha1,
+    };
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(Error::ChecksumMismatch {
+            file_name: info.file_name.clone(),
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        })
+    }
+}
+
+/// Reads the Sha1 of the whole file from the headers of a download response
+///
+/// Uses 'X-Bz-Content-Sha1', or 'X-Bz-Info-large_file_sha1' for large files, see [LARGE_FILE_SHA1][crate::api::LARGE_FILE_SHA1]
+pub fn expected_sha1(headers: &HeaderMap) -> Option<String> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    match header("x-bz-content-sha1") {
+        None | Some("none") => header("x-bz-info-large_file_sha1").map(String::from),
+        Some(sha1) => Some(sha1.trim_start_matches("unverified:").to_string()),
+    }
+}
+
+/// Checks an uploaded file as specified by 'verify', see [VerifySha1]
+///
+/// If the version has to be deleted but deleting fails, the mismatch is still returned
+pub async fn verify_upload(
+    client: &Client,
+    info: &B2FileInfo,
+    expected: &str,
+    verify: VerifySha1<'_>,
+) -> Result<(), Error> {
+    let res = match verify {
+        VerifySha1::Skip => return Ok(()),
+        _ => check_content_sha1(info, expected),
+    };
+    if let (Err(_), VerifySha1::DeleteOnMismatch(auth), Some(file_id)) =
+        (&res, verify, &info.file_id)
+    {
+        let _ = b2_delete_file_version(client, auth, &info.file_name, file_id).await;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{test_file, LARGE_FILE_SHA1};
+
+    #[test]
+    fn test_check_content_sha1() {
+        let mut info = test_file("hello.txt");
+        info.content_length = 5;
+        info.content_sha1 = Some("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d".to_string());
+        assert!(check_content_sha1(&info, "AAF4C61DDCC5E8A2DABEDE0F3B482CD9AEA9434D").is_ok());
+        assert!(matches!(
+            check_content_sha1(&info, "0000000000000000000000000000000000000000"),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        info.content_sha1 = Some("none".to_string());
+        assert!(check_content_sha1(&info, "0000000000000000000000000000000000000000").is_ok());
+        info.file_info = Some(
+            [(
+                LARGE_FILE_SHA1.to_string(),
+                "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d".to_string(),
+            )]
+            .into(),
+        );
+        assert!(check_content_sha1(&info, "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d").is_ok());
+        assert!(check_content_sha1(&info, "0000000000000000000000000000000000000000").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! [AsyncWrite] wrappers, the counterparts of the stream wrappers in [readers][crate::utils::BytesStreamExt] for downloading.
+//! These can be nested to combine their effects
+use crate::utils::{max_chunk, sleep, Digests, Sleep, DEFAULT_CHUNK_DURATION};
+use digest::DynDigest;
+use futures::ready;
+use pin_project::pin_project;
+use sha1::Sha1;
+use std::io::Error as IoError;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::AsyncWrite;
+
+/// Wraps an [AsyncWrite], limiting how fast it can be written to. \
+/// Useful for limiting download bandwidth, by writing the response body through it.
+///
+/// bandwidth: maximum bytes per second, 0 is unlimited \
+/// Writes are cut so each takes about [DEFAULT_CHUNK_DURATION] at that bandwidth, same as [BytesStreamThrottled][crate::utils::BytesStreamThrottled].
+#[pin_project]
+pub struct AsyncWriteThrottled<W> {
+    #[pin]
+    inner: W,
+    bandwidth: f64,
+    chunk_duration: Duration,
+    sleep: Option<Sleep>,
+}
+
+impl<W: AsyncWrite> AsyncWriteThrottled<W> {
+    pub fn wrap(inner: W, bandwidth: usize) -> Self {
+        Self {
+            inner,
+            bandwidth: bandwidth as f64,
+            chunk_duration: DEFAULT_CHUNK_DURATION,
+            sleep: None,
+        }
+    }
+
+    /// Cuts writes so each takes about 'duration', [Duration::ZERO] passes writes on as they are
+    pub fn with_chunk_duration(mut self, duration: Duration) -> Self {
+        self.chunk_duration = duration;
+        self
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for AsyncWriteThrottled<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let this = self.project();
+        if *this.bandwidth <= 0.0 {
+            return this.inner.poll_write(cx, buf);
+        }
+        if let Some(sleep) = this.sleep.as_mut() {
+            ready!(sleep.as_mut().poll(cx));
+            *this.sleep = None;
+        }
+        let max = max_chunk(*this.bandwidth, *this.chunk_duration).unwrap_or(buf.len());
+        let written = ready!(this.inner.poll_write(cx, &buf[..buf.len().min(max)]))?;
+        *this.sleep = Some(sleep(Duration::from_secs_f64(
+            written as f64 / *this.bandwidth,
+        )));
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        let this = self.project();
+        // The last write isn't done until its time has passed
+        if let Some(sleep) = this.sleep.as_mut() {
+            ready!(sleep.as_mut().poll(cx));
+            *this.sleep = None;
+        }
+        this.inner.poll_shutdown(cx)
+    }
+}
+
+/// Wraps an [AsyncWrite], computing the Sha1 hash of everything written to it
+///
+/// The digests are available through [digests][AsyncWriteHashing::digests] once the writer was shut down,
+/// e.g. with [AsyncWriteExt::shutdown][tokio::io::AsyncWriteExt::shutdown], and can then be compared
+/// to [B2FileInfo::whole_file_sha1][crate::api::B2FileInfo::whole_file_sha1]. \
+/// Other digests can be computed in the same pass with [with_digest][AsyncWriteHashing::with_digest].
+#[pin_project]
+pub struct AsyncWriteHashing<W> {
+    #[pin]
+    inner: W,
+    hash: Sha1,
+    extra: Vec<(String, Box<dyn DynDigest + Send + Sync>)>,
+    digests: Digests,
+}
+
+impl<W: AsyncWrite> AsyncWriteHashing<W> {
+    pub fn wrap(inner: W) -> Self {
+        Self {
+            inner,
+            hash: Sha1::new(),
+            extra: Vec::new(),
+            digests: Digests::default(),
+        }
+    }
+
+    /// Also computes 'digest', e.g. `sha2::Sha256::new()`, available under 'name' once the writer was shut down
+    pub fn with_digest<N, D>(mut self, name: N, digest: D) -> Self
+    where
+        N: Into<String>,
+        D: DynDigest + Send + Sync + 'static,
+    {
+        self.extra.push((name.into(), Box::new(digest)));
+        self
+    }
+
+    /// A handle to the digests, which can be kept after the writer is moved
+    pub fn digests(&self) -> Digests {
+        self.digests.clone()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for AsyncWriteHashing<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        let this = self.project();
+        let written = ready!(this.inner.poll_write(cx, buf))?;
+        this.hash.update(&buf[..written]);
+        for (_, digest) in this.extra.iter_mut() {
+            digest.update(&buf[..written]);
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        let this = self.project();
+        ready!(this.inner.poll_shutdown(cx))?;
+        if !this.digests.is_done() {
+            let mut results = vec![("sha1".to_string(), this.hash.hexdigest())];
+            results.extend(
+                this.extra
+                    .iter_mut()
+                    .map(|(name, d)| (name.clone(), hex::encode(d.finalize_reset()))),
+            );
+            this.digests.set(results);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::CallbackSink;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_throttled_hashing_writer() {
+        let mut sizes = Vec::new();
+        {
+            let sink = CallbackSink::new(|d: &[u8]| sizes.push(d.len()));
+            let throttled = AsyncWriteThrottled::wrap(sink, 10_000)
+                .with_chunk_duration(Duration::from_millis(10));
+            let mut writer = AsyncWriteHashing::wrap(throttled);
+            let digests = writer.digests();
+            writer.write_all(&[b'a'; 250]).await.unwrap();
+            assert!(!digests.is_done());
+            writer.shutdown().await.unwrap();
+            assert_eq!(
+                digests.sha1().unwrap(),
+                "b5d5e3e0fcccfb49d704a1e10bc97ce9761a14fe"
+            );
+        }
+        assert_eq!(sizes, [100, 100, 50]);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_writer() {
+        let mut sizes = Vec::new();
+        {
+            let sink = CallbackSink::new(|d: &[u8]| sizes.push(d.len()));
+            let mut writer = AsyncWriteThrottled::wrap(sink, 0);
+            writer.write_all(&[b'a'; 250]).await.unwrap();
+            writer.shutdown().await.unwrap();
+        }
+        assert_eq!(sizes, [250]);
+    }
+}
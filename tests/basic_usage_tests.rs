@@ -5,11 +5,10 @@ use common::*;
 
 #[tokio::test]
 async fn test_basic_usage() {
-    let TestSetup {
-        client,
-        auth,
-        bucket_id,
-    } = setup_test_with_auth().await;
+    let bucket = temp_bucket().await;
+    let client = bucket.client.clone();
+    let auth = bucket.auth.clone();
+    let bucket_id = bucket.bucket_id.clone();
 
     let upauth = b2_get_upload_url(&client, &auth, &bucket_id).await.unwrap();
 
@@ -57,7 +56,7 @@ async fn test_basic_usage() {
     assert_eq!(info.modified(), modf);
 
     let param2 = B2GetDownloadAuthParams {
-        bucket_id: bucket_id.to_string(),
+        bucket_id: bucket_id.clone(),
         file_name_prefix: "".to_string(),
         valid_duration_in_seconds: 500,
     };
@@ -1,11 +1,15 @@
-use raze::api::{self, B2Auth};
+#![allow(dead_code)]
+
+use raze::api::{self, B2Auth, B2BucketType, BucketId, ListFileVersionsRequest};
+use raze::Error;
 use reqwest::Client;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::{fs::File, sync::OnceCell};
 
 pub struct TestSetup {
     pub client: Client,
     pub auth: B2Auth,
-    pub bucket_id: String,
+    pub bucket_id: BucketId,
 }
 
 pub async fn setup_test_with_auth() -> TestSetup {
@@ -21,7 +25,7 @@ pub async fn setup_test_with_auth() -> TestSetup {
         })
         .await
         .clone();
-    let bucket_id = std::env::var("B2_TEST_BUCKET_ID").unwrap();
+    let bucket_id = BucketId::new(std::env::var("B2_TEST_BUCKET_ID").unwrap());
     TestSetup {
         client,
         auth,
@@ -37,3 +41,89 @@ pub async fn open_test_file(name: &str) -> File {
     let file = tokio::fs::File::open(&path).await.unwrap();
     file
 }
+
+/// A private bucket created for a single test, emptied and deleted when dropped
+///
+/// Cleanup also runs when the test panics. The test key needs the
+/// 'writeBuckets', 'deleteBuckets', 'listFiles' and 'deleteFiles' capabilities.
+pub struct TempBucket {
+    pub client: Client,
+    pub auth: B2Auth,
+    pub bucket_id: BucketId,
+    pub bucket_name: String,
+}
+
+pub async fn temp_bucket() -> TempBucket {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let TestSetup { client, auth, .. } = setup_test_with_auth().await;
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    // Bucket names are global across all accounts
+    let bucket_name = format!(
+        "raze-test-{}-{}-{}",
+        nanos,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let bucket = api::b2_create_bucket(&client, &auth, &bucket_name, B2BucketType::AllPrivate)
+        .await
+        .unwrap();
+    TempBucket {
+        client,
+        auth,
+        bucket_id: bucket.bucket_id,
+        bucket_name,
+    }
+}
+
+impl Drop for TempBucket {
+    fn drop(&mut self) {
+        let auth = self.auth.clone();
+        let bucket_id = self.bucket_id.clone();
+        // Drop can't await, and the test's runtime may be shutting down after a panic,
+        // so the cleanup gets a runtime of its own on another thread.
+        // The test's Client keeps connections tied to the test's runtime, so it gets a fresh one as well
+        let cleanup = std::thread::spawn(move || {
+            let client = Client::new();
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    empty_bucket(&client, &auth, &bucket_id).await?;
+                    api::b2_delete_bucket(&client, &auth, &bucket_id).await
+                })
+        });
+        match cleanup.join() {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => eprintln!("failed to delete bucket {}: {}", self.bucket_name, e),
+            Err(_) => eprintln!("failed to delete bucket {}", self.bucket_name),
+        }
+    }
+}
+
+/// Deletes every file version in a bucket
+///
+/// Versions without a file id can't be deleted and are skipped
+pub async fn empty_bucket(
+    client: &Client,
+    auth: &B2Auth,
+    bucket_id: &BucketId,
+) -> Result<(), Error> {
+    let mut request = ListFileVersionsRequest::new(bucket_id.clone()).max_file_count(1000);
+    loop {
+        let page = api::b2_list_file_versions(client, auth, request.clone()).await?;
+        for file in &page.items {
+            if let Some(file_id) = &file.file_id {
+                api::b2_delete_file_version(client, auth, &file.file_name, file_id).await?;
+            }
+        }
+        match page.next_cursor() {
+            Some(cursor) => request = request.resume_from(&cursor),
+            None => return Ok(()),
+        }
+    }
+}
@@ -13,10 +13,14 @@ async fn test_list_all_files() {
         bucket_id,
     } = setup_test_with_auth().await;
 
-    let expected_files = b2_list_file_names(&client, &auth, &bucket_id, "", 16)
-        .await
-        .unwrap()
-        .files;
+    let expected_files = b2_list_file_names(
+        &client,
+        &auth,
+        ListFileNamesRequest::new(bucket_id.clone()).max_file_count(16),
+    )
+    .await
+    .unwrap()
+    .items;
 
     let stream = list_all_files_stream(client, auth, bucket_id, 4);
     let files: Vec<B2FileInfo> = stream.take(16).try_collect().await.unwrap();